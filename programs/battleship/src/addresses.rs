@@ -0,0 +1,623 @@
+//! Address and PDA derivations, mirrored by clients
+
+use crate::*;
+
+/// Derives the compressed game address for `(creator, game_id)`.
+/// Returns the address and the address seed. Clients can call this to locate
+/// a game without fetching anything from chain.
+pub fn derive_game_address(
+    creator: &Pubkey,
+    game_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[GAME_SEED, creator.as_ref(), &game_id.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the system-owned PDA that holds a game's deposits
+pub fn derive_vault_address(game_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_SEED, game_address], &crate::ID)
+}
+
+/// Derives the system-owned PDA holding a referrer's claimable fee shares
+pub fn derive_referral_vault_address(referrer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REFERRAL_VAULT_SEED, referrer.as_ref()], &crate::ID)
+}
+
+/// Derives the system-owned PDA holding the progressive jackpot
+pub fn derive_jackpot_vault_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[JACKPOT_VAULT_SEED], &crate::ID)
+}
+
+/// Derives the PDA that owns a game's escrowed compressed tokens
+pub fn derive_token_escrow_address(game_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TOKEN_ESCROW_SEED, game_address], &crate::ID)
+}
+
+/// Derives the PDA that is the mint authority of the reward token
+pub fn derive_reward_authority_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[REWARD_AUTHORITY_SEED], &crate::ID)
+}
+
+/// Derives the system-owned PDA that holds a game's side-bet stakes
+pub fn derive_side_vault_address(game_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SIDE_VAULT_SEED, game_address], &crate::ID)
+}
+
+/// Derives the system-owned PDA that holds a game's sealed-wager collateral
+pub fn derive_sealed_vault_address(game_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[SEALED_VAULT_SEED, game_address], &crate::ID)
+}
+
+/// Derives the move log address for a game
+pub fn derive_move_log_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[MOVE_LOG_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a game's `GameChat`
+pub fn derive_chat_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(&[CHAT_SEED, game_address], address_tree_pubkey, &crate::ID)
+}
+
+/// Derives the address of `owner`'s `BoardBackup` for a game
+pub fn derive_board_backup_address(
+    game_address: &[u8; 32],
+    owner: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[BACKUP_SEED, game_address, owner.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a rating bucket's `BucketQueue`
+pub fn derive_bucket_queue_address(
+    bucket: u8,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[BUCKET_QUEUE_SEED, &[bucket]],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `PlayQueue` of lobby `mode`, verified by
+/// `issuer` if set
+pub fn derive_play_queue_address(
+    mode: u8,
+    issuer: Option<&Pubkey>,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    match issuer {
+        Some(issuer) => derive_address(
+            &[PLAY_QUEUE_SEED, &[mode], issuer.as_ref()],
+            address_tree_pubkey,
+            &crate::ID,
+        ),
+        None => derive_address(&[PLAY_QUEUE_SEED, &[mode]], address_tree_pubkey, &crate::ID),
+    }
+}
+
+/// Derives the address of the `Credential` `issuer` gave `holder`
+pub fn derive_credential_address(
+    issuer: &Pubkey,
+    holder: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[CREDENTIAL_SEED, issuer.as_ref(), holder.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s matchmaking ticket `ticket_id`
+pub fn derive_match_ticket_address(
+    owner: &Pubkey,
+    ticket_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[MATCH_TICKET_SEED, owner.as_ref(), &ticket_id.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a game's `SideBetPool`
+pub fn derive_side_pool_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SIDE_POOL_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a game's `GameViewers`
+pub fn derive_game_viewers_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[VIEWERS_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a registered `Watcher` of a game
+pub fn derive_watcher_address(
+    game_address: &[u8; 32],
+    watcher: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[WATCHER_SEED, game_address, watcher.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a spectator's `SideBet` on a game
+pub fn derive_side_bet_address(
+    game_address: &[u8; 32],
+    bettor: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SIDE_BET_SEED, game_address, bettor.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the system-owned PDA that holds a game's betting-market pool
+pub fn derive_market_vault_address(game_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MARKET_VAULT_SEED, game_address], &crate::ID)
+}
+
+/// Derives the address of a game's `BetMarket`
+pub fn derive_market_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[MARKET_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `bettor`'s `MarketBet` on a game
+pub fn derive_market_bet_address(
+    game_address: &[u8; 32],
+    bettor: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[MARKET_BET_SEED, game_address, bettor.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `organizer`'s prize pool `pool_id`
+pub fn derive_prize_pool_address(
+    organizer: &Pubkey,
+    pool_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[PRIZE_POOL_SEED, organizer.as_ref(), &pool_id.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `organizer`'s tournament `tournament_id`
+pub fn derive_tournament_address(
+    organizer: &Pubkey,
+    tournament_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[
+            TOURNAMENT_SEED,
+            organizer.as_ref(),
+            &tournament_id.to_le_bytes(),
+        ],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `Season` number `season`
+pub fn derive_season_address(season: u32, address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SEASON_SEED, &season.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `SeasonArchive` for `season`
+pub fn derive_season_archive_address(
+    season: u32,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SEASON_ARCHIVE_SEED, &season.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `Leaderboard` for `season`
+pub fn derive_leaderboard_address(
+    season: u32,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[LEADERBOARD_SEED, &season.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `player`'s `SeasonScore` in `season`
+pub fn derive_season_score_address(
+    season: u32,
+    player: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SEASON_SCORE_SEED, &season.to_le_bytes(), player.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the rewards pool for `season`
+pub fn derive_season_rewards_address(
+    season: u32,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SEASON_REWARDS_SEED, &season.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the system-owned PDA that holds a prize pool's entry fees, or a
+/// season rewards pool's funding
+pub fn derive_prize_vault_address(pool_address: &[u8; 32]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PRIZE_VAULT_SEED, pool_address], &crate::ID)
+}
+
+/// Derives the address of a game's `SealedWager`
+pub fn derive_sealed_wager_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[SEALED_WAGER_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of round `round_id` of a game's randomness
+pub fn derive_rand_round_address(
+    game_address: &[u8; 32],
+    round_id: u8,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[RAND_SEED, game_address, &[round_id]],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the singleton `ProtocolConfig`
+pub fn derive_protocol_config_address(address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(&[PROTOCOL_CONFIG_SEED], address_tree_pubkey, &crate::ID)
+}
+
+/// Derives the address of the singleton `OracleConfig`
+pub fn derive_oracle_config_address(address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(&[ORACLE_CONFIG_SEED], address_tree_pubkey, &crate::ID)
+}
+
+/// Derives the address of an owner's `ProofAttestation`
+pub fn derive_attestation_address(
+    owner: &Pubkey,
+    attestation_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[
+            ATTESTATION_SEED,
+            owner.as_ref(),
+            &attestation_id.to_le_bytes(),
+        ],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `version` of the `CircuitVerifier` for
+/// `circuit_id` and `backend`
+pub fn derive_verifier_address(
+    circuit_id: u8,
+    backend: u8,
+    version: u16,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[
+            VERIFIER_SEED,
+            &[circuit_id],
+            &[backend],
+            &version.to_le_bytes(),
+        ],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a finished game's `Rematch`
+pub fn derive_rematch_address(
+    previous_game: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[REMATCH_SEED, previous_game],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a batch game's `BatchPlans`
+pub fn derive_batch_plans_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[BATCH_PLANS_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `PlayerView` for `seat` (1 = A, 2 = B)
+pub fn derive_player_view_address(
+    game_address: &[u8; 32],
+    seat: u8,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[VIEW_SEED, game_address, &[seat]],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s `Achievements`
+pub fn derive_achievements_address(
+    owner: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[ACHIEVEMENTS_SEED, owner.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s `Badge` of `kind`
+pub fn derive_badge_address(
+    owner: &Pubkey,
+    kind: u8,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[BADGE_SEED, owner.as_ref(), &[kind]],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the club `name`
+pub fn derive_club_address(name: &str, address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[CLUB_SEED, name.as_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `player`'s `ClubMember`
+pub fn derive_club_member_address(
+    player: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[CLUB_MEMBER_SEED, player.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `ClubClaim` for `member` in a game result
+pub fn derive_club_claim_address(
+    result_address: &[u8; 32],
+    member: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[CLUB_CLAIM_SEED, result_address, member.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the display name `name`
+pub fn derive_name_address(name: &str, address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[NAME_SEED, name.as_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s `FriendList`
+pub fn derive_friend_list_address(
+    owner: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[FRIEND_LIST_SEED, owner.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s `LoyaltyPoints`
+pub fn derive_loyalty_address(
+    owner: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[LOYALTY_SEED, owner.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of page `page` of `owner`'s match history
+pub fn derive_match_history_address(
+    owner: &Pubkey,
+    page: u32,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[MATCH_HISTORY_SEED, owner.as_ref(), &page.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `LoyaltyClaim` for `player` in a game
+pub fn derive_loyalty_claim_address(
+    game_address: &[u8; 32],
+    player: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[LOYALTY_CLAIM_SEED, game_address, player.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s `PlayerProfile`
+pub fn derive_profile_address(
+    owner: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[PROFILE_SEED, owner.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `GlobalStats` singleton
+pub fn derive_global_stats_address(address_tree_pubkey: &Pubkey) -> ([u8; 32], AddressSeed) {
+    derive_address(&[GLOBAL_STATS_SEED], address_tree_pubkey, &crate::ID)
+}
+
+/// Derives the address of the `Rivalry` between two players, in either
+/// order
+pub fn derive_rivalry_address(
+    a: &Pubkey,
+    b: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    let (first, second) = rivalry_pair(a, b);
+    derive_address(
+        &[RIVALRY_SEED, first.as_ref(), second.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `player`'s `Ban`
+pub fn derive_ban_address(
+    player: &Pubkey,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[BAN_SEED, player.as_ref()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of the `FleetPreset` with `preset_id`
+pub fn derive_fleet_preset_address(
+    preset_id: u16,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[PRESET_SEED, &preset_id.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of `owner`'s campaign `campaign_id`
+pub fn derive_campaign_address(
+    owner: &Pubkey,
+    campaign_id: u64,
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[CAMPAIGN_SEED, owner.as_ref(), &campaign_id.to_le_bytes()],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
+
+/// Derives the address of a game's `GameResult` record
+pub fn derive_game_result_address(
+    game_address: &[u8; 32],
+    address_tree_pubkey: &Pubkey,
+) -> ([u8; 32], AddressSeed) {
+    derive_address(
+        &[RESULT_SEED, game_address],
+        address_tree_pubkey,
+        &crate::ID,
+    )
+}
@@ -0,0 +1,188 @@
+//! Ship placement, rule validation and shot resolution on a grid
+
+use crate::*;
+
+/// Validates a ship placement and returns a grid with the ship on it
+pub(crate) fn place_ship(
+    ship_start_x: u8,
+    ship_start_y: u8,
+    is_horizontal: bool,
+) -> Result<[u8; GRID_CELLS]> {
+    let mut grid = [CELL_EMPTY; GRID_CELLS];
+    place_ship_on(
+        &mut grid,
+        ship_start_x,
+        ship_start_y,
+        is_horizontal,
+        SHIP_LENGTH as u8,
+        GRID_SIZE as u8,
+    )?;
+    Ok(grid)
+}
+
+/// Places every ship of `fleet` (lengths, in order) at the matching `ships`
+/// entry on top of `grid`, which may already hold obstacles
+pub(crate) fn place_fleet(
+    mut grid: [u8; GRID_CELLS],
+    fleet: &[u8],
+    ships: &[ShipPlacement],
+    grid_size: u8,
+) -> Result<[u8; GRID_CELLS]> {
+    if fleet.len() != ships.len() {
+        msg!("Expected {} ships, got {}", fleet.len(), ships.len());
+        return Err(BattleshipError::InvalidRules.into());
+    }
+    for (&length, ship) in fleet.iter().zip(ships) {
+        place_ship_on(
+            &mut grid,
+            ship.x,
+            ship.y,
+            ship.is_horizontal,
+            length,
+            grid_size,
+        )?;
+    }
+    Ok(grid)
+}
+
+/// Adds one ship of `length` cells to `grid`, rejecting placements that
+/// overlap or leave the `grid_size` x `grid_size` playable area
+pub(crate) fn place_ship_on(
+    grid: &mut [u8; GRID_CELLS],
+    ship_start_x: u8,
+    ship_start_y: u8,
+    is_horizontal: bool,
+    length: u8,
+    grid_size: u8,
+) -> Result<()> {
+    if ship_start_x >= grid_size || ship_start_y >= grid_size {
+        msg!("Invalid ship start position");
+        return Err(BattleshipError::InvalidPosition.into());
+    }
+
+    // Check ship fits in grid
+    if is_horizontal {
+        if ship_start_x + length > grid_size {
+            msg!("Ship doesn't fit horizontally");
+            return Err(BattleshipError::ShipOutOfBounds.into());
+        }
+    } else if ship_start_y + length > grid_size {
+        msg!("Ship doesn't fit vertically");
+        return Err(BattleshipError::ShipOutOfBounds.into());
+    }
+
+    for i in 0..length {
+        let (x, y) = if is_horizontal {
+            (ship_start_x + i, ship_start_y)
+        } else {
+            (ship_start_x, ship_start_y + i)
+        };
+        let index = (y as usize * GRID_SIZE) + x as usize;
+        if grid[index] == CELL_BLOCKED {
+            msg!("Obstacle at ({}, {})", x, y);
+            return Err(BattleshipError::CellBlocked.into());
+        }
+        if grid[index] != CELL_EMPTY {
+            msg!("Ships overlap at ({}, {})", x, y);
+            return Err(BattleshipError::ShipsOverlap.into());
+        }
+        grid[index] = CELL_SHIP;
+    }
+    Ok(())
+}
+
+/// Number of cells an opponent must hit to sink the whole fleet
+pub(crate) fn fleet_cells(fleet: &[u8]) -> u8 {
+    fleet.iter().sum()
+}
+
+/// Bounds custom rules so fleets fit the grid and salvos stay playable
+pub(crate) fn validate_rules(rules: &GameRules) -> Result<()> {
+    if !(MIN_GRID_SIZE..=GRID_SIZE as u8).contains(&rules.grid_size) {
+        return Err(BattleshipError::InvalidRules.into());
+    }
+    let fleet_ok = |fleet: &[u8]| {
+        (1..=MAX_SHIPS).contains(&fleet.len())
+            && fleet
+                .iter()
+                .all(|&len| (1..=rules.grid_size).contains(&len))
+            && fleet_cells(fleet) <= MAX_FLEET_CELLS
+    };
+    let salvo_ok = |shots: u8| (1..=MAX_SALVO).contains(&shots);
+    if !fleet_ok(&rules.fleet_a)
+        || !fleet_ok(&rules.fleet_b)
+        || !salvo_ok(rules.shots_per_turn_a)
+        || !salvo_ok(rules.shots_per_turn_b)
+        || rules.obstacles > MAX_OBSTACLES
+        || (rules.sealed_obstacles && rules.obstacles == 0)
+    {
+        return Err(BattleshipError::InvalidRules.into());
+    }
+    Ok(())
+}
+
+/// Scatters `rules.obstacles` blocked cells over the playable area,
+/// deterministically from the game address. Both boards share the layout.
+/// Sealed obstacles are left to the players' `RAND_OBSTACLES` round, drawn
+/// after both fleets are placed. That round is commit-reveal, not MPC: the
+/// second revealer sees the map before it lands and can stall into a
+/// forfeit rather than play it.
+pub(crate) fn obstacle_grid(game_address: &[u8; 32], rules: &GameRules) -> [u8; GRID_CELLS] {
+    let mut grid = [CELL_EMPTY; GRID_CELLS];
+    if !rules.sealed_obstacles {
+        let mut other = [CELL_EMPTY; GRID_CELLS];
+        scatter_obstacles(&mut grid, &mut other, game_address, rules);
+    }
+    grid
+}
+
+/// Blocks up to `rules.obstacles` cells that are empty on both grids,
+/// picked from the hash of `seed`
+pub(crate) fn scatter_obstacles(
+    grid_a: &mut [u8; GRID_CELLS],
+    grid_b: &mut [u8; GRID_CELLS],
+    seed: &[u8; 32],
+    rules: &GameRules,
+) {
+    let size = rules.grid_size as usize;
+    let seed = hashv(&[b"obstacles", seed]).to_bytes();
+    let mut placed = 0;
+    // Each seed byte is a candidate cell; taken cells are skipped
+    for byte in seed {
+        if placed >= rules.obstacles {
+            break;
+        }
+        let cell = byte as usize % (size * size);
+        let index = (cell / size) * GRID_SIZE + cell % size;
+        if grid_a[index] == CELL_EMPTY && grid_b[index] == CELL_EMPTY {
+            grid_a[index] = CELL_BLOCKED;
+            grid_b[index] = CELL_BLOCKED;
+            placed += 1;
+        }
+    }
+}
+
+/// Fires at a cell, marking it hit or missed. Obstacles stay as they are.
+/// Returns `CELL_HIT`, `CELL_MISS` or `CELL_BLOCKED`.
+pub(crate) fn resolve_shot(grid: &mut [u8; GRID_CELLS], hits: &mut u8, index: usize) -> Result<u8> {
+    match grid[index] {
+        CELL_BLOCKED => Ok(CELL_BLOCKED),
+        CELL_HIT | CELL_MISS => {
+            msg!(
+                "Cell ({}, {}) already attacked!",
+                index % GRID_SIZE,
+                index / GRID_SIZE
+            );
+            Err(BattleshipError::AlreadyAttacked.into())
+        }
+        CELL_SHIP => {
+            grid[index] = CELL_HIT;
+            *hits += 1;
+            Ok(CELL_HIT)
+        }
+        _ => {
+            grid[index] = CELL_MISS;
+            Ok(CELL_MISS)
+        }
+    }
+}
@@ -0,0 +1,338 @@
+//! Seeds, limits and enum-like values shared by the program and clients
+
+use crate::*;
+
+/// Grid constants
+pub const GRID_SIZE: usize = 5;
+pub const GRID_CELLS: usize = GRID_SIZE * GRID_SIZE; // 25 cells
+pub const SHIP_LENGTH: usize = 4;
+/// Total shots (both players) after which an undecided game is a draw
+pub const MAX_MOVES: u16 = 40;
+/// Slots a player has to act before anyone can finalize the game against
+/// them (~10 minutes)
+pub const TURN_TIMEOUT_SLOTS: u64 = 1_500;
+/// Slots a game can wait for Player B before anyone may expire it (~1 day)
+pub const OPEN_GAME_EXPIRY_SLOTS: u64 = 216_000;
+/// Slots a wagered game can wait for Player B before anyone may reclaim
+/// the escrow for Player A (~1 hour)
+pub const STALE_WAGER_SLOTS: u64 = 9_000;
+/// Unfinished games one profile may hold at once, unless the protocol config
+/// sets another limit
+pub const DEFAULT_MAX_ACTIVE_GAMES: u16 = 10;
+/// Slots a `ProofAttestation` can be consumed for after it is made (~1 minute)
+pub const ATTESTATION_TIMEOUT_SLOTS: u64 = 150;
+/// Slots an off-chain settlement stays open to a longer transcript (~20 minutes)
+pub const CHALLENGE_WINDOW_SLOTS: u64 = 3_000;
+
+/// Address seed prefix for game accounts
+pub const GAME_SEED: &[u8] = b"battleship";
+/// Address seed prefix for per-player views (followed by the game address and seat)
+pub const VIEW_SEED: &[u8] = b"view";
+/// Address seed prefix for game chats (followed by the game address)
+pub const CHAT_SEED: &[u8] = b"chat";
+/// Address seed prefix for player profiles (followed by the owner)
+pub const PROFILE_SEED: &[u8] = b"profile";
+/// Address seed prefix for fleet presets (followed by the preset id)
+pub const PRESET_SEED: &[u8] = b"fleet_preset";
+/// Address seed prefix for game results (followed by the game address)
+pub const RESULT_SEED: &[u8] = b"result";
+/// Address seed prefix for achievement records (followed by the owner)
+pub const ACHIEVEMENTS_SEED: &[u8] = b"achievements";
+/// Address seed prefix for badges (followed by the owner and badge kind)
+pub const BADGE_SEED: &[u8] = b"badge";
+/// Badge kinds, also the bit each sets in `Achievements::badges`
+pub const BADGE_FIRST_WIN: u8 = 0;
+pub const BADGE_WIN_STREAK: u8 = 1;
+pub const BADGE_PERFECT_GAME: u8 = 2;
+/// Wins in a row that earn `BADGE_WIN_STREAK`
+pub const WIN_STREAK_BADGE_WINS: u16 = 10;
+/// Profile milestones, also the bit each sets in `PlayerProfile::milestones`
+pub const MILESTONE_FIRST_WIN: u8 = 0;
+pub const MILESTONE_TEN_GAMES: u8 = 1;
+pub const MILESTONE_HUNDRED_GAMES: u8 = 2;
+pub const MILESTONE_FIVE_STREAK: u8 = 3;
+pub const MILESTONE_TEN_STREAK: u8 = 4;
+/// Address seed of the protocol-wide `GlobalStats` singleton
+pub const GLOBAL_STATS_SEED: &[u8] = b"global_stats";
+/// Address seed prefix for rivalries (followed by both players, lower
+/// pubkey first)
+pub const RIVALRY_SEED: &[u8] = b"rivalry";
+/// Address seed prefix for bans (followed by the banned key)
+pub const BAN_SEED: &[u8] = b"ban";
+/// Address seed prefix for display names (followed by the name)
+pub const NAME_SEED: &[u8] = b"name";
+/// Display names are `MIN_NAME_LEN`-`MAX_NAME_LEN` bytes of a-z, 0-9 and _
+pub const MIN_NAME_LEN: usize = 3;
+pub const MAX_NAME_LEN: usize = 20;
+/// Address seed prefix for clubs (followed by the club name, which follows
+/// the display-name rules)
+pub const CLUB_SEED: &[u8] = b"club";
+/// Address seed prefix for club memberships (followed by the member)
+pub const CLUB_MEMBER_SEED: &[u8] = b"club_member";
+/// Address seed prefix for club result claims (followed by the result address and member)
+pub const CLUB_CLAIM_SEED: &[u8] = b"club_claim";
+/// Address seed prefix for friend lists (followed by the owner)
+pub const FRIEND_LIST_SEED: &[u8] = b"friend_list";
+/// Most friends, and most pending invites, one friend list holds
+pub const MAX_FRIENDS: usize = 32;
+pub const MAX_PENDING_INVITES: usize = 16;
+/// Address seed prefix for match-history pages (followed by the owner and
+/// the page number as u32 LE)
+pub const MATCH_HISTORY_SEED: &[u8] = b"match_history";
+/// Results one match-history page holds before spilling into the next
+pub const MAX_HISTORY_ENTRIES: usize = 16;
+/// Address seed prefix for loyalty points (followed by the owner)
+pub const LOYALTY_SEED: &[u8] = b"loyalty";
+/// Address seed prefix for loyalty claims (followed by the game address and player)
+pub const LOYALTY_CLAIM_SEED: &[u8] = b"loyalty_claim";
+/// Loyalty points for a finished game, and the bonus for winning it
+pub const LOYALTY_POINTS_PLAYED: u64 = 1;
+pub const LOYALTY_POINTS_WIN_BONUS: u64 = 2;
+/// Shots both players must have fired for a game to earn loyalty points
+pub const LOYALTY_MIN_MOVES: u16 = 20;
+/// Recent opponents a game must not repeat to earn loyalty points
+pub const LOYALTY_RECENT_OPPONENTS: usize = 4;
+/// Address seed prefix for campaigns (followed by the owner and campaign id)
+pub const CAMPAIGN_SEED: &[u8] = b"campaign";
+/// Address seed prefix for matchmaking tickets (followed by the owner and ticket id)
+pub const MATCH_TICKET_SEED: &[u8] = b"match_ticket";
+/// Address seed prefix for rating-bucket queues (followed by the bucket)
+pub const BUCKET_QUEUE_SEED: &[u8] = b"bucket_queue";
+/// Address seed prefix of the `PlayQueue`s (followed by the WAGER_MODE_*
+/// lobby, casual or ranked, then the credential issuer of verified queues)
+pub const PLAY_QUEUE_SEED: &[u8] = b"play_queue";
+/// Address seed prefix for credentials (followed by the issuer and holder)
+pub const CREDENTIAL_SEED: &[u8] = b"credential";
+/// Address seed of the singleton `OracleConfig`
+pub const ORACLE_CONFIG_SEED: &[u8] = b"oracle_config";
+/// Address seed of the singleton `ProtocolConfig`
+pub const PROTOCOL_CONFIG_SEED: &[u8] = b"protocol_config";
+/// Address seed prefix for rematches (followed by the previous game's address)
+pub const REMATCH_SEED: &[u8] = b"rematch";
+/// Address seed prefix for batch games' committed plans (followed by the game address)
+pub const BATCH_PLANS_SEED: &[u8] = b"batch_plans";
+/// Address seed prefix for registered proof verifiers (followed by the
+/// circuit id, backend and version)
+pub const VERIFIER_SEED: &[u8] = b"verifier";
+/// Address seed prefix for proof attestations (followed by the owner and
+/// attestation id)
+pub const ATTESTATION_SEED: &[u8] = b"attestation";
+/// PDA seed prefix for a game's deposit vault (followed by the game address)
+pub const VAULT_SEED: &[u8] = b"vault";
+/// Lamports each player locks at create/join, refunded on a clean finish and
+/// forfeited to the opponent when the game is lost on time
+pub const GAME_DEPOSIT_LAMPORTS: u64 = 10_000_000;
+/// Smallest wager `create_game_with_wager` takes
+pub const MIN_WAGER_LAMPORTS: u64 = 1_000_000;
+/// Highest protocol fee the admin can set, in basis points of a wager pot
+pub const MAX_FEE_BPS: u16 = 1_000;
+/// Wagered game modes, each with its own `ModeRake` in the `ProtocolConfig`
+pub const WAGER_MODE_CASUAL: u8 = 0;
+pub const WAGER_MODE_RANKED: u8 = 1;
+pub const WAGER_MODE_TOURNAMENT: u8 = 2;
+pub const WAGER_MODE_COUNT: usize = 3;
+/// PDA seed prefix for the owner of a game's escrowed compressed tokens
+/// (followed by the game address)
+pub const TOKEN_ESCROW_SEED: &[u8] = b"token_escrow";
+/// Light's compressed token program, which holds token wagers
+pub const COMPRESSED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("cTokenmWW8bLPjZEBAUgYy3zKxQZW6VKi7bqNFEVv5m");
+/// PDA seed of the mint authority of the protocol's reward token
+pub const REWARD_AUTHORITY_SEED: &[u8] = b"reward_authority";
+/// PDA seed prefix for a game's sealed-wager collateral (followed by the game address)
+pub const SEALED_VAULT_SEED: &[u8] = b"sealed_vault";
+/// Address seed prefix for sealed wagers (followed by the game address)
+pub const SEALED_WAGER_SEED: &[u8] = b"sealed_wager";
+/// Address seed prefix for a game's viewer counter (followed by the game address)
+pub const VIEWERS_SEED: &[u8] = b"viewers";
+/// Address seed prefix for registered watchers (followed by the game address and watcher)
+pub const WATCHER_SEED: &[u8] = b"watcher";
+/// PDA seed prefix for a game's spectator side-bet pool (followed by the game address)
+pub const SIDE_VAULT_SEED: &[u8] = b"side_vault";
+/// Address seed prefix for side-bet pools (followed by the game address)
+pub const SIDE_POOL_SEED: &[u8] = b"side_pool";
+/// Address seed prefix for side bets (followed by the game address and bettor)
+pub const SIDE_BET_SEED: &[u8] = b"side_bet";
+/// PDA seed prefix for a game's betting-market pool (followed by the game address)
+pub const MARKET_VAULT_SEED: &[u8] = b"market_vault";
+/// Address seed prefix for betting markets (followed by the game address)
+pub const MARKET_SEED: &[u8] = b"bet_market";
+/// Address seed prefix for market bets (followed by the game address and bettor)
+pub const MARKET_BET_SEED: &[u8] = b"market_bet";
+/// PDA seed prefix for a referrer's claimable fee share (followed by the referrer)
+pub const REFERRAL_VAULT_SEED: &[u8] = b"referral_vault";
+/// Share of the protocol fee a referred player generates that goes to their
+/// referrer, in basis points
+pub const REFERRAL_SHARE_BPS: u16 = 2_000;
+/// PDA seed of the progressive jackpot vault
+pub const JACKPOT_VAULT_SEED: &[u8] = b"jackpot_vault";
+/// Slice of every settled wager pot routed into the jackpot, in basis points
+pub const JACKPOT_BPS: u16 = 50;
+/// Address seed prefix for prize pools (followed by the organizer and pool id)
+pub const PRIZE_POOL_SEED: &[u8] = b"prize_pool";
+/// Address seed prefix for tournaments (followed by the organizer and tournament id)
+pub const TOURNAMENT_SEED: &[u8] = b"tournament";
+/// Most players one tournament bracket takes
+pub const MAX_TOURNAMENT_PLAYERS: usize = 16;
+/// Tournament phases
+pub const TOURNAMENT_REGISTERING: u8 = 0;
+pub const TOURNAMENT_RUNNING: u8 = 1;
+pub const TOURNAMENT_FINISHED: u8 = 2;
+/// Address seed prefix for seasons (followed by the season)
+pub const SEASON_SEED: &[u8] = b"season";
+/// Address seed prefix for season leaderboards (followed by the season)
+pub const LEADERBOARD_SEED: &[u8] = b"leaderboard";
+/// Address seed prefix for final season standings (followed by the season)
+pub const SEASON_ARCHIVE_SEED: &[u8] = b"season_archive";
+/// Address seed prefix for a player's season score (followed by the season and player)
+pub const SEASON_SCORE_SEED: &[u8] = b"season_score";
+/// Players a season leaderboard ranks
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Address seed prefix for season rewards pools (followed by the season)
+pub const SEASON_REWARDS_SEED: &[u8] = b"season_rewards";
+/// PDA seed prefix for a prize pool's entry fees (followed by the pool address)
+pub const PRIZE_VAULT_SEED: &[u8] = b"prize_vault";
+/// Most entrants one prize pool holds
+pub const MAX_PRIZE_ENTRANTS: usize = 32;
+/// Most paid places in a prize split
+pub const MAX_PRIZE_PLACES: usize = 8;
+/// Stake of every side bet; only the side it backs is sealed
+pub const SIDE_BET_STAKE_LAMPORTS: u64 = 100_000_000;
+/// Slots after the game's result is final for bettors to reveal their side
+pub const SIDE_BET_REVEAL_SLOTS: u64 = 3_000;
+/// Collateral every sealed-wager player locks, whatever they actually bet,
+/// so the vault balance says nothing about the stake
+pub const WAGER_COLLATERAL_LAMPORTS: u64 = 1_000_000_000;
+/// Slots after the last move for both players to reveal their wager, for
+/// games on the default turn timeout; others reveal within their own
+pub const WAGER_REVEAL_SLOTS: u64 = 3_000;
+/// Address seed prefix for commit-reveal randomness rounds (followed by the
+/// game address and round id)
+pub const RAND_SEED: &[u8] = b"rand";
+/// Address seed prefix for board backups (followed by the game address and owner)
+pub const BACKUP_SEED: &[u8] = b"board_backup";
+/// Address seed prefix for move logs (followed by the game address)
+pub const MOVE_LOG_SEED: &[u8] = b"move_log";
+/// Address seed prefix for session keys (followed by owner and session key)
+pub const SESSION_SEED: &[u8] = b"session";
+/// Longest a session key can stay valid (~1 day of slots)
+pub const MAX_SESSION_SLOTS: u64 = 216_000;
+
+/// Cell states
+pub const CELL_EMPTY: u8 = 0;
+pub const CELL_SHIP: u8 = 1;
+pub const CELL_HIT: u8 = 2;
+pub const CELL_MISS: u8 = 3;
+pub const CELL_BLOCKED: u8 = 4; // Obstacle: holds no ship, every shot at it is wasted
+
+/// Move log result for a turn skipped with `force_skip_turn`
+pub const RESULT_SKIPPED: u8 = 0;
+
+/// Match ticket states
+pub const TICKET_QUEUED: u8 = 0;
+pub const TICKET_MATCHED: u8 = 1;
+
+// Randomness round ids with a built-in use; others are free for clients
+pub const RAND_FIRST_TURN: u8 = 0; // Decides who fires first, before the first shot
+pub const RAND_OBSTACLES: u8 = 1; // Lays out a sealed-obstacle game's map once both fleets are placed
+pub const RAND_REMATCH: u8 = 2; // Seeds a finished game's rematch: seats, first turn and obstacles
+
+// Circuits with a registered verifier program
+pub const CIRCUIT_BOARD: u8 = 0; // circuits/battleship: public output is the board hash
+pub const CIRCUIT_SHOT: u8 = 1; // circuits/battleship_shot: board hash, cell index, hit
+pub const CIRCUIT_FLEET: u8 = 2; // circuits/battleship_fleet: board hash, grid size, fleet
+pub const CIRCUIT_RATING: u8 = 3; // circuits/battleship_rating: rating commitment, min, max
+pub const CIRCUIT_AFLOAT: u8 = 4; // circuits/battleship_afloat: board hash, opponent's targeted mask
+pub const CIRCUIT_COUNT: usize = 5;
+
+/// `boards_verified` once both players have proven their board
+pub const BOARDS_VERIFIED: u8 = 0b11;
+
+// Proof systems a verifier program can check
+pub const PROOF_BACKEND_GROTH16: u8 = 0; // Verifying key compiled into the verifier
+pub const PROOF_BACKEND_ULTRA_HONK: u8 = 1; // Barretenberg; verifying key read from an account
+
+/// Game modes
+pub const GAME_MODE_PVP: u8 = 0;
+pub const GAME_MODE_PVE: u8 = 1;
+
+/// How a finished game ended
+pub const END_REASON_NONE: u8 = 0;
+pub const END_REASON_SUNK: u8 = 1;
+pub const END_REASON_MOVE_LIMIT: u8 = 2;
+pub const END_REASON_TIMEOUT: u8 = 3;
+pub const END_REASON_SURVIVED: u8 = 4;
+pub const END_REASON_VOID: u8 = 5; // Nobody was at fault; deposits are split
+pub const END_REASON_RESIGN: u8 = 6;
+pub const END_REASON_CANCELLED: u8 = 7; // Nobody joined; Player A's stakes are refunded
+
+/// Win conditions
+pub const WIN_CONDITION_SINK: u8 = 0;
+pub const WIN_CONDITION_BLOCKADE: u8 = 1;
+/// Shots a blockade defender has to survive
+pub const BLOCKADE_TURNS: u8 = 12;
+
+/// Lobby tag allow-lists: regions (REGION_ANY matches every region) and
+/// speed formats
+pub const REGION_ANY: u8 = 0;
+pub const REGION_AMERICAS: u8 = 1;
+pub const REGION_EUROPE: u8 = 2;
+pub const REGION_ASIA_PACIFIC: u8 = 3;
+pub const REGION_COUNT: u8 = 4;
+pub const SPEED_STANDARD: u8 = 0;
+pub const SPEED_BLITZ: u8 = 1;
+pub const SPEED_CORRESPONDENCE: u8 = 2;
+pub const SPEED_COUNT: u8 = 3;
+
+/// Custom rule limits
+pub const MIN_GRID_SIZE: u8 = 3;
+pub const MAX_SHIPS: usize = 4;
+pub const MAX_FLEET_CELLS: u8 = 12;
+pub const MAX_SALVO: u8 = 3;
+pub const MAX_OBSTACLES: u8 = 4;
+
+/// Games in a campaign; see `campaign_rules` for how each stage escalates
+pub const CAMPAIGN_STAGES: u8 = 4;
+/// Campaign points for winning a stage, multiplied by the stage number.
+/// A draw is worth a quarter of that.
+pub const CAMPAIGN_WIN_POINTS: u32 = 100;
+
+/// Chat bounds. Kept small since the chat and game both ride in the
+/// instruction data of every `send_chat`.
+pub const MAX_CHAT_MESSAGES: usize = 4;
+pub const MAX_CHAT_LEN: usize = 32;
+/// Largest encrypted chat message: `MAX_CHAT_LEN` plus the cipher's tag
+pub const MAX_SEALED_CHAT_LEN: usize = MAX_CHAT_LEN + 16;
+
+/// Largest encrypted board backup: every ship placement plus the salt,
+/// with room for the cipher's tag
+pub const MAX_BACKUP_LEN: usize = 96;
+
+/// Number of emotes clients can render; ids are `0..EMOTE_COUNT`
+pub const EMOTE_COUNT: u8 = 8;
+/// Slots a player waits between reactions (~10 seconds)
+pub const REACTION_COOLDOWN_SLOTS: u64 = 25;
+
+/// Ranked queue brackets (inclusive); tickets only pair within a bracket
+pub const RATING_BRACKETS: [(u16, u16); 4] =
+    [(0, 1099), (1100, 1399), (1400, 1799), (1800, u16::MAX)];
+/// Rating every new profile starts at
+pub const INITIAL_RATING: u16 = 1200;
+/// Rating gap worth one bonus shot per turn in handicap games
+pub const HANDICAP_STEP: u16 = 200;
+/// Most rating points one ranked game moves
+pub const RATING_K: i32 = 32;
+
+/// Board modes
+/// Public: grids are stored in the account and resolved by the program.
+/// Merkle: only a Merkle root over the cells is stored; the defender reveals
+/// each attacked cell with a Merkle path.
+pub const BOARD_MODE_PUBLIC: u8 = 0;
+pub const BOARD_MODE_MERKLE: u8 = 1;
+pub const BOARD_MODE_ZK: u8 = 3;
+pub const BOARD_MODE_BATCH: u8 = 4; // Whole game played out from both players' revealed plans
+/// Depth of the board Merkle tree (25 cells padded to 32 leaves)
+pub const BOARD_TREE_DEPTH: usize = 5;
+
+/// Player B key used for the bot seat in PvE games
+pub const BOT_PLAYER: Pubkey = crate::ID;
@@ -0,0 +1,237 @@
+//! Account contexts the instructions share
+
+use crate::*;
+
+/// The player signing the move, and optionally a separate account (a
+/// relayer or dapp) paying the Light fees for it
+#[derive(Accounts)]
+pub struct GameAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+}
+
+/// Gives each context `payer()`: who pays the Light system program's and
+/// compressed token program's fees, the fee payer if one signed, otherwise
+/// the signer
+macro_rules! impl_payer {
+    ($($accounts:ident),* $(,)?) => {
+        $(
+            impl<'info> $accounts<'info> {
+                pub fn payer(&self) -> &AccountInfo<'info> {
+                    match &self.fee_payer {
+                        Some(fee_payer) => fee_payer.as_ref(),
+                        None => self.signer.as_ref(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_payer!(
+    GameAccounts,
+    AdminAccounts,
+    AdminPayoutAccounts,
+    DepositAccounts,
+    ClaimDepositAccounts,
+    TokenClaimAccounts,
+    RewardMintAccounts,
+    RefundAccounts,
+    JackpotAccounts,
+    SideBetPayoutAccounts,
+    VerifierAccounts,
+    SettleAccounts,
+);
+
+/// Admin instructions: the signer must be the program's upgrade authority
+#[derive(Accounts)]
+pub struct AdminAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Battleship>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(signer.key())
+            @ BattleshipError::NotAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+}
+
+/// `AdminAccounts` plus a vault to pay out of
+#[derive(Accounts)]
+pub struct AdminPayoutAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Battleship>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(signer.key())
+            @ BattleshipError::NotAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    /// Checked against the pool address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// `GameAccounts` plus the game's deposit vault
+#[derive(Accounts)]
+pub struct DepositAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// Checked against the game address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Vault and both players for paying out deposits
+#[derive(Accounts)]
+pub struct ClaimDepositAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// Checked against the game address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub player_a: SystemAccount<'info>,
+    #[account(mut)]
+    pub player_b: SystemAccount<'info>,
+    /// Wagered games with a fee: checked against the game's treasury
+    #[account(mut)]
+    pub treasury: Option<SystemAccount<'info>>,
+    /// Referral vaults of the players' linked referrers, if any
+    #[account(mut)]
+    pub referral_vault_a: Option<SystemAccount<'info>>,
+    #[account(mut)]
+    pub referral_vault_b: Option<SystemAccount<'info>>,
+    /// Wagered games: the jackpot vault
+    #[account(mut)]
+    pub jackpot: Option<SystemAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// `DepositAccounts` plus the compressed token program, for token wagers
+#[derive(Accounts)]
+pub struct TokenWagerAccounts<'info> {
+    pub deposit: DepositAccounts<'info>,
+    /// CHECK: constrained to the compressed token program
+    #[account(address = COMPRESSED_TOKEN_PROGRAM_ID)]
+    pub compressed_token_program: UncheckedAccount<'info>,
+}
+
+/// A game's token escrow and the compressed token program, for paying out
+/// token wagers
+#[derive(Accounts)]
+pub struct TokenClaimAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// CHECK: checked against the game address in the instruction
+    pub escrow: UncheckedAccount<'info>,
+    /// CHECK: constrained to the compressed token program
+    #[account(address = COMPRESSED_TOKEN_PROGRAM_ID)]
+    pub compressed_token_program: UncheckedAccount<'info>,
+}
+
+/// The reward token's mint authority and the compressed token program, for
+/// minting win rewards
+#[derive(Accounts)]
+pub struct RewardMintAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// CHECK: checked against the reward authority PDA in the instruction
+    pub mint_authority: UncheckedAccount<'info>,
+    /// CHECK: constrained to the compressed token program
+    #[account(address = COMPRESSED_TOKEN_PROGRAM_ID)]
+    pub compressed_token_program: UncheckedAccount<'info>,
+}
+
+/// A waiting game's vault and its creator, for refunding a cancelled game
+#[derive(Accounts)]
+pub struct RefundAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// Checked against the game address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub player_a: SystemAccount<'info>,
+    /// Sponsored games: checked against the game's sponsor
+    #[account(mut)]
+    pub sponsor: Option<SystemAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+/// The jackpot vault and the perfect game's winner
+#[derive(Accounts)]
+pub struct JackpotAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// Checked against the jackpot PDA in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub winner: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Side-bet vault and the bettor being paid
+#[derive(Accounts)]
+pub struct SideBetPayoutAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// Checked against the game address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    #[account(mut)]
+    pub bettor: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// `GameAccounts` plus the program that verifies the submitted proof
+#[derive(Accounts)]
+pub struct VerifierAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// CHECK: checked against the registered `CircuitVerifier`
+    #[account(executable)]
+    pub verifier_program: UncheckedAccount<'info>,
+    /// CHECK: checked against the verifier's `verifying_key`; only backends
+    /// that read the key from an account need it
+    pub verifying_key: Option<UncheckedAccount<'info>>,
+}
+
+/// `GameAccounts` plus the instructions sysvar, for reading ed25519 checks
+#[derive(Accounts)]
+pub struct SettleAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub fee_payer: Option<Signer<'info>>,
+    /// CHECK: constrained to the instructions sysvar
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
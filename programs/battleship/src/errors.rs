@@ -0,0 +1,221 @@
+//! Program errors
+
+use crate::*;
+
+#[error_code]
+pub enum BattleshipError {
+    #[msg("Invalid position: coordinates out of bounds")]
+    InvalidPosition,
+    #[msg("Ship placement out of bounds")]
+    ShipOutOfBounds,
+    #[msg("Cell already attacked")]
+    AlreadyAttacked,
+    #[msg("Game is already over")]
+    GameOver,
+    #[msg("Not player's turn")]
+    NotPlayerTurn,
+    #[msg("Move log does not belong to this game")]
+    MoveLogMismatch,
+    #[msg("Replayed moves do not match the game state")]
+    ReplayMismatch,
+    #[msg("Instruction does not match the game's board mode")]
+    WrongBoardMode,
+    #[msg("A shot is already waiting for the defender")]
+    ShotPending,
+    #[msg("No shot is waiting for a reveal")]
+    NoPendingShot,
+    #[msg("Only the defending player can reveal")]
+    NotDefender,
+    #[msg("Cell reveal does not match the committed board")]
+    InvalidCellReveal,
+    #[msg("Signer is not an authorized session key for this player")]
+    InvalidSessionKey,
+    #[msg("Session key has expired")]
+    SessionExpired,
+    #[msg("Session expiry is in the past or too far in the future")]
+    InvalidSessionExpiry,
+    #[msg("The turn clock has not run out yet")]
+    TurnNotExpired,
+    #[msg("Instruction is not available in this game mode")]
+    WrongGameMode,
+    #[msg("Missing ed25519 signature from a player")]
+    MissingSignature,
+    #[msg("An off-chain settlement is still in its challenge window")]
+    ChallengeWindowOpen,
+    #[msg("No off-chain settlement is open to challenge")]
+    NoOpenSettlement,
+    #[msg("Challenge transcript must be longer than the settled one")]
+    TranscriptNotLonger,
+    #[msg("Vault account does not belong to this game")]
+    WrongVault,
+    #[msg("Player account does not match the game")]
+    WrongPlayerAccount,
+    #[msg("Game has not finished yet")]
+    GameNotFinished,
+    #[msg("No deposits left to claim")]
+    NothingToClaim,
+    #[msg("Ships overlap")]
+    ShipsOverlap,
+    #[msg("Invalid game rules or fleet placement")]
+    InvalidRules,
+    #[msg("Player did not win this game")]
+    NotWinner,
+    #[msg("Signer is not the program admin")]
+    NotAdmin,
+    #[msg("Fleet preset is retired")]
+    PresetInactive,
+    #[msg("Handicap games need both player profiles")]
+    ProfilesRequired,
+    #[msg("Profile does not belong to this player")]
+    WrongProfile,
+    #[msg("Signer is not a player in this game")]
+    NotAPlayer,
+    #[msg("Chat does not belong to this game")]
+    ChatMismatch,
+    #[msg("Chat message is empty or too long")]
+    ChatTooLong,
+    #[msg("Only one chat message per player per move")]
+    ChatRateLimited,
+    #[msg("Unknown emote id")]
+    InvalidEmote,
+    #[msg("Reacting too fast")]
+    ReactionRateLimited,
+    #[msg("Cell is blocked by an obstacle")]
+    CellBlocked,
+    #[msg("Player views are already open")]
+    ViewsAlreadyOpen,
+    #[msg("This game keeps player views; pass both")]
+    ViewsRequired,
+    #[msg("Player view does not belong to this game")]
+    ViewMismatch,
+    #[msg("Signer does not own this campaign")]
+    NotCampaignOwner,
+    #[msg("Campaign has no stages left")]
+    CampaignComplete,
+    #[msg("Finish the current campaign game first")]
+    CampaignGameOpen,
+    #[msg("Game is not the campaign's current game")]
+    CampaignMismatch,
+    #[msg("Signer is not the ticket's oracle")]
+    NotOracle,
+    #[msg("Waiting for the coin flip")]
+    CoinFlipPending,
+    #[msg("Match ticket is not in the expected state")]
+    TicketNotQueued,
+    #[msg("Signer does not own this match ticket")]
+    NotTicketOwner,
+    #[msg("Game is reserved for another player")]
+    GameReserved,
+    #[msg("Sealed wager can't be opened or matched now")]
+    WagerClosed,
+    #[msg("Sealed wager does not belong to this game")]
+    WagerMismatch,
+    #[msg("Reveal does not open the sealed commitment")]
+    InvalidWagerReveal,
+    #[msg("Verifier is not registered for this circuit")]
+    VerifierMismatch,
+    #[msg("Verifying key version has been rotated out")]
+    VerifierRetired,
+    #[msg("Both boards must be proven before the first shot")]
+    BoardNotVerified,
+    #[msg("Randomness round does not belong to this game")]
+    RandRoundMismatch,
+    #[msg("Randomness round can't take this step now")]
+    RandClosed,
+    #[msg("Reveal does not open the randomness commitment")]
+    InvalidRandReveal,
+    #[msg("Board backup is empty or too long")]
+    BackupTooLong,
+    #[msg("Rating bracket does not match")]
+    BracketMismatch,
+    #[msg("Both players need a registered chat key")]
+    ChatKeyMissing,
+    #[msg("Chat key is already registered")]
+    ChatKeyRegistered,
+    #[msg("Attestation is not the signer's or is for another proof")]
+    AttestationMismatch,
+    #[msg("Attestation has expired")]
+    AttestationExpired,
+    #[msg("Side betting is closed")]
+    SideBetClosed,
+    #[msg("Side bet does not belong to this pool")]
+    SideBetMismatch,
+    #[msg("Rematch round has not been revealed yet")]
+    RematchPending,
+    #[msg("Wager is below the minimum")]
+    InvalidWager,
+    #[msg("Token inputs don't cover the transfer")]
+    InsufficientTokens,
+    #[msg("Protocol fee is above the maximum")]
+    InvalidFee,
+    #[msg("Treasury account does not match the game")]
+    WrongTreasury,
+    #[msg("Prize split is invalid")]
+    InvalidPrizeSplit,
+    #[msg("Prize pool is closed")]
+    PrizePoolClosed,
+    #[msg("Player is not eligible for this prize pool")]
+    PrizePoolMismatch,
+    #[msg("Only the organizer can do this")]
+    NotOrganizer,
+    #[msg("A raise is waiting for an answer")]
+    RaisePending,
+    #[msg("No raise can be made or answered here")]
+    RaiseNotAllowed,
+    #[msg("Sponsor does not match the game")]
+    SponsorMismatch,
+    #[msg("Prize must be more than zero")]
+    InvalidPrize,
+    #[msg("Betting market is closed")]
+    MarketClosed,
+    #[msg("Bet does not match the market")]
+    MarketMismatch,
+    #[msg("Ranked games need the protocol config")]
+    ProtocolConfigRequired,
+    #[msg("Reward tokens are turned off")]
+    RewardsDisabled,
+    #[msg("Mint is not the reward token")]
+    WrongMint,
+    #[msg("Game does not earn loyalty points")]
+    LoyaltyIneligible,
+    #[msg("Season does not match or is not in the right phase")]
+    SeasonMismatch,
+    #[msg("Tournament does not match or is not in the right phase")]
+    TournamentMismatch,
+    #[msg("Players are not friends, or there is no such invite")]
+    NotFriends,
+    #[msg("Friend list is full")]
+    FriendListFull,
+    #[msg("Profile holds too many active games")]
+    ActiveGameLimit,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("Staking needs proof the player isn't banned")]
+    BanCheckRequired,
+    #[msg("Name is too short, too long or has characters outside a-z, 0-9 and _")]
+    InvalidName,
+    #[msg("Only the name's owner can do this")]
+    NotNameOwner,
+    #[msg("Result is already in this match-history page")]
+    AlreadyInHistory,
+    #[msg("Rating is sealed, or not idle long enough to decay")]
+    RatingNotIdle,
+    #[msg("Lobby tags are outside the allow-lists")]
+    InvalidLobbyTags,
+    #[msg("Lobby tags do not match the filter")]
+    LobbyTagsMismatch,
+    #[msg("Players can't spectate their own game")]
+    NotASpectator,
+    #[msg("Viewer counter does not belong to this game")]
+    ViewersMismatch,
+    #[msg("Membership does not belong to this club, or the game predates it")]
+    ClubMismatch,
+    #[msg("Credential is missing, expired or from the wrong issuer")]
+    CredentialRequired,
+    #[msg("Batch plans do not belong to this game")]
+    BatchPlansMismatch,
+    #[msg("Reveal does not open the batch commitment")]
+    InvalidBatchReveal,
+    #[msg("Reveal does not open the side bet commitment")]
+    InvalidSideBetReveal,
+}
@@ -0,0 +1,153 @@
+//! Events emitted for indexers and clients
+
+use crate::*;
+
+/// Emitted whenever a game is created, by any create path
+#[event]
+pub struct GameCreated {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey, // Set when the game starts active, e.g. PvE or a paired queue
+    pub reserved_for: Pubkey, // Only this key may join, default = anyone
+    pub game_status: u8,
+    pub game_mode: u8,
+    pub board_mode: u8,
+    pub win_condition: u8,
+    pub wager: u64,
+    pub wager_mode: u8,
+    pub token_mint: Pubkey,
+    pub token_wager: u64,
+}
+
+/// Emitted when Player B joins and the game becomes active
+#[event]
+pub struct GameJoined {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+    pub wager: u64,
+    pub token_wager: u64,
+}
+
+/// Emitted whenever a game finishes, however it ended
+#[event]
+pub struct GameEnded {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+    pub game_status: u8,
+    pub end_reason: u8,
+    pub move_count: u16,
+    pub stats_a: ShotStats,
+    pub stats_b: ShotStats,
+    pub game_mode: u8,
+    pub wager: u64,
+    pub wager_mode: u8,
+    pub token_wager: u64,
+}
+
+/// Emitted when a game nobody joined is cancelled or expires
+#[event]
+pub struct GameCancelled {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub wager: u64,
+    pub token_wager: u64,
+    pub refunded: u64, // Lamports returned to Player A and any sponsor
+}
+
+/// Emitted when a game hits `MAX_MOVES` without a winner.
+/// Wagered games settle a draw by refunding both sides.
+#[event]
+pub struct GameDrawn {
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+    pub move_count: u16,
+}
+
+/// Emitted by every `watch_game`
+#[event]
+pub struct GameWatched {
+    pub game_address: [u8; 32],
+    pub watcher: Pubkey,
+    pub registered: bool,
+    pub views: u32,
+    pub watchers: u32,
+}
+
+/// Emitted when a ticket joins the queue
+#[event]
+pub struct MatchRequested {
+    pub ticket_address: [u8; 32],
+    pub owner: Pubkey,
+}
+
+/// Emitted when the oracle pairs two tickets
+#[event]
+pub struct MatchFound {
+    pub host: Pubkey,
+    pub guest: Pubkey,
+}
+
+/// Emitted when a ZK-game defender proves a ship cell is still un-hit
+#[event]
+pub struct FleetAfloat {
+    pub game_id: u64,
+    pub game_address: [u8; 32],
+    pub player: Pubkey,
+    pub move_count: u16, // Shots fired when the proof landed
+}
+
+/// A player's emote; see `react`
+#[event]
+pub struct Reaction {
+    pub game_id: u64,
+    pub game_address: [u8; 32],
+    pub sender: u8, // 1 = A, 2 = B
+    pub emote_id: u8,
+}
+
+/// Emitted when a member's game is added to their club's record
+#[event]
+pub struct ClubResultRecorded {
+    pub club_address: [u8; 32],
+    pub member: Pubkey,
+    pub result_address: [u8; 32],
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+/// Emitted when a settled game unlocks one of a player's MILESTONE_*
+#[event]
+pub struct AchievementUnlocked {
+    pub player: Pubkey,
+    pub milestone: u8,
+    pub game_address: [u8; 32],
+}
+
+/// Emitted when a recorded game updates a `Rivalry`
+#[event]
+pub struct RivalryUpdated {
+    pub player_one: Pubkey,
+    pub player_two: Pubkey,
+    pub wins_one: u32,
+    pub wins_two: u32,
+    pub draws: u32,
+    pub streak_holder: Pubkey,
+    pub streak: u16,
+    pub game_address: [u8; 32],
+}
+
+/// Emitted when a game is created as a challenge to a friend
+#[event]
+pub struct FriendChallenged {
+    pub game_address: [u8; 32],
+    pub from: Pubkey,
+    pub to: Pubkey,
+}
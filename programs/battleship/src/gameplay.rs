@@ -0,0 +1,483 @@
+//! Turn flow, move bookkeeping and game events shared by the game modes
+
+use crate::*;
+
+/// Cancels a game still waiting for Player B and refunds Player A's
+/// deposit and lamport wager from the vault, and any sponsor's prize
+pub(crate) fn close_open_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    stats: Option<GlobalStatsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 0 {
+        msg!("Only games still waiting for Player B can be cancelled");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    if ctx.accounts.player_a.key() != current_game.player_a {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+    let (vault, bump) = derive_vault_address(&account_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.game_status = 5; // Cancelled
+    game_account.end_reason = END_REASON_CANCELLED;
+    game_account.last_action_slot = Clock::get()?.slot;
+    let game_id = game_account.game_id;
+    let (wager, token_wager) = (game_account.wager, game_account.token_wager);
+    let prize = game_account.prize;
+    let counted = game_account.in_stats;
+    let sponsor = match (&ctx.accounts.sponsor, prize) {
+        (_, 0) => None,
+        (Some(sponsor), _) if sponsor.key() == game_account.sponsor => Some(sponsor),
+        _ => return Err(BattleshipError::SponsorMismatch.into()),
+    };
+
+    let cpi = new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?;
+    with_global_stats(cpi, stats, |stats| {
+        if counted {
+            stats.active_games = stats.active_games.saturating_sub(1);
+        }
+    })?
+    .invoke(light_cpi_accounts)?;
+
+    let refund = ctx.accounts.vault.lamports();
+    let seeds: &[&[u8]] = &[VAULT_SEED, &account_meta.address, &[bump]];
+    let refunds = [
+        (Some(&ctx.accounts.player_a), refund - prize.min(refund)),
+        (sponsor, prize.min(refund)),
+    ];
+    for (to, amount) in refunds {
+        let Some(to) = to.filter(|_| amount > 0) else {
+            continue;
+        };
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: to.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )?;
+    }
+
+    msg!("Game {} cancelled, {} lamports refunded", game_id, refund);
+    emit!(GameCancelled {
+        game_address: account_meta.address,
+        game_id,
+        player_a: ctx.accounts.player_a.key(),
+        wager,
+        token_wager,
+        refunded: refund,
+    });
+
+    Ok(())
+}
+
+/// A game with the move log that travels with it. The accounts share the
+/// CPI's single validity proof, so a move and the history it appends to
+/// update together or not at all.
+pub(crate) struct GameBatch {
+    game: LightAccount<GameState>,
+    log: Option<LightAccount<MoveLog>>,
+}
+
+impl GameBatch {
+    pub(crate) fn new(game: LightAccount<GameState>) -> Self {
+        Self { game, log: None }
+    }
+
+    pub(crate) fn log(mut self, log: LightAccount<MoveLog>) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Adds the game, then the log if present, to `cpi`. Accounts
+    /// created with the batch are outputs in that order; see
+    /// [`batch_new_addresses`].
+    pub(crate) fn append(self, cpi: LightSystemProgramCpi) -> Result<LightSystemProgramCpi> {
+        let mut cpi = cpi.with_light_account(self.game)?;
+        if let Some(log) = self.log {
+            cpi = cpi.with_light_account(log)?;
+        }
+        Ok(cpi)
+    }
+}
+
+/// New-address params for accounts created together, assigned to the CPI's
+/// outputs in the order their seeds are given (game first)
+pub(crate) fn batch_new_addresses(
+    address_tree_info: PackedAddressTreeInfo,
+    seeds: &[AddressSeed],
+) -> Vec<NewAddressParamsAssignedPacked> {
+    seeds
+        .iter()
+        .enumerate()
+        .map(|(index, seed)| {
+            address_tree_info.into_new_address_params_assigned_packed(*seed, Some(index as u8))
+        })
+        .collect()
+}
+
+/// Returns the player a move is made for: the signer itself, or the owner of
+/// the session key the signer holds
+pub(crate) fn acting_player(signer: &Pubkey, session: Option<&SessionAuth>) -> Result<Pubkey> {
+    let Some(session) = session else {
+        return Ok(*signer);
+    };
+    if session.account.session_key != *signer {
+        msg!("Signer is not the session key");
+        return Err(BattleshipError::InvalidSessionKey.into());
+    }
+    if Clock::get()?.slot >= session.account.expires_at_slot {
+        msg!("Session key expired");
+        return Err(BattleshipError::SessionExpired.into());
+    }
+    Ok(session.account.owner)
+}
+
+/// Adds the session account as read-only so the Light system program
+/// proves it still exists (i.e. it hasn't been revoked)
+pub(crate) fn with_session(
+    cpi: LightSystemProgramCpi,
+    session: Option<SessionAuth>,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    let Some(session) = session else {
+        return Ok(cpi);
+    };
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let session_account = LightAccount::<SessionKey>::new_read_only(
+        &crate::ID,
+        &session.meta,
+        session.account,
+        &tree_pubkeys,
+    )?;
+    Ok(cpi.with_light_account(session_account)?)
+}
+
+/// Adds both `PlayerView`s, rewritten from `game`. Games with open views
+/// must pass them; they are checked against the game they claim to mirror.
+pub(crate) fn with_views(
+    cpi: LightSystemProgramCpi,
+    game: &GameState,
+    game_address: &[u8; 32],
+    views: Option<PlayerViews>,
+) -> Result<LightSystemProgramCpi> {
+    let Some(views) = views else {
+        if game.views_open {
+            msg!("This game keeps player views; pass both");
+            return Err(BattleshipError::ViewsRequired.into());
+        }
+        return Ok(cpi);
+    };
+    if views.view_a.game_address != *game_address
+        || views.view_b.game_address != *game_address
+        || views.view_a.seat != 1
+        || views.view_b.seat != 2
+    {
+        return Err(BattleshipError::ViewMismatch.into());
+    }
+
+    let mut view_a = LightAccount::<PlayerView>::new_mut(&crate::ID, &views.meta_a, views.view_a)?;
+    refresh_view(&mut view_a, game);
+    let mut view_b = LightAccount::<PlayerView>::new_mut(&crate::ID, &views.meta_b, views.view_b)?;
+    refresh_view(&mut view_b, game);
+    Ok(cpi.with_light_account(view_a)?.with_light_account(view_b)?)
+}
+
+/// Copies what the view's player may see: their own board as is, and the
+/// opponent's with ships they haven't hit hidden
+pub(crate) fn refresh_view(view: &mut PlayerView, game: &GameState) {
+    let (own, target) = if view.seat == 1 {
+        (&game.grid_a, &game.grid_b)
+    } else {
+        (&game.grid_b, &game.grid_a)
+    };
+    view.own_grid = *own;
+    view.target_grid = target.map(|cell| if cell == CELL_SHIP { CELL_EMPTY } else { cell });
+    view.current_turn = game.current_turn;
+    view.game_status = game.game_status;
+    view.move_count = game.move_count;
+}
+
+/// Copies the config's default timeouts into a game it creates
+pub(crate) fn pin_timeouts(game: &mut GameState, config: &ProtocolConfig) {
+    game.turn_timeout_slots = config.turn_timeout_slots;
+    game.open_game_expiry_slots = config.open_game_expiry_slots;
+}
+
+/// Slots a player may stay idle in a game before the opponent can claim it
+pub(crate) fn turn_timeout(game: &GameState) -> u64 {
+    match game.turn_timeout_slots {
+        0 => TURN_TIMEOUT_SLOTS,
+        slots => slots,
+    }
+}
+
+/// Slot after which the player on turn counts as idle. After a mid-game
+/// settlement the clock restarts when the challenge window closes.
+pub(crate) fn turn_deadline(game: &GameState) -> u64 {
+    game.last_action_slot
+        .max(game.challenge_deadline)
+        .saturating_add(turn_timeout(game))
+}
+
+/// Players for seats A and B of a rematch: the seed's low bit swaps them
+pub fn rematch_seats(rematch: &Rematch, seed: &[u8; 32]) -> (Pubkey, Pubkey) {
+    if seed[0] & 1 == 1 {
+        (rematch.player_b, rematch.player_a)
+    } else {
+        (rematch.player_a, rematch.player_b)
+    }
+}
+
+/// Resolves the pending hidden-board shot against the revealed cell value
+/// and hands the turn to the defender. Returns the move to log.
+pub(crate) fn resolve_pending_shot(
+    game: &mut GameState,
+    index: usize,
+    cell_value: u8,
+) -> Result<MoveRecord> {
+    let attacker = game.current_turn;
+    let result = if attacker == 1 {
+        game.grid_b[index] = cell_value;
+        let result = resolve_shot(&mut game.grid_b, &mut game.hits_b, index)?;
+        if game.hits_b >= fleet_cells(&game.rules.fleet_b) {
+            game.game_status = 2; // A Won
+            game.end_reason = END_REASON_SUNK;
+            msg!("🎉 Player A Wins!");
+        }
+        game.current_turn = 2;
+        result
+    } else {
+        game.grid_a[index] = cell_value;
+        let result = resolve_shot(&mut game.grid_a, &mut game.hits_a, index)?;
+        if game.hits_a >= fleet_cells(&game.rules.fleet_a) {
+            game.game_status = 3; // B Won
+            game.end_reason = END_REASON_SUNK;
+            msg!("🎉 Player B Wins!");
+        }
+        game.current_turn = 1;
+        result
+    };
+    msg!(
+        "Revealed ({}, {}): {}",
+        index % GRID_SIZE,
+        index / GRID_SIZE,
+        if result == CELL_HIT {
+            "💥 HIT"
+        } else {
+            "💨 MISS"
+        }
+    );
+
+    game.pending_shot = None;
+    let record = MoveRecord {
+        player: attacker,
+        x: (index % GRID_SIZE) as u8,
+        y: (index / GRID_SIZE) as u8,
+        result,
+    };
+    count_move(game, &record);
+    Ok(record)
+}
+
+/// Games created from a match only take the matched opponent
+pub(crate) fn require_invited(game: &GameState, signer: &Pubkey) -> Result<()> {
+    if game.reserved_for != Pubkey::default() && game.reserved_for != *signer {
+        msg!("Game is reserved for {:?}", game.reserved_for);
+        return Err(BattleshipError::GameReserved.into());
+    }
+    Ok(())
+}
+
+/// Seat of `signer` in the game (1 = A, 2 = B), for player-only instructions
+pub(crate) fn player_number(game: &GameState, signer: &Pubkey) -> Result<u8> {
+    if *signer == game.player_a {
+        Ok(1)
+    } else if *signer == game.player_b && game.player_b != Pubkey::default() {
+        Ok(2)
+    } else {
+        msg!("Signer is not a player in this game");
+        Err(BattleshipError::NotAPlayer.into())
+    }
+}
+
+/// The winning player of a finished game, `None` while active or on a draw
+pub fn game_winner(game: &GameState) -> Option<Pubkey> {
+    match game.game_status {
+        2 => Some(game.player_a), // A Won
+        3 => Some(game.player_b), // B Won
+        _ => None,
+    }
+}
+
+/// Whether the winner sank the whole fleet without missing a shot
+pub fn is_perfect_game(game: &GameState) -> bool {
+    let winner_stats = match game.game_status {
+        2 => game.stats_a,
+        3 => game.stats_b,
+        _ => return false,
+    };
+    game.end_reason == END_REASON_SUNK && winner_stats.misses == 0
+}
+
+/// Emits `GameCreated` for a new game at `game_address`
+pub(crate) fn emit_game_created(game: &GameState, game_address: &[u8; 32]) {
+    emit!(GameCreated {
+        game_address: *game_address,
+        game_id: game.game_id,
+        player_a: game.player_a,
+        player_b: game.player_b,
+        reserved_for: game.reserved_for,
+        game_status: game.game_status,
+        game_mode: game.game_mode,
+        board_mode: game.board_mode,
+        win_condition: game.win_condition,
+        wager: game.wager,
+        wager_mode: game.wager_mode,
+        token_mint: game.token_mint,
+        token_wager: game.token_wager,
+    });
+}
+
+/// Emits `GameJoined` once Player B is in and the game is active
+pub(crate) fn emit_game_joined(game: &GameState, game_address: &[u8; 32]) {
+    emit!(GameJoined {
+        game_address: *game_address,
+        game_id: game.game_id,
+        player_a: game.player_a,
+        player_b: game.player_b,
+        wager: game.wager,
+        token_wager: game.token_wager,
+    });
+}
+
+/// Emits `GameEnded` once the game is over, plus `GameDrawn` for draws
+pub(crate) fn emit_if_ended(game: &GameState, game_address: &[u8; 32]) {
+    if matches!(game.game_status, 2..=4) {
+        emit!(GameEnded {
+            game_address: *game_address,
+            game_id: game.game_id,
+            player_a: game.player_a,
+            player_b: game.player_b,
+            game_status: game.game_status,
+            end_reason: game.end_reason,
+            move_count: game.move_count,
+            stats_a: game.stats_a,
+            stats_b: game.stats_b,
+            game_mode: game.game_mode,
+            wager: game.wager,
+            wager_mode: game.wager_mode,
+            token_wager: game.token_wager,
+        });
+    }
+    if game.game_status == 4 {
+        msg!("🤝 Move limit reached. Game {} is a draw.", game.game_id);
+        emit!(GameDrawn {
+            game_id: game.game_id,
+            player_a: game.player_a,
+            player_b: game.player_b,
+            move_count: game.move_count,
+        });
+    }
+}
+
+/// Counts a shot toward the move limit and the shooter's stats, remembers it
+/// as the last attack, and ends an undecided game in a draw
+pub(crate) fn count_move(game: &mut GameState, record: &MoveRecord) {
+    game.last_attack_x = record.x;
+    game.last_attack_y = record.y;
+    game.last_attack_result = record.result;
+    game.last_attack_by = record.player;
+
+    let stats = if record.player == 1 {
+        &mut game.stats_a
+    } else {
+        &mut game.stats_b
+    };
+    stats.shots += 1;
+    if record.result == CELL_HIT {
+        stats.hits += 1;
+    } else {
+        stats.misses += 1;
+    }
+
+    game.move_count += 1;
+    if game.game_status == 1 && game.move_count >= MAX_MOVES {
+        game.game_status = 4; // Draw
+        game.end_reason = END_REASON_MOVE_LIMIT;
+    }
+}
+
+/// Hands the turn over without a shot; still counts toward the move limit
+pub(crate) fn skip_turn(game: &mut GameState) {
+    game.current_turn = if game.current_turn == 1 { 2 } else { 1 };
+    game.shots_this_turn = 0;
+    game.move_count += 1;
+    if game.game_status == 1 && game.move_count >= MAX_MOVES {
+        game.game_status = 4; // Draw
+        game.end_reason = END_REASON_MOVE_LIMIT;
+    }
+}
+
+/// Passes the turn to the other player once the shooter's salvo is used up.
+/// In blockade mode the attacker keeps the turn, and the defender wins once
+/// they have survived `BLOCKADE_TURNS`.
+pub(crate) fn advance_turn(game: &mut GameState, shooter: u8) {
+    if game.win_condition != WIN_CONDITION_BLOCKADE {
+        let salvo = if shooter == 1 {
+            game.rules.shots_per_turn_a
+        } else {
+            game.rules.shots_per_turn_b
+        };
+        game.shots_this_turn += 1;
+        if game.shots_this_turn >= salvo {
+            game.current_turn = if shooter == 1 { 2 } else { 1 };
+            game.shots_this_turn = 0;
+        }
+        return;
+    }
+
+    let shots = if shooter == 1 {
+        game.stats_a.shots
+    } else {
+        game.stats_b.shots
+    };
+    if game.game_status == 1 && shots >= BLOCKADE_TURNS {
+        game.game_status = if shooter == 1 { 3 } else { 2 }; // Defender survived
+        game.end_reason = END_REASON_SURVIVED;
+        msg!("🛡️ Blockade held for {} shots!", shots);
+    }
+}
+
+/// Rejects on-chain moves while an off-chain settlement can still be challenged
+pub(crate) fn require_no_open_challenge(game: &GameState) -> Result<()> {
+    if game.challenge_deadline != 0 && Clock::get()?.slot <= game.challenge_deadline {
+        msg!(
+            "Settlement is challengeable until slot {}",
+            game.challenge_deadline
+        );
+        return Err(BattleshipError::ChallengeWindowOpen.into());
+    }
+    Ok(())
+}
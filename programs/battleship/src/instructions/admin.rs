@@ -0,0 +1,346 @@
+use crate::*;
+
+pub fn init_global_stats<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_global_stats_address(&address_tree_pubkey);
+    let stats_account =
+        LightAccount::<GlobalStats>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(stats_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn init_protocol_config<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    rakes: [ModeRake; WAGER_MODE_COUNT],
+    treasury: Pubkey,
+    ranked_stake: u64,
+) -> Result<()> {
+    validate_rakes(&rakes)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_protocol_config_address(&address_tree_pubkey);
+    let mut config_account = LightAccount::<ProtocolConfig>::new_init(
+        &crate::ID,
+        Some(address),
+        output_state_tree_index,
+    );
+    config_account.rakes = rakes;
+    config_account.treasury = treasury;
+    config_account.ranked_stake = ranked_stake;
+    config_account.admin = ctx.accounts.signer.key();
+
+    msg!("Protocol rakes set, paid to {:?}", treasury);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn update_protocol_config<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    rakes: [ModeRake; WAGER_MODE_COUNT],
+    treasury: Pubkey,
+    ranked_stake: u64,
+) -> Result<()> {
+    validate_rakes(&rakes)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.rakes = rakes;
+    config_account.treasury = treasury;
+    config_account.ranked_stake = ranked_stake;
+
+    msg!("Protocol rakes set, paid to {:?}", treasury);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn ban_player<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    player: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_ban_address(&player, &address_tree_pubkey);
+    let mut ban_account =
+        LightAccount::<Ban>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    ban_account.player = player;
+    ban_account.banned_slot = Clock::get()?.slot;
+
+    msg!("🚫 {:?} banned", player);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(ban_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn unban_player<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    ban: Ban,
+    account_meta: CompressedAccountMeta,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    msg!("{:?} unbanned", ban.player);
+
+    let ban_account = LightAccount::<Ban>::new_close(&crate::ID, &account_meta, ban)?;
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(ban_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn update_config<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    admin: Pubkey,
+    turn_timeout_slots: u64,
+    open_game_expiry_slots: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.admin = admin;
+    config_account.turn_timeout_slots = turn_timeout_slots;
+    config_account.open_game_expiry_slots = open_game_expiry_slots;
+
+    msg!("Protocol admin set to {:?}", admin);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn set_rating_decay<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    decay_idle_slots: u64,
+    decay_points: u16,
+    decay_floor: u16,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.decay_idle_slots = decay_idle_slots;
+    config_account.decay_points = decay_points;
+    config_account.decay_floor = decay_floor;
+
+    msg!(
+        "Ratings idle for {} slots decay by {}",
+        decay_idle_slots,
+        decay_points
+    );
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn pause_protocol<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    paused: bool,
+) -> Result<()> {
+    if current_config.admin != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotAdmin.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.paused = paused;
+
+    msg!("Protocol paused: {}", paused);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn set_reward_token<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    reward_mint: Pubkey,
+    reward_per_win: u64,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.reward_mint = reward_mint;
+    config_account.reward_per_win = reward_per_win;
+
+    msg!(
+        "Wins earn {} of reward token {:?}",
+        reward_per_win,
+        reward_mint
+    );
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn set_active_game_limit<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: ProtocolConfig,
+    account_meta: CompressedAccountMeta,
+    max_active_games: u16,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<ProtocolConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.max_active_games = max_active_games;
+
+    msg!(
+        "Profiles may hold {} active games",
+        active_game_limit(&config_account)
+    );
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Every mode's fee is at most `MAX_FEE_BPS`, and a wager cap is above the
+/// mode's minimum
+fn validate_rakes(rakes: &[ModeRake]) -> Result<()> {
+    for rake in rakes {
+        if rake.fee_bps > MAX_FEE_BPS {
+            return Err(BattleshipError::InvalidFee.into());
+        }
+        if rake.max_wager != 0 && rake.max_wager < rake.min_wager {
+            return Err(BattleshipError::InvalidWager.into());
+        }
+    }
+    Ok(())
+}
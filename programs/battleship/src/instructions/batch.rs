@@ -0,0 +1,390 @@
+use crate::*;
+
+pub fn create_batch_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    commitment: [u8; 32],
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let game_id = slot.profile.next_game_id;
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.current_turn = 0; // Nobody takes turns
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_BATCH;
+    game_account.slots_held = 1; // Player A's profile
+
+    msg!(
+        "Batch game {} created by {:?}! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let (plans_address, plans_address_seed) =
+        derive_batch_plans_address(&address, &address_tree_pubkey);
+    let mut plans_account = LightAccount::<BatchPlans>::new_init(
+        &crate::ID,
+        Some(plans_address),
+        output_state_tree_index,
+    );
+    plans_account.game_address = address;
+    plans_account.commitment_a = commitment;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account)
+        .append(cpi)?
+        .with_light_account(plans_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, plans_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_batch_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    plans: BatchPlansRef,
+    commitment: [u8; 32],
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let BatchPlansRef {
+        plans: current_plans,
+        meta: plans_meta,
+    } = plans;
+
+    if current_game.game_status != 0 {
+        msg!(
+            "Game is not in waiting state (Status: {})",
+            current_game.game_status
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    require_invited(&current_game, &ctx.accounts.signer.key())?;
+
+    if current_game.board_mode != BOARD_MODE_BATCH {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if current_plans.game_address != account_meta.address {
+        return Err(BattleshipError::BatchPlansMismatch.into());
+    }
+
+    lock_deposit(ctx.accounts, &account_meta.address)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.player_b = ctx.accounts.signer.key();
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+    game_account.slots_held |= 2; // Player B's profile
+
+    let mut plans_account =
+        LightAccount::<BatchPlans>::new_mut(&crate::ID, &plans_meta, current_plans)?;
+    plans_account.commitment_b = commitment;
+
+    msg!(
+        "Player B joined batch game {}! Both plans are due",
+        game_account.game_id
+    );
+
+    emit_game_joined(&game_account, &account_meta.address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account)
+        .append(cpi)?
+        .with_light_account(plans_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        false,
+        &light_cpi_accounts,
+    )?
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn reveal_batch_plan<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    plans: BatchPlansRef,
+    plan: BatchPlan,
+    salt: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let BatchPlansRef {
+        plans: current_plans,
+        meta: plans_meta,
+    } = plans;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if current_game.board_mode != BOARD_MODE_BATCH {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if current_plans.game_address != account_meta.address {
+        return Err(BattleshipError::BatchPlansMismatch.into());
+    }
+
+    let signer = ctx.accounts.signer.key();
+    let seat = player_number(&current_game, &signer)?;
+    let (commitment, revealed) = if seat == 1 {
+        (current_plans.commitment_a, current_plans.plan_a.is_some())
+    } else {
+        (current_plans.commitment_b, current_plans.plan_b.is_some())
+    };
+    if revealed {
+        msg!("Plan already revealed");
+        return Err(BattleshipError::InvalidBatchReveal.into());
+    }
+    if batch_commitment(&account_meta.address, &signer, &plan, &salt) != commitment {
+        msg!("Reveal does not open the commitment");
+        return Err(BattleshipError::InvalidBatchReveal.into());
+    }
+    // A plan that doesn't fit the rules can never be revealed, so its owner
+    // forfeits once the reveal window closes
+    let fleet = if seat == 1 {
+        &current_game.rules.fleet_a
+    } else {
+        &current_game.rules.fleet_b
+    };
+    place_fleet(
+        [CELL_EMPTY; GRID_CELLS],
+        fleet,
+        &plan.ships,
+        current_game.rules.grid_size,
+    )?;
+    validate_batch_shots(&plan.shots, current_game.rules.grid_size)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut plans_account =
+        LightAccount::<BatchPlans>::new_mut(&crate::ID, &plans_meta, current_plans)?;
+    if seat == 1 {
+        plans_account.plan_a = Some(plan);
+    } else {
+        plans_account.plan_b = Some(plan);
+    }
+    game_account.last_action_slot = Clock::get()?.slot;
+
+    if let (Some(plan_a), Some(plan_b)) = (&plans_account.plan_a, &plans_account.plan_b) {
+        play_out(&mut game_account, plan_a, plan_b)?;
+        emit_if_ended(&game_account, &account_meta.address);
+        msg!(
+            "🏁 Batch game {} played out in {} shots",
+            game_account.game_id,
+            game_account.move_count
+        );
+    } else {
+        msg!(
+            "Player {} revealed their plan",
+            if seat == 1 { "A" } else { "B" }
+        );
+    }
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    GameBatch::new(game_account)
+        .append(cpi)?
+        .with_light_account(plans_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn forfeit_batch<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    plans: BatchPlans,
+    plans_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if current_game.board_mode != BOARD_MODE_BATCH {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if plans.game_address != account_meta.address {
+        return Err(BattleshipError::BatchPlansMismatch.into());
+    }
+    let deadline = turn_deadline(&current_game);
+    if Clock::get()?.slot <= deadline {
+        msg!("Plans are due until slot {}", deadline);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let (status, end_reason) = match (plans.plan_a.is_some(), plans.plan_b.is_some()) {
+        (true, false) => (2, END_REASON_TIMEOUT), // A Won
+        (false, true) => (3, END_REASON_TIMEOUT), // B Won
+        _ => (4, END_REASON_VOID),
+    };
+    game_account.game_status = status;
+    game_account.end_reason = end_reason;
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "⏰ Batch plans overdue. Game {} finalized by {:?}",
+        game_account.game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let plans_account =
+        LightAccount::<BatchPlans>::new_read_only(&crate::ID, &plans_meta, plans, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(plans_account)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Rejects shot orders that leave the playable area, repeat a cell or
+/// exceed one shot per cell
+fn validate_batch_shots(shots: &[u8], grid_size: u8) -> Result<()> {
+    if shots.len() > GRID_CELLS {
+        return Err(BattleshipError::InvalidPosition.into());
+    }
+    let mut fired = [false; GRID_CELLS];
+    for &cell in shots {
+        let cell = cell as usize;
+        if cell >= GRID_CELLS
+            || cell % GRID_SIZE >= grid_size as usize
+            || cell / GRID_SIZE >= grid_size as usize
+        {
+            msg!("Shot {} is off the board", cell);
+            return Err(BattleshipError::InvalidPosition.into());
+        }
+        if fired[cell] {
+            return Err(BattleshipError::AlreadyAttacked.into());
+        }
+        fired[cell] = true;
+    }
+    Ok(())
+}
+
+/// Places both revealed fleets and fires the shot orders alternately
+/// (A, B, A, ...) until a fleet is sunk. A draw if both orders run out.
+fn play_out(game: &mut GameState, plan_a: &BatchPlan, plan_b: &BatchPlan) -> Result<()> {
+    let rules = &game.rules;
+    let mut grid_a = place_fleet(
+        [CELL_EMPTY; GRID_CELLS],
+        &rules.fleet_a,
+        &plan_a.ships,
+        rules.grid_size,
+    )?;
+    let mut grid_b = place_fleet(
+        [CELL_EMPTY; GRID_CELLS],
+        &rules.fleet_b,
+        &plan_b.ships,
+        rules.grid_size,
+    )?;
+    let (target_a, target_b) = (fleet_cells(&rules.fleet_a), fleet_cells(&rules.fleet_b));
+    let (mut hits_a, mut hits_b, mut moves) = (0, 0, 0);
+    let mut winner = 0;
+    for i in 0..GRID_CELLS {
+        if let Some(&cell) = plan_a.shots.get(i) {
+            resolve_shot(&mut grid_b, &mut hits_b, cell as usize)?;
+            moves += 1;
+            if hits_b == target_b {
+                winner = 1;
+                break;
+            }
+        }
+        if let Some(&cell) = plan_b.shots.get(i) {
+            resolve_shot(&mut grid_a, &mut hits_a, cell as usize)?;
+            moves += 1;
+            if hits_a == target_a {
+                winner = 2;
+                break;
+            }
+        }
+    }
+
+    game.grid_a = grid_a;
+    game.grid_b = grid_b;
+    game.hits_a = hits_a;
+    game.hits_b = hits_b;
+    game.move_count = moves;
+    (game.game_status, game.end_reason) = match winner {
+        1 => (2, END_REASON_SUNK),       // A Won
+        2 => (3, END_REASON_SUNK),       // B Won
+        _ => (4, END_REASON_MOVE_LIMIT), // Draw
+    };
+    Ok(())
+}
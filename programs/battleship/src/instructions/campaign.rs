@@ -0,0 +1,235 @@
+use crate::*;
+
+pub fn start_campaign<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    campaign_id: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_campaign_address(
+        &ctx.accounts.signer.key(),
+        campaign_id,
+        &address_tree_pubkey,
+    );
+    let mut campaign_account =
+        LightAccount::<Campaign>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    campaign_account.owner = ctx.accounts.signer.key();
+    campaign_account.campaign_id = campaign_id;
+
+    msg!(
+        "🗺️ Campaign {} started by {:?}",
+        campaign_id,
+        ctx.accounts.signer.key()
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(campaign_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_campaign_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    current_campaign: Campaign,
+    campaign_meta: CompressedAccountMeta,
+    fleet: FleetBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let FleetBoard { ships, board_hash } = fleet;
+
+    let game_id = slot.profile.next_game_id;
+    if current_campaign.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotCampaignOwner.into());
+    }
+    if current_campaign.stage >= CAMPAIGN_STAGES {
+        return Err(BattleshipError::CampaignComplete.into());
+    }
+    if current_campaign.current_game != [0u8; 32] {
+        msg!("Finish the current campaign game first");
+        return Err(BattleshipError::CampaignGameOpen.into());
+    }
+    let rules = campaign_rules(current_campaign.stage);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    let grid = place_fleet(
+        [CELL_EMPTY; GRID_CELLS],
+        &rules.fleet_a,
+        &ships,
+        rules.grid_size,
+    )?;
+    lock_deposit(ctx.accounts, &address)?;
+
+    msg!(
+        "Campaign {} stage {} game {} created! Waiting for Player B.",
+        current_campaign.campaign_id,
+        current_campaign.stage + 1,
+        game_id
+    );
+
+    let mut campaign_account =
+        LightAccount::<Campaign>::new_mut(&crate::ID, &campaign_meta, current_campaign)?;
+    campaign_account.current_game = address;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.rules = rules;
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    // The new accounts go first so they are outputs 0 and 1
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .with_light_account(campaign_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn advance_campaign<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_campaign: Campaign,
+    campaign_meta: CompressedAccountMeta,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_campaign.current_game == [0u8; 32]
+        || current_campaign.current_game != game_meta.address
+    {
+        msg!("Game is not the campaign's current game");
+        return Err(BattleshipError::CampaignMismatch.into());
+    }
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut campaign_account =
+        LightAccount::<Campaign>::new_mut(&crate::ID, &campaign_meta, current_campaign)?;
+    let stage_points = CAMPAIGN_WIN_POINTS * (campaign_account.stage as u32 + 1);
+    // The owner is always Player A
+    match game.game_status {
+        2 => {
+            campaign_account.games_won += 1;
+            campaign_account.score += stage_points;
+        }
+        3 => campaign_account.games_lost += 1,
+        _ => campaign_account.score += stage_points / 4, // Draw
+    }
+    campaign_account.stage += 1;
+    campaign_account.current_game = [0u8; 32];
+
+    msg!(
+        "Campaign {} stage {}/{} done, score {}",
+        campaign_account.campaign_id,
+        campaign_account.stage,
+        CAMPAIGN_STAGES,
+        campaign_account.score
+    );
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(campaign_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Rules for a campaign stage: the grid grows first, then the fleets
+fn campaign_rules(stage: u8) -> GameRules {
+    let (grid_size, fleet): (u8, &[u8]) = match stage {
+        0 => (3, &[2]),
+        1 => (4, &[2, 2]),
+        2 => (5, &[3, 2]),
+        _ => (5, &[4, 3, 2]),
+    };
+    GameRules {
+        grid_size,
+        fleet_a: fleet.to_vec(),
+        fleet_b: fleet.to_vec(),
+        ..GameRules::default()
+    }
+}
@@ -0,0 +1,256 @@
+use crate::*;
+
+pub fn create_club<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    name: String,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    validate_name(&name)?;
+    let founder = ctx.accounts.signer.key();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_club_address(&name, &address_tree_pubkey);
+    let mut club_account =
+        LightAccount::<Club>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    club_account.founder = founder;
+    club_account.name = name;
+    club_account.members = 1;
+
+    let (member_address, member_seed) = derive_club_member_address(&founder, &address_tree_pubkey);
+    let mut member_account = LightAccount::<ClubMember>::new_init(
+        &crate::ID,
+        Some(member_address),
+        output_state_tree_index,
+    );
+    member_account.player = founder;
+    member_account.club = address;
+    member_account.joined_at = Clock::get()?.unix_timestamp;
+
+    msg!("🏴 Club {} founded by {:?}", club_account.name, founder);
+
+    // The new accounts go first so they are outputs 0 and 1
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(club_account)?
+        .with_light_account(member_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+            address_tree_info.into_new_address_params_assigned_packed(member_seed, Some(1)),
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_club<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    club: ClubRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let player = ctx.accounts.signer.key();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (member_address, member_seed) = derive_club_member_address(&player, &address_tree_pubkey);
+    let mut member_account = LightAccount::<ClubMember>::new_init(
+        &crate::ID,
+        Some(member_address),
+        output_state_tree_index,
+    );
+    member_account.player = player;
+    member_account.club = club.meta.address;
+    member_account.joined_at = Clock::get()?.unix_timestamp;
+
+    let mut club_account = LightAccount::<Club>::new_mut(&crate::ID, &club.meta, club.club)?;
+    club_account.members += 1;
+
+    msg!(
+        "🏴 {:?} joined club {}, {} members",
+        player,
+        club_account.name,
+        club_account.members
+    );
+
+    // The new membership goes first so it is output 0
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(member_account)?
+        .with_light_account(club_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(member_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn leave_club<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    membership: ClubMember,
+    membership_meta: CompressedAccountMeta,
+    club: ClubRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if membership.player != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+    if membership.club != club.meta.address {
+        return Err(BattleshipError::ClubMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let member_account =
+        LightAccount::<ClubMember>::new_close(&crate::ID, &membership_meta, membership)?;
+    let mut club_account = LightAccount::<Club>::new_mut(&crate::ID, &club.meta, club.club)?;
+    club_account.members = club_account.members.saturating_sub(1);
+
+    msg!(
+        "🏴 {:?} left club {}",
+        ctx.accounts.signer.key(),
+        club_account.name
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(member_account)?
+        .with_light_account(club_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn record_club_result<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    result: GameResultRef,
+    membership: ClubMemberRef,
+    club: ClubRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameResultRef {
+        result,
+        meta: result_meta,
+    } = result;
+    let ClubMemberRef {
+        membership,
+        meta: membership_meta,
+    } = membership;
+
+    let member = membership.player;
+    if result.player_a != member && result.player_b != member {
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+    if membership.club != club.meta.address {
+        return Err(BattleshipError::ClubMismatch.into());
+    }
+    if result.timestamp < membership.joined_at {
+        msg!("Game finished before the member joined");
+        return Err(BattleshipError::ClubMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (claim_address, claim_seed) =
+        derive_club_claim_address(&result_meta.address, &member, &address_tree_pubkey);
+    let mut claim_account = LightAccount::<ClubClaim>::new_init(
+        &crate::ID,
+        Some(claim_address),
+        output_state_tree_index,
+    );
+    claim_account.result_address = result_meta.address;
+    claim_account.member = member;
+
+    let mut club_account = LightAccount::<Club>::new_mut(&crate::ID, &club.meta, club.club)?;
+    if result.winner == Pubkey::default() {
+        club_account.draws += 1;
+    } else if result.winner == member {
+        club_account.wins += 1;
+    } else {
+        club_account.losses += 1;
+    }
+
+    emit!(ClubResultRecorded {
+        club_address: club.meta.address,
+        member,
+        result_address: result_meta.address,
+        wins: club_account.wins,
+        losses: club_account.losses,
+        draws: club_account.draws,
+    });
+
+    let result_account =
+        LightAccount::<GameResult>::new_read_only(&crate::ID, &result_meta, result, &tree_pubkeys)?;
+    let member_account = LightAccount::<ClubMember>::new_read_only(
+        &crate::ID,
+        &membership_meta,
+        membership,
+        &tree_pubkeys,
+    )?;
+
+    // The new claim goes first so it is output 0
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(claim_account)?
+        .with_light_account(club_account)?
+        .with_light_account(result_account)?
+        .with_light_account(member_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(claim_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
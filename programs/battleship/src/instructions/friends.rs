@@ -0,0 +1,140 @@
+use crate::*;
+
+pub fn create_friend_list<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let owner = ctx.accounts.signer.key();
+    let (address, address_seed) = derive_friend_list_address(&owner, &address_tree_pubkey);
+    let mut list_account =
+        LightAccount::<FriendList>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    list_account.owner = owner;
+
+    msg!("Friend list created for {:?}", owner);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(list_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn send_invite<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    recipient_list: FriendList,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let sender = ctx.accounts.signer.key();
+    if recipient_list.owner == sender
+        || recipient_list.friends.contains(&sender)
+        || recipient_list.pending.contains(&sender)
+    {
+        msg!("Nothing to invite");
+        return Err(BattleshipError::NotFriends.into());
+    }
+    if recipient_list.pending.len() >= MAX_PENDING_INVITES {
+        msg!("{:?} has too many pending invites", recipient_list.owner);
+        return Err(BattleshipError::FriendListFull.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut list_account =
+        LightAccount::<FriendList>::new_mut(&crate::ID, &account_meta, recipient_list)?;
+    list_account.pending.push(sender);
+
+    msg!("💌 {:?} invited {:?}", sender, list_account.owner);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(list_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn accept_invite<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    list: FriendListRef,
+    inviter: Pubkey,
+    inviter_list: Option<FriendListRef>,
+    accept: bool,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let FriendListRef {
+        list: current_list,
+        meta: account_meta,
+    } = list;
+
+    if current_list.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::WrongProfile.into());
+    }
+    if !current_list.pending.contains(&inviter) {
+        msg!("No pending invite from {:?}", inviter);
+        return Err(BattleshipError::NotFriends.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let owner = current_list.owner;
+    let mut list_account =
+        LightAccount::<FriendList>::new_mut(&crate::ID, &account_meta, current_list)?;
+    list_account.pending.retain(|pending| *pending != inviter);
+
+    let mut cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    if accept {
+        let Some(inviter_list) = inviter_list.filter(|l| l.list.owner == inviter) else {
+            return Err(BattleshipError::WrongProfile.into());
+        };
+        if list_account.friends.len() >= MAX_FRIENDS
+            || inviter_list.list.friends.len() >= MAX_FRIENDS
+        {
+            return Err(BattleshipError::FriendListFull.into());
+        }
+        list_account.friends.push(inviter);
+        let mut inviter_account =
+            LightAccount::<FriendList>::new_mut(&crate::ID, &inviter_list.meta, inviter_list.list)?;
+        inviter_account.pending.retain(|pending| *pending != owner);
+        if !inviter_account.friends.contains(&owner) {
+            inviter_account.friends.push(owner);
+        }
+        msg!("🤝 {:?} and {:?} are now friends", owner, inviter);
+        cpi = cpi.with_light_account(inviter_account)?;
+    } else {
+        msg!("{:?} declined {:?}'s invite", owner, inviter);
+    }
+    cpi.with_light_account(list_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
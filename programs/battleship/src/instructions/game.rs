@@ -0,0 +1,1415 @@
+use crate::*;
+
+pub fn create_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    board: ShipBoard,
+    lobby: LobbyOptions,
+    slot: ActiveGameSlot,
+    stats: Option<GlobalStatsRef>,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+    let LobbyOptions {
+        mode,
+        tags,
+        challenge,
+    } = lobby;
+
+    // Validate and place ship
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+    let game_id = slot.profile.next_game_id;
+    let ranked = match mode {
+        WAGER_MODE_CASUAL => None,
+        WAGER_MODE_RANKED => Some(ranked_config(&slot.protocol)?),
+        _ => return Err(BattleshipError::WrongGameMode.into()),
+    };
+    validate_lobby_tags(&tags, GRID_SIZE as u8)?;
+    if let Some(challenge) = &challenge {
+        if challenge.list.owner != ctx.accounts.signer.key()
+            || !challenge.list.friends.contains(&challenge.friend)
+        {
+            msg!("Only friends on the signer's list can be challenged");
+            return Err(BattleshipError::NotFriends.into());
+        }
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+    if let Some(config) = &ranked {
+        lock_lamports(
+            ctx.accounts,
+            derive_vault_address(&address).0,
+            config.ranked_stake,
+        )?;
+    }
+    msg!("Derived Address: {:?}", address);
+    msg!("Program ID: {:?}", crate::ID);
+
+    msg!(
+        "Game {} created by {:?}! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.player_b = Pubkey::default();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.in_stats = stats.is_some();
+    game_account.tags = tags;
+    if let Some(config) = &ranked {
+        game_account.wager_mode = WAGER_MODE_RANKED;
+        game_account.ranked_stake = config.ranked_stake;
+        game_account.treasury = config.treasury;
+    }
+
+    // Init Player A
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.hits_a = 0;
+
+    // Init Player B (Empty)
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+    game_account.board_hash_b = [0u8; 32];
+    game_account.hits_b = 0;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    if let Some(challenge) = challenge {
+        // Only the challenged friend may join
+        game_account.reserved_for = challenge.friend;
+        emit!(FriendChallenged {
+            game_address: address,
+            from: ctx.accounts.signer.key(),
+            to: challenge.friend,
+        });
+
+        let tree_pubkeys = light_cpi_accounts
+            .tree_pubkeys()
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+        let list_account = LightAccount::<FriendList>::new_read_only(
+            &crate::ID,
+            &challenge.list_meta,
+            challenge.list,
+            &tree_pubkeys,
+        )?;
+        cpi = cpi.with_light_account(list_account)?;
+    }
+
+    emit_game_created(&game_account, &address);
+
+    // The new accounts go first so they are outputs 0 and 1
+    let mut cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    if ranked.is_some() {
+        cpi = with_no_ban(
+            cpi,
+            &ctx.accounts.signer.key(),
+            address_tree_info,
+            &light_cpi_accounts,
+        )?;
+    }
+    let cpi = with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?;
+    with_global_stats(cpi, stats, |stats| {
+        stats.total_games += 1;
+        stats.active_games += 1;
+    })?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, log_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_game_with_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    board: ShipBoard,
+    terms: WagerTerms,
+    slot: ActiveGameSlot,
+    stats: Option<GlobalStatsRef>,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+    let WagerTerms { wager, mode } = terms;
+
+    let game_id = slot.profile.next_game_id;
+    let config = slot.protocol.config.clone();
+    let rake = mode_rake(&config, mode)?;
+    let min_wager = MIN_WAGER_LAMPORTS.max(rake.min_wager);
+    if wager < min_wager || (rake.max_wager != 0 && wager > rake.max_wager) {
+        msg!(
+            "Mode {} wagers run from {} to {} lamports",
+            mode,
+            min_wager,
+            rake.max_wager
+        );
+        return Err(BattleshipError::InvalidWager.into());
+    }
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+    lock_lamports(ctx.accounts, derive_vault_address(&address).0, wager)?;
+    let ranked_stake = if mode == WAGER_MODE_RANKED {
+        config.ranked_stake
+    } else {
+        0
+    };
+    if ranked_stake > 0 {
+        lock_lamports(ctx.accounts, derive_vault_address(&address).0, ranked_stake)?;
+    }
+
+    msg!(
+        "Game {} created by {:?} with a {} lamport wager! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key(),
+        wager
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.wager = wager;
+    game_account.wager_mode = mode;
+    game_account.ranked_stake = ranked_stake;
+    game_account.fee_bps = rake.fee_bps;
+    game_account.treasury = config.treasury;
+    pin_timeouts(&mut game_account, &config);
+    game_account.slots_held = 1; // Player A's profile
+    game_account.in_stats = stats.is_some();
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    let cpi = with_no_ban(
+        cpi,
+        &ctx.accounts.signer.key(),
+        address_tree_info,
+        &light_cpi_accounts,
+    )?;
+    let cpi = with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?;
+    with_global_stats(cpi, stats, |stats| {
+        stats.total_games += 1;
+        stats.active_games += 1;
+        stats.total_wagered += wager;
+    })?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, log_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_custom_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    rules: GameRules,
+    fleet: FleetBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let FleetBoard { ships, board_hash } = fleet;
+
+    validate_rules(&rules)?;
+    let game_id = slot.profile.next_game_id;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    let obstacles = obstacle_grid(&address, &rules);
+    let grid = place_fleet(obstacles, &rules.fleet_a, &ships, rules.grid_size)?;
+    lock_deposit(ctx.accounts, &address)?;
+    msg!("Derived Address: {:?}", address);
+    msg!("Program ID: {:?}", crate::ID);
+
+    msg!(
+        "Custom game {} created by {:?}! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.player_b = Pubkey::default();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.rules = rules;
+
+    // Init Player A
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.hits_a = 0;
+
+    // Init Player B (Empty apart from any obstacles)
+    game_account.grid_b = obstacles;
+    game_account.board_hash_b = [0u8; 32];
+    game_account.hits_b = 0;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, log_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_blockade_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    board: ShipBoard,
+    creator_attacks: bool,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+
+    // Validate and place ship
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+    let game_id = slot.profile.next_game_id;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+    msg!("Derived Address: {:?}", address);
+    msg!("Program ID: {:?}", crate::ID);
+
+    msg!(
+        "Blockade game {} created by {:?} ({})! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key(),
+        if creator_attacks {
+            "attacking"
+        } else {
+            "defending"
+        }
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.player_b = Pubkey::default();
+    game_account.blockade_attacker = if creator_attacks { 1 } else { 2 };
+    game_account.current_turn = game_account.blockade_attacker; // Attacker starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.win_condition = WIN_CONDITION_BLOCKADE;
+
+    // Init Player A
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.hits_a = 0;
+
+    // Init Player B (Empty)
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+    game_account.board_hash_b = [0u8; 32];
+    game_account.hits_b = 0;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, log_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_pve_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    board: ShipBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+
+    // Validate and place ship
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+    let game_id = slot.profile.next_game_id;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.player_b = BOT_PLAYER;
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 1; // Active, no join needed
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVE;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+
+    // Init Player A
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.hits_a = 0;
+
+    // Init Bot
+    game_account.grid_b = bot_grid(&address);
+    game_account.board_hash_b = [0u8; 32];
+    game_account.hits_b = 0;
+
+    msg!(
+        "PvE game {} created by {:?}! Bot is ready.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+    let cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&batch_new_addresses(
+        address_tree_info,
+        &[address_seed, log_address_seed],
+    ))
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    board: ShipBoard,
+    options: JoinOptions,
+    slot: ActiveGameSlot,
+    stats: Option<GlobalStatsRef>,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let ShipBoard { ship, board_hash } = board;
+    let JoinOptions {
+        coin_flip,
+        filter,
+        ban_check,
+    } = options;
+
+    // Validate game status
+    if current_game.game_status != 0 {
+        msg!(
+            "Game is not in waiting state (Status: {})",
+            current_game.game_status
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    require_invited(&current_game, &ctx.accounts.signer.key())?;
+
+    if current_game.board_mode != BOARD_MODE_PUBLIC {
+        msg!("Hidden-board games must be joined with join_hidden_game");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    if current_game.rules != GameRules::default() {
+        msg!("Custom-rule games must be joined with join_custom_game");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    if current_game.token_wager > 0 {
+        msg!("Token-wager games must be joined with join_token_wager_game");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    // The blockade attacker always fires first
+    if coin_flip && current_game.win_condition == WIN_CONDITION_BLOCKADE {
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    if let Some(filter) = &filter {
+        if !lobby_tags_match(&current_game.tags, filter) {
+            return Err(BattleshipError::LobbyTagsMismatch.into());
+        }
+    }
+
+    // Validate and place ship
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+
+    lock_deposit(ctx.accounts, &account_meta.address)?;
+    let current_wager = current_game.wager;
+    let stakes = current_wager + current_game.ranked_stake;
+    if stakes > 0 {
+        // Match Player A's wager and ranked stake
+        lock_lamports(
+            ctx.accounts,
+            derive_vault_address(&account_meta.address).0,
+            stakes,
+        )?;
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+
+    // Set Player B
+    game_account.player_b = ctx.accounts.signer.key();
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+
+    game_account.grid_b = grid;
+    game_account.board_hash_b = board_hash;
+    game_account.hits_b = 0;
+    game_account.slots_held |= 2; // Player B's profile
+
+    msg!(
+        "Player B joined! Game {} is now Active! Ship at ({}, {})",
+        game_account.game_id,
+        ship.x,
+        ship.y
+    );
+
+    emit_game_joined(&game_account, &account_meta.address);
+
+    if coin_flip {
+        // Nobody moves until both players have drawn who starts
+        game_account.current_turn = 0;
+        game_account.pending_draw = Some(RAND_FIRST_TURN);
+    }
+
+    let mut cpi =
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof).with_light_account(game_account)?;
+    if stakes > 0 {
+        // Banned keys can't stake
+        let ban_check = ban_check.ok_or(BattleshipError::BanCheckRequired)?;
+        cpi = with_no_ban(
+            cpi,
+            &ctx.accounts.signer.key(),
+            ban_check,
+            &light_cpi_accounts,
+        )?;
+    }
+    let cpi = with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        false,
+        &light_cpi_accounts,
+    )?;
+    with_global_stats(cpi, stats, |stats| stats.total_wagered += current_wager)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn release_active_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    profile: PlayerProfile,
+    profile_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status < 2 {
+        msg!("Game is still open or in progress");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    let seat = if profile.owner == current_game.player_a {
+        1
+    } else if profile.owner == current_game.player_b {
+        2
+    } else {
+        return Err(BattleshipError::WrongProfile.into());
+    };
+    if current_game.slots_held & seat == 0 {
+        msg!("This game holds no slot in that profile");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.slots_held &= !seat;
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_mut(&crate::ID, &profile_meta, profile)?;
+    profile_account.active_games = profile_account.active_games.saturating_sub(1);
+
+    msg!(
+        "{:?} now holds {} active games",
+        profile_account.owner,
+        profile_account.active_games
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(profile_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_custom_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    fleet: FleetBoard,
+    opponent: Option<OpponentProfile>,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let FleetBoard { ships, board_hash } = fleet;
+
+    // Validate game status
+    if current_game.game_status != 0 {
+        msg!(
+            "Game is not in waiting state (Status: {})",
+            current_game.game_status
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    require_invited(&current_game, &ctx.accounts.signer.key())?;
+
+    if current_game.board_mode != BOARD_MODE_PUBLIC {
+        msg!("Hidden-board games must be joined with join_hidden_game");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if current_game.wager > 0 || current_game.token_wager > 0 || current_game.ranked_stake > 0 {
+        msg!("Wagered and ranked games must be joined with join_game or join_token_wager_game");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    // Handicap games also need Player A's profile; otherwise it's ignored
+    let opponent = if current_game.rules.handicap {
+        let Some(opponent) = opponent else {
+            msg!("Handicap games need both player profiles");
+            return Err(BattleshipError::ProfilesRequired.into());
+        };
+        if opponent.profile.owner != current_game.player_a {
+            return Err(BattleshipError::WrongProfile.into());
+        }
+        if opponent.profile.rating_commitment != [0u8; 32]
+            || slot.profile.rating_commitment != [0u8; 32]
+        {
+            msg!("Sealed ratings can't set a handicap");
+            return Err(BattleshipError::WrongProfile.into());
+        }
+        Some(opponent)
+    } else {
+        None
+    };
+
+    // B's grid holds the obstacles laid out at create
+    let grid = place_fleet(
+        current_game.grid_b,
+        &current_game.rules.fleet_b,
+        &ships,
+        current_game.rules.grid_size,
+    )?;
+
+    lock_deposit(ctx.accounts, &account_meta.address)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+
+    // Set Player B
+    game_account.player_b = ctx.accounts.signer.key();
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+
+    game_account.grid_b = grid;
+    game_account.board_hash_b = board_hash;
+    game_account.hits_b = 0;
+    game_account.slots_held |= 2; // Player B's profile
+
+    if game_account.rules.sealed_obstacles {
+        // Nobody moves until both players have drawn the obstacle map. Both
+        // fleets are fixed by now, so the map can't be played around, only
+        // refused by the second revealer at the cost of a forfeit
+        game_account.current_turn = 0;
+        game_account.pending_draw = Some(RAND_OBSTACLES);
+    }
+
+    if let Some(opponent) = &opponent {
+        apply_handicap(
+            &mut game_account.rules,
+            opponent.profile.rating,
+            slot.profile.rating,
+        );
+        msg!(
+            "Handicap applied: A fires {}, B fires {} per turn",
+            game_account.rules.shots_per_turn_a,
+            game_account.rules.shots_per_turn_b
+        );
+    }
+
+    msg!(
+        "Player B joined! Game {} is now Active with {} ships",
+        game_account.game_id,
+        ships.len()
+    );
+
+    emit_game_joined(&game_account, &account_meta.address);
+
+    let cpi =
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof).with_light_account(game_account)?;
+    let cpi = with_opponent_profile(cpi, opponent, &light_cpi_accounts)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        false,
+        &light_cpi_accounts,
+    )?
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn attack<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    turn: GameTurn,
+    attack_x: u8,
+    attack_y: u8,
+    session: Option<SessionAuth>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameTurn {
+        game: current_game,
+        game_meta: account_meta,
+        log: current_log,
+        log_meta,
+        views,
+    } = turn;
+
+    // Validate coordinates
+    let grid_size = current_game.rules.grid_size;
+    if attack_x >= grid_size || attack_y >= grid_size {
+        msg!("Attack coordinates out of bounds");
+        return Err(BattleshipError::InvalidPosition.into());
+    }
+
+    // Check game is active
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    require_no_open_challenge(&current_game)?;
+    if current_game.board_mode == BOARD_MODE_BATCH {
+        msg!("Batch games are played out from the revealed plans");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if current_game.current_turn == 0 {
+        msg!("Waiting for the coin flip");
+        return Err(BattleshipError::CoinFlipPending.into());
+    }
+    if current_game.raise_offered_by != 0 {
+        msg!("The raise must be accepted or declined first");
+        return Err(BattleshipError::RaisePending.into());
+    }
+    if current_game.board_mode == BOARD_MODE_ZK && current_game.boards_verified != BOARDS_VERIFIED {
+        msg!("Both fleets must be proven with verify_board");
+        return Err(BattleshipError::BoardNotVerified.into());
+    }
+
+    if current_log.game_address != account_meta.address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    let game = &mut *game_account;
+    let index = (attack_y as usize * GRID_SIZE) + attack_x as usize;
+    let player = acting_player(&ctx.accounts.signer.key(), session.as_ref())?;
+
+    // Hidden boards: hold the shot until the defender reveals the cell
+    if game.board_mode != BOARD_MODE_PUBLIC {
+        queue_hidden_shot(game, &player, index)?;
+        msg!(
+            "🎯 Shot at ({}, {}) awaiting resolution",
+            attack_x,
+            attack_y
+        );
+    }
+    // Determine target grid and update logic based on turn
+    else if game.current_turn == 1 {
+        // Player A attacking Player B
+        if game.player_a != player {
+            msg!("Not Player A's turn!");
+            return Err(BattleshipError::NotPlayerTurn.into());
+        }
+
+        let result = resolve_shot(&mut game.grid_b, &mut game.hits_b, index)?;
+        if result == CELL_HIT {
+            msg!("💥 HIT on Player B!");
+            if game.hits_b >= fleet_cells(&game.rules.fleet_b) {
+                game.game_status = 2; // A Won
+                game.end_reason = END_REASON_SUNK;
+                msg!("🎉 Player A Wins!");
+            }
+        } else if result == CELL_BLOCKED {
+            msg!("🪨 Shot wasted on an obstacle.");
+        } else {
+            msg!("💨 MISS on Player B.");
+        }
+        let record = MoveRecord {
+            player: 1,
+            x: attack_x,
+            y: attack_y,
+            result,
+        };
+        count_move(game, &record);
+        log_account.moves.push(record);
+
+        // Switch turn to B (blockade attackers keep firing)
+        advance_turn(game, 1);
+
+        // In PvE the bot answers right away
+        if game.game_mode == GAME_MODE_PVE && game.game_status == 1 && game.current_turn == 2 {
+            let bot_move = bot_counter_attack(game, &account_meta.address);
+            log_account.moves.extend(bot_move);
+        }
+    } else {
+        // Player B attacking Player A
+        if game.player_b != player {
+            msg!("Not Player B's turn!");
+            return Err(BattleshipError::NotPlayerTurn.into());
+        }
+
+        let result = resolve_shot(&mut game.grid_a, &mut game.hits_a, index)?;
+        if result == CELL_HIT {
+            msg!("💥 HIT on Player A!");
+            if game.hits_a >= fleet_cells(&game.rules.fleet_a) {
+                game.game_status = 3; // B Won
+                game.end_reason = END_REASON_SUNK;
+                msg!("🎉 Player B Wins!");
+            }
+        } else if result == CELL_BLOCKED {
+            msg!("🪨 Shot wasted on an obstacle.");
+        } else {
+            msg!("💨 MISS on Player A.");
+        }
+        let record = MoveRecord {
+            player: 2,
+            x: attack_x,
+            y: attack_y,
+            result,
+        };
+        count_move(game, &record);
+        log_account.moves.push(record);
+
+        // Switch turn to A (blockade attackers keep firing)
+        advance_turn(game, 2);
+    }
+
+    game.last_action_slot = Clock::get()?.slot;
+    emit_if_ended(game, &account_meta.address);
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    let cpi = with_views(cpi, &game_account, &account_meta.address, views)?;
+    let cpi = GameBatch::new(game_account).log(log_account).append(cpi)?;
+    with_session(cpi, session, &light_cpi_accounts)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn finalize_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+
+    require_no_open_challenge(&current_game)?;
+    if current_game.board_mode == BOARD_MODE_BATCH {
+        msg!("Overdue batch plans are forfeited with forfeit_batch");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    // ZK games can't start until both fleets are proven
+    let unproven =
+        current_game.board_mode == BOARD_MODE_ZK && current_game.boards_verified != BOARDS_VERIFIED;
+
+    // Skip-on-timeout games only forfeit a defender sitting on a reveal
+    if current_game.rules.skip_on_timeout
+        && current_game.pending_shot.is_none()
+        && current_game.current_turn != 0
+        && !unproven
+    {
+        msg!("Idle turns in this game are skipped with force_skip_turn");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    let deadline = turn_deadline(&current_game);
+    if Clock::get()?.slot <= deadline {
+        msg!("Turn clock runs until slot {}", deadline);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+
+    // A stalled draw is forfeited with forfeit_rand first; the game is only
+    // voided here a full turn after the round itself timed out
+    let undrawn = game_account.current_turn == 0 && game_account.pending_draw.is_some();
+    let unstarted = game_account.current_turn == 0;
+    if unstarted || (unproven && game_account.boards_verified == 0) {
+        // Neither player is at fault
+        game_account.game_status = 4;
+        game_account.end_reason = END_REASON_VOID;
+        game_account.pending_shot = None;
+        game_account.pending_draw = None;
+        emit_if_ended(&game_account, &account_meta.address);
+        msg!(
+            "⏰ {}. Game {} voided",
+            if undrawn {
+                "Draw never finished"
+            } else if unstarted {
+                "Game never started"
+            } else {
+                "Neither fleet was proven"
+            },
+            game_account.game_id
+        );
+
+        new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+            .with_light_account(game_account)?
+            .invoke(light_cpi_accounts)?;
+        return Ok(());
+    }
+
+    let idle_player = match (game_account.pending_shot, game_account.current_turn) {
+        // The player who never proved their fleet
+        _ if unproven => 3 - game_account.boards_verified,
+        (Some(_), 1) => 2,
+        (Some(_), _) => 1,
+        (None, turn) => turn,
+    };
+
+    // The idle player forfeits
+    game_account.game_status = if idle_player == 1 { 3 } else { 2 };
+    game_account.end_reason = END_REASON_TIMEOUT;
+    game_account.pending_shot = None;
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "⏰ Player {} timed out. Game {} finalized by {:?}",
+        if idle_player == 1 { "A" } else { "B" },
+        game_account.game_id,
+        ctx.accounts.signer.key()
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn force_skip_turn<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    turn: GameTurn,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameTurn {
+        game: current_game,
+        game_meta: account_meta,
+        log: current_log,
+        log_meta,
+        views,
+    } = turn;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    require_no_open_challenge(&current_game)?;
+
+    if !current_game.rules.skip_on_timeout || current_game.win_condition == WIN_CONDITION_BLOCKADE {
+        msg!("Turns can't be skipped in this game");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    if current_game.pending_shot.is_some() {
+        msg!("A pending reveal times out with finalize_game");
+        return Err(BattleshipError::ShotPending.into());
+    }
+    if current_log.game_address != account_meta.address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+
+    if current_game.current_turn == 0 {
+        msg!("Nobody holds the turn yet");
+        return Err(BattleshipError::CoinFlipPending.into());
+    }
+    let skipped = current_game.current_turn;
+    if player_number(&current_game, &ctx.accounts.signer.key())? == skipped {
+        msg!("Only the opponent can skip an idle turn");
+        return Err(BattleshipError::NotPlayerTurn.into());
+    }
+
+    let deadline = turn_deadline(&current_game);
+    let slot = Clock::get()?.slot;
+    if slot <= deadline {
+        msg!("Turn clock runs until slot {}", deadline);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    skip_turn(&mut game_account);
+    game_account.last_action_slot = slot;
+    log_account.moves.push(MoveRecord {
+        player: skipped,
+        x: 0,
+        y: 0,
+        result: RESULT_SKIPPED,
+    });
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "⏭️ Player {} idle, turn skipped",
+        if skipped == 1 { "A" } else { "B" }
+    );
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    let cpi = with_views(cpi, &game_account, &account_meta.address, views)?;
+    GameBatch::new(game_account)
+        .log(log_account)
+        .append(cpi)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn cancel_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    stats: Option<GlobalStatsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.player_a != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+    close_open_game(ctx, proof, current_game, account_meta, stats, protocol)
+}
+
+pub fn expire_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    stats: Option<GlobalStatsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let expiry = match current_game.open_game_expiry_slots {
+        0 => OPEN_GAME_EXPIRY_SLOTS,
+        slots => slots,
+    };
+    let expires_slot = current_game.last_action_slot + expiry;
+    if Clock::get()?.slot <= expires_slot {
+        msg!("Game stays open until slot {}", expires_slot);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+    close_open_game(ctx, proof, current_game, account_meta, stats, protocol)
+}
+
+pub fn resign<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    require_no_open_challenge(&current_game)?;
+    let seat = player_number(&current_game, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.game_status = if seat == 1 { 3 } else { 2 };
+    game_account.end_reason = END_REASON_RESIGN;
+    game_account.pending_shot = None;
+    game_account.last_action_slot = Clock::get()?.slot;
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "🏳️ Player {} resigned game {}",
+        if seat == 1 { "A" } else { "B" },
+        game_account.game_id
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Places the bot's ship deterministically from the game address
+fn bot_grid(game_address: &[u8; 32]) -> [u8; GRID_CELLS] {
+    let seed = hashv(&[b"bot_ship", game_address]).to_bytes();
+    let is_horizontal = seed[0] & 1 == 0;
+    let along = seed[1] % (GRID_SIZE - SHIP_LENGTH + 1) as u8;
+    let across = seed[2] % GRID_SIZE as u8;
+    let (x, y) = if is_horizontal {
+        (along, across)
+    } else {
+        (across, along)
+    };
+    // Always in bounds by construction
+    place_ship(x, y, is_horizontal).unwrap_or([CELL_EMPTY; GRID_CELLS])
+}
+
+/// Executes the bot's shot at Player A and hands the turn back.
+/// The target is derived from the game address and the number of shots
+/// already taken, probing forward to the next cell that hasn't been hit.
+fn bot_counter_attack(game: &mut GameState, game_address: &[u8; 32]) -> Option<MoveRecord> {
+    let shots_taken = game
+        .grid_a
+        .iter()
+        .filter(|&&cell| cell == CELL_HIT || cell == CELL_MISS)
+        .count() as u8;
+    let seed = hashv(&[b"bot_shot", game_address, &[shots_taken]]).to_bytes();
+    let start = seed[0] as usize % GRID_CELLS;
+
+    let target = (0..GRID_CELLS)
+        .map(|offset| (start + offset) % GRID_CELLS)
+        .find(|&index| game.grid_a[index] == CELL_EMPTY || game.grid_a[index] == CELL_SHIP);
+
+    game.current_turn = 1;
+
+    let index = target?;
+    let (x, y) = ((index % GRID_SIZE) as u8, (index / GRID_SIZE) as u8);
+    let result = resolve_shot(&mut game.grid_a, &mut game.hits_a, index).ok()?;
+    if result == CELL_HIT {
+        msg!("🤖 Bot HIT Player A at ({}, {})!", x, y);
+        if game.hits_a >= fleet_cells(&game.rules.fleet_a) {
+            game.game_status = 3; // B Won
+            game.end_reason = END_REASON_SUNK;
+            msg!("🤖 Bot Wins!");
+        }
+    } else {
+        msg!("🤖 Bot MISSED at ({}, {}).", x, y);
+    }
+    let record = MoveRecord {
+        player: 2,
+        x,
+        y,
+        result,
+    };
+    count_move(game, &record);
+    Some(record)
+}
+
+/// Adds Player A's profile as read-only so the rating used for a handicap
+/// is proven current; Player B's is the slot's
+fn with_opponent_profile(
+    cpi: LightSystemProgramCpi,
+    opponent: Option<OpponentProfile>,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    let Some(opponent) = opponent else {
+        return Ok(cpi);
+    };
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let profile_account = LightAccount::<PlayerProfile>::new_read_only(
+        &crate::ID,
+        &opponent.meta,
+        opponent.profile,
+        &tree_pubkeys,
+    )?;
+    Ok(cpi.with_light_account(profile_account)?)
+}
+
+/// Gives the lower-rated player one extra shot per turn for every
+/// `HANDICAP_STEP` rating points between them, capped at `MAX_SALVO`
+fn apply_handicap(rules: &mut GameRules, rating_a: u16, rating_b: u16) {
+    let bonus = (rating_a.abs_diff(rating_b) / HANDICAP_STEP).min(MAX_SALVO as u16) as u8;
+    let underdog = if rating_a < rating_b {
+        &mut rules.shots_per_turn_a
+    } else {
+        &mut rules.shots_per_turn_b
+    };
+    *underdog = (*underdog + bonus).min(MAX_SALVO);
+}
+
+/// Records a shot on a hidden board, to be resolved by `respond_attack`
+fn queue_hidden_shot(game: &mut GameState, signer: &Pubkey, index: usize) -> Result<()> {
+    let (attacker, targeted) = if game.current_turn == 1 {
+        (game.player_a, &mut game.targeted_a)
+    } else {
+        (game.player_b, &mut game.targeted_b)
+    };
+    if attacker != *signer {
+        msg!("Not your turn!");
+        return Err(BattleshipError::NotPlayerTurn.into());
+    }
+    if game.pending_shot.is_some() {
+        msg!("Waiting for the defender to reveal the previous shot");
+        return Err(BattleshipError::ShotPending.into());
+    }
+    // The defender's grid isn't on chain, so repeats are caught by cell
+    // index, including a shot still waiting on its reveal
+    let bit = 1u32 << index;
+    if *targeted & bit != 0 {
+        return Err(BattleshipError::AlreadyAttacked.into());
+    }
+    *targeted |= bit;
+    game.pending_shot = Some(index as u8);
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use crate::*;
+
+pub fn create_hidden_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    board_root: [u8; 32],
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let game_id = slot.profile.next_game_id;
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.player_b = Pubkey::default();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_MERKLE;
+    game_account.slots_held = 1; // Player A's profile
+
+    // Grids only ever hold revealed cells
+    game_account.grid_a = [CELL_EMPTY; GRID_CELLS];
+    game_account.board_hash_a = board_root;
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+    game_account.board_hash_b = [0u8; 32];
+
+    msg!(
+        "Hidden game {} created by {:?}! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_hidden_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    board_root: [u8; 32],
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    if current_game.game_status != 0 {
+        msg!(
+            "Game is not in waiting state (Status: {})",
+            current_game.game_status
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    require_invited(&current_game, &ctx.accounts.signer.key())?;
+
+    if current_game.board_mode != BOARD_MODE_MERKLE && current_game.board_mode != BOARD_MODE_ZK {
+        msg!("Public-board games must be joined with join_game");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    lock_deposit(ctx.accounts, &account_meta.address)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+
+    game_account.player_b = ctx.accounts.signer.key();
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+    game_account.board_hash_b = board_root;
+    game_account.slots_held |= 2; // Player B's profile
+
+    msg!("Player B joined hidden game {}!", game_account.game_id);
+
+    emit_game_joined(&game_account, &account_meta.address);
+
+    let cpi =
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof).with_light_account(game_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        false,
+        &light_cpi_accounts,
+    )?
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn respond_attack<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    turn: GameTurn,
+    opening: CellOpening,
+    session: Option<SessionAuth>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameTurn {
+        game: current_game,
+        game_meta: account_meta,
+        log: current_log,
+        log_meta,
+        views,
+    } = turn;
+    let CellOpening {
+        cell_value,
+        salt,
+        merkle_path,
+    } = opening;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+
+    if current_game.board_mode != BOARD_MODE_MERKLE {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    if current_log.game_address != account_meta.address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+
+    let index = current_game
+        .pending_shot
+        .ok_or(BattleshipError::NoPendingShot)? as usize;
+
+    if cell_value != CELL_EMPTY && cell_value != CELL_SHIP {
+        return Err(BattleshipError::InvalidCellReveal.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    let game = &mut *game_account;
+    let attacker = game.current_turn;
+
+    // The defender is whoever is not on turn
+    let (defender, root) = if attacker == 1 {
+        (game.player_b, game.board_hash_b)
+    } else {
+        (game.player_a, game.board_hash_a)
+    };
+    if defender != acting_player(&ctx.accounts.signer.key(), session.as_ref())? {
+        msg!("Only the defender can reveal this cell");
+        return Err(BattleshipError::NotDefender.into());
+    }
+
+    if !verify_cell_proof(&root, index as u8, cell_value, &salt, &merkle_path) {
+        msg!("Merkle proof does not match committed board");
+        return Err(BattleshipError::InvalidCellReveal.into());
+    }
+
+    // Resolve against the revealed cell
+    let record = resolve_pending_shot(game, index, cell_value)?;
+    log_account.moves.push(record);
+
+    game.last_action_slot = Clock::get()?.slot;
+    emit_if_ended(game, &account_meta.address);
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    let cpi = with_views(cpi, &game_account, &account_meta.address, views)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    with_session(cpi, session, &light_cpi_accounts)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
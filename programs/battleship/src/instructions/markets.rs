@@ -0,0 +1,268 @@
+use crate::*;
+
+pub fn open_market<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    close_move: u16,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+
+    if game.game_status != 1 || close_move <= game.move_count {
+        msg!("Markets open on active games, closing at a later move");
+        return Err(BattleshipError::MarketClosed.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) = derive_market_address(&game_meta.address, &address_tree_pubkey);
+    let mut market_account =
+        LightAccount::<BetMarket>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    market_account.game_address = game_meta.address;
+    market_account.game_id = game.game_id;
+    market_account.close_move = close_move;
+
+    msg!(
+        "📈 Market open on game {} until move {}",
+        game.game_id,
+        close_move
+    );
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(market_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn place_bet<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    market: BetMarketRef,
+    stake: BetStake,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let BetStake { side, amount } = stake;
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let BetMarketRef {
+        market: current_market,
+        meta: market_meta,
+    } = market;
+
+    if current_market.game_address != game_meta.address {
+        return Err(BattleshipError::MarketMismatch.into());
+    }
+    if game.game_status != 1 || game.move_count >= current_market.close_move {
+        msg!("Betting on game {} is closed", game.game_id);
+        return Err(BattleshipError::MarketClosed.into());
+    }
+    let bettor = ctx.accounts.signer.key();
+    if bettor == game.player_a || bettor == game.player_b {
+        msg!("Players can't bet on their own game");
+        return Err(BattleshipError::MarketClosed.into());
+    }
+    if !matches!(side, 1 | 2) || amount == 0 {
+        return Err(BattleshipError::MarketMismatch.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_market_vault_address(&game_meta.address).0,
+        amount,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_market_bet_address(&game_meta.address, &bettor, &address_tree_pubkey);
+    let mut bet_account =
+        LightAccount::<MarketBet>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    bet_account.game_address = game_meta.address;
+    bet_account.bettor = bettor;
+    bet_account.side = side;
+    bet_account.amount = amount;
+
+    let mut market_account =
+        LightAccount::<BetMarket>::new_mut(&crate::ID, &market_meta, current_market)?;
+    if side == 1 {
+        market_account.pool_a += amount;
+    } else {
+        market_account.pool_b += amount;
+    }
+
+    msg!(
+        "📈 {} lamports on Player {} in game {}",
+        amount,
+        if side == 1 { "A" } else { "B" },
+        game.game_id
+    );
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // The new account goes first so it is output 0
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(bet_account)?
+        .with_light_account(market_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn claim_winnings<'info>(
+    ctx: Context<'_, '_, '_, 'info, SideBetPayoutAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    market: BetMarketRef,
+    bet: MarketBetRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let BetMarketRef {
+        market,
+        meta: market_meta,
+    } = market;
+    let MarketBetRef {
+        bet,
+        meta: bet_meta,
+    } = bet;
+
+    if market.game_address != game_meta.address || bet.game_address != game_meta.address {
+        return Err(BattleshipError::MarketMismatch.into());
+    }
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    if ctx.accounts.bettor.key() != bet.bettor {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+    let (vault, bump) = derive_market_vault_address(&game_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+
+    let (winning_side, winning_pool) = match game.game_status {
+        2 => (1, market.pool_a),
+        3 => (2, market.pool_b),
+        _ => (0, 0),
+    };
+    let pool = market.pool_a + market.pool_b;
+    // Stakes that get paid: the winning side's, or every one on a refund
+    let paid_pool = if winning_pool == 0 {
+        pool
+    } else if bet.side == winning_side {
+        winning_pool
+    } else {
+        msg!("Bet lost");
+        return Err(BattleshipError::NothingToClaim.into());
+    };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let game_id = game.game_id;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    let mut market_account = LightAccount::<BetMarket>::new_mut(&crate::ID, &market_meta, market)?;
+    market_account.claimed += bet.amount;
+    // Shares are floored, so the last claim takes the remainder with it
+    let amount = if market_account.claimed == paid_pool {
+        ctx.accounts.vault.lamports()
+    } else {
+        (bet.amount as u128 * pool as u128 / paid_pool as u128) as u64
+    };
+    // Closing the bet makes a second claim impossible
+    let bet_account = LightAccount::<MarketBet>::new_close(&crate::ID, &bet_meta, bet)?;
+
+    new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(market_account)?
+        .with_light_account(bet_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[MARKET_VAULT_SEED, &game_meta.address, &[bump]];
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.bettor.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )?;
+
+    msg!("💰 Market bet on game {} paid {} lamports", game_id, amount);
+
+    Ok(())
+}
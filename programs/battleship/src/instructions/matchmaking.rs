@@ -0,0 +1,1023 @@
+use crate::*;
+
+pub fn enqueue_match<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    ticket: OracleTicket,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let OracleTicket {
+        ticket_id,
+        oracle_config,
+        preferences,
+    } = ticket;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_match_ticket_address(&ctx.accounts.signer.key(), ticket_id, &address_tree_pubkey);
+    let mut ticket_account =
+        LightAccount::<MatchTicket>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    ticket_account.owner = ctx.accounts.signer.key();
+    ticket_account.ticket_id = ticket_id;
+    ticket_account.preferences = preferences;
+    ticket_account.oracle = oracle_config.config.oracle;
+    ticket_account.status = TICKET_QUEUED;
+
+    emit!(MatchRequested {
+        ticket_address: address,
+        owner: ctx.accounts.signer.key(),
+    });
+    msg!("🎫 {:?} queued for a match", ctx.accounts.signer.key());
+
+    let config_account = LightAccount::<OracleConfig>::new_read_only(
+        &crate::ID,
+        &oracle_config.meta,
+        oracle_config.config,
+        &tree_pubkeys,
+    )?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(config_account)?
+        .with_light_account(ticket_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn enqueue_ranked_match<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    ticket: OracleTicket,
+    rating: RatingProof,
+    bracket: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let OracleTicket {
+        ticket_id,
+        oracle_config,
+        preferences,
+    } = ticket;
+    let RatingProof {
+        profile,
+        profile_meta,
+        verifier,
+        verifier_meta,
+        proof: rating_proof,
+    } = rating;
+
+    if profile.owner != ctx.accounts.signer.key() || profile.rating_commitment == [0u8; 32] {
+        msg!("Ranked queue needs the signer's sealed profile");
+        return Err(BattleshipError::WrongProfile.into());
+    }
+    let (min, max) = *RATING_BRACKETS
+        .get(bracket as usize)
+        .ok_or(BattleshipError::BracketMismatch)?;
+    verify_rating_range(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        profile.rating_commitment,
+        min,
+        max,
+        &rating_proof,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_match_ticket_address(&ctx.accounts.signer.key(), ticket_id, &address_tree_pubkey);
+    let mut ticket_account =
+        LightAccount::<MatchTicket>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    ticket_account.owner = ctx.accounts.signer.key();
+    ticket_account.ticket_id = ticket_id;
+    ticket_account.preferences = preferences;
+    ticket_account.oracle = oracle_config.config.oracle;
+    ticket_account.status = TICKET_QUEUED;
+    ticket_account.bracket = Some(bracket);
+
+    emit!(MatchRequested {
+        ticket_address: address,
+        owner: ctx.accounts.signer.key(),
+    });
+    msg!(
+        "🎫 {:?} queued for a ranked match in bracket {}",
+        ctx.accounts.signer.key(),
+        bracket
+    );
+
+    let config_account = LightAccount::<OracleConfig>::new_read_only(
+        &crate::ID,
+        &oracle_config.meta,
+        oracle_config.config,
+        &tree_pubkeys,
+    )?;
+    let profile_account = LightAccount::<PlayerProfile>::new_read_only(
+        &crate::ID,
+        &profile_meta,
+        profile,
+        &tree_pubkeys,
+    )?;
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(config_account)?
+        .with_light_account(profile_account)?
+        .with_light_account(verifier_account)?
+        .with_light_account(ticket_account)?;
+    let cpi = with_no_ban(
+        cpi,
+        &ctx.accounts.signer.key(),
+        address_tree_info,
+        &light_cpi_accounts,
+    )?;
+    with_attestation(cpi, rating_proof)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn match_callback<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    host_ticket: MatchTicket,
+    host_meta: CompressedAccountMeta,
+    guest_ticket: MatchTicket,
+    guest_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let signer = ctx.accounts.signer.key();
+    for ticket in [&host_ticket, &guest_ticket] {
+        if ticket.oracle != signer {
+            msg!("Only the tickets' oracle can pair them");
+            return Err(BattleshipError::NotOracle.into());
+        }
+        if ticket.status != TICKET_QUEUED {
+            return Err(BattleshipError::TicketNotQueued.into());
+        }
+    }
+    if host_ticket.owner == guest_ticket.owner {
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+    if host_ticket.bracket != guest_ticket.bracket {
+        msg!("Ranked tickets only pair within their rating bracket");
+        return Err(BattleshipError::BracketMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let host = host_ticket.owner;
+    let guest = guest_ticket.owner;
+    let mut host_account =
+        LightAccount::<MatchTicket>::new_mut(&crate::ID, &host_meta, host_ticket)?;
+    host_account.status = TICKET_MATCHED;
+    host_account.opponent = guest;
+    host_account.is_host = true;
+    let mut guest_account =
+        LightAccount::<MatchTicket>::new_mut(&crate::ID, &guest_meta, guest_ticket)?;
+    guest_account.status = TICKET_MATCHED;
+    guest_account.opponent = host;
+
+    emit!(MatchFound { host, guest });
+    msg!("🤝 Matched {:?} with {:?}", host, guest);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(host_account)?
+        .with_light_account(guest_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn cancel_match<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    ticket: MatchTicket,
+    ticket_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if ticket.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotTicketOwner.into());
+    }
+    if ticket.status != TICKET_QUEUED {
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+    if ticket.oracle == Pubkey::default() {
+        msg!("Bucket queue tickets leave with leave_bucket_queue");
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let ticket_account = LightAccount::<MatchTicket>::new_close(&crate::ID, &ticket_meta, ticket)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(ticket_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn open_bucket_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    bucket: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if bucket as usize >= RATING_BRACKETS.len() {
+        return Err(BattleshipError::BracketMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_bucket_queue_address(bucket, &address_tree_pubkey);
+    let mut queue_account =
+        LightAccount::<BucketQueue>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    queue_account.bucket = bucket;
+
+    msg!("Bucket {} queue opened", bucket);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(queue_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_bucket_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    seat: BucketSeat,
+    rating: RatingProof,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let BucketSeat {
+        ticket_id,
+        queue: current_queue,
+        queue_meta,
+        waiting,
+    } = seat;
+    let RatingProof {
+        profile,
+        profile_meta,
+        verifier,
+        verifier_meta,
+        proof: rating_proof,
+    } = rating;
+
+    let signer = ctx.accounts.signer.key();
+    if profile.owner != signer || profile.rating_commitment == [0u8; 32] {
+        msg!("Bucket queues need the signer's sealed profile");
+        return Err(BattleshipError::WrongProfile.into());
+    }
+    let bucket = current_queue.bucket;
+    let (min, max) = RATING_BRACKETS[bucket as usize];
+    verify_rating_range(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        profile.rating_commitment,
+        min,
+        max,
+        &rating_proof,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_match_ticket_address(&signer, ticket_id, &address_tree_pubkey);
+    let mut ticket_account =
+        LightAccount::<MatchTicket>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    ticket_account.owner = signer;
+    ticket_account.ticket_id = ticket_id;
+    ticket_account.bracket = Some(bucket);
+
+    let mut queue_account =
+        LightAccount::<BucketQueue>::new_mut(&crate::ID, &queue_meta, current_queue)?;
+
+    let host_account = match (queue_account.waiting, waiting) {
+        (Some(waiting_address), Some(host)) => {
+            if host.meta.address != waiting_address
+                || host.ticket.status != TICKET_QUEUED
+                || host.ticket.owner == signer
+            {
+                return Err(BattleshipError::TicketNotQueued.into());
+            }
+            let host_owner = host.ticket.owner;
+            let mut host_account =
+                LightAccount::<MatchTicket>::new_mut(&crate::ID, &host.meta, host.ticket)?;
+            host_account.status = TICKET_MATCHED;
+            host_account.opponent = signer;
+            host_account.is_host = true;
+
+            ticket_account.status = TICKET_MATCHED;
+            ticket_account.opponent = host_owner;
+            queue_account.waiting = None;
+
+            emit!(MatchFound {
+                host: host_owner,
+                guest: signer,
+            });
+            msg!(
+                "🤝 Matched {:?} with {:?} in bucket {}",
+                host_owner,
+                signer,
+                bucket
+            );
+            Some(host_account)
+        }
+        (Some(_), None) => {
+            msg!("Someone is waiting in this bucket; pass their ticket");
+            return Err(BattleshipError::TicketNotQueued.into());
+        }
+        (None, _) => {
+            ticket_account.status = TICKET_QUEUED;
+            queue_account.waiting = Some(address);
+            emit!(MatchRequested {
+                ticket_address: address,
+                owner: signer,
+            });
+            msg!("🎫 {:?} waiting in bucket {}", signer, bucket);
+            None
+        }
+    };
+
+    let profile_account = LightAccount::<PlayerProfile>::new_read_only(
+        &crate::ID,
+        &profile_meta,
+        profile,
+        &tree_pubkeys,
+    )?;
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    // The new ticket goes first so it is output 0
+    let mut cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(profile_account)?
+        .with_light_account(verifier_account)?
+        .with_light_account(ticket_account)?
+        .with_light_account(queue_account)?;
+    if let Some(host_account) = host_account {
+        cpi = cpi.with_light_account(host_account)?;
+    }
+    let cpi = with_no_ban(cpi, &signer, address_tree_info, &light_cpi_accounts)?;
+    with_attestation(cpi, rating_proof)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn leave_bucket_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_queue: BucketQueue,
+    queue_meta: CompressedAccountMeta,
+    ticket: MatchTicket,
+    ticket_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if ticket.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotTicketOwner.into());
+    }
+    if ticket.status != TICKET_QUEUED || current_queue.waiting != Some(ticket_meta.address) {
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut queue_account =
+        LightAccount::<BucketQueue>::new_mut(&crate::ID, &queue_meta, current_queue)?;
+    queue_account.waiting = None;
+    let ticket_account = LightAccount::<MatchTicket>::new_close(&crate::ID, &ticket_meta, ticket)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(queue_account)?
+        .with_light_account(ticket_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn issue_credential<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    holder: Pubkey,
+    expires_slot: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let issuer = ctx.accounts.signer.key();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_credential_address(&issuer, &holder, &address_tree_pubkey);
+    let mut credential_account =
+        LightAccount::<Credential>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    credential_account.issuer = issuer;
+    credential_account.holder = holder;
+    credential_account.issued_slot = Clock::get()?.slot;
+    credential_account.expires_slot = expires_slot;
+
+    msg!("🪪 {:?} issued a credential to {:?}", issuer, holder);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(credential_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn revoke_credential<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    credential: Credential,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if credential.issuer != ctx.accounts.signer.key() {
+        return Err(BattleshipError::CredentialRequired.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    msg!("🪪 Credential of {:?} revoked", credential.holder);
+    let credential_account =
+        LightAccount::<Credential>::new_close(&crate::ID, &account_meta, credential)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(credential_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn open_play_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    mode: u8,
+    issuer: Option<Pubkey>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if mode != WAGER_MODE_CASUAL && mode != WAGER_MODE_RANKED {
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_play_queue_address(mode, issuer.as_ref(), &address_tree_pubkey);
+    let mut queue_account =
+        LightAccount::<PlayQueue>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    queue_account.mode = mode;
+    queue_account.issuer = issuer;
+
+    msg!("Play queue {} opened", mode);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(queue_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn join_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    queue: PlayQueueRef,
+    entry: QueueEntry,
+    slot: ActiveGameSlot,
+    credential: Option<CredentialRef>,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let PlayQueueRef {
+        queue: current_queue,
+        meta: queue_meta,
+    } = queue;
+    let QueueEntry {
+        ship,
+        board_hash,
+        tags,
+    } = entry;
+
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+    validate_lobby_tags(&tags, GRID_SIZE as u8)?;
+    let signer = ctx.accounts.signer.key();
+    let ranked = match current_queue.mode {
+        WAGER_MODE_CASUAL => None,
+        WAGER_MODE_RANKED => Some(ranked_config(&slot.protocol)?),
+        _ => return Err(BattleshipError::WrongGameMode.into()),
+    };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let mut queue_account =
+        LightAccount::<PlayQueue>::new_mut(&crate::ID, &queue_meta, current_queue)?;
+
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let credential_account = verified_entry(
+        queue_account.issuer.as_ref(),
+        &signer,
+        credential,
+        &tree_pubkeys,
+    )?;
+
+    let Some(host) = queue_account.waiting.take() else {
+        // Waiting spends the id of the game the next joiner will start
+        let game_id = slot.profile.next_game_id;
+        let (address, _) = derive_game_address(&signer, game_id, &address_tree_pubkey);
+        lock_deposit(ctx.accounts, &address)?;
+        let ranked_stake = ranked.as_ref().map_or(0, |config| config.ranked_stake);
+        if ranked_stake > 0 {
+            lock_lamports(ctx.accounts, derive_vault_address(&address).0, ranked_stake)?;
+        }
+        queue_account.waiting = Some(QueuedPlayer {
+            player: signer,
+            game_id,
+            grid,
+            board_hash,
+            ranked_stake,
+            tags,
+        });
+        msg!(
+            "🎫 {:?} waiting in play queue {}",
+            signer,
+            queue_account.mode
+        );
+
+        let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(queue_account)?;
+        if let Some(credential_account) = credential_account {
+            cpi = cpi.with_light_account(credential_account)?;
+        }
+        if ranked.is_some() {
+            cpi = with_no_ban(cpi, &signer, address_tree_info, &light_cpi_accounts)?;
+        }
+        with_active_game_slot(cpi, slot, &signer, true, &light_cpi_accounts)?
+            .invoke(light_cpi_accounts)?;
+        return Ok(());
+    };
+    if !lobby_tags_match(&host.tags, &tags) {
+        msg!("The waiting player's tags differ, wait for them to be paired");
+        return Err(BattleshipError::LobbyTagsMismatch.into());
+    }
+    if host.player == signer {
+        msg!("Already waiting in the play queue");
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&host.player, host.game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+    if host.ranked_stake > 0 {
+        // Match the stake the host waited with
+        lock_lamports(
+            ctx.accounts,
+            derive_vault_address(&address).0,
+            host.ranked_stake,
+        )?;
+    }
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = host.game_id;
+    game_account.player_a = host.player;
+    game_account.player_b = signer;
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 3; // Both profiles
+    game_account.grid_a = host.grid;
+    game_account.board_hash_a = host.board_hash;
+    game_account.grid_b = grid;
+    game_account.board_hash_b = board_hash;
+    game_account.tags = host.tags;
+    if let Some(config) = &ranked {
+        game_account.wager_mode = WAGER_MODE_RANKED;
+        game_account.ranked_stake = host.ranked_stake;
+        game_account.treasury = config.treasury;
+    }
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit!(MatchFound {
+        host: host.player,
+        guest: signer,
+    });
+    msg!(
+        "🤝 Game {} started between {:?} and {:?}",
+        host.game_id,
+        host.player,
+        signer
+    );
+
+    emit_game_created(&game_account, &address);
+    emit_game_joined(&game_account, &address);
+
+    // The new accounts go first so they are outputs 0 and 1
+    let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .with_light_account(queue_account)?;
+    if let Some(credential_account) = credential_account {
+        cpi = cpi.with_light_account(credential_account)?;
+    }
+    if ranked.is_some() {
+        cpi = with_no_ban(cpi, &signer, address_tree_info, &light_cpi_accounts)?;
+    }
+    with_active_game_slot(cpi, slot, &signer, false, &light_cpi_accounts)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+            address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn leave_queue<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    queue: PlayQueueRef,
+    profile: PlayerProfile,
+    profile_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let PlayQueueRef {
+        queue: current_queue,
+        meta: queue_meta,
+    } = queue;
+
+    let signer = ctx.accounts.signer.key();
+    let Some(waiting) = current_queue
+        .waiting
+        .as_ref()
+        .filter(|w| w.player == signer)
+    else {
+        return Err(BattleshipError::TicketNotQueued.into());
+    };
+    if profile.owner != signer {
+        return Err(BattleshipError::WrongProfile.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let (address, _) = derive_game_address(&signer, waiting.game_id, &address_tree_pubkey);
+    let (vault, bump) = derive_vault_address(&address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+
+    let mut queue_account =
+        LightAccount::<PlayQueue>::new_mut(&crate::ID, &queue_meta, current_queue)?;
+    queue_account.waiting = None;
+    // Waiting held an active game; its id stays spent
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_mut(&crate::ID, &profile_meta, profile)?;
+    profile_account.active_games = profile_account.active_games.saturating_sub(1);
+
+    new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(queue_account)?
+        .with_light_account(profile_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let refund = ctx.accounts.vault.lamports();
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.signer.to_account_info(),
+            },
+            &[&[VAULT_SEED, &address, &[bump]]],
+        ),
+        refund,
+    )?;
+
+    msg!(
+        "{:?} left the play queue, {} lamports refunded",
+        signer,
+        refund
+    );
+
+    Ok(())
+}
+
+pub fn create_matched_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    ticket: MatchTicket,
+    ticket_meta: CompressedAccountMeta,
+    board: ShipBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+
+    let game_id = slot.profile.next_game_id;
+    if ticket.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotTicketOwner.into());
+    }
+    if ticket.status != TICKET_MATCHED || !ticket.is_host {
+        msg!("Ticket is not a matched host ticket");
+        return Err(BattleshipError::TicketNotQueued.into());
+    }
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+
+    // Ranked tickets carry a proven bracket
+    let ranked = ticket
+        .bracket
+        .is_some()
+        .then(|| slot.protocol.config.clone());
+    let ranked_stake = ranked.as_ref().map_or(0, |config| config.ranked_stake);
+    if ranked_stake > 0 {
+        lock_lamports(ctx.accounts, derive_vault_address(&address).0, ranked_stake)?;
+    }
+
+    msg!(
+        "Matched game {} created by {:?} for {:?}",
+        game_id,
+        ctx.accounts.signer.key(),
+        ticket.opponent
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.reserved_for = ticket.opponent;
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.ranked_stake = ranked_stake;
+    if let Some(config) = &ranked {
+        game_account.wager_mode = WAGER_MODE_RANKED;
+        game_account.treasury = config.treasury;
+    }
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    let ticket_account = LightAccount::<MatchTicket>::new_close(&crate::ID, &ticket_meta, ticket)?;
+
+    emit_game_created(&game_account, &address);
+
+    // The new accounts go first so they are outputs 0 and 1
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .with_light_account(ticket_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// The signer's credential a verified queue requires, checked against the
+/// queue's issuer and expiry and passed read-only. Open queues take none.
+fn verified_entry(
+    issuer: Option<&Pubkey>,
+    signer: &Pubkey,
+    credential: Option<CredentialRef>,
+    tree_pubkeys: &[Pubkey],
+) -> Result<Option<LightAccount<Credential>>> {
+    let Some(issuer) = issuer else {
+        if credential.is_some() {
+            msg!("Open queues take no credential");
+            return Err(BattleshipError::CredentialRequired.into());
+        }
+        return Ok(None);
+    };
+    let Some(credential) = credential else {
+        msg!("Verified queue needs a credential from {:?}", issuer);
+        return Err(BattleshipError::CredentialRequired.into());
+    };
+    let expires_slot = credential.credential.expires_slot;
+    if credential.credential.issuer != *issuer
+        || credential.credential.holder != *signer
+        || (expires_slot != 0 && Clock::get()?.slot >= expires_slot)
+    {
+        return Err(BattleshipError::CredentialRequired.into());
+    }
+    Ok(Some(LightAccount::<Credential>::new_read_only(
+        &crate::ID,
+        &credential.meta,
+        credential.credential,
+        tree_pubkeys,
+    )?))
+}
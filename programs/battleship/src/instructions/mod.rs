@@ -0,0 +1,29 @@
+//! Instruction handlers grouped by feature. Each is called from its entry
+//! point in `lib.rs`, which carries the docs.
+
+pub mod admin;
+pub mod batch;
+pub mod campaign;
+pub mod clubs;
+pub mod friends;
+pub mod game;
+pub mod hidden;
+pub mod markets;
+pub mod matchmaking;
+pub mod oracle;
+pub mod payouts;
+pub mod presets;
+pub mod prize_pools;
+pub mod profile;
+pub mod randomness;
+pub mod records;
+pub mod rematch;
+pub mod sealed_wager;
+pub mod seasons;
+pub mod session;
+pub mod settlement;
+pub mod side_bets;
+pub mod social;
+pub mod token_wager;
+pub mod tournaments;
+pub mod zk;
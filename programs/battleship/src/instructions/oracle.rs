@@ -0,0 +1,65 @@
+use crate::*;
+
+pub fn init_oracle_config<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    oracle: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_oracle_config_address(&address_tree_pubkey);
+    let mut config_account =
+        LightAccount::<OracleConfig>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    config_account.oracle = oracle;
+
+    msg!("Oracle set to {:?}", oracle);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn update_oracle_config<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_config: OracleConfig,
+    account_meta: CompressedAccountMeta,
+    oracle: Pubkey,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut config_account =
+        LightAccount::<OracleConfig>::new_mut(&crate::ID, &account_meta, current_config)?;
+    config_account.oracle = oracle;
+
+    msg!("Oracle set to {:?}", oracle);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
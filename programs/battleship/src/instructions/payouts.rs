@@ -0,0 +1,498 @@
+use crate::*;
+
+pub fn claim_deposits<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimDepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    if ctx.accounts.player_a.key() != game.player_a || ctx.accounts.player_b.key() != game.player_b
+    {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+
+    let (vault, bump) = derive_vault_address(&game_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    let pot = ctx.accounts.vault.lamports();
+    if pot == 0 {
+        msg!("Deposits already claimed");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+
+    // What the loser gets back on a clean finish: their deposit and
+    // ranked stake. Losing on time forfeits the deposit to the winner
+    // and slashes the stake into the treasury.
+    let refund = (GAME_DEPOSIT_LAMPORTS + game.ranked_stake).min(pot / 2);
+    let slashed = match game.end_reason {
+        END_REASON_TIMEOUT => game.ranked_stake.min(pot),
+        _ => 0,
+    };
+    let (mut to_a, mut to_b) = match (game.end_reason, game.game_status) {
+        (END_REASON_TIMEOUT, 2) => (pot - slashed, 0),
+        (END_REASON_TIMEOUT, _) => (0, pot - slashed),
+        (_, 2) => (pot - refund, refund),
+        (_, 3) => (refund, pot - refund),
+        _ => (pot / 2, pot - pot / 2),
+    };
+
+    // A won wager pays the protocol fee out of the winnings
+    let fee = match game.game_status {
+        2 | 3 => protocol_fee(2 * game.wager, game.fee_bps).min(pot),
+        _ => 0,
+    };
+    if game.game_status == 2 {
+        to_a -= fee;
+    } else {
+        to_b -= fee;
+    }
+
+    // Every wager feeds the jackpot, out of the winnings or both halves
+    // of a draw, once the jackpot vault is open
+    let jackpot = protocol_fee(2 * game.wager, JACKPOT_BPS);
+    let jackpot = match &ctx.accounts.jackpot {
+        _ if jackpot == 0 => 0,
+        Some(vault) if vault.key() == derive_jackpot_vault_address().0 => {
+            if vault.lamports() + jackpot >= Rent::get()?.minimum_balance(0) {
+                jackpot
+            } else {
+                0
+            }
+        }
+        _ => return Err(BattleshipError::WrongVault.into()),
+    };
+    match game.game_status {
+        2 => to_a -= jackpot.min(to_a),
+        3 => to_b -= jackpot.min(to_b),
+        _ => {
+            to_a -= (jackpot / 2).min(to_a);
+            to_b -= (jackpot - jackpot / 2).min(to_b);
+        }
+    }
+    let jackpot = pot - to_a - to_b - fee - slashed;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let game_id = game.game_id;
+    let treasury = game.treasury;
+    let referrers = game.referrers;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // Prove the finished game state is current before moving lamports
+    new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[VAULT_SEED, &game_meta.address, &[bump]];
+    pay_out(ctx.accounts, seeds, to_a, to_b)?;
+    let referred = pay_referrals(ctx.accounts, seeds, &referrers, fee)?;
+    pay_fee(ctx.accounts, seeds, &treasury, fee - referred + slashed)?;
+    if let Some(vault) = ctx.accounts.jackpot.as_ref().filter(|_| jackpot > 0) {
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: vault.to_account_info(),
+                },
+                &[seeds],
+            ),
+            jackpot,
+        )?;
+    }
+
+    msg!(
+        "💰 Game {} deposits paid out: A {} / B {} lamports, {} fee, {} slashed",
+        game_id,
+        to_a,
+        to_b,
+        fee,
+        slashed
+    );
+
+    Ok(())
+}
+
+pub fn claim_jackpot<'info>(
+    ctx: Context<'_, '_, '_, 'info, JackpotAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let Some(winner) = game_winner(&current_game) else {
+        msg!("Game has no winner");
+        return Err(BattleshipError::GameNotFinished.into());
+    };
+    require_no_open_challenge(&current_game)?;
+    if current_game.wager == 0 || !is_perfect_game(&current_game) || current_game.jackpot_claimed {
+        msg!("Only a perfect wagered win takes the jackpot");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    if ctx.accounts.winner.key() != winner {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+    let (vault, bump) = derive_jackpot_vault_address();
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    let amount = ctx
+        .accounts
+        .vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0));
+    if amount == 0 {
+        msg!("Jackpot is empty");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.jackpot_claimed = true;
+    let game_id = game_account.game_id;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.winner.to_account_info(),
+            },
+            &[&[JACKPOT_VAULT_SEED, &[bump]]],
+        ),
+        amount,
+    )?;
+
+    msg!(
+        "🎰 Perfect game {}! {:?} wins the {} lamport jackpot",
+        game_id,
+        winner,
+        amount
+    );
+
+    Ok(())
+}
+
+pub fn reclaim_stale_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    stats: Option<GlobalStatsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.wager == 0 && current_game.token_wager == 0 {
+        msg!("Game has no wager; use expire_game");
+        return Err(BattleshipError::InvalidWager.into());
+    }
+    let stale_slot = current_game.last_action_slot + STALE_WAGER_SLOTS;
+    if Clock::get()?.slot <= stale_slot {
+        msg!("Wager stays escrowed until slot {}", stale_slot);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+    close_open_game(ctx, proof, current_game, account_meta, stats, protocol)
+}
+
+pub fn offer_raise<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    require_no_open_challenge(&current_game)?;
+    if current_game.wager == 0 {
+        msg!("Only wagered games can be raised");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    if current_game.raise_offered_by != 0 {
+        return Err(BattleshipError::RaisePending.into());
+    }
+    let seat = player_number(&current_game, &ctx.accounts.signer.key())?;
+    if current_game.current_turn != seat {
+        msg!("Not your turn!");
+        return Err(BattleshipError::NotPlayerTurn.into());
+    }
+    if current_game.raise_holder != 0 && current_game.raise_holder != seat {
+        msg!("Only the player who accepted the last raise may raise again");
+        return Err(BattleshipError::RaiseNotAllowed.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_vault_address(&account_meta.address).0,
+        current_game.wager,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.raise_offered_by = seat;
+    // The answer is owed now, so the turn clock runs against the opponent
+    game_account.current_turn = 3 - seat;
+    game_account.last_action_slot = Clock::get()?.slot;
+
+    msg!(
+        "🎲 Player {} offers to raise game {} to {} lamports each",
+        if seat == 1 { "A" } else { "B" },
+        game_account.game_id,
+        game_account.wager * 2
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn accept_raise<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    respondent_seat(&current_game, &ctx.accounts.signer.key())?;
+    lock_lamports(
+        ctx.accounts,
+        derive_vault_address(&account_meta.address).0,
+        current_game.wager,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.wager *= 2;
+    game_account.raise_holder = 3 - game_account.raise_offered_by;
+    game_account.current_turn = game_account.raise_offered_by;
+    game_account.raise_offered_by = 0;
+    game_account.last_action_slot = Clock::get()?.slot;
+
+    msg!(
+        "🎲 Raise accepted: game {} is now for {} lamports each",
+        game_account.game_id,
+        game_account.wager
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn decline_raise<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let seat = respondent_seat(&current_game, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.game_status = if seat == 1 { 3 } else { 2 };
+    game_account.end_reason = END_REASON_RESIGN;
+    game_account.raise_offered_by = 0;
+    game_account.last_action_slot = Clock::get()?.slot;
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "🏳️ Player {} declined the raise and resigned game {}",
+        if seat == 1 { "A" } else { "B" },
+        game_account.game_id
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn sponsor_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    amount: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status > 1 {
+        msg!("Game is over!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    let sponsor = ctx.accounts.signer.key();
+    if sponsor == current_game.player_a
+        || sponsor == current_game.player_b
+        || (current_game.prize > 0 && sponsor != current_game.sponsor)
+    {
+        msg!("Games are sponsored by one third party");
+        return Err(BattleshipError::SponsorMismatch.into());
+    }
+    if amount == 0 {
+        return Err(BattleshipError::InvalidPrize.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_vault_address(&account_meta.address).0,
+        amount,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.prize += amount;
+    game_account.sponsor = sponsor;
+
+    msg!(
+        "🎁 Game {} now has a {} lamport prize from {:?}",
+        game_account.game_id,
+        game_account.prize,
+        sponsor
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Sends `fee` from a vault PDA signed with `seeds` to the game's treasury,
+/// which must be the passed `treasury` account
+fn pay_fee(
+    accounts: &ClaimDepositAccounts,
+    seeds: &[&[u8]],
+    treasury: &Pubkey,
+    fee: u64,
+) -> Result<()> {
+    if fee == 0 {
+        return Ok(());
+    }
+    let Some(treasury_account) = accounts.treasury.as_ref().filter(|t| t.key() == *treasury) else {
+        return Err(BattleshipError::WrongTreasury.into());
+    };
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.vault.to_account_info(),
+                to: treasury_account.to_account_info(),
+            },
+            &[seeds],
+        ),
+        fee,
+    )
+}
+
+/// Credits each pinned referrer `REFERRAL_SHARE_BPS` of half of `fee`, the
+/// part their player generated, returning the total credited. A share that
+/// would leave an unopened referral vault below rent exemption stays with
+/// the treasury.
+fn pay_referrals(
+    accounts: &ClaimDepositAccounts,
+    seeds: &[&[u8]],
+    referrers: &[Pubkey; 2],
+    fee: u64,
+) -> Result<u64> {
+    let share = protocol_fee(fee / 2, REFERRAL_SHARE_BPS);
+    let rent_exempt = Rent::get()?.minimum_balance(0);
+    let vaults = [&accounts.referral_vault_a, &accounts.referral_vault_b];
+    let mut credited = 0;
+    for (referrer, vault) in referrers.iter().zip(vaults) {
+        if *referrer == Pubkey::default() || share == 0 {
+            continue;
+        }
+        let Some(vault) = vault
+            .as_ref()
+            .filter(|v| v.key() == derive_referral_vault_address(referrer).0)
+        else {
+            return Err(BattleshipError::WrongVault.into());
+        };
+        if vault.lamports() + share < rent_exempt {
+            continue;
+        }
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: accounts.vault.to_account_info(),
+                    to: vault.to_account_info(),
+                },
+                &[seeds],
+            ),
+            share,
+        )?;
+        credited += share;
+    }
+    Ok(credited)
+}
+
+/// The signer's seat, if they owe the answer to an open raise
+fn respondent_seat(game: &GameState, signer: &Pubkey) -> Result<u8> {
+    if game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if game.raise_offered_by == 0 {
+        msg!("No raise has been offered");
+        return Err(BattleshipError::RaiseNotAllowed.into());
+    }
+    let seat = player_number(game, signer)?;
+    if seat == game.raise_offered_by {
+        msg!("The opponent answers the raise");
+        return Err(BattleshipError::NotPlayerTurn.into());
+    }
+    Ok(seat)
+}
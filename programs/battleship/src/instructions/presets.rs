@@ -0,0 +1,180 @@
+use crate::*;
+
+pub fn create_fleet_preset<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    preset_id: u16,
+    rules: GameRules,
+) -> Result<()> {
+    validate_rules(&rules)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_fleet_preset_address(preset_id, &address_tree_pubkey);
+    let mut preset_account =
+        LightAccount::<FleetPreset>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    preset_account.preset_id = preset_id;
+    preset_account.rules = rules;
+    preset_account.active = true;
+
+    msg!("Fleet preset {} published", preset_id);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(preset_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn update_fleet_preset<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    current_preset: FleetPreset,
+    account_meta: CompressedAccountMeta,
+    rules: GameRules,
+    active: bool,
+) -> Result<()> {
+    validate_rules(&rules)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut preset_account =
+        LightAccount::<FleetPreset>::new_mut(&crate::ID, &account_meta, current_preset)?;
+    preset_account.rules = rules;
+    preset_account.active = active;
+
+    msg!(
+        "Fleet preset {} updated (active: {})",
+        preset_account.preset_id,
+        active
+    );
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(preset_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_preset_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    preset: FleetPreset,
+    preset_meta: CompressedAccountMetaReadOnly,
+    fleet: FleetBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let FleetBoard { ships, board_hash } = fleet;
+
+    let game_id = slot.profile.next_game_id;
+    if !preset.active {
+        msg!("Fleet preset {} is retired", preset.preset_id);
+        return Err(BattleshipError::PresetInactive.into());
+    }
+    let rules = preset.rules.clone();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    let obstacles = obstacle_grid(&address, &rules);
+    let grid = place_fleet(obstacles, &rules.fleet_a, &ships, rules.grid_size)?;
+    lock_deposit(ctx.accounts, &address)?;
+
+    msg!(
+        "Game {} created from preset {} by {:?}! Waiting for Player B.",
+        game_id,
+        preset.preset_id,
+        ctx.accounts.signer.key()
+    );
+
+    let preset_account = LightAccount::<FleetPreset>::new_read_only(
+        &crate::ID,
+        &preset_meta,
+        preset,
+        &tree_pubkeys,
+    )?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.rules = rules;
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.grid_b = obstacles;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(preset_account)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use crate::*;
+
+pub fn create_prize_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    pool_id: u64,
+    entry_fee: u64,
+    split_bps: Vec<u16>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+
+    validate_prize_split(&split_bps)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_prize_pool_address(&ctx.accounts.signer.key(), pool_id, &address_tree_pubkey);
+    let mut pool_account =
+        LightAccount::<PrizePool>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    pool_account.organizer = ctx.accounts.signer.key();
+    pool_account.pool_id = pool_id;
+    pool_account.entry_fee = entry_fee;
+    pool_account.split_bps = split_bps;
+
+    msg!(
+        "🏆 Prize pool {} opened: {} lamports to enter",
+        pool_id,
+        entry_fee
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(pool_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn register_for_prize_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_pool: PrizePool,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let entrant = ctx.accounts.signer.key();
+    if current_pool.distributed || current_pool.entrants.len() >= MAX_PRIZE_ENTRANTS {
+        msg!("Prize pool {} is closed", current_pool.pool_id);
+        return Err(BattleshipError::PrizePoolClosed.into());
+    }
+    if current_pool.entrants.contains(&entrant) {
+        msg!("Already registered");
+        return Err(BattleshipError::PrizePoolMismatch.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_prize_vault_address(&account_meta.address).0,
+        current_pool.entry_fee,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut pool_account =
+        LightAccount::<PrizePool>::new_mut(&crate::ID, &account_meta, current_pool)?;
+    pool_account.entrants.push(entrant);
+
+    msg!(
+        "🎟️ {:?} entered prize pool {} ({} entrants)",
+        entrant,
+        pool_account.pool_id,
+        pool_account.entrants.len()
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn sponsor_prize_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_pool: PrizePool,
+    account_meta: CompressedAccountMeta,
+    amount: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_pool.distributed {
+        msg!("Prize pool {} is closed", current_pool.pool_id);
+        return Err(BattleshipError::PrizePoolClosed.into());
+    }
+    if amount == 0 {
+        return Err(BattleshipError::InvalidPrize.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_prize_vault_address(&account_meta.address).0,
+        amount,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut pool_account =
+        LightAccount::<PrizePool>::new_mut(&crate::ID, &account_meta, current_pool)?;
+    pool_account.sponsored += amount;
+
+    msg!(
+        "🎁 Prize pool {} sponsored with {} lamports",
+        pool_account.pool_id,
+        amount
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn distribute_prizes<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_pool: PrizePool,
+    account_meta: CompressedAccountMeta,
+    standings: Vec<Pubkey>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_pool.organizer != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotOrganizer.into());
+    }
+    if current_pool.distributed {
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    if standings.is_empty() || standings.len() > current_pool.split_bps.len() {
+        return Err(BattleshipError::InvalidPrizeSplit.into());
+    }
+    for (place, player) in standings.iter().enumerate() {
+        if !current_pool.entrants.contains(player) || standings[..place].contains(player) {
+            msg!("{:?} can't take place {}", player, place + 1);
+            return Err(BattleshipError::PrizePoolMismatch.into());
+        }
+    }
+    let (vault, bump) = derive_prize_vault_address(&account_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    if ctx.remaining_accounts.len() < standings.len() {
+        return Err(ErrorCode::AccountNotEnoughKeys.into());
+    }
+    let (winners, light_accounts) = ctx.remaining_accounts.split_at(standings.len());
+    let split_bps = current_pool.split_bps.clone();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let pool_id = current_pool.pool_id;
+    let mut pool_account =
+        LightAccount::<PrizePool>::new_mut(&crate::ID, &account_meta, current_pool)?;
+    pool_account.distributed = true;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(pool_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[PRIZE_VAULT_SEED, &account_meta.address, &[bump]];
+    let pot = pay_standings(
+        &ctx.accounts.vault,
+        &ctx.accounts.system_program,
+        seeds,
+        winners,
+        &standings,
+        &split_bps,
+    )?;
+
+    msg!(
+        "🏆 Prize pool {} paid {} lamports to {} places",
+        pool_id,
+        pot,
+        standings.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,344 @@
+use crate::*;
+
+pub fn decay_rating<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_profile: PlayerProfile,
+    profile_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let config = &protocol.config;
+    let slot = Clock::get()?.slot;
+    if config.decay_idle_slots == 0 || config.decay_points == 0 {
+        msg!("Rating decay is off");
+        return Err(BattleshipError::RatingNotIdle.into());
+    }
+    if current_profile.rating == 0
+        || slot.saturating_sub(current_profile.rated_slot) <= config.decay_idle_slots
+    {
+        msg!(
+            "Only public ratings idle for {} slots decay",
+            config.decay_idle_slots
+        );
+        return Err(BattleshipError::RatingNotIdle.into());
+    }
+    let floor = if config.decay_floor == 0 {
+        INITIAL_RATING
+    } else {
+        config.decay_floor
+    };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_mut(&crate::ID, &profile_meta, current_profile)?;
+    if profile_account.rating > floor {
+        profile_account.rating = profile_account
+            .rating
+            .saturating_sub(config.decay_points)
+            .max(floor);
+    }
+    profile_account.rated_slot = slot;
+
+    msg!(
+        "📉 {:?} rating decayed to {}",
+        profile_account.owner,
+        profile_account.rating
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(profile_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_profile<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    referrer: Option<Pubkey>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if referrer == Some(ctx.accounts.signer.key()) {
+        msg!("Players can't refer themselves");
+        return Err(BattleshipError::WrongProfile.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_profile_address(&ctx.accounts.signer.key(), &address_tree_pubkey);
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    profile_account.owner = ctx.accounts.signer.key();
+    profile_account.rating = INITIAL_RATING;
+    profile_account.referrer = referrer.unwrap_or_default();
+    profile_account.rated_slot = Clock::get()?.slot;
+
+    msg!("Profile created for {:?}", ctx.accounts.signer.key());
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(profile_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn register_name<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    name: String,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    validate_name(&name)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_name_address(&name, &address_tree_pubkey);
+    let mut name_account =
+        LightAccount::<PlayerName>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    name_account.owner = ctx.accounts.signer.key();
+    name_account.name = name;
+
+    msg!(
+        "Name {} registered to {:?}",
+        name_account.name,
+        name_account.owner
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(name_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn transfer_name<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_name: PlayerName,
+    account_meta: CompressedAccountMeta,
+    new_owner: Pubkey,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_name.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotNameOwner.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut name_account =
+        LightAccount::<PlayerName>::new_mut(&crate::ID, &account_meta, current_name)?;
+    name_account.owner = new_owner;
+
+    msg!("Name {} transferred to {:?}", name_account.name, new_owner);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(name_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn link_referrer<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    profile: PlayerProfile,
+    profile_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status > 1 {
+        msg!("Game is over!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if current_game.wager == 0 {
+        msg!("Only wagered games pay referral shares");
+        return Err(BattleshipError::InvalidWager.into());
+    }
+    let seat = player_number(&current_game, &profile.owner)?;
+    if profile.referrer == Pubkey::default() {
+        msg!("Player has no referrer");
+        return Err(BattleshipError::WrongProfile.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.referrers[seat as usize - 1] = profile.referrer;
+
+    msg!(
+        "Referrer {:?} linked for player {} of game {}",
+        profile.referrer,
+        seat,
+        game_account.game_id
+    );
+
+    let profile_account = LightAccount::<PlayerProfile>::new_read_only(
+        &crate::ID,
+        &profile_meta,
+        profile,
+        &tree_pubkeys,
+    )?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(profile_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn claim_referral_rewards(ctx: Context<DepositAccounts>) -> Result<()> {
+    let referrer = ctx.accounts.signer.key();
+    let (vault, bump) = derive_referral_vault_address(&referrer);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    let amount = ctx
+        .accounts
+        .vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0));
+    if amount == 0 {
+        msg!("No referral rewards to claim");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+
+    let seeds: &[&[u8]] = &[REFERRAL_VAULT_SEED, referrer.as_ref(), &[bump]];
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.signer.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )?;
+
+    msg!(
+        "💸 {:?} claimed {} lamports of referral rewards",
+        referrer,
+        amount
+    );
+
+    Ok(())
+}
+
+pub fn seal_rating<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    current_profile: PlayerProfile,
+    account_meta: CompressedAccountMeta,
+    rating_proof: CircuitProof,
+    commitment: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let CircuitProof {
+        verifier,
+        verifier_meta,
+        proof: rating_proof,
+    } = rating_proof;
+
+    if current_profile.owner != ctx.accounts.signer.key() {
+        return Err(BattleshipError::WrongProfile.into());
+    }
+    if current_profile.rating_commitment != [0u8; 32] || commitment == [0u8; 32] {
+        msg!("Rating is already sealed");
+        return Err(BattleshipError::WrongProfile.into());
+    }
+
+    let rating = current_profile.rating;
+    verify_rating_range(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        commitment,
+        rating,
+        rating,
+        &rating_proof,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_mut(&crate::ID, &account_meta, current_profile)?;
+    profile_account.rating = 0;
+    profile_account.rating_commitment = commitment;
+
+    msg!("🔒 Rating sealed for {:?}", ctx.accounts.signer.key());
+
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(verifier_account)?
+        .with_light_account(profile_account)?;
+    with_attestation(cpi, rating_proof)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use crate::*;
+
+pub fn commit_rand<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameRef,
+    commit: RandCommitment,
+    current_round: Option<RandRoundRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let RandCommitment {
+        round_id,
+        commitment,
+    } = commit;
+
+    if round_id == RAND_REMATCH {
+        if !matches!(current_game.game_status, 2..=4) {
+            msg!("Rematches are drawn once the game is over");
+            return Err(BattleshipError::GameNotFinished.into());
+        }
+    } else if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    let seat = player_number(&current_game, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let deadline = Clock::get()?.slot + turn_timeout(&current_game);
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    let cpi = match current_round {
+        Some(current) => {
+            if current.round.game_address != account_meta.address
+                || current.round.round_id != round_id
+            {
+                return Err(BattleshipError::RandRoundMismatch.into());
+            }
+            let mut round_account =
+                LightAccount::<RandRound>::new_mut(&crate::ID, &current.meta, current.round)?;
+            let slot = if seat == 1 {
+                &mut round_account.commitment_a
+            } else {
+                &mut round_account.commitment_b
+            };
+            if *slot != [0u8; 32] {
+                msg!("Already committed to this round");
+                return Err(BattleshipError::RandClosed.into());
+            }
+            *slot = commitment;
+            round_account.deadline = deadline;
+            if game_account.pending_draw == Some(round_id) {
+                game_account.last_action_slot = deadline;
+            }
+            cpi.with_light_account(round_account)?
+                .with_light_account(game_account)?
+        }
+        None => {
+            let address_tree_pubkey = address_tree_info
+                .get_tree_pubkey(&light_cpi_accounts)
+                .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+            if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+                msg!("Invalid address tree");
+                return Err(ProgramError::InvalidAccountData.into());
+            }
+
+            if round_id == RAND_FIRST_TURN && game_account.pending_draw != Some(round_id) {
+                if game_account.move_count != 0 || game_account.current_turn == 0 {
+                    msg!("First turn can only be drawn before the first shot");
+                    return Err(BattleshipError::RandClosed.into());
+                }
+                game_account.current_turn = 0; // Held until both reveal
+                game_account.pending_draw = Some(round_id);
+            }
+            if game_account.pending_draw == Some(round_id) {
+                // finalize_game only voids once the round has timed out
+                game_account.last_action_slot = deadline;
+            }
+
+            let (address, address_seed) =
+                derive_rand_round_address(&account_meta.address, round_id, &address_tree_pubkey);
+            let mut round_account = LightAccount::<RandRound>::new_init(
+                &crate::ID,
+                Some(address),
+                output_state_tree_index,
+            );
+            round_account.game_address = account_meta.address;
+            round_account.round_id = round_id;
+            if seat == 1 {
+                round_account.commitment_a = commitment;
+            } else {
+                round_account.commitment_b = commitment;
+            }
+            round_account.deadline = deadline;
+
+            // The new account goes first so it is output 0
+            cpi.with_light_account(round_account)?
+                .with_light_account(game_account)?
+                .with_new_addresses(&[address_tree_info
+                    .into_new_address_params_assigned_packed(address_seed, Some(0))])
+        }
+    };
+
+    msg!(
+        "🎲 Player {} committed to round {}",
+        if seat == 1 { "A" } else { "B" },
+        round_id
+    );
+
+    cpi.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn reveal_rand<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    round: RandRoundRef,
+    reveal: RandReveal,
+    views: Option<PlayerViews>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let RandRoundRef {
+        round: current_round,
+        meta: round_meta,
+    } = round;
+    let RandReveal { value, salt } = reveal;
+
+    if current_round.game_address != account_meta.address {
+        return Err(BattleshipError::RandRoundMismatch.into());
+    }
+    if current_round.commitment_a == [0u8; 32]
+        || current_round.commitment_b == [0u8; 32]
+        || current_round.result.is_some()
+    {
+        msg!("Reveals open once both players have committed");
+        return Err(BattleshipError::RandClosed.into());
+    }
+
+    let signer = ctx.accounts.signer.key();
+    let seat = player_number(&current_game, &signer)?;
+    let commitment = if seat == 1 {
+        current_round.commitment_a
+    } else {
+        current_round.commitment_b
+    };
+    if rand_commitment(&round_meta.address, &signer, &value, &salt) != commitment {
+        msg!("Reveal does not open the commitment");
+        return Err(BattleshipError::InvalidRandReveal.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut round_account =
+        LightAccount::<RandRound>::new_mut(&crate::ID, &round_meta, current_round)?;
+    let slot = Clock::get()?.slot;
+    if seat == 1 {
+        round_account.revealed_a = Some(value);
+    } else {
+        round_account.revealed_b = Some(value);
+    }
+    round_account.deadline = slot + turn_timeout(&game_account);
+    if game_account.pending_draw == Some(round_account.round_id) {
+        game_account.last_action_slot = round_account.deadline;
+    }
+
+    if let (Some(value_a), Some(value_b)) = (round_account.revealed_a, round_account.revealed_b) {
+        let result = hashv(&[&value_a, &value_b]).to_bytes();
+        round_account.result = Some(result);
+        msg!("🎲 Round {} resolved", round_account.round_id);
+
+        if game_account.game_status == 1
+            && game_account.pending_draw == Some(round_account.round_id)
+        {
+            if round_account.round_id == RAND_OBSTACLES {
+                let (mut grid_a, mut grid_b) = (game_account.grid_a, game_account.grid_b);
+                scatter_obstacles(&mut grid_a, &mut grid_b, &result, &game_account.rules);
+                game_account.grid_a = grid_a;
+                game_account.grid_b = grid_b;
+                game_account.current_turn = 1; // Player A starts
+                msg!("🪨 Obstacles placed for game {}", game_account.game_id);
+            } else {
+                // The second revealer saw this coming; stalling instead
+                // would only have cost them the game in forfeit_rand
+                let first = 1 + (result[0] & 1);
+                game_account.current_turn = first;
+                game_account.first_turn = first;
+                msg!(
+                    "🪙 Player {} starts game {}",
+                    if first == 1 { "A" } else { "B" },
+                    game_account.game_id
+                );
+            }
+            game_account.pending_draw = None;
+            game_account.last_action_slot = slot;
+        }
+    }
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    with_views(cpi, &game_account, &account_meta.address, views)?
+        .with_light_account(game_account)?
+        .with_light_account(round_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn forfeit_rand<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    round: RandRound,
+    round_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if round.game_address != account_meta.address {
+        return Err(BattleshipError::RandRoundMismatch.into());
+    }
+    if round.result.is_some() {
+        msg!("Round already resolved");
+        return Err(BattleshipError::RandClosed.into());
+    }
+    if Clock::get()?.slot <= round.deadline {
+        msg!("Round is open until slot {}", round.deadline);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+
+    let unset = [0u8; 32];
+    let both_committed = round.commitment_a != unset && round.commitment_b != unset;
+    let stalled_a = round.commitment_a == unset || (both_committed && round.revealed_a.is_none());
+    let stalled_b = round.commitment_b == unset || (both_committed && round.revealed_b.is_none());
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let (status, end_reason) = match (stalled_a, stalled_b) {
+        (true, false) => (3, END_REASON_TIMEOUT), // B Won
+        (false, true) => (2, END_REASON_TIMEOUT), // A Won
+        _ => (4, END_REASON_VOID),
+    };
+    game_account.game_status = status;
+    game_account.end_reason = end_reason;
+    game_account.pending_shot = None;
+    game_account.pending_draw = None;
+    emit_if_ended(&game_account, &account_meta.address);
+
+    msg!(
+        "⏰ Randomness round {} stalled. Game {} finalized by {:?}",
+        round.round_id,
+        game_account.game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let round_account =
+        LightAccount::<RandRound>::new_read_only(&crate::ID, &round_meta, round, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(round_account)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
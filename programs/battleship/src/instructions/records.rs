@@ -0,0 +1,736 @@
+use crate::*;
+
+pub fn verify_win<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    winner: Pubkey,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    require_no_open_challenge(&game)?;
+    if game_winner(&game) != Some(winner) {
+        msg!("{:?} did not win game {}", winner, game.game_id);
+        return Err(BattleshipError::NotWinner.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    msg!("🏆 Verified {:?} as winner", winner);
+    Ok(())
+}
+
+pub fn record_game_result<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    updates: ResultUpdates,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let ResultUpdates {
+        season,
+        profiles,
+        rivalry,
+        stats,
+    } = updates;
+
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_game_result_address(&game_meta.address, &address_tree_pubkey);
+    let mut result_account =
+        LightAccount::<GameResult>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    let (winner, loser) = match game.game_status {
+        2 => (game.player_a, game.player_b), // A Won
+        3 => (game.player_b, game.player_a), // B Won
+        _ => (Pubkey::default(), Pubkey::default()),
+    };
+    result_account.game_address = game_meta.address;
+    result_account.game_id = game.game_id;
+    result_account.winner = winner;
+    result_account.loser = loser;
+    result_account.player_a = game.player_a;
+    result_account.player_b = game.player_b;
+    result_account.game_status = game.game_status;
+    result_account.end_reason = game.end_reason;
+    result_account.move_count = game.move_count;
+    result_account.timestamp = Clock::get()?.unix_timestamp;
+
+    msg!("📜 Result of game {} recorded", game.game_id);
+
+    let mut new_addresses =
+        vec![address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))];
+    let ended_slot = game.last_action_slot;
+    let (player_a, player_b) = (game.player_a, game.player_b);
+    let counted = game.in_stats;
+    let ranked = game.wager_mode == WAGER_MODE_RANKED;
+    if ranked && profiles.is_none() {
+        msg!("Ranked results settle both players' ratings");
+        return Err(BattleshipError::ProfilesRequired.into());
+    }
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // The new result goes first so it is output 0, then the rivalry
+    let mut cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(result_account)?;
+    let mut next_output = 1;
+    if let Some(update) = rivalry {
+        if player_b == BOT_PLAYER || player_b == Pubkey::default() {
+            msg!("Rivalries are between two players");
+            return Err(BattleshipError::WrongGameMode.into());
+        }
+        let (first, second) = rivalry_pair(&player_a, &player_b);
+        let mut rivalry_account = match update.rivalry {
+            Some(rivalry) => {
+                if rivalry.rivalry.player_one != first || rivalry.rivalry.player_two != second {
+                    return Err(BattleshipError::NotAPlayer.into());
+                }
+                LightAccount::<Rivalry>::new_mut(&crate::ID, &rivalry.meta, rivalry.rivalry)?
+            }
+            None => {
+                let (rivalry_address, rivalry_seed) =
+                    derive_rivalry_address(&player_a, &player_b, &address_tree_pubkey);
+                new_addresses.push(
+                    address_tree_info
+                        .into_new_address_params_assigned_packed(rivalry_seed, Some(1)),
+                );
+                let mut rivalry_account = LightAccount::<Rivalry>::new_init(
+                    &crate::ID,
+                    Some(rivalry_address),
+                    output_state_tree_index,
+                );
+                rivalry_account.player_one = first;
+                rivalry_account.player_two = second;
+                rivalry_account
+            }
+        };
+        settle_rivalry(&mut rivalry_account, winner);
+        emit!(RivalryUpdated {
+            player_one: rivalry_account.player_one,
+            player_two: rivalry_account.player_two,
+            wins_one: rivalry_account.wins_one,
+            wins_two: rivalry_account.wins_two,
+            draws: rivalry_account.draws,
+            streak_holder: rivalry_account.streak_holder,
+            streak: rivalry_account.streak,
+            game_address: game_meta.address,
+        });
+        cpi = cpi.with_light_account(rivalry_account)?;
+        next_output += 1;
+    }
+    if let Some(update) = season {
+        if winner == Pubkey::default() {
+            msg!("Draws don't score on the season leaderboard");
+            return Err(BattleshipError::SeasonMismatch.into());
+        }
+        let number = update.season.season;
+        if update.leaderboard.season != number
+            || update.leaderboard.archived
+            || !(update.season.start_slot..update.season.end_slot).contains(&ended_slot)
+        {
+            msg!("Game did not end during season {}", number);
+            return Err(BattleshipError::SeasonMismatch.into());
+        }
+
+        let mut score_account = match update.score {
+            Some(score) => {
+                if score.score.season != number || score.score.player != winner {
+                    return Err(BattleshipError::SeasonMismatch.into());
+                }
+                LightAccount::<SeasonScore>::new_mut(&crate::ID, &score.meta, score.score)?
+            }
+            None => {
+                let (score_address, score_seed) =
+                    derive_season_score_address(number, &winner, &address_tree_pubkey);
+                new_addresses.push(
+                    address_tree_info
+                        .into_new_address_params_assigned_packed(score_seed, Some(next_output)),
+                );
+                let mut score_account = LightAccount::<SeasonScore>::new_init(
+                    &crate::ID,
+                    Some(score_address),
+                    output_state_tree_index,
+                );
+                score_account.season = number;
+                score_account.player = winner;
+                score_account
+            }
+        };
+        score_account.wins += 1;
+
+        let mut leaderboard_account = LightAccount::<Leaderboard>::new_mut(
+            &crate::ID,
+            &update.leaderboard_meta,
+            update.leaderboard,
+        )?;
+        rank_on_leaderboard(&mut leaderboard_account.entries, winner, score_account.wins);
+        msg!(
+            "🏆 {:?} has {} season {} wins",
+            winner,
+            score_account.wins,
+            number
+        );
+
+        let season_account = LightAccount::<Season>::new_read_only(
+            &crate::ID,
+            &update.season_meta,
+            update.season,
+            &tree_pubkeys,
+        )?;
+        cpi = cpi
+            .with_light_account(score_account)?
+            .with_light_account(leaderboard_account)?
+            .with_light_account(season_account)?;
+    }
+    if let Some(profiles) = profiles {
+        // Results are write-once, so each game settles a profile once
+        if profiles.profile_a.owner != player_a
+            || profiles.profile_b.owner != player_b
+            || player_b == BOT_PLAYER
+        {
+            return Err(BattleshipError::WrongProfile.into());
+        }
+        let mut profile_a = LightAccount::<PlayerProfile>::new_mut(
+            &crate::ID,
+            &profiles.meta_a,
+            profiles.profile_a,
+        )?;
+        let mut profile_b = LightAccount::<PlayerProfile>::new_mut(
+            &crate::ID,
+            &profiles.meta_b,
+            profiles.profile_b,
+        )?;
+        for (profile, won) in [
+            (&mut profile_a, winner == player_a),
+            (&mut profile_b, winner == player_b),
+        ] {
+            for milestone in settle_profile(profile, won) {
+                emit!(AchievementUnlocked {
+                    player: profile.owner,
+                    milestone,
+                    game_address: game_meta.address,
+                });
+            }
+        }
+        // Casual games never touch ratings
+        if ranked {
+            let slot = Clock::get()?.slot;
+            profile_a.rated_slot = slot;
+            profile_b.rated_slot = slot;
+            if winner == player_a {
+                apply_rating_change(&mut profile_a, &mut profile_b);
+            } else if winner == player_b {
+                apply_rating_change(&mut profile_b, &mut profile_a);
+            }
+        }
+        cpi = cpi
+            .with_light_account(profile_a)?
+            .with_light_account(profile_b)?;
+    }
+    // Results are write-once, so a game leaves the active count once
+    with_global_stats(cpi, stats, |stats| {
+        if counted {
+            stats.active_games = stats.active_games.saturating_sub(1);
+        }
+    })?
+    .with_new_addresses(&new_addresses)
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn append_match_history<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    result: GameResultRef,
+    page: Option<MatchHistoryRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameResultRef {
+        result,
+        meta: result_meta,
+    } = result;
+
+    let owner = ctx.accounts.signer.key();
+    if result.player_a != owner && result.player_b != owner {
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let entry = result_meta.address;
+    let result_account =
+        LightAccount::<GameResult>::new_read_only(&crate::ID, &result_meta, result, &tree_pubkeys)?;
+
+    let (full_page, next_page) = match page {
+        Some(page) => {
+            if page.history.owner != owner {
+                return Err(BattleshipError::WrongProfile.into());
+            }
+            if page.history.results.contains(&entry) {
+                return Err(BattleshipError::AlreadyInHistory.into());
+            }
+            if page.history.results.len() < MAX_HISTORY_ENTRIES {
+                let mut page_account =
+                    LightAccount::<MatchHistory>::new_mut(&crate::ID, &page.meta, page.history)?;
+                page_account.results.push(entry);
+                msg!(
+                    "🗂️ {:?} history page {} holds {} results",
+                    owner,
+                    page_account.page,
+                    page_account.results.len()
+                );
+
+                new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+                    .with_light_account(page_account)?
+                    .with_light_account(result_account)?
+                    .invoke(light_cpi_accounts)?;
+                return Ok(());
+            }
+            let next_page = page.history.page + 1;
+            // Carried over unchanged, proving the page is full
+            let full_page =
+                LightAccount::<MatchHistory>::new_mut(&crate::ID, &page.meta, page.history)?;
+            (Some(full_page), next_page)
+        }
+        None => (None, 0),
+    };
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_match_history_address(&owner, next_page, &address_tree_pubkey);
+    let mut page_account =
+        LightAccount::<MatchHistory>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    page_account.owner = owner;
+    page_account.page = next_page;
+    page_account.results.push(entry);
+
+    msg!("🗂️ {:?} started history page {}", owner, next_page);
+
+    // The new page goes first so it is output 0
+    let mut cpi =
+        new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?.with_light_account(page_account)?;
+    if let Some(full_page) = full_page {
+        cpi = cpi.with_light_account(full_page)?;
+    }
+    cpi.with_light_account(result_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn earn_loyalty_points<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    loyalty: Option<LoyaltyPointsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    let owner = ctx.accounts.signer.key();
+    let seat = player_number(&game, &owner)?;
+    let opponent = if seat == 1 {
+        game.player_b
+    } else {
+        game.player_a
+    };
+    if game.game_mode != GAME_MODE_PVP || game.move_count < LOYALTY_MIN_MOVES {
+        msg!(
+            "Only PvP games of {} shots or more earn points",
+            LOYALTY_MIN_MOVES
+        );
+        return Err(BattleshipError::LoyaltyIneligible.into());
+    }
+    let points = LOYALTY_POINTS_PLAYED
+        + if game.game_status == seat + 1 {
+            LOYALTY_POINTS_WIN_BONUS
+        } else {
+            0
+        };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (claim_address, claim_seed) =
+        derive_loyalty_claim_address(&game_meta.address, &owner, &address_tree_pubkey);
+    let mut claim_account = LightAccount::<LoyaltyClaim>::new_init(
+        &crate::ID,
+        Some(claim_address),
+        output_state_tree_index,
+    );
+    claim_account.game_address = game_meta.address;
+    claim_account.owner = owner;
+
+    let mut new_addresses =
+        vec![address_tree_info.into_new_address_params_assigned_packed(claim_seed, Some(0))];
+    let mut loyalty_account = match loyalty {
+        Some(loyalty) => {
+            if loyalty.points.owner != owner {
+                return Err(BattleshipError::WrongProfile.into());
+            }
+            LightAccount::<LoyaltyPoints>::new_mut(&crate::ID, &loyalty.meta, loyalty.points)?
+        }
+        None => {
+            let (address, address_seed) = derive_loyalty_address(&owner, &address_tree_pubkey);
+            new_addresses.push(
+                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1)),
+            );
+            let mut loyalty_account = LightAccount::<LoyaltyPoints>::new_init(
+                &crate::ID,
+                Some(address),
+                output_state_tree_index,
+            );
+            loyalty_account.owner = owner;
+            loyalty_account
+        }
+    };
+    if loyalty_account.recent_opponents.contains(&opponent) {
+        msg!("Games against a recent opponent earn no points");
+        return Err(BattleshipError::LoyaltyIneligible.into());
+    }
+    let slot = loyalty_account.games_counted as usize % LOYALTY_RECENT_OPPONENTS;
+    loyalty_account.recent_opponents[slot] = opponent;
+    loyalty_account.games_counted += 1;
+    loyalty_account.points += points;
+
+    msg!(
+        "⭐ {:?} earned {} loyalty points for game {}, {} total",
+        owner,
+        points,
+        game.game_id,
+        loyalty_account.points
+    );
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // The new accounts go first so they are outputs 0 and 1
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(claim_account)?
+        .with_light_account(loyalty_account)?
+        .with_light_account(game_account)?
+        .with_new_addresses(&new_addresses)
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn record_achievements<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    player: Pubkey,
+    achievements: Option<AchievementsRef>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    let seat = player_number(&game, &player)?;
+    if game.game_mode != GAME_MODE_PVP {
+        msg!("Only PvP games count toward achievements");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (mut record, record_meta) = match achievements {
+        Some(achievements) => {
+            if achievements.achievements.owner != player {
+                return Err(BattleshipError::WrongProfile.into());
+            }
+            (achievements.achievements, Some(achievements.meta))
+        }
+        None => (
+            Achievements {
+                owner: player,
+                ..Default::default()
+            },
+            None,
+        ),
+    };
+    if game.last_action_slot <= record.last_game_slot {
+        msg!("Game is not newer than the last one recorded");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    record.last_game_slot = game.last_action_slot;
+    let won = game.game_status == seat + 1;
+    if won {
+        record.wins += 1;
+        record.streak += 1;
+    } else {
+        record.streak = 0;
+    }
+    let earned = earned_badges(&game, won, &record);
+    record.badges |= earned.iter().fold(0, |bits, kind| bits | 1 << kind);
+
+    let mut new_addresses = vec![];
+    let mut badge_accounts = vec![];
+    for &kind in &earned {
+        let (address, address_seed) = derive_badge_address(&player, kind, &address_tree_pubkey);
+        new_addresses.push(address_tree_info.into_new_address_params_assigned_packed(
+            address_seed,
+            Some(badge_accounts.len() as u8),
+        ));
+        let mut badge_account =
+            LightAccount::<Badge>::new_init(&crate::ID, Some(address), output_state_tree_index);
+        badge_account.owner = player;
+        badge_account.kind = kind;
+        badge_account.game_address = game_meta.address;
+        badge_account.earned_slot = game.last_action_slot;
+        badge_accounts.push(badge_account);
+        msg!(
+            "🏆 {:?} earned badge {} in game {}",
+            player,
+            kind,
+            game.game_id
+        );
+    }
+    let record_account = match record_meta {
+        Some(meta) => LightAccount::<Achievements>::new_mut(&crate::ID, &meta, record)?,
+        None => {
+            let (address, address_seed) =
+                derive_achievements_address(&player, &address_tree_pubkey);
+            new_addresses.push(address_tree_info.into_new_address_params_assigned_packed(
+                address_seed,
+                Some(badge_accounts.len() as u8),
+            ));
+            let mut record_account = LightAccount::<Achievements>::new_init(
+                &crate::ID,
+                Some(address),
+                output_state_tree_index,
+            );
+            *record_account = record;
+            record_account
+        }
+    };
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // The new badges go first so they are the first outputs, then the
+    // record
+    let mut cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    for badge_account in badge_accounts {
+        cpi = cpi.with_light_account(badge_account)?;
+    }
+    cpi.with_light_account(record_account)?
+        .with_light_account(game_account)?
+        .with_new_addresses(&new_addresses)
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Moves `player` to their new `wins` on a leaderboard, most wins first.
+/// Players reaching a score later rank below those already on it, and
+/// only the top `LEADERBOARD_SIZE` are kept.
+fn rank_on_leaderboard(entries: &mut Vec<LeaderboardEntry>, player: Pubkey, wins: u32) {
+    entries.retain(|entry| entry.player != player);
+    let place = entries.partition_point(|entry| entry.wins >= wins);
+    entries.insert(place, LeaderboardEntry { player, wins });
+    entries.truncate(LEADERBOARD_SIZE);
+}
+
+/// Adds a game won by `winner` (default for a draw) to a rivalry. The
+/// streak counts the holder's wins in a row and a draw ends it.
+fn settle_rivalry(rivalry: &mut Rivalry, winner: Pubkey) {
+    rivalry.games += 1;
+    if winner == Pubkey::default() {
+        rivalry.draws += 1;
+        rivalry.streak_holder = Pubkey::default();
+        rivalry.streak = 0;
+        return;
+    }
+    if winner == rivalry.player_one {
+        rivalry.wins_one += 1;
+    } else {
+        rivalry.wins_two += 1;
+    }
+    if rivalry.streak_holder == winner {
+        rivalry.streak = rivalry.streak.saturating_add(1);
+    } else {
+        rivalry.streak_holder = winner;
+        rivalry.streak = 1;
+    }
+}
+
+/// Moves `RATING_K` points, less the more the winner was favoured, along
+/// a linear fit of the Elo curve. Sealed ratings stay as they are.
+fn apply_rating_change(winner: &mut PlayerProfile, loser: &mut PlayerProfile) {
+    if winner.rating == 0 || loser.rating == 0 {
+        return;
+    }
+    let gap = loser.rating as i32 - winner.rating as i32;
+    let points = (RATING_K / 2 + gap / 25).clamp(1, RATING_K - 1) as u16;
+    winner.rating = winner.rating.saturating_add(points);
+    loser.rating = loser.rating.saturating_sub(points).max(1);
+}
+
+/// Badges a finished game earns the player whose record is `record`, with
+/// the game already counted in it. Badges the record holds are not earned
+/// twice.
+fn earned_badges(game: &GameState, won: bool, record: &Achievements) -> Vec<u8> {
+    let checks = [
+        (BADGE_FIRST_WIN, won),
+        (BADGE_WIN_STREAK, record.streak >= WIN_STREAK_BADGE_WINS),
+        (BADGE_PERFECT_GAME, won && is_perfect_game(game)),
+    ];
+    checks
+        .into_iter()
+        .filter(|&(kind, earned)| earned && record.badges & (1 << kind) == 0)
+        .map(|(kind, _)| kind)
+        .collect()
+}
+
+/// Counts a settled game in the profile's streaks and returns the
+/// milestones it unlocks. Draws and losses both end a streak.
+fn settle_profile(profile: &mut PlayerProfile, won: bool) -> Vec<u8> {
+    profile.games_played += 1;
+    if won {
+        profile.wins += 1;
+        profile.streak += 1;
+        profile.best_streak = profile.best_streak.max(profile.streak);
+    } else {
+        profile.streak = 0;
+    }
+    let reached = [
+        (MILESTONE_FIRST_WIN, profile.wins >= 1),
+        (MILESTONE_TEN_GAMES, profile.games_played >= 10),
+        (MILESTONE_HUNDRED_GAMES, profile.games_played >= 100),
+        (MILESTONE_FIVE_STREAK, profile.streak >= 5),
+        (MILESTONE_TEN_STREAK, profile.streak >= 10),
+    ];
+    let unlocked: Vec<u8> = reached
+        .into_iter()
+        .filter(|&(milestone, reached)| reached && profile.milestones & 1 << milestone == 0)
+        .map(|(milestone, _)| milestone)
+        .collect();
+    profile.milestones |= unlocked
+        .iter()
+        .fold(0, |bits, milestone| bits | 1 << milestone);
+    unlocked
+}
@@ -0,0 +1,187 @@
+use crate::*;
+
+pub fn request_rematch<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    previous: GameState,
+    previous_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if !matches!(previous.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    if previous.game_mode != GAME_MODE_PVP || previous.board_mode != BOARD_MODE_PUBLIC {
+        msg!("Rematches are for public PvP games");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    player_number(&previous, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_rematch_address(&previous_meta.address, &address_tree_pubkey);
+    let mut rematch_account =
+        LightAccount::<Rematch>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    rematch_account.previous_game = previous_meta.address;
+    rematch_account.player_a = previous.player_a;
+    rematch_account.player_b = previous.player_b;
+    // The handicap already applied is carried over in the salvo sizes
+    rematch_account.rules = GameRules {
+        handicap: false,
+        sealed_obstacles: false,
+        ..previous.rules.clone()
+    };
+    msg!("🔁 Rematch of game {} requested", previous.game_id);
+
+    let previous_account = LightAccount::<GameState>::new_read_only(
+        &crate::ID,
+        &previous_meta,
+        previous,
+        &tree_pubkeys,
+    )?;
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(previous_account)?
+        .with_light_account(rematch_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_rematch_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    draw: RematchDraw,
+    fleet: FleetBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let RematchDraw {
+        rematch,
+        rematch_meta,
+        round,
+        round_meta,
+    } = draw;
+    let FleetBoard { ships, board_hash } = fleet;
+
+    let game_id = slot.profile.next_game_id;
+    if round.game_address != rematch.previous_game || round.round_id != RAND_REMATCH {
+        return Err(BattleshipError::RandRoundMismatch.into());
+    }
+    let seed = round.result.ok_or(BattleshipError::RematchPending)?;
+    let (seat_a, seat_b) = rematch_seats(&rematch, &seed);
+    if ctx.accounts.signer.key() != seat_a {
+        msg!("The seed put {:?} in seat A", seat_a);
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+    let first = 1 + (seed[1] & 1);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    let (mut obstacles_a, mut obstacles_b) = ([CELL_EMPTY; GRID_CELLS], [CELL_EMPTY; GRID_CELLS]);
+    scatter_obstacles(&mut obstacles_a, &mut obstacles_b, &seed, &rematch.rules);
+    let grid = place_fleet(
+        obstacles_a,
+        &rematch.rules.fleet_a,
+        &ships,
+        rematch.rules.grid_size,
+    )?;
+    lock_deposit(ctx.accounts, &address)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = seat_a;
+    game_account.reserved_for = seat_b;
+    game_account.current_turn = first;
+    game_account.first_turn = first;
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.rules = rematch.rules.clone();
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.grid_b = obstacles_b;
+
+    msg!(
+        "🔁 Rematch game {} created, Player {} shoots first",
+        game_id,
+        if first == 1 { "A" } else { "B" }
+    );
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    // Closing the rematch makes a second game from it impossible
+    let rematch_account = LightAccount::<Rematch>::new_close(&crate::ID, &rematch_meta, rematch)?;
+    let round_account =
+        LightAccount::<RandRound>::new_read_only(&crate::ID, &round_meta, round, &tree_pubkeys)?;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .with_light_account(rematch_account)?
+        .with_light_account(round_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
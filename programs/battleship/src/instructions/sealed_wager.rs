@@ -0,0 +1,291 @@
+use crate::*;
+
+pub fn seal_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    commitment: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+
+    if game.game_status != 0 || game.player_a != ctx.accounts.signer.key() {
+        msg!("Only Player A can seal a wager, before the game starts");
+        return Err(BattleshipError::WagerClosed.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_sealed_vault_address(&game_meta.address).0,
+        WAGER_COLLATERAL_LAMPORTS,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_sealed_wager_address(&game_meta.address, &address_tree_pubkey);
+    let mut wager_account =
+        LightAccount::<SealedWager>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    wager_account.game_address = game_meta.address;
+    wager_account.commitment_a = commitment;
+
+    msg!("🔒 Sealed wager opened on game {}", game.game_id);
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(wager_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn match_sealed_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    wager: SealedWagerRef,
+    commitment: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let SealedWagerRef {
+        wager: current_wager,
+        meta: wager_meta,
+    } = wager;
+
+    if current_wager.game_address != game_meta.address {
+        return Err(BattleshipError::WagerMismatch.into());
+    }
+    if game.game_status != 1
+        || game.move_count != 0
+        || game.player_b != ctx.accounts.signer.key()
+        || current_wager.commitment_b != [0u8; 32]
+    {
+        msg!("Only Player B can match the wager, before the first shot");
+        return Err(BattleshipError::WagerClosed.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_sealed_vault_address(&game_meta.address).0,
+        WAGER_COLLATERAL_LAMPORTS,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut wager_account =
+        LightAccount::<SealedWager>::new_mut(&crate::ID, &wager_meta, current_wager)?;
+    wager_account.commitment_b = commitment;
+
+    msg!("🔒 Sealed wager matched on game {}", game.game_id);
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(wager_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn reveal_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    wager: SealedWagerRef,
+    amount: u64,
+    salt: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let SealedWagerRef {
+        wager: current_wager,
+        meta: wager_meta,
+    } = wager;
+
+    if current_wager.game_address != game_meta.address {
+        return Err(BattleshipError::WagerMismatch.into());
+    }
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+
+    let signer = ctx.accounts.signer.key();
+    let seat = player_number(&game, &signer)?;
+    let commitment = if seat == 1 {
+        current_wager.commitment_a
+    } else {
+        current_wager.commitment_b
+    };
+    if amount > WAGER_COLLATERAL_LAMPORTS
+        || wager_commitment(&game_meta.address, &signer, amount, &salt) != commitment
+    {
+        msg!("Reveal does not open the sealed commitment");
+        return Err(BattleshipError::InvalidWagerReveal.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut wager_account =
+        LightAccount::<SealedWager>::new_mut(&crate::ID, &wager_meta, current_wager)?;
+    if seat == 1 {
+        wager_account.revealed_a = Some(amount);
+    } else {
+        wager_account.revealed_b = Some(amount);
+    }
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(wager_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn settle_sealed_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, ClaimDepositAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    wager: SealedWager,
+    wager_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if wager.game_address != game_meta.address {
+        return Err(BattleshipError::WagerMismatch.into());
+    }
+    if ctx.accounts.player_a.key() != game.player_a || ctx.accounts.player_b.key() != game.player_b
+    {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+    let (vault, bump) = derive_sealed_vault_address(&game_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+
+    let collateral = WAGER_COLLATERAL_LAMPORTS;
+    let (to_a, to_b) = if wager.commitment_b == [0u8; 32] {
+        // Never matched: A just gets the collateral back
+        if game.game_status == 1 {
+            return Err(BattleshipError::GameNotFinished.into());
+        }
+        (collateral, 0)
+    } else {
+        if !matches!(game.game_status, 2..=4) {
+            msg!("Game has not finished yet");
+            return Err(BattleshipError::GameNotFinished.into());
+        }
+        require_no_open_challenge(&game)?;
+        let reveal_window = match game.turn_timeout_slots {
+            0 => WAGER_REVEAL_SLOTS,
+            slots => slots,
+        };
+        let reveal_deadline = game
+            .last_action_slot
+            .max(game.challenge_deadline)
+            .saturating_add(reveal_window);
+        match (wager.revealed_a, wager.revealed_b) {
+            (Some(a), Some(b)) => {
+                let stake = a.min(b);
+                match game.game_status {
+                    2 => (collateral + stake, collateral - stake),
+                    3 => (collateral - stake, collateral + stake),
+                    _ => (collateral, collateral),
+                }
+            }
+            _ if Clock::get()?.slot <= reveal_deadline => {
+                msg!("Reveal window runs until slot {}", reveal_deadline);
+                return Err(BattleshipError::TurnNotExpired.into());
+            }
+            (Some(_), None) => (2 * collateral, 0),
+            (None, Some(_)) => (0, 2 * collateral),
+            (None, None) => (collateral, collateral),
+        }
+    };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let game_id = game.game_id;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    // Closing the wager makes a second settlement impossible
+    let wager_account = LightAccount::<SealedWager>::new_close(&crate::ID, &wager_meta, wager)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(wager_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[SEALED_VAULT_SEED, &game_meta.address, &[bump]];
+    pay_out(ctx.accounts, seeds, to_a, to_b)?;
+
+    msg!(
+        "💰 Sealed wager on game {} settled: A {} / B {} lamports",
+        game_id,
+        to_a,
+        to_b
+    );
+
+    Ok(())
+}
@@ -0,0 +1,345 @@
+use crate::*;
+
+pub fn rollover_season<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    current: Option<SeasonRollover>,
+    season_slots: u64,
+) -> Result<()> {
+    if season_slots == 0 {
+        return Err(BattleshipError::SeasonMismatch.into());
+    }
+    let slot = Clock::get()?.slot;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let number = current.as_ref().map_or(1, |c| c.season.season + 1);
+    let (season_address, season_seed) = derive_season_address(number, &address_tree_pubkey);
+    let mut season_account =
+        LightAccount::<Season>::new_init(&crate::ID, Some(season_address), output_state_tree_index);
+    season_account.season = number;
+    season_account.start_slot = slot;
+    season_account.end_slot = slot + season_slots;
+    let (board_address, board_seed) = derive_leaderboard_address(number, &address_tree_pubkey);
+    let mut leaderboard_account = LightAccount::<Leaderboard>::new_init(
+        &crate::ID,
+        Some(board_address),
+        output_state_tree_index,
+    );
+    leaderboard_account.season = number;
+
+    msg!(
+        "🗓️ Season {} runs until slot {}",
+        number,
+        slot + season_slots
+    );
+
+    // The new accounts go first so they are outputs 0 and 1
+    let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(season_account)?
+        .with_light_account(leaderboard_account)?;
+    if let Some(current) = current {
+        if current.leaderboard.season != current.season.season || current.leaderboard.archived {
+            return Err(BattleshipError::SeasonMismatch.into());
+        }
+        if slot < current.season.end_slot {
+            msg!("Season runs until slot {}", current.season.end_slot);
+            return Err(BattleshipError::SeasonMismatch.into());
+        }
+        let mut old_board = LightAccount::<Leaderboard>::new_mut(
+            &crate::ID,
+            &current.leaderboard_meta,
+            current.leaderboard,
+        )?;
+        old_board.archived = true;
+        let old_season = LightAccount::<Season>::new_read_only(
+            &crate::ID,
+            &current.season_meta,
+            current.season,
+            &tree_pubkeys,
+        )?;
+        cpi = cpi
+            .with_light_account(old_board)?
+            .with_light_account(old_season)?;
+    }
+    cpi.with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(season_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(board_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn reset_season<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    current: SeasonRollover,
+) -> Result<()> {
+    let number = current.season.season;
+    if current.leaderboard.season != number || !current.leaderboard.archived {
+        msg!("Season {} has not been rolled over yet", number);
+        return Err(BattleshipError::SeasonMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) = derive_season_archive_address(number, &address_tree_pubkey);
+    let mut archive_account =
+        LightAccount::<SeasonArchive>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    archive_account.season = number;
+    archive_account.start_slot = current.season.start_slot;
+    archive_account.end_slot = current.season.end_slot;
+    archive_account.standings = current.leaderboard.entries.clone();
+    archive_account.archived_slot = Clock::get()?.slot;
+
+    msg!(
+        "🗄️ Season {} archived with {} standings",
+        number,
+        archive_account.standings.len()
+    );
+
+    let board_account = LightAccount::<Leaderboard>::new_close(
+        &crate::ID,
+        &current.leaderboard_meta,
+        current.leaderboard,
+    )?;
+    let season_account = LightAccount::<Season>::new_read_only(
+        &crate::ID,
+        &current.season_meta,
+        current.season,
+        &tree_pubkeys,
+    )?;
+
+    // The new archive goes first so it is output 0
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(archive_account)?
+        .with_light_account(board_account)?
+        .with_light_account(season_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_season_rewards<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    season: u32,
+    split_bps: Vec<u16>,
+) -> Result<()> {
+    validate_prize_split(&split_bps)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_season_rewards_address(season, &address_tree_pubkey);
+    let mut rewards_account =
+        LightAccount::<SeasonRewards>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    rewards_account.season = season;
+    rewards_account.split_bps = split_bps;
+
+    msg!("🏅 Season {} rewards pool opened", season);
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(rewards_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn fund_season_rewards<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    current_rewards: SeasonRewards,
+    account_meta: CompressedAccountMeta,
+    amount: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_rewards.distributed {
+        msg!("Season {} rewards are paid out", current_rewards.season);
+        return Err(BattleshipError::PrizePoolClosed.into());
+    }
+    if amount == 0 {
+        return Err(BattleshipError::InvalidPrize.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_prize_vault_address(&account_meta.address).0,
+        amount,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut rewards_account =
+        LightAccount::<SeasonRewards>::new_mut(&crate::ID, &account_meta, current_rewards)?;
+    rewards_account.funded += amount;
+
+    msg!(
+        "🏅 Season {} rewards pool now holds {} lamports",
+        rewards_account.season,
+        rewards_account.funded
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(rewards_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn distribute_season_rewards<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminPayoutAccounts<'info>>,
+    proof: ValidityProof,
+    current_rewards: SeasonRewards,
+    account_meta: CompressedAccountMeta,
+    final_standings: SeasonStandings,
+) -> Result<()> {
+    if current_rewards.distributed {
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    let (season, entries) = match &final_standings {
+        SeasonStandings::Leaderboard(board) => {
+            if !board.leaderboard.archived {
+                msg!(
+                    "Season {} has not been rolled over yet",
+                    current_rewards.season
+                );
+                return Err(BattleshipError::SeasonMismatch.into());
+            }
+            (board.leaderboard.season, &board.leaderboard.entries)
+        }
+        SeasonStandings::Archive(archive) => (archive.archive.season, &archive.archive.standings),
+    };
+    if season != current_rewards.season {
+        return Err(BattleshipError::SeasonMismatch.into());
+    }
+    let standings: Vec<Pubkey> = entries
+        .iter()
+        .take(current_rewards.split_bps.len())
+        .map(|entry| entry.player)
+        .collect();
+    if standings.is_empty() {
+        return Err(BattleshipError::InvalidPrizeSplit.into());
+    }
+    let (vault, bump) = derive_prize_vault_address(&account_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    if ctx.remaining_accounts.len() < standings.len() {
+        return Err(ErrorCode::AccountNotEnoughKeys.into());
+    }
+    let (winners, light_accounts) = ctx.remaining_accounts.split_at(standings.len());
+    let split_bps = current_rewards.split_bps.clone();
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let season = current_rewards.season;
+    let mut rewards_account =
+        LightAccount::<SeasonRewards>::new_mut(&crate::ID, &account_meta, current_rewards)?;
+    rewards_account.distributed = true;
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(rewards_account)?;
+    match final_standings {
+        SeasonStandings::Leaderboard(board) => {
+            cpi.with_light_account(LightAccount::<Leaderboard>::new_read_only(
+                &crate::ID,
+                &board.meta,
+                board.leaderboard,
+                &tree_pubkeys,
+            )?)?
+        }
+        SeasonStandings::Archive(archive) => {
+            cpi.with_light_account(LightAccount::<SeasonArchive>::new_read_only(
+                &crate::ID,
+                &archive.meta,
+                archive.archive,
+                &tree_pubkeys,
+            )?)?
+        }
+    }
+    .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[PRIZE_VAULT_SEED, &account_meta.address, &[bump]];
+    let pot = pay_standings(
+        &ctx.accounts.vault,
+        &ctx.accounts.system_program,
+        seeds,
+        winners,
+        &standings,
+        &split_bps,
+    )?;
+
+    msg!(
+        "🏅 Season {} rewards paid {} lamports to {} places",
+        season,
+        pot,
+        standings.len()
+    );
+
+    Ok(())
+}
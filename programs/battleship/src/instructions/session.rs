@@ -0,0 +1,95 @@
+use crate::*;
+
+pub fn authorize_session_key<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    session_key: Pubkey,
+    expires_at_slot: u64,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let current_slot = Clock::get()?.slot;
+    if expires_at_slot <= current_slot
+        || expires_at_slot > current_slot.saturating_add(MAX_SESSION_SLOTS)
+    {
+        msg!("Session expiry must be within {} slots", MAX_SESSION_SLOTS);
+        return Err(BattleshipError::InvalidSessionExpiry.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_address(
+        &[
+            SESSION_SEED,
+            ctx.accounts.signer.key().as_ref(),
+            session_key.as_ref(),
+        ],
+        &address_tree_pubkey,
+        &crate::ID,
+    );
+
+    let mut session_account =
+        LightAccount::<SessionKey>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    session_account.owner = ctx.accounts.signer.key();
+    session_account.session_key = session_key;
+    session_account.expires_at_slot = expires_at_slot;
+
+    msg!(
+        "Session key {:?} authorized until slot {}",
+        session_key,
+        expires_at_slot
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(session_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn revoke_session_key<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_session: SessionKey,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_session.owner != ctx.accounts.signer.key() {
+        msg!("Only the session owner can revoke it");
+        return Err(BattleshipError::InvalidSessionKey.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    msg!("Session key {:?} revoked", current_session.session_key);
+
+    let session_account =
+        LightAccount::<SessionKey>::new_close(&crate::ID, &account_meta, current_session)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(session_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
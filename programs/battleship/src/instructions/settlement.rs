@@ -0,0 +1,408 @@
+use crate::*;
+
+pub fn verify_replay<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    log: MoveLogReadOnlyRef,
+    ships_a: Vec<ShipPlacement>,
+    ships_b: Vec<ShipPlacement>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let MoveLogReadOnlyRef {
+        log,
+        meta: log_meta,
+    } = log;
+
+    if log.game_address != game_meta.address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+    if game.board_mode != BOARD_MODE_PUBLIC {
+        msg!("Hidden boards can't be replayed on-chain");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    let rules = &game.rules;
+    let board_a = revealed_grid(&game.grid_a, &rules.fleet_a, &ships_a, rules.grid_size)?;
+    let board_b = revealed_grid(&game.grid_b, &rules.fleet_b, &ships_b, rules.grid_size)?;
+    let replayed = replay_game(&game, &log, board_a, board_b)?;
+    if !replay_matches(&replayed, &game) {
+        msg!("Replay does not match stored game state");
+        return Err(BattleshipError::ReplayMismatch.into());
+    }
+
+    msg!(
+        "Replay of game {} verified ({} moves)",
+        game.game_id,
+        log.moves.len()
+    );
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    let log_account =
+        LightAccount::<MoveLog>::new_read_only(&crate::ID, &log_meta, log, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn settle_offchain_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, SettleAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    log: MoveLogRef,
+    final_status: u8,
+    moves: Vec<MoveRecord>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let MoveLogRef {
+        log: current_log,
+        meta: log_meta,
+    } = log;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    require_no_open_challenge(&current_game)?;
+
+    let slot = Clock::get()?.slot;
+    let mut settled = replay_settlement(
+        &ctx.accounts.instructions.to_account_info(),
+        &current_game,
+        &account_meta.address,
+        &current_log,
+        final_status,
+        &moves,
+    )?;
+    settled.last_action_slot = slot;
+    settled.challenge_deadline = slot.saturating_add(CHALLENGE_WINDOW_SLOTS);
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    let game: &mut GameState = &mut game_account;
+    *game = settled;
+    log_account.moves = moves;
+
+    msg!(
+        "🤝 Game {} settled off-chain after {} moves (status {}), challengeable until slot {}",
+        game.game_id,
+        game.move_count,
+        game.game_status,
+        game.challenge_deadline
+    );
+    emit_if_ended(game, &account_meta.address);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn challenge_settlement<'info>(
+    ctx: Context<'_, '_, '_, 'info, SettleAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    log: MoveLogRef,
+    final_status: u8,
+    moves: Vec<MoveRecord>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let MoveLogRef {
+        log: current_log,
+        meta: log_meta,
+    } = log;
+
+    let signer = ctx.accounts.signer.key();
+    if signer != current_game.player_a && signer != current_game.player_b {
+        msg!("Only the players can challenge a settlement");
+        return Err(BattleshipError::NotPlayerTurn.into());
+    }
+    if current_game.challenge_deadline == 0 || Clock::get()?.slot > current_game.challenge_deadline
+    {
+        msg!("No open settlement to challenge");
+        return Err(BattleshipError::NoOpenSettlement.into());
+    }
+    if moves.len() <= current_log.moves.len() {
+        msg!("Challenge must extend the settled transcript");
+        return Err(BattleshipError::TranscriptNotLonger.into());
+    }
+
+    let mut settled = replay_settlement(
+        &ctx.accounts.instructions.to_account_info(),
+        &current_game,
+        &account_meta.address,
+        &current_log,
+        final_status,
+        &moves,
+    )?;
+    settled.last_action_slot = current_game.last_action_slot;
+    settled.challenge_deadline = current_game.challenge_deadline;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    let game: &mut GameState = &mut game_account;
+    *game = settled;
+    log_account.moves = moves;
+
+    msg!(
+        "⚖️ Settlement of game {} superseded: {} moves (status {})",
+        game.game_id,
+        game.move_count,
+        game.game_status
+    );
+    emit_if_ended(game, &account_meta.address);
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Rebuilds a starting board from the revealed placements, on top of the
+/// obstacles the game was played with
+fn revealed_grid(
+    grid: &[u8; GRID_CELLS],
+    fleet: &[u8],
+    ships: &[ShipPlacement],
+    grid_size: u8,
+) -> Result<[u8; GRID_CELLS]> {
+    let obstacles = grid.map(|cell| {
+        if cell == CELL_BLOCKED {
+            CELL_BLOCKED
+        } else {
+            CELL_EMPTY
+        }
+    });
+    place_fleet(obstacles, fleet, ships, grid_size)
+}
+
+/// Recovers the starting board from a grid the program has kept up to date:
+/// every ship cell, hit or not, plus the obstacles
+fn initial_grid(grid: &[u8; GRID_CELLS]) -> [u8; GRID_CELLS] {
+    grid.map(|cell| match cell {
+        CELL_SHIP | CELL_HIT => CELL_SHIP,
+        CELL_BLOCKED => CELL_BLOCKED,
+        _ => CELL_EMPTY,
+    })
+}
+
+/// Replays the logged moves from the given starting boards
+fn replay_game(
+    game: &GameState,
+    log: &MoveLog,
+    grid_a: [u8; GRID_CELLS],
+    grid_b: [u8; GRID_CELLS],
+) -> Result<GameState> {
+    let mut replayed = game.clone();
+    replayed.grid_a = grid_a;
+    replayed.grid_b = grid_b;
+    replayed.hits_a = 0;
+    replayed.hits_b = 0;
+    replayed.move_count = 0;
+    replayed.stats_a = ShotStats::default();
+    replayed.stats_b = ShotStats::default();
+    replayed.shots_this_turn = 0;
+    replayed.current_turn = if game.win_condition == WIN_CONDITION_BLOCKADE {
+        game.blockade_attacker
+    } else if game.first_turn == 2 {
+        2
+    } else {
+        1
+    };
+    // Games that never got a second player have nothing to replay
+    replayed.game_status = if game.game_status == 0 { 0 } else { 1 };
+
+    for record in &log.moves {
+        if replayed.game_status != 1 || record.player != replayed.current_turn {
+            return Err(BattleshipError::ReplayMismatch.into());
+        }
+        if record.result == RESULT_SKIPPED && replayed.rules.skip_on_timeout {
+            skip_turn(&mut replayed);
+            continue;
+        }
+        let grid_size = replayed.rules.grid_size;
+        if record.x >= grid_size || record.y >= grid_size {
+            return Err(BattleshipError::InvalidPosition.into());
+        }
+        let index = (record.y as usize * GRID_SIZE) + record.x as usize;
+
+        let result = if record.player == 1 {
+            let result = resolve_shot(&mut replayed.grid_b, &mut replayed.hits_b, index)?;
+            if replayed.hits_b >= fleet_cells(&replayed.rules.fleet_b) {
+                replayed.game_status = 2;
+            }
+            result
+        } else {
+            let result = resolve_shot(&mut replayed.grid_a, &mut replayed.hits_a, index)?;
+            if replayed.hits_a >= fleet_cells(&replayed.rules.fleet_a) {
+                replayed.game_status = 3;
+            }
+            result
+        };
+        if result != record.result {
+            return Err(BattleshipError::ReplayMismatch.into());
+        }
+        count_move(&mut replayed, record);
+        advance_turn(&mut replayed, record.player);
+    }
+
+    Ok(replayed)
+}
+
+/// Compares the gameplay fields a replay is able to reconstruct
+fn replay_matches(replayed: &GameState, stored: &GameState) -> bool {
+    replayed.grid_a == stored.grid_a
+        && replayed.grid_b == stored.grid_b
+        && replayed.hits_a == stored.hits_a
+        && replayed.hits_b == stored.hits_b
+        && (replayed.game_status == stored.game_status
+            // Timeouts happen off the log; the replay just ends mid-game
+            || (stored.end_reason == END_REASON_TIMEOUT && replayed.game_status == 1))
+        && replayed.current_turn == stored.current_turn
+        && replayed.move_count == stored.move_count
+        && replayed.stats_a == stored.stats_a
+        && replayed.stats_b == stored.stats_b
+}
+
+/// Checks a co-signed off-chain transcript and replays it on top of the
+/// committed placements. Moves already recorded on-chain can't be rewritten.
+fn replay_settlement(
+    instructions: &AccountInfo,
+    game: &GameState,
+    game_address: &[u8; 32],
+    log: &MoveLog,
+    final_status: u8,
+    moves: &[MoveRecord],
+) -> Result<GameState> {
+    if game.game_mode != GAME_MODE_PVP {
+        msg!("Only PvP games can be settled off-chain");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+    if game.board_mode != BOARD_MODE_PUBLIC {
+        msg!("Hidden boards can't be replayed on-chain");
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if log.game_address != *game_address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+    if !moves.starts_with(&log.moves) {
+        msg!("Transcript does not extend the on-chain move log");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+
+    let message = settlement_message(game_address, final_status, moves);
+    verify_ed25519_signature(instructions, &game.player_a, &message)?;
+    verify_ed25519_signature(instructions, &game.player_b, &message)?;
+
+    let transcript = MoveLog {
+        game_address: *game_address,
+        moves: moves.to_vec(),
+    };
+    // The on-chain grids are the committed placements: only the program
+    // writes them, so a settlement may start from them
+    let mut replayed = replay_game(
+        game,
+        &transcript,
+        initial_grid(&game.grid_a),
+        initial_grid(&game.grid_b),
+    )?;
+    if replayed.game_status != final_status {
+        msg!("Signed result does not match the replay");
+        return Err(BattleshipError::ReplayMismatch.into());
+    }
+    if replayed.game_status == 2 || replayed.game_status == 3 {
+        replayed.end_reason = END_REASON_SUNK;
+    }
+    Ok(replayed)
+}
+
+/// Size of one signature's offsets entry in ed25519 program instruction data
+const ED25519_OFFSETS_LEN: usize = 14;
+
+/// Checks that an ed25519 program instruction earlier in this transaction
+/// verified `signer`'s signature over `message`
+fn verify_ed25519_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    for index in 0..current {
+        let ix = load_instruction_at_checked(index, instructions)?;
+        if ix.program_id == ed25519_program::ID && ed25519_signs(&ix.data, signer, message) {
+            return Ok(());
+        }
+    }
+    msg!("No ed25519 signature from {:?}", signer);
+    Err(BattleshipError::MissingSignature.into())
+}
+
+/// Whether ed25519 instruction `data` contains a signature by `signer` over `message`
+fn ed25519_signs(data: &[u8], signer: &Pubkey, message: &[u8]) -> bool {
+    let count = data.first().copied().unwrap_or(0) as usize;
+    (0..count).any(|i| {
+        let start = 2 + i * ED25519_OFFSETS_LEN;
+        let Some(offsets) = data.get(start..start + ED25519_OFFSETS_LEN) else {
+            return false;
+        };
+        let field = |n: usize| u16::from_le_bytes([offsets[2 * n], offsets[2 * n + 1]]) as usize;
+        // Signature, key and message must all live in this instruction
+        if field(1) != u16::MAX as usize
+            || field(3) != u16::MAX as usize
+            || field(6) != u16::MAX as usize
+        {
+            return false;
+        }
+        let (key, msg_start, msg_len) = (field(2), field(4), field(5));
+        data.get(key..key + 32) == Some(signer.as_ref())
+            && data.get(msg_start..msg_start + msg_len) == Some(message)
+    })
+}
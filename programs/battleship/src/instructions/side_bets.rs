@@ -0,0 +1,346 @@
+use crate::*;
+
+pub fn open_side_pool<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if game.game_status != 1 {
+        msg!("Side bets open on active games");
+        return Err(BattleshipError::SideBetClosed.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_side_pool_address(&game_meta.address, &address_tree_pubkey);
+    let mut pool_account =
+        LightAccount::<SideBetPool>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    pool_account.game_address = game_meta.address;
+    pool_account.game_id = game.game_id;
+
+    msg!("🎲 Side bets open on game {}", game.game_id);
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(pool_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn place_side_bet<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    pool: SideBetPoolRef,
+    commitment: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let SideBetPoolRef {
+        pool: current_pool,
+        meta: pool_meta,
+    } = pool;
+
+    if current_pool.game_address != game_meta.address {
+        return Err(BattleshipError::SideBetMismatch.into());
+    }
+    if game.game_status != 1 {
+        msg!("Betting on game {} is closed", game.game_id);
+        return Err(BattleshipError::SideBetClosed.into());
+    }
+    let bettor = ctx.accounts.signer.key();
+    if bettor == game.player_a || bettor == game.player_b {
+        msg!("Players can't bet on their own game");
+        return Err(BattleshipError::SideBetClosed.into());
+    }
+    lock_lamports(
+        ctx.accounts,
+        derive_side_vault_address(&game_meta.address).0,
+        SIDE_BET_STAKE_LAMPORTS,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_side_bet_address(&game_meta.address, &bettor, &address_tree_pubkey);
+    let mut bet_account =
+        LightAccount::<SideBet>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    bet_account.game_address = game_meta.address;
+    bet_account.bettor = bettor;
+    bet_account.commitment = commitment;
+
+    let mut pool_account =
+        LightAccount::<SideBetPool>::new_mut(&crate::ID, &pool_meta, current_pool)?;
+    pool_account.bets += 1;
+
+    msg!(
+        "🎲 Sealed side bet #{} on game {}",
+        pool_account.bets,
+        game.game_id
+    );
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    // The new account goes first so it is output 0
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(bet_account)?
+        .with_light_account(pool_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Bets can be revealed until `SIDE_BET_REVEAL_SLOTS` after the result is
+/// final
+fn reveal_deadline(game: &GameState) -> u64 {
+    game.last_action_slot
+        .max(game.challenge_deadline)
+        .saturating_add(SIDE_BET_REVEAL_SLOTS)
+}
+
+pub fn reveal_side_bet<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    pool: SideBetPoolRef,
+    bet: SideBetRef,
+    reveal: SideBetReveal,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let SideBetPoolRef {
+        pool: current_pool,
+        meta: pool_meta,
+    } = pool;
+    let SideBetRef {
+        bet: current_bet,
+        meta: bet_meta,
+    } = bet;
+    let SideBetReveal { side, salt } = reveal;
+
+    if current_pool.game_address != game_meta.address
+        || current_bet.game_address != game_meta.address
+    {
+        return Err(BattleshipError::SideBetMismatch.into());
+    }
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    let deadline = reveal_deadline(&game);
+    if Clock::get()?.slot > deadline {
+        msg!("Reveal window closed at slot {}", deadline);
+        return Err(BattleshipError::SideBetClosed.into());
+    }
+    if current_bet.side != 0
+        || !matches!(side, 1 | 2)
+        || side_bet_commitment(&game_meta.address, &current_bet.bettor, side, &salt)
+            != current_bet.commitment
+    {
+        msg!("Reveal does not open the side bet commitment");
+        return Err(BattleshipError::InvalidSideBetReveal.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut pool_account =
+        LightAccount::<SideBetPool>::new_mut(&crate::ID, &pool_meta, current_pool)?;
+    if side == 1 {
+        pool_account.revealed_a += 1;
+    } else {
+        pool_account.revealed_b += 1;
+    }
+    let mut bet_account = LightAccount::<SideBet>::new_mut(&crate::ID, &bet_meta, current_bet)?;
+    bet_account.side = side;
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(pool_account)?
+        .with_light_account(bet_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn claim_side_bet<'info>(
+    ctx: Context<'_, '_, '_, 'info, SideBetPayoutAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    pool: SideBetPoolRef,
+    bet: SideBetRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let SideBetPoolRef {
+        pool,
+        meta: pool_meta,
+    } = pool;
+    let SideBetRef {
+        bet,
+        meta: bet_meta,
+    } = bet;
+
+    if pool.game_address != game_meta.address || bet.game_address != game_meta.address {
+        return Err(BattleshipError::SideBetMismatch.into());
+    }
+    if !matches!(game.game_status, 2..=4) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&game)?;
+    if ctx.accounts.bettor.key() != bet.bettor {
+        return Err(BattleshipError::WrongPlayerAccount.into());
+    }
+    let (vault, bump) = derive_side_vault_address(&game_meta.address);
+    if ctx.accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    // The tally is final once every bet is revealed or the window closes
+    let deadline = reveal_deadline(&game);
+    if pool.revealed_a + pool.revealed_b < pool.bets && Clock::get()?.slot <= deadline {
+        msg!("Reveal window runs until slot {}", deadline);
+        return Err(BattleshipError::TurnNotExpired.into());
+    }
+    let revealed = pool.revealed_a + pool.revealed_b;
+    if bet.side == 0 && revealed > 0 {
+        msg!("Unrevealed bets are forfeited");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+
+    // Winners split every stake, revealed or not. Without a winner the
+    // revealed bets split them, and with nothing revealed each bet is
+    // refunded.
+    let (winning_side, winners) = match game.game_status {
+        2 => (1, pool.revealed_a),
+        3 => (2, pool.revealed_b),
+        _ => (0, 0),
+    };
+    let claimants = if winners > 0 {
+        if bet.side != winning_side {
+            msg!("Bet lost");
+            return Err(BattleshipError::NothingToClaim.into());
+        }
+        winners
+    } else if revealed > 0 {
+        revealed
+    } else {
+        pool.bets
+    };
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let game_id = game.game_id;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    let mut pool_account = LightAccount::<SideBetPool>::new_mut(&crate::ID, &pool_meta, pool)?;
+    pool_account.claimed += 1;
+    // Shares are floored, so the last claim takes the remainder with it
+    let amount = if pool_account.claimed == claimants {
+        ctx.accounts.vault.lamports()
+    } else {
+        u64::from(pool_account.bets) * SIDE_BET_STAKE_LAMPORTS / u64::from(claimants)
+    };
+    // Closing the bet makes a second claim impossible
+    let bet_account = LightAccount::<SideBet>::new_close(&crate::ID, &bet_meta, bet)?;
+
+    new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(pool_account)?
+        .with_light_account(bet_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[SIDE_VAULT_SEED, &game_meta.address, &[bump]];
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.vault.to_account_info(),
+                to: ctx.accounts.bettor.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+    )?;
+
+    msg!("💰 Side bet on game {} paid {} lamports", game_id, amount);
+
+    Ok(())
+}
@@ -0,0 +1,600 @@
+use crate::*;
+
+pub fn store_board_backup<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    backup: BackupCiphertext,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let BackupCiphertext {
+        ephemeral_key,
+        nonce,
+        ciphertext,
+    } = backup;
+
+    let owner = ctx.accounts.signer.key();
+    player_number(&game, &owner)?;
+
+    if ciphertext.is_empty() || ciphertext.len() > MAX_BACKUP_LEN {
+        msg!("Board backups are 1-{} bytes", MAX_BACKUP_LEN);
+        return Err(BattleshipError::BackupTooLong.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) =
+        derive_board_backup_address(&game_meta.address, &owner, &address_tree_pubkey);
+    let mut backup_account =
+        LightAccount::<BoardBackup>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    backup_account.game_address = game_meta.address;
+    backup_account.owner = owner;
+    backup_account.ephemeral_key = ephemeral_key;
+    backup_account.nonce = nonce;
+    backup_account.ciphertext = ciphertext;
+
+    msg!("🔐 Board backup stored for game {}", game.game_id);
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(backup_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn open_chat<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    player_number(&game, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) = derive_chat_address(&game_meta.address, &address_tree_pubkey);
+    let mut chat_account =
+        LightAccount::<GameChat>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    chat_account.game_address = game_meta.address;
+    chat_account.game_id = game.game_id;
+    chat_account.player_a = game.player_a;
+    chat_account.player_b = game.player_b;
+
+    msg!("💬 Chat opened for game {}", game.game_id);
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(chat_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn send_chat<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    chat: GameChatRef,
+    text: String,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let GameChatRef {
+        chat: current_chat,
+        meta: chat_meta,
+    } = chat;
+
+    let sender = player_number(&game, &ctx.accounts.signer.key())?;
+    if current_chat.game_address != game_meta.address {
+        msg!("Chat does not belong to this game");
+        return Err(BattleshipError::ChatMismatch.into());
+    }
+    if text.is_empty() || text.len() > MAX_CHAT_LEN {
+        msg!("Chat messages are 1-{} bytes", MAX_CHAT_LEN);
+        return Err(BattleshipError::ChatTooLong.into());
+    }
+    let last_sent = if sender == 1 {
+        current_chat.last_move_a
+    } else {
+        current_chat.last_move_b
+    };
+    if last_sent == Some(game.move_count) {
+        msg!("One message per move");
+        return Err(BattleshipError::ChatRateLimited.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut chat_account = LightAccount::<GameChat>::new_mut(&crate::ID, &chat_meta, current_chat)?;
+    if chat_account.messages.len() >= MAX_CHAT_MESSAGES {
+        chat_account.messages.remove(0);
+    }
+    chat_account.messages.push(ChatMessage {
+        sender,
+        move_count: game.move_count,
+        text,
+    });
+    if sender == 1 {
+        chat_account.last_move_a = Some(game.move_count);
+    } else {
+        chat_account.last_move_b = Some(game.move_count);
+    }
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(chat_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn register_chat_key<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_chat: GameChat,
+    chat_meta: CompressedAccountMeta,
+    chat_key: [u8; 32],
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let signer = ctx.accounts.signer.key();
+    let sender = if signer == current_chat.player_a {
+        1
+    } else if signer == current_chat.player_b {
+        2
+    } else {
+        return Err(BattleshipError::NotAPlayer.into());
+    };
+    let registered = if sender == 1 {
+        current_chat.chat_key_a
+    } else {
+        current_chat.chat_key_b
+    };
+    if registered.is_some() {
+        msg!("Chat key is already registered");
+        return Err(BattleshipError::ChatKeyRegistered.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut chat_account = LightAccount::<GameChat>::new_mut(&crate::ID, &chat_meta, current_chat)?;
+    if sender == 1 {
+        chat_account.chat_key_a = Some(chat_key);
+    } else {
+        chat_account.chat_key_b = Some(chat_key);
+    }
+
+    msg!(
+        "🔑 Player {} registered a chat key",
+        if sender == 1 { "A" } else { "B" }
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(chat_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn send_sealed_chat<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameReadOnlyRef,
+    chat: GameChatRef,
+    nonce: [u8; 24],
+    ciphertext: Vec<u8>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+    let GameChatRef {
+        chat: current_chat,
+        meta: chat_meta,
+    } = chat;
+
+    let sender = player_number(&game, &ctx.accounts.signer.key())?;
+    if current_chat.game_address != game_meta.address {
+        msg!("Chat does not belong to this game");
+        return Err(BattleshipError::ChatMismatch.into());
+    }
+    if current_chat.chat_key_a.is_none() || current_chat.chat_key_b.is_none() {
+        msg!("Both players must register a chat key first");
+        return Err(BattleshipError::ChatKeyMissing.into());
+    }
+    if ciphertext.is_empty() || ciphertext.len() > MAX_SEALED_CHAT_LEN {
+        msg!(
+            "Encrypted chat messages are 1-{} bytes",
+            MAX_SEALED_CHAT_LEN
+        );
+        return Err(BattleshipError::ChatTooLong.into());
+    }
+    let last_sent = if sender == 1 {
+        current_chat.last_move_a
+    } else {
+        current_chat.last_move_b
+    };
+    if last_sent == Some(game.move_count) {
+        msg!("One message per move");
+        return Err(BattleshipError::ChatRateLimited.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut chat_account = LightAccount::<GameChat>::new_mut(&crate::ID, &chat_meta, current_chat)?;
+    if chat_account.sealed_messages.len() >= MAX_CHAT_MESSAGES {
+        chat_account.sealed_messages.remove(0);
+    }
+    chat_account.sealed_messages.push(SealedChatMessage {
+        sender,
+        move_count: game.move_count,
+        nonce,
+        ciphertext,
+    });
+    if sender == 1 {
+        chat_account.last_move_a = Some(game.move_count);
+    } else {
+        chat_account.last_move_b = Some(game.move_count);
+    }
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .with_light_account(chat_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn react<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_chat: GameChat,
+    chat_meta: CompressedAccountMeta,
+    emote_id: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if emote_id >= EMOTE_COUNT {
+        return Err(BattleshipError::InvalidEmote.into());
+    }
+    let signer = ctx.accounts.signer.key();
+    let sender = if signer == current_chat.player_a {
+        1
+    } else if signer == current_chat.player_b {
+        2
+    } else {
+        return Err(BattleshipError::NotAPlayer.into());
+    };
+
+    let slot = Clock::get()?.slot;
+    let last = if sender == 1 {
+        current_chat.last_reaction_slot_a
+    } else {
+        current_chat.last_reaction_slot_b
+    };
+    if last != 0 && slot < last.saturating_add(REACTION_COOLDOWN_SLOTS) {
+        msg!(
+            "Reaction cooldown until slot {}",
+            last + REACTION_COOLDOWN_SLOTS
+        );
+        return Err(BattleshipError::ReactionRateLimited.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut chat_account = LightAccount::<GameChat>::new_mut(&crate::ID, &chat_meta, current_chat)?;
+    if sender == 1 {
+        chat_account.last_reaction_slot_a = slot;
+    } else {
+        chat_account.last_reaction_slot_b = slot;
+    }
+
+    emit!(Reaction {
+        game_id: chat_account.game_id,
+        game_address: chat_account.game_address,
+        sender,
+        emote_id,
+    });
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(chat_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn open_views<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if current_game.views_open {
+        return Err(BattleshipError::ViewsAlreadyOpen.into());
+    }
+    player_number(&current_game, &ctx.accounts.signer.key())?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.views_open = true;
+
+    let mut new_addresses = Vec::with_capacity(2);
+    let mut view_accounts = Vec::with_capacity(2);
+    for seat in [1u8, 2] {
+        let (address, address_seed) =
+            derive_player_view_address(&account_meta.address, seat, &address_tree_pubkey);
+        let mut view_account = LightAccount::<PlayerView>::new_init(
+            &crate::ID,
+            Some(address),
+            output_state_tree_index,
+        );
+        view_account.game_address = account_meta.address;
+        view_account.owner = if seat == 1 {
+            game_account.player_a
+        } else {
+            game_account.player_b
+        };
+        view_account.seat = seat;
+        refresh_view(&mut view_account, &game_account);
+
+        // Output 0 is the game, so the views are outputs 1 and 2
+        new_addresses.push(
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(seat)),
+        );
+        view_accounts.push(view_account);
+    }
+
+    msg!("👁️ Player views opened for game {}", game_account.game_id);
+
+    let mut cpi =
+        new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?.with_light_account(game_account)?;
+    for view_account in view_accounts {
+        cpi = cpi.with_light_account(view_account)?;
+    }
+    cpi.with_new_addresses(&new_addresses)
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn sync_views<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameState,
+    game_meta: CompressedAccountMetaReadOnly,
+    views: PlayerViews,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?;
+    let cpi = with_views(cpi, &game, &game_meta.address, Some(views))?;
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    cpi.with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn watch_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    game: GameReadOnlyRef,
+    viewers: Option<GameViewersRef>,
+    register: bool,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let GameReadOnlyRef {
+        game,
+        meta: game_meta,
+    } = game;
+
+    let watcher = ctx.accounts.signer.key();
+    if game.game_status > 1 {
+        msg!("Game has already finished");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    if watcher == game.player_a || watcher == game.player_b {
+        msg!("Players can't watch their own game");
+        return Err(BattleshipError::NotASpectator.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut new_addresses = vec![];
+    let mut viewers_account = match viewers {
+        Some(viewers) => {
+            if viewers.viewers.game_address != game_meta.address {
+                msg!("Viewer counter does not belong to this game");
+                return Err(BattleshipError::ViewersMismatch.into());
+            }
+            LightAccount::<GameViewers>::new_mut(&crate::ID, &viewers.meta, viewers.viewers)?
+        }
+        None => {
+            let (address, address_seed) =
+                derive_game_viewers_address(&game_meta.address, &address_tree_pubkey);
+            new_addresses.push(
+                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+            );
+            let mut viewers_account = LightAccount::<GameViewers>::new_init(
+                &crate::ID,
+                Some(address),
+                output_state_tree_index,
+            );
+            viewers_account.game_address = game_meta.address;
+            viewers_account
+        }
+    };
+    viewers_account.views += 1;
+
+    // The watcher is output 1, after the counter
+    let watcher_account = if register {
+        let (address, address_seed) =
+            derive_watcher_address(&game_meta.address, &watcher, &address_tree_pubkey);
+        new_addresses
+            .push(address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(1)));
+        let mut watcher_account =
+            LightAccount::<Watcher>::new_init(&crate::ID, Some(address), output_state_tree_index);
+        watcher_account.game_address = game_meta.address;
+        watcher_account.watcher = watcher;
+        viewers_account.watchers += 1;
+        Some(watcher_account)
+    } else {
+        None
+    };
+
+    emit!(GameWatched {
+        game_address: game_meta.address,
+        watcher,
+        registered: register,
+        views: viewers_account.views,
+        watchers: viewers_account.watchers,
+    });
+
+    let game_account =
+        LightAccount::<GameState>::new_read_only(&crate::ID, &game_meta, game, &tree_pubkeys)?;
+    let mut cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(viewers_account)?;
+    if let Some(watcher_account) = watcher_account {
+        cpi = cpi.with_light_account(watcher_account)?;
+    }
+    cpi.with_light_account(game_account)?
+        .with_new_addresses(&new_addresses)
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
@@ -0,0 +1,532 @@
+use crate::*;
+
+/// Anchor discriminator of the compressed token program's `transfer`
+const CTOKEN_TRANSFER_DISCRIMINATOR: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
+/// Anchor discriminator of the compressed token program's `mint_to`
+const CTOKEN_MINT_TO_DISCRIMINATOR: [u8; 8] = [241, 34, 48, 186, 37, 179, 123, 192];
+
+pub fn create_game_with_token_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenWagerAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    board: ShipBoard,
+    terms: TokenWagerTerms,
+    stake: TokenInputs,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ShipBoard { ship, board_hash } = board;
+    let TokenWagerTerms { mint, amount, mode } = terms;
+
+    let game_id = slot.profile.next_game_id;
+    let config = slot.protocol.config.clone();
+    if amount == 0 || mint == Pubkey::default() {
+        return Err(BattleshipError::InvalidWager.into());
+    }
+    // Wager limits are in lamports, so only the mode's fee applies
+    let rake = mode_rake(&config, mode)?;
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+
+    let signer = &ctx.accounts.deposit.signer;
+    let (light_accounts, token_accounts) =
+        split_token_accounts(ctx.remaining_accounts, stake.token_accounts_offset)?;
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.deposit.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_game_address(&signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(&ctx.accounts.deposit, &address)?;
+
+    msg!(
+        "Game {} created by {:?} with a {} token wager! Waiting for Player B.",
+        game_id,
+        signer.key(),
+        amount
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = signer.key();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.token_mint = mint;
+    game_account.token_wager = amount;
+    game_account.wager_mode = mode;
+    game_account.fee_bps = rake.fee_bps;
+    game_account.treasury = config.treasury;
+    pin_timeouts(&mut game_account, &config);
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    let cpi = with_no_ban(cpi, &signer.key(), address_tree_info, &light_cpi_accounts)?;
+    with_active_game_slot(cpi, slot, &signer.key(), true, &light_cpi_accounts)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+            address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    let escrow = derive_token_escrow_address(&address).0;
+    transfer_ctokens(
+        TokenCpiAccounts {
+            program: &ctx.accounts.compressed_token_program,
+            fee_payer: ctx.accounts.deposit.payer(),
+            authority: signer,
+            remaining: token_accounts,
+        },
+        &[],
+        mint,
+        stake,
+        &[(escrow, amount)],
+    )
+}
+
+pub fn join_token_wager_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenWagerAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    board: ShipBoard,
+    stake: TokenInputs,
+    ban_check: PackedAddressTreeInfo,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let ShipBoard { ship, board_hash } = board;
+
+    if current_game.game_status != 0 {
+        msg!(
+            "Game is not in waiting state (Status: {})",
+            current_game.game_status
+        );
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let signer = &ctx.accounts.deposit.signer;
+    require_invited(&current_game, &signer.key())?;
+    if current_game.token_wager == 0 {
+        msg!("Game has no token wager");
+        return Err(BattleshipError::WrongGameMode.into());
+    }
+
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+    lock_deposit(&ctx.accounts.deposit, &account_meta.address)?;
+
+    let (light_accounts, token_accounts) =
+        split_token_accounts(ctx.remaining_accounts, stake.token_accounts_offset)?;
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.deposit.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mint = current_game.token_mint;
+    let amount = current_game.token_wager;
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.player_b = signer.key();
+    game_account.game_status = 1; // Active
+    game_account.last_action_slot = Clock::get()?.slot;
+    game_account.grid_b = grid;
+    game_account.board_hash_b = board_hash;
+    game_account.slots_held |= 2; // Player B's profile
+
+    msg!(
+        "Player B matched the {} token wager! Game {} is now Active!",
+        amount,
+        game_account.game_id
+    );
+
+    emit_game_joined(&game_account, &account_meta.address);
+
+    let cpi =
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof).with_light_account(game_account)?;
+    let cpi = with_no_ban(cpi, &signer.key(), ban_check, &light_cpi_accounts)?;
+    with_active_game_slot(cpi, slot, &signer.key(), false, &light_cpi_accounts)?
+        .invoke(light_cpi_accounts)?;
+
+    let escrow = derive_token_escrow_address(&account_meta.address).0;
+    transfer_ctokens(
+        TokenCpiAccounts {
+            program: &ctx.accounts.compressed_token_program,
+            fee_payer: ctx.accounts.deposit.payer(),
+            authority: signer,
+            remaining: token_accounts,
+        },
+        &[],
+        mint,
+        stake,
+        &[(escrow, amount)],
+    )
+}
+
+pub fn claim_token_wager<'info>(
+    ctx: Context<'_, '_, '_, 'info, TokenClaimAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    stake: TokenInputs,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if !matches!(current_game.game_status, 2..=5) {
+        msg!("Game has not finished yet");
+        return Err(BattleshipError::GameNotFinished.into());
+    }
+    require_no_open_challenge(&current_game)?;
+    if current_game.token_wager == 0 || current_game.tokens_paid {
+        msg!("No escrowed tokens to pay out");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    let (escrow, bump) = derive_token_escrow_address(&account_meta.address);
+    if ctx.accounts.escrow.key() != escrow {
+        return Err(BattleshipError::WrongVault.into());
+    }
+
+    let stake_each = current_game.token_wager;
+    let pot = stake_each * 2;
+    let fee = protocol_fee(pot, current_game.fee_bps);
+    let payouts = match current_game.game_status {
+        2 => vec![
+            (current_game.player_a, pot - fee),
+            (current_game.treasury, fee),
+        ],
+        3 => vec![
+            (current_game.player_b, pot - fee),
+            (current_game.treasury, fee),
+        ],
+        // Cancelled before anyone matched Player A's stake
+        5 => vec![(current_game.player_a, stake_each)],
+        _ => vec![
+            (current_game.player_a, stake_each),
+            (current_game.player_b, stake_each),
+        ],
+    };
+    let mint = current_game.token_mint;
+    let game_id = current_game.game_id;
+
+    let (light_accounts, token_accounts) =
+        split_token_accounts(ctx.remaining_accounts, stake.token_accounts_offset)?;
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.tokens_paid = true;
+
+    new_withdrawal_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(game_account)?
+        .invoke(light_cpi_accounts)?;
+
+    let seeds: &[&[u8]] = &[TOKEN_ESCROW_SEED, &account_meta.address, &[bump]];
+    transfer_ctokens(
+        TokenCpiAccounts {
+            program: &ctx.accounts.compressed_token_program,
+            fee_payer: ctx.accounts.payer(),
+            authority: &ctx.accounts.escrow,
+            remaining: token_accounts,
+        },
+        &[seeds],
+        mint,
+        stake,
+        &payouts,
+    )?;
+
+    msg!("💰 Game {} token wager paid out: {} tokens", game_id, pot);
+
+    Ok(())
+}
+
+pub fn claim_reward_tokens<'info>(
+    ctx: Context<'_, '_, '_, 'info, RewardMintAccounts<'info>>,
+    proof: ValidityProof,
+    current_game: GameState,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+    token_accounts_offset: u8,
+) -> Result<()> {
+    require_unpaused(&protocol.config)?;
+    let winner = match current_game.game_status {
+        2 => current_game.player_a,
+        3 => current_game.player_b,
+        _ => {
+            msg!("Game has no winner");
+            return Err(BattleshipError::GameNotFinished.into());
+        }
+    };
+    require_no_open_challenge(&current_game)?;
+    if current_game.game_mode != GAME_MODE_PVP
+        || current_game.wager > 0
+        || current_game.token_wager > 0
+        || current_game.reward_claimed
+    {
+        msg!("No reward to claim for this game");
+        return Err(BattleshipError::NothingToClaim.into());
+    }
+    let (reward_mint, amount) = (protocol.config.reward_mint, protocol.config.reward_per_win);
+    if amount == 0 {
+        return Err(BattleshipError::RewardsDisabled.into());
+    }
+    let (authority, bump) = derive_reward_authority_address();
+    if ctx.accounts.mint_authority.key() != authority {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    let game_id = current_game.game_id;
+
+    let (light_accounts, token_accounts) =
+        split_token_accounts(ctx.remaining_accounts, token_accounts_offset)?;
+    // `mint_to` takes the mint right after the token program's CPI authority
+    if token_accounts.get(1).map(|mint| mint.key()) != Some(reward_mint) {
+        return Err(BattleshipError::WrongMint.into());
+    }
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        light_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    game_account.reward_claimed = true;
+    let config_account = LightAccount::<ProtocolConfig>::new_read_only(
+        &crate::ID,
+        &protocol.meta,
+        protocol.config,
+        &tree_pubkeys,
+    )?;
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(config_account)?
+        .invoke(light_cpi_accounts)?;
+
+    mint_ctokens(
+        TokenCpiAccounts {
+            program: &ctx.accounts.compressed_token_program,
+            fee_payer: ctx.accounts.payer(),
+            authority: &ctx.accounts.mint_authority,
+            remaining: token_accounts,
+        },
+        &[&[REWARD_AUTHORITY_SEED, &[bump]]],
+        winner,
+        amount,
+    )?;
+
+    msg!(
+        "🪙 Game {} winner {:?} earned {} reward tokens",
+        game_id,
+        winner,
+        amount
+    );
+
+    Ok(())
+}
+
+/// Splits the remaining accounts at `offset` into this program's Light
+/// accounts and the compressed token program's
+fn split_token_accounts<'c, 'info>(
+    remaining_accounts: &'c [AccountInfo<'info>],
+    offset: u8,
+) -> Result<(&'c [AccountInfo<'info>], &'c [AccountInfo<'info>])> {
+    if offset as usize > remaining_accounts.len() {
+        return Err(ErrorCode::AccountNotEnoughKeys.into());
+    }
+    Ok(remaining_accounts.split_at(offset as usize))
+}
+
+/// The compressed token program, the fee payer and token authority it
+/// signs with, and its accounts after those two
+struct TokenCpiAccounts<'a, 'info> {
+    program: &'a AccountInfo<'info>,
+    fee_payer: &'a AccountInfo<'info>,
+    authority: &'a AccountInfo<'info>,
+    remaining: &'a [AccountInfo<'info>],
+}
+
+/// Pays `outputs` out of the compressed `mint` tokens in `stake` with a
+/// compressed token program `transfer`, returning the change to
+/// `authority`. `signer_seeds` sign for a PDA authority.
+fn transfer_ctokens<'info>(
+    accounts: TokenCpiAccounts<'_, 'info>,
+    signer_seeds: &[&[&[u8]]],
+    mint: Pubkey,
+    stake: TokenInputs,
+    outputs: &[(Pubkey, u64)],
+) -> Result<()> {
+    let TokenCpiAccounts {
+        program: token_program,
+        fee_payer,
+        authority,
+        remaining: token_accounts,
+    } = accounts;
+    let spent = stake
+        .accounts
+        .iter()
+        .try_fold(0u64, |total, input| total.checked_add(input.amount))
+        .ok_or(BattleshipError::InsufficientTokens)?;
+    let paid: u64 = outputs.iter().map(|(_, amount)| amount).sum();
+    let change = spent
+        .checked_sub(paid)
+        .ok_or(BattleshipError::InsufficientTokens)?;
+
+    let mut output_compressed_accounts = vec![];
+    for &(owner, amount) in outputs.iter().chain(&[(authority.key(), change)]) {
+        if amount == 0 {
+            continue;
+        }
+        output_compressed_accounts.push(TokenOutputData {
+            owner,
+            amount,
+            lamports: None,
+            merkle_tree_index: stake.output_tree_index,
+            tlv: None,
+        });
+    }
+    let transfer = TokenTransferData {
+        proof: stake.proof,
+        mint,
+        delegated_transfer: None,
+        input_token_data_with_context: stake.accounts,
+        output_compressed_accounts,
+        is_compress: false,
+        compress_or_decompress_amount: None,
+        cpi_context: None,
+        lamports_change_account_merkle_tree_index: None,
+        with_transaction_hash: false,
+    };
+
+    // `transfer` takes its arguments as one borsh-encoded byte vector
+    let mut data = CTOKEN_TRANSFER_DISCRIMINATOR.to_vec();
+    transfer.try_to_vec()?.serialize(&mut data)?;
+
+    let mut metas = vec![
+        AccountMeta::new(fee_payer.key(), true),
+        AccountMeta::new_readonly(authority.key(), true),
+    ];
+    let mut infos = vec![token_program.clone(), fee_payer.clone(), authority.clone()];
+    for account in token_accounts {
+        metas.push(if account.is_writable {
+            AccountMeta::new(account.key(), false)
+        } else {
+            AccountMeta::new_readonly(account.key(), false)
+        });
+        infos.push(account.clone());
+    }
+    invoke_signed(
+        &Instruction {
+            program_id: COMPRESSED_TOKEN_PROGRAM_ID,
+            accounts: metas,
+            data,
+        },
+        &infos,
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Mints `amount` compressed tokens to `recipient` with a compressed token
+/// program `mint_to`. `signer_seeds` sign for the PDA mint authority.
+fn mint_ctokens<'info>(
+    accounts: TokenCpiAccounts<'_, 'info>,
+    signer_seeds: &[&[&[u8]]],
+    recipient: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    let TokenCpiAccounts {
+        program: token_program,
+        fee_payer,
+        authority,
+        remaining: token_accounts,
+    } = accounts;
+    // `mint_to(public_keys, amounts, lamports)`
+    let mut data = CTOKEN_MINT_TO_DISCRIMINATOR.to_vec();
+    vec![recipient].serialize(&mut data)?;
+    vec![amount].serialize(&mut data)?;
+    None::<u64>.serialize(&mut data)?;
+
+    let mut metas = vec![
+        AccountMeta::new(fee_payer.key(), true),
+        AccountMeta::new_readonly(authority.key(), true),
+    ];
+    let mut infos = vec![token_program.clone(), fee_payer.clone(), authority.clone()];
+    for account in token_accounts {
+        metas.push(if account.is_writable {
+            AccountMeta::new(account.key(), false)
+        } else {
+            AccountMeta::new_readonly(account.key(), false)
+        });
+        infos.push(account.clone());
+    }
+    invoke_signed(
+        &Instruction {
+            program_id: COMPRESSED_TOKEN_PROGRAM_ID,
+            accounts: metas,
+            data,
+        },
+        &infos,
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// The compressed token program's `PackedTokenTransferOutputData`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+struct TokenOutputData {
+    owner: Pubkey,
+    amount: u64,
+    lamports: Option<u64>,
+    merkle_tree_index: u8,
+    tlv: Option<Vec<u8>>,
+}
+
+/// The compressed token program's `CompressedTokenInstructionDataTransfer`.
+/// The delegated transfer is `(owner, change account index)` and the CPI
+/// context `(set_context, first_set_context, account index)`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+struct TokenTransferData {
+    proof: ValidityProof,
+    mint: Pubkey,
+    delegated_transfer: Option<(Pubkey, Option<u8>)>,
+    input_token_data_with_context: Vec<TokenInputData>,
+    output_compressed_accounts: Vec<TokenOutputData>,
+    is_compress: bool,
+    compress_or_decompress_amount: Option<u64>,
+    cpi_context: Option<(bool, bool, u8)>,
+    lamports_change_account_merkle_tree_index: Option<u8>,
+    with_transaction_hash: bool,
+}
@@ -0,0 +1,357 @@
+use crate::*;
+
+pub fn create_tournament<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    tournament_id: u64,
+    max_players: u8,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if !(2..=MAX_TOURNAMENT_PLAYERS).contains(&(max_players as usize)) {
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let organizer = ctx.accounts.signer.key();
+    let (address, address_seed) =
+        derive_tournament_address(&organizer, tournament_id, &address_tree_pubkey);
+    let mut tournament_account =
+        LightAccount::<Tournament>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    tournament_account.organizer = organizer;
+    tournament_account.tournament_id = tournament_id;
+    tournament_account.max_players = max_players;
+
+    msg!(
+        "🏟️ Tournament {} opened for {} players",
+        tournament_id,
+        max_players
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(tournament_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn register_for_tournament<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_tournament: Tournament,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let player = ctx.accounts.signer.key();
+    if current_tournament.status != TOURNAMENT_REGISTERING
+        || current_tournament.players.len() >= current_tournament.max_players as usize
+    {
+        msg!("Tournament {} is closed", current_tournament.tournament_id);
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+    if current_tournament.players.contains(&player) {
+        msg!("Already registered");
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut tournament_account =
+        LightAccount::<Tournament>::new_mut(&crate::ID, &account_meta, current_tournament)?;
+    tournament_account.players.push(player);
+
+    msg!(
+        "🎟️ {:?} registered for tournament {} ({} players)",
+        player,
+        tournament_account.tournament_id,
+        tournament_account.players.len()
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(tournament_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn start_tournament<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    current_tournament: Tournament,
+    account_meta: CompressedAccountMeta,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    if current_tournament.organizer != ctx.accounts.signer.key() {
+        return Err(BattleshipError::NotOrganizer.into());
+    }
+    if current_tournament.status != TOURNAMENT_REGISTERING || current_tournament.players.len() < 2 {
+        msg!("Tournament needs two registered players to start");
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let mut tournament_account =
+        LightAccount::<Tournament>::new_mut(&crate::ID, &account_meta, current_tournament)?;
+    tournament_account.status = TOURNAMENT_RUNNING;
+    tournament_account.round = 1;
+    tournament_account.round_started_at = Clock::get()?.unix_timestamp;
+    tournament_account.matches = pair_round(&tournament_account.players);
+
+    msg!(
+        "🏟️ Tournament {} started with {} matches",
+        tournament_account.tournament_id,
+        tournament_account.matches.len()
+    );
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(tournament_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn create_tournament_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    tournament: TournamentRef,
+    match_index: u8,
+    board: ShipBoard,
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let TournamentRef {
+        tournament: current_tournament,
+        meta: tournament_meta,
+    } = tournament;
+    let ShipBoard { ship, board_hash } = board;
+
+    let game_id = slot.profile.next_game_id;
+    if current_tournament.status != TOURNAMENT_RUNNING {
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+    let signer = ctx.accounts.signer.key();
+    let Some(pairing) = current_tournament.matches.get(match_index as usize) else {
+        return Err(BattleshipError::TournamentMismatch.into());
+    };
+    if pairing.player_a != signer {
+        return Err(BattleshipError::NotAPlayer.into());
+    }
+    if pairing.winner != Pubkey::default() || pairing.game_address != [0u8; 32] {
+        msg!("Match already has a game");
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+    let opponent = pairing.player_b;
+
+    let grid = place_ship(ship.x, ship.y, ship.is_horizontal)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) = derive_game_address(&signer, game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+    game_account.game_id = game_id;
+    game_account.player_a = signer;
+    game_account.reserved_for = opponent;
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_PUBLIC;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.grid_a = grid;
+    game_account.board_hash_a = board_hash;
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    let mut tournament_account =
+        LightAccount::<Tournament>::new_mut(&crate::ID, &tournament_meta, current_tournament)?;
+    tournament_account.matches[match_index as usize].game_address = address;
+
+    msg!(
+        "⚔️ Tournament {} round {} game {} created for {:?}",
+        tournament_account.tournament_id,
+        tournament_account.round,
+        game_id,
+        opponent
+    );
+
+    emit_game_created(&game_account, &address);
+
+    // The new accounts go first so they are outputs 0 and 1
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?
+        .with_light_account(tournament_account)?;
+    with_active_game_slot(cpi, slot, &signer, true, &light_cpi_accounts)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+            address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn report_tournament_result<'info>(
+    ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+    proof: ValidityProof,
+    tournament: TournamentRef,
+    match_index: u8,
+    result: GameResultRef,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let TournamentRef {
+        tournament: current_tournament,
+        meta: account_meta,
+    } = tournament;
+    let GameResultRef {
+        result,
+        meta: result_meta,
+    } = result;
+
+    if current_tournament.status != TOURNAMENT_RUNNING {
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+    let Some(pairing) = current_tournament.matches.get(match_index as usize) else {
+        return Err(BattleshipError::TournamentMismatch.into());
+    };
+    if pairing.winner != Pubkey::default()
+        || pairing.game_address == [0u8; 32]
+        || result.game_address != pairing.game_address
+    {
+        msg!("Result is not for this match's game");
+        return Err(BattleshipError::TournamentMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let winner = result.winner;
+    let mut tournament_account =
+        LightAccount::<Tournament>::new_mut(&crate::ID, &account_meta, current_tournament)?;
+    let tournament_id = tournament_account.tournament_id;
+    if winner == Pubkey::default() {
+        msg!(
+            "Tournament {} match {} drawn; replay it",
+            tournament_id,
+            match_index
+        );
+        tournament_account.matches[match_index as usize].game_address = [0u8; 32];
+    } else {
+        tournament_account.matches[match_index as usize].winner = winner;
+        msg!("🏆 {:?} advances in tournament {}", winner, tournament_id);
+        advance_tournament(&mut tournament_account, Clock::get()?.unix_timestamp);
+        if tournament_account.status == TOURNAMENT_FINISHED {
+            msg!(
+                "👑 {:?} wins tournament {}",
+                tournament_account.champion,
+                tournament_id
+            );
+        }
+    }
+
+    let result_account =
+        LightAccount::<GameResult>::new_read_only(&crate::ID, &result_meta, result, &tree_pubkeys)?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(tournament_account)?
+        .with_light_account(result_account)?
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Pairs `players` in order into a round's matches. An odd player out
+/// gets a bye and has already won their match.
+fn pair_round(players: &[Pubkey]) -> Vec<TournamentMatch> {
+    players
+        .chunks(2)
+        .map(|pair| match *pair {
+            [player_a, player_b] => TournamentMatch {
+                player_a,
+                player_b,
+                ..Default::default()
+            },
+            _ => TournamentMatch {
+                player_a: pair[0],
+                winner: pair[0],
+                ..Default::default()
+            },
+        })
+        .collect()
+}
+
+/// Once every match of the round has a winner, draws the next round from
+/// the winners in bracket order, or crowns the last one champion
+fn advance_tournament(tournament: &mut Tournament, now: i64) {
+    if tournament
+        .matches
+        .iter()
+        .any(|pairing| pairing.winner == Pubkey::default())
+    {
+        return;
+    }
+    let winners: Vec<Pubkey> = tournament.matches.iter().map(|m| m.winner).collect();
+    if let [champion] = winners[..] {
+        tournament.champion = champion;
+        tournament.status = TOURNAMENT_FINISHED;
+        return;
+    }
+    tournament.round += 1;
+    tournament.round_started_at = now;
+    tournament.matches = pair_round(&winners);
+}
@@ -0,0 +1,619 @@
+use crate::*;
+
+pub fn create_zk_game<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+    proof: ValidityProof,
+    address_tree_info: PackedAddressTreeInfo,
+    output_state_tree_index: u8,
+    rules: GameRules,
+    board_hash: [u8; 32],
+    slot: ActiveGameSlot,
+) -> Result<()> {
+    let game_id = slot.profile.next_game_id;
+    validate_rules(&rules)?;
+    // Shots are answered one at a time and the circuits only see ships
+    if rules.shots_per_turn_a != 1
+        || rules.shots_per_turn_b != 1
+        || rules.obstacles != 0
+        || rules.handicap
+    {
+        msg!("ZK games take no salvos, obstacles or handicap");
+        return Err(BattleshipError::InvalidRules.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_game_address(&ctx.accounts.signer.key(), game_id, &address_tree_pubkey);
+    lock_deposit(ctx.accounts, &address)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
+
+    game_account.game_id = game_id;
+    game_account.player_a = ctx.accounts.signer.key();
+    game_account.current_turn = 1; // Player A starts
+    game_account.game_status = 0; // Waiting for B
+    game_account.last_action_slot = Clock::get()?.slot;
+    pin_timeouts(&mut game_account, &slot.protocol.config);
+    game_account.game_mode = GAME_MODE_PVP;
+    game_account.board_mode = BOARD_MODE_ZK;
+    game_account.slots_held = 1; // Player A's profile
+    game_account.rules = rules;
+
+    // Grids only ever hold revealed cells
+    game_account.grid_a = [CELL_EMPTY; GRID_CELLS];
+    game_account.board_hash_a = board_hash;
+    game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
+
+    msg!(
+        "ZK game {} created by {:?}! Waiting for Player B.",
+        game_id,
+        ctx.accounts.signer.key()
+    );
+
+    let (log_address, log_address_seed) = derive_move_log_address(&address, &address_tree_pubkey);
+    let mut log_account =
+        LightAccount::<MoveLog>::new_init(&crate::ID, Some(log_address), output_state_tree_index);
+    log_account.game_address = address;
+
+    emit_game_created(&game_account, &address);
+
+    let cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    with_active_game_slot(
+        cpi,
+        slot,
+        &ctx.accounts.signer.key(),
+        true,
+        &light_cpi_accounts,
+    )?
+    .with_new_addresses(&[
+        address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0)),
+        address_tree_info.into_new_address_params_assigned_packed(log_address_seed, Some(1)),
+    ])
+    .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn respond_zk_attack<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    turn: GameTurn,
+    shot_proof: CircuitProof,
+    hit: bool,
+    session: Option<SessionAuth>,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameTurn {
+        game: current_game,
+        game_meta: account_meta,
+        log: current_log,
+        log_meta,
+        views,
+    } = turn;
+    let CircuitProof {
+        verifier,
+        verifier_meta,
+        proof: shot_proof,
+    } = shot_proof;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+
+    if current_game.board_mode != BOARD_MODE_ZK {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+
+    if current_log.game_address != account_meta.address {
+        msg!("Move log does not belong to this game");
+        return Err(BattleshipError::MoveLogMismatch.into());
+    }
+
+    if verifier.circuit_id != CIRCUIT_SHOT {
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    let index = current_game
+        .pending_shot
+        .ok_or(BattleshipError::NoPendingShot)?;
+
+    // The defender is whoever is not on turn
+    let (defender, board_hash) = if current_game.current_turn == 1 {
+        (current_game.player_b, current_game.board_hash_b)
+    } else {
+        (current_game.player_a, current_game.board_hash_a)
+    };
+    if defender != acting_player(&ctx.accounts.signer.key(), session.as_ref())? {
+        msg!("Only the defender can answer this shot");
+        return Err(BattleshipError::NotDefender.into());
+    }
+
+    check_proof(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        &shot_proof,
+        &[
+            board_hash,
+            field_from_u64(index.into()),
+            field_from_u64(hit as u64),
+        ],
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    let mut log_account = LightAccount::<MoveLog>::new_mut(&crate::ID, &log_meta, current_log)?;
+
+    let game = &mut *game_account;
+    pin_verifier(game, &verifier_meta.address, &verifier)?;
+
+    // Resolve against the proven cell
+    let cell_value = if hit { CELL_SHIP } else { CELL_EMPTY };
+    let record = resolve_pending_shot(game, index as usize, cell_value)?;
+    log_account.moves.push(record);
+
+    game.last_action_slot = Clock::get()?.slot;
+    emit_if_ended(game, &account_meta.address);
+
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(verifier_account)?;
+    let cpi = with_views(cpi, &game_account, &account_meta.address, views)?
+        .with_light_account(game_account)?
+        .with_light_account(log_account)?;
+    let cpi = with_session(cpi, session, &light_cpi_accounts)?;
+    with_attestation(cpi, shot_proof)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn register_verifier<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    circuit_id: u8,
+    backend: u8,
+    verifier_program: Pubkey,
+    verifying_key: Pubkey,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+
+    if circuit_id as usize >= CIRCUIT_COUNT || backend > PROOF_BACKEND_ULTRA_HONK {
+        msg!("Unknown circuit {} or backend {}", circuit_id, backend);
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let (address, address_seed) =
+        derive_verifier_address(circuit_id, backend, 0, &address_tree_pubkey);
+    let mut verifier_account = LightAccount::<CircuitVerifier>::new_init(
+        &crate::ID,
+        Some(address),
+        output_state_tree_index,
+    );
+    verifier_account.circuit_id = circuit_id;
+    verifier_account.backend = backend;
+    verifier_account.verifier_program = verifier_program;
+    verifier_account.verifying_key = verifying_key;
+
+    msg!(
+        "Circuit {} backend {} verified by {:?}",
+        circuit_id,
+        backend,
+        verifier_program
+    );
+
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(verifier_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn rotate_verifier<'info>(
+    ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    current_verifier: CircuitVerifier,
+    account_meta: CompressedAccountMeta,
+    verifier_program: Pubkey,
+    verifying_key: Pubkey,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+
+    if current_verifier.retired {
+        msg!("Version {} was already rotated", current_verifier.version);
+        return Err(BattleshipError::VerifierRetired.into());
+    }
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+
+    let version = current_verifier
+        .version
+        .checked_add(1)
+        .ok_or(BattleshipError::VerifierRetired)?;
+    let (address, address_seed) = derive_verifier_address(
+        current_verifier.circuit_id,
+        current_verifier.backend,
+        version,
+        &address_tree_pubkey,
+    );
+    let mut next_account = LightAccount::<CircuitVerifier>::new_init(
+        &crate::ID,
+        Some(address),
+        output_state_tree_index,
+    );
+    next_account.circuit_id = current_verifier.circuit_id;
+    next_account.backend = current_verifier.backend;
+    next_account.version = version;
+    next_account.verifier_program = verifier_program;
+    next_account.verifying_key = verifying_key;
+
+    let mut current_account =
+        LightAccount::<CircuitVerifier>::new_mut(&crate::ID, &account_meta, current_verifier)?;
+    current_account.retired = true;
+
+    msg!(
+        "Circuit {} backend {} rotated to version {}, verified by {:?}",
+        next_account.circuit_id,
+        next_account.backend,
+        version,
+        verifier_program
+    );
+
+    // The new account goes first so it is output 0
+    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+        .with_light_account(next_account)?
+        .with_light_account(current_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn verify_board<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    board_proof: CircuitProof,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let CircuitProof {
+        verifier,
+        verifier_meta,
+        proof: board_proof,
+    } = board_proof;
+
+    if current_game.game_status > 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+
+    let seat = player_number(&current_game, &ctx.accounts.signer.key())?;
+    let (board_hash, fleet) = if seat == 1 {
+        (current_game.board_hash_a, &current_game.rules.fleet_a)
+    } else {
+        (current_game.board_hash_b, &current_game.rules.fleet_b)
+    };
+
+    let mut public_inputs = vec![board_hash];
+    let circuit = if current_game.board_mode == BOARD_MODE_ZK {
+        // Unused ship slots are proven empty
+        public_inputs.push(field_from_u64(current_game.rules.grid_size.into()));
+        public_inputs.extend(
+            (0..MAX_SHIPS).map(|i| field_from_u64(fleet.get(i).copied().unwrap_or(0).into())),
+        );
+        CIRCUIT_FLEET
+    } else {
+        CIRCUIT_BOARD
+    };
+    if verifier.circuit_id != circuit {
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    check_proof(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        &board_proof,
+        &public_inputs,
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    pin_verifier(&mut game_account, &verifier_meta.address, &verifier)?;
+    game_account.boards_verified |= seat;
+
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    msg!(
+        "Player {} board verified in game {}",
+        if seat == 1 { "A" } else { "B" },
+        game_account.game_id
+    );
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(verifier_account)?
+        .with_light_account(game_account)?;
+    with_attestation(cpi, board_proof)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn prove_fleet_afloat<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    game: GameRef,
+    afloat_proof: CircuitProof,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let GameRef {
+        game: current_game,
+        meta: account_meta,
+    } = game;
+    let CircuitProof {
+        verifier,
+        verifier_meta,
+        proof: afloat_proof,
+    } = afloat_proof;
+
+    if current_game.game_status != 1 {
+        msg!("Game is not active!");
+        return Err(BattleshipError::GameOver.into());
+    }
+    if current_game.board_mode != BOARD_MODE_ZK {
+        return Err(BattleshipError::WrongBoardMode.into());
+    }
+    if verifier.circuit_id != CIRCUIT_AFLOAT {
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    let seat = player_number(&current_game, &ctx.accounts.signer.key())?;
+    // The mask is the opponent's shots at this board
+    let (board_hash, targeted) = if seat == 1 {
+        (current_game.board_hash_a, current_game.targeted_b)
+    } else {
+        (current_game.board_hash_b, current_game.targeted_a)
+    };
+
+    check_proof(
+        ctx.accounts,
+        &verifier,
+        &verifier_meta.address,
+        &afloat_proof,
+        &[board_hash, field_from_u64(targeted.into())],
+    )?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let mut game_account =
+        LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
+    pin_verifier(&mut game_account, &verifier_meta.address, &verifier)?;
+
+    emit!(FleetAfloat {
+        game_id: game_account.game_id,
+        game_address: account_meta.address,
+        player: ctx.accounts.signer.key(),
+        move_count: game_account.move_count,
+    });
+    msg!(
+        "Player {} fleet proven afloat at move {}",
+        if seat == 1 { "A" } else { "B" },
+        game_account.move_count
+    );
+
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    let cpi = new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(verifier_account)?
+        .with_light_account(game_account)?;
+    with_attestation(cpi, afloat_proof)?.invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+pub fn attest_proof<'info>(
+    ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+    proof: ValidityProof,
+    trees: NewAccountTrees,
+    attestation_id: u64,
+    statement: ProofStatement,
+    protocol: ProtocolConfigRef,
+) -> Result<()> {
+    let NewAccountTrees {
+        address_tree_info,
+        output_state_tree_index,
+    } = trees;
+    let ProofStatement {
+        verifier,
+        verifier_meta,
+        public_inputs,
+        zk_proof,
+    } = statement;
+
+    verify_proof(ctx.accounts, &verifier, &zk_proof, &public_inputs)?;
+
+    let light_cpi_accounts = CpiAccounts::new(
+        ctx.accounts.payer(),
+        ctx.remaining_accounts,
+        crate::LIGHT_CPI_SIGNER,
+    );
+
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(&light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+    let (address, address_seed) = derive_attestation_address(
+        &ctx.accounts.signer.key(),
+        attestation_id,
+        &address_tree_pubkey,
+    );
+    let mut attestation_account = LightAccount::<ProofAttestation>::new_init(
+        &crate::ID,
+        Some(address),
+        output_state_tree_index,
+    );
+    attestation_account.owner = ctx.accounts.signer.key();
+    attestation_account.verifier = verifier_meta.address;
+    attestation_account.inputs_hash = public_inputs_hash(&public_inputs);
+    attestation_account.expires_slot = Clock::get()?.slot + ATTESTATION_TIMEOUT_SLOTS;
+
+    msg!("✅ Circuit {} proof attested", verifier.circuit_id);
+
+    let verifier_account = LightAccount::<CircuitVerifier>::new_read_only(
+        &crate::ID,
+        &verifier_meta,
+        verifier,
+        &tree_pubkeys,
+    )?;
+
+    new_unpaused_cpi(proof, protocol, &light_cpi_accounts)?
+        .with_light_account(verifier_account)?
+        .with_light_account(attestation_account)?
+        .with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+    Ok(())
+}
+
+/// Pins the game to the `CircuitVerifier` at `address` the first time its
+/// circuit is used, and to its backend on the game's first proof. Only
+/// active versions can be pinned. Later proofs must go through the same
+/// verifier, so rotating a key never changes the rules of a running game.
+fn pin_verifier(
+    game: &mut GameState,
+    address: &[u8; 32],
+    verifier: &CircuitVerifier,
+) -> Result<()> {
+    let unused = [0u8; 32];
+    if game.verifiers.iter().all(|pinned| *pinned == unused) {
+        game.proof_backend = verifier.backend;
+    } else if game.proof_backend != verifier.backend {
+        msg!("This game uses proof backend {}", game.proof_backend);
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    let pinned = &mut game.verifiers[verifier.circuit_id as usize];
+    if *pinned == unused {
+        if verifier.retired {
+            msg!("Version {} has been rotated out", verifier.version);
+            return Err(BattleshipError::VerifierRetired.into());
+        }
+        *pinned = *address;
+    } else if pinned != address {
+        msg!(
+            "This game pinned another verifier for circuit {}",
+            verifier.circuit_id
+        );
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+    Ok(())
+}
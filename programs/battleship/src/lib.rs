@@ -1,13 +1,33 @@
 #![allow(unexpected_cfgs)]
 #![allow(deprecated)]
 
-use anchor_lang::{prelude::*, AnchorDeserialize, AnchorSerialize};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        ed25519_program,
+        hash::hashv,
+        instruction::{AccountMeta, Instruction},
+        program::{invoke, invoke_signed},
+        sysvar::instructions::{
+            self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked,
+        },
+    },
+    system_program, AnchorDeserialize, AnchorSerialize,
+};
 use light_sdk::{
     account::LightAccount,
-    address::v2::derive_address,
-    cpi::{v2::CpiAccounts, CpiSigner},
+    address::{
+        v2::derive_address, AddressSeed, NewAddressParamsAssignedPacked, PackedReadOnlyAddress,
+    },
+    cpi::{
+        v2::{CpiAccounts, LightSystemProgramCpi},
+        CpiSigner, InvokeLightSystemProgram, LightCpiInstruction,
+    },
     derive_light_cpi_signer,
-    instruction::{account_meta::CompressedAccountMeta, PackedAddressTreeInfo, ValidityProof},
+    instruction::{
+        account_meta::{CompressedAccountMeta, CompressedAccountMetaReadOnly},
+        PackedAddressTreeInfo, ValidityProof,
+    },
     LightDiscriminator,
 };
 use light_sdk_types::ADDRESS_TREE_V2;
@@ -17,354 +37,2472 @@ declare_id!("3gogNiRRhYTAT5UJUh4QCQ7XksCgrRr8dhGGMqjM3HLp");
 pub const LIGHT_CPI_SIGNER: CpiSigner =
     derive_light_cpi_signer!("3gogNiRRhYTAT5UJUh4QCQ7XksCgrRr8dhGGMqjM3HLp");
 
-/// Grid constants
-pub const GRID_SIZE: usize = 5;
-pub const GRID_CELLS: usize = GRID_SIZE * GRID_SIZE; // 25 cells
-pub const SHIP_LENGTH: usize = 4;
+pub mod addresses;
+mod board;
+pub mod constants;
+pub mod contexts;
+pub mod errors;
+pub mod events;
+mod gameplay;
+pub mod instructions;
+mod payments;
+mod proofs;
+mod protocol;
+pub mod state;
 
-/// Cell states
-pub const CELL_EMPTY: u8 = 0;
-pub const CELL_SHIP: u8 = 1;
-pub const CELL_HIT: u8 = 2;
-pub const CELL_MISS: u8 = 3;
+pub use addresses::*;
+use board::*;
+pub use constants::*;
+pub use contexts::*;
+pub use errors::*;
+pub use events::*;
+pub use gameplay::*;
+pub use payments::*;
+pub use proofs::*;
+use protocol::*;
+pub use state::*;
 
 #[program]
 pub mod battleship {
     use super::*;
-    use light_sdk::cpi::{
-        v2::LightSystemProgramCpi, InvokeLightSystemProgram, LightCpiInstruction,
-    };
 
-    /// Creates a new game with ship placement
-    /// ship_start_x, ship_start_y: Starting coordinates (0-4)
-    /// is_horizontal: true = horizontal placement, false = vertical
+    /// Creates a new game with ship placement, along with its empty move log.
+    /// The game id is the next one from the signer's profile, so ids are
+    /// unique per creator without the client picking one.
+    /// board.ship.x, board.ship.y: Starting coordinates (0-4)
+    /// board.ship.is_horizontal: true = horizontal placement, false = vertical
+    /// lobby.mode: WAGER_MODE_CASUAL, or WAGER_MODE_RANKED to have both
+    /// players lock the ranked stake of the config passed in `slot`
+    /// lobby.tags: region, speed and grid size shown in the lobby, see
+    /// `LobbyTags`
+    /// lobby.challenge: reserves the game for a friend on the signer's
+    /// `FriendList`
     pub fn create_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        board: ShipBoard,
+        lobby: LobbyOptions,
+        slot: ActiveGameSlot,
+        stats: Option<GlobalStatsRef>,
+    ) -> Result<()> {
+        instructions::game::create_game(ctx, proof, trees, board, lobby, slot, stats)
+    }
+
+    /// Creates the signer's empty `FriendList`
+    pub fn create_friend_list<'info>(
         ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
         proof: ValidityProof,
         address_tree_info: PackedAddressTreeInfo,
         output_state_tree_index: u8,
-        game_id: u64,
-        ship_start_x: u8,
-        ship_start_y: u8,
-        is_horizontal: bool,
-        board_hash: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::friends::create_friend_list(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            protocol,
+        )
+    }
+
+    /// Sends a friend invite: adds the signer to the pending invites on the
+    /// recipient's `FriendList`
+    pub fn send_invite<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        recipient_list: FriendList,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::friends::send_invite(ctx, proof, recipient_list, account_meta, protocol)
+    }
+
+    /// Answers a pending invite on the signer's list. Accepting adds the
+    /// signer and the inviter to each other's friends; declining just drops
+    /// the invite, and `inviter_list` may then be `None`.
+    pub fn accept_invite<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        list: FriendListRef,
+        inviter: Pubkey,
+        inviter_list: Option<FriendListRef>,
+        accept: bool,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::friends::accept_invite(
+            ctx,
+            proof,
+            list,
+            inviter,
+            inviter_list,
+            accept,
+            protocol,
+        )
+    }
+
+    /// Like `create_game`, but both players also stake `terms.wager` lamports in
+    /// the game's vault. `join_game` locks the same amount from Player B,
+    /// and `claim_deposits` pays both stakes to the winner, or refunds them
+    /// on a draw. The mode limits and fee of the config in `slot` apply.
+    pub fn create_game_with_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        board: ShipBoard,
+        terms: WagerTerms,
+        slot: ActiveGameSlot,
+        stats: Option<GlobalStatsRef>,
+    ) -> Result<()> {
+        instructions::game::create_game_with_wager(ctx, proof, trees, board, terms, slot, stats)
+    }
+
+    /// Like `create_game_with_wager`, but the stake is `terms.amount`
+    /// compressed tokens of `terms.mint`, transferred from `stake` to the
+    /// game's escrow PDA.
+    /// Join with `join_token_wager_game`; `claim_token_wager` pays out.
+    pub fn create_game_with_token_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenWagerAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        board: ShipBoard,
+        terms: TokenWagerTerms,
+        stake: TokenInputs,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::token_wager::create_game_with_token_wager(
+            ctx, proof, trees, board, terms, stake, slot,
+        )
+    }
+
+    /// Creates a game with custom rules, e.g. a large fleet with single shots
+    /// against a small fleet firing salvos. `fleet.ships` places
+    /// `rules.fleet_a` in order.
+    pub fn create_custom_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        rules: GameRules,
+        fleet: FleetBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::game::create_custom_game(ctx, proof, trees, rules, fleet, slot)
+    }
+
+    /// Creates a blockade game: only the attacker fires, and the defender
+    /// wins if their ship is still afloat after `BLOCKADE_TURNS` shots.
+    /// creator_attacks: true = Player A attacks, false = Player A defends
+    pub fn create_blockade_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        board: ShipBoard,
+        creator_attacks: bool,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::game::create_blockade_game(ctx, proof, trees, board, creator_attacks, slot)
+    }
+
+    /// Creates a single-player game against the on-chain bot.
+    /// The bot takes the Player B seat; its ship is placed deterministically
+    /// from the game address and it fires back immediately after every attack.
+    pub fn create_pve_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        board: ShipBoard,
+        slot: ActiveGameSlot,
     ) -> Result<()> {
-        // Validate ship placement
-        if ship_start_x >= GRID_SIZE as u8 || ship_start_y >= GRID_SIZE as u8 {
-            msg!("Invalid ship start position");
-            return Err(BattleshipError::InvalidPosition.into());
-        }
-
-        // Check ship fits in grid
-        if is_horizontal {
-            if ship_start_x + SHIP_LENGTH as u8 > GRID_SIZE as u8 {
-                msg!("Ship doesn't fit horizontally");
-                return Err(BattleshipError::ShipOutOfBounds.into());
-            }
-        } else {
-            if ship_start_y + SHIP_LENGTH as u8 > GRID_SIZE as u8 {
-                msg!("Ship doesn't fit vertically");
-                return Err(BattleshipError::ShipOutOfBounds.into());
-            }
-        }
-
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.signer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
-
-        let address_tree_pubkey = address_tree_info
-            .get_tree_pubkey(&light_cpi_accounts)
-            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
-
-        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
-            msg!("Invalid address tree");
-            return Err(ProgramError::InvalidAccountData.into());
-        }
-
-        let (address, address_seed) = derive_address(
-            &[b"battleship", &game_id.to_le_bytes()],
-            &address_tree_pubkey,
-            &crate::ID,
-        );
-        msg!("Derived Address: {:?}", address);
-        msg!("Program ID: {:?}", crate::ID);
-
-        // Initialize grid with empty cells
-        let mut grid = [CELL_EMPTY; GRID_CELLS];
-        let mut ship_cells = [0u8; SHIP_LENGTH];
-
-        // Place ship on grid
-        for i in 0..SHIP_LENGTH {
-            let (x, y) = if is_horizontal {
-                (ship_start_x + i as u8, ship_start_y)
-            } else {
-                (ship_start_x, ship_start_y + i as u8)
-            };
-            let index = (y as usize * GRID_SIZE) + x as usize;
-            grid[index] = CELL_SHIP;
-            ship_cells[i] = index as u8;
-        }
-
-        msg!(
-            "Game {} created by {:?}! Waiting for Player B.",
-            game_id,
-            ctx.accounts.signer.key()
-        );
-
-        let mut game_account =
-            LightAccount::<GameState>::new_init(&crate::ID, Some(address), output_state_tree_index);
-
-        game_account.game_id = game_id;
-        game_account.player_a = ctx.accounts.signer.key();
-        game_account.player_b = Pubkey::default();
-        game_account.current_turn = 1; // Player A starts
-        game_account.game_status = 0; // Waiting for B
-
-        // Init Player A
-        game_account.grid_a = grid;
-        game_account.board_hash_a = board_hash;
-        game_account.hits_a = 0;
-
-        // Init Player B (Empty)
-        game_account.grid_b = [CELL_EMPTY; GRID_CELLS];
-        game_account.board_hash_b = [0u8; 32];
-        game_account.hits_b = 0;
-
-        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
-            .with_light_account(game_account)?
-            .with_new_addresses(&[
-                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
-            ])
-            .invoke(light_cpi_accounts)?;
-
-        Ok(())
+        instructions::game::create_pve_game(ctx, proof, trees, board, slot)
     }
 
     /// Join an existing game as Player B
-    /// ship_start_x, ship_start_y: Starting coordinates (0-4)
-    /// is_horizontal: true = horizontal placement, false = vertical
+    /// board.ship.x, board.ship.y: Starting coordinates (0-4)
+    /// board.ship.is_horizontal: true = horizontal placement, false = vertical
+    /// options.coin_flip: hold the first shot until both players draw who
+    /// starts with a `RAND_FIRST_TURN` round (`commit_rand`, then
+    /// `reveal_rand`) instead of Player A starting
+    /// options.filter: lobby tags the game must match
+    /// options.ban_check: address tree proving the signer isn't banned,
+    /// needed for wagered and ranked games
     pub fn join_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        board: ShipBoard,
+        options: JoinOptions,
+        slot: ActiveGameSlot,
+        stats: Option<GlobalStatsRef>,
+    ) -> Result<()> {
+        instructions::game::join_game(ctx, proof, game, board, options, slot, stats)
+    }
+
+    /// Frees the active-game slot a finished or cancelled game holds in one
+    /// of its players' profiles. Anyone can call this.
+    pub fn release_active_game<'info>(
         ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
         proof: ValidityProof,
         current_game: GameState,
         account_meta: CompressedAccountMeta,
-        ship_start_x: u8,
-        ship_start_y: u8,
-        is_horizontal: bool,
-        board_hash: [u8; 32],
+        profile: PlayerProfile,
+        profile_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::release_active_game(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            profile,
+            profile_meta,
+            protocol,
+        )
+    }
+
+    /// Creates the zeroed `GlobalStats` singleton. Anyone can call this
+    /// once.
+    pub fn init_global_stats<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::admin::init_global_stats(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            protocol,
+        )
+    }
+
+    /// Joins a token-wager game, escrowing the same `token_wager` of the
+    /// game's mint from `stake`
+    pub fn join_token_wager_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenWagerAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        board: ShipBoard,
+        stake: TokenInputs,
+        ban_check: PackedAddressTreeInfo,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::token_wager::join_token_wager_game(
+            ctx, proof, game, board, stake, ban_check, slot,
+        )
+    }
+
+    /// Join a custom-rule game as Player B, placing `rules.fleet_b` in order.
+    /// Games created with `rules.handicap` also take Player A's profile as
+    /// `opponent`; B's rating is read from `slot`.
+    pub fn join_custom_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        fleet: FleetBoard,
+        opponent: Option<OpponentProfile>,
+        slot: ActiveGameSlot,
     ) -> Result<()> {
-        // Validate game status
-        if current_game.game_status != 0 {
-            msg!("Game is not in waiting state (Status: {})", current_game.game_status);
-            return Err(ProgramError::InvalidAccountData.into());
-        }
-
-        // Validate ship placement
-         if ship_start_x >= GRID_SIZE as u8 || ship_start_y >= GRID_SIZE as u8 {
-            msg!("Invalid ship start position");
-            return Err(BattleshipError::InvalidPosition.into());
-        }
-
-        // Check ship fits in grid
-        if is_horizontal {
-            if ship_start_x + SHIP_LENGTH as u8 > GRID_SIZE as u8 {
-                msg!("Ship doesn't fit horizontally");
-                return Err(BattleshipError::ShipOutOfBounds.into());
-            }
-        } else {
-            if ship_start_y + SHIP_LENGTH as u8 > GRID_SIZE as u8 {
-                msg!("Ship doesn't fit vertically");
-                return Err(BattleshipError::ShipOutOfBounds.into());
-            }
-        }
-
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.signer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
-
-        let mut game_account =
-            LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
-
-        // Set Player B
-        game_account.player_b = ctx.accounts.signer.key();
-        game_account.game_status = 1; // Active
-
-        // Initialize grid B with empty cells
-        let mut grid = [CELL_EMPTY; GRID_CELLS];
-        let mut ship_cells = [0u8; SHIP_LENGTH];
-
-        // Place ship on grid B
-        for i in 0..SHIP_LENGTH {
-            let (x, y) = if is_horizontal {
-                (ship_start_x + i as u8, ship_start_y)
-            } else {
-                (ship_start_x, ship_start_y + i as u8)
-            };
-            let index = (y as usize * GRID_SIZE) + x as usize;
-            grid[index] = CELL_SHIP;
-            ship_cells[i] = index as u8;
-        }
-
-        game_account.grid_b = grid;
-        game_account.board_hash_b = board_hash;
-        game_account.hits_b = 0;
-
-        msg!(
-            "Player B joined! Game {} is now Active! Ship at ({}, {})",
-            game_account.game_id,
-            ship_start_x,
-            ship_start_y
-        );
-
-        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
-            .with_light_account(game_account)?
-            .invoke(light_cpi_accounts)?;
-
-        Ok(())
+        instructions::game::join_custom_game(ctx, proof, game, fleet, opponent, slot)
     }
+
     /// Attack a cell at (x, y) coordinates
+    /// The shot is appended to the game's move log in the same CPI.
     pub fn attack<'info>(
         ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
         proof: ValidityProof,
-        current_game: GameState,
-        account_meta: CompressedAccountMeta,
+        turn: GameTurn,
         attack_x: u8,
         attack_y: u8,
+        session: Option<SessionAuth>,
+        protocol: ProtocolConfigRef,
     ) -> Result<()> {
-        // Validate coordinates
-        if attack_x >= GRID_SIZE as u8 || attack_y >= GRID_SIZE as u8 {
-            msg!("Attack coordinates out of bounds");
-            return Err(BattleshipError::InvalidPosition.into());
-        }
-
-        // Check game is active
-        if current_game.game_status != 1 {
-            msg!("Game is not active!");
-            return Err(BattleshipError::GameOver.into());
-        }
-
-        let light_cpi_accounts = CpiAccounts::new(
-            ctx.accounts.signer.as_ref(),
-            ctx.remaining_accounts,
-            crate::LIGHT_CPI_SIGNER,
-        );
-
-        let mut game_account =
-            LightAccount::<GameState>::new_mut(&crate::ID, &account_meta, current_game)?;
-
-
-        // Determine target grid and update logic based on turn
-        if game_account.current_turn == 1 {
-            // Player A attacking Player B
-            if game_account.player_a != ctx.accounts.signer.key() {
-                msg!("Not Player A's turn!");
-                return Err(BattleshipError::NotPlayerTurn.into());
-            }
-
-            let index = (attack_y as usize * GRID_SIZE) + attack_x as usize;
-            let cell = game_account.grid_b[index];
-
-            if cell == CELL_HIT || cell == CELL_MISS {
-                msg!("Cell ({}, {}) already attacked!", attack_x, attack_y);
-                return Err(BattleshipError::AlreadyAttacked.into());
-            }
-
-            if cell == CELL_SHIP {
-                game_account.grid_b[index] = CELL_HIT;
-                game_account.hits_b += 1;
-                msg!("💥 HIT on Player B!");
-                if game_account.hits_b >= SHIP_LENGTH as u8 {
-                    game_account.game_status = 2; // A Won
-                    msg!("🎉 Player A Wins!");
-                }
-            } else {
-                game_account.grid_b[index] = CELL_MISS;
-                msg!("💨 MISS on Player B.");
-            }
-            
-            // Switch turn to B
-            game_account.current_turn = 2;
-        } else {
-            // Player B attacking Player A
-            if game_account.player_b != ctx.accounts.signer.key() {
-                msg!("Not Player B's turn!");
-                return Err(BattleshipError::NotPlayerTurn.into());
-            }
-
-            let index = (attack_y as usize * GRID_SIZE) + attack_x as usize;
-            let cell = game_account.grid_a[index];
-
-            if cell == CELL_HIT || cell == CELL_MISS {
-                msg!("Cell ({}, {}) already attacked!", attack_x, attack_y);
-                return Err(BattleshipError::AlreadyAttacked.into());
-            }
-
-            if cell == CELL_SHIP {
-                game_account.grid_a[index] = CELL_HIT;
-                game_account.hits_a += 1;
-                msg!("� HIT on Player A!");
-                if game_account.hits_a >= SHIP_LENGTH as u8 {
-                    game_account.game_status = 3; // B Won
-                    msg!("🎉 Player B Wins!");
-                }
-            } else {
-                game_account.grid_a[index] = CELL_MISS;
-                msg!("💨 MISS on Player A.");
-            }
-
-            // Switch turn to A
-            game_account.current_turn = 1;
-        }
-
-        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
-            .with_light_account(game_account)?
-            .invoke(light_cpi_accounts)?;
-
-        Ok(())
+        instructions::game::attack(ctx, proof, turn, attack_x, attack_y, session, protocol)
     }
-}
 
-#[derive(Accounts)]
-pub struct GameAccounts<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
-}
+    /// Creates a hidden-board game. Only the Merkle root of Player A's
+    /// cells goes on chain; see `board_merkle_root` for the leaf layout.
+    pub fn create_hidden_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        board_root: [u8; 32],
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::hidden::create_hidden_game(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            board_root,
+            slot,
+        )
+    }
 
-/// The game state stored as a compressed account
-#[event]
-#[derive(Clone, Debug, Default, LightDiscriminator)]
-pub struct GameState {
-    pub game_id: u64,
-    pub player_a: Pubkey,
-    pub player_b: Pubkey,
-    pub current_turn: u8, // 1 = A, 2 = B
-    pub game_status: u8,  // 0 = Waiting, 1 = Active, 2 = A Won, 3 = B Won
-
-    // Player A
-    pub grid_a: [u8; GRID_CELLS],
-    pub board_hash_a: [u8; 32], // Noir Pedersen Hash (bytes)
-    pub hits_a: u8,
-
-    // Player B
-    pub grid_b: [u8; GRID_CELLS],
-    pub board_hash_b: [u8; 32], // Noir Pedersen Hash (bytes)
-    pub hits_b: u8,
-}
+    /// Join a hidden-board game as Player B with a commitment to B's board:
+    /// a Merkle root over B's cells, or the Noir board hash in ZK games
+    pub fn join_hidden_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        board_root: [u8; 32],
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::hidden::join_hidden_game(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            board_root,
+            slot,
+        )
+    }
+
+    /// Defender reveals the cell targeted by the pending shot.
+    /// The revealed value and salt must hash to a leaf on the defender's
+    /// committed Merkle root.
+    pub fn respond_attack<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        turn: GameTurn,
+        opening: CellOpening,
+        session: Option<SessionAuth>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::hidden::respond_attack(ctx, proof, turn, opening, session, protocol)
+    }
+
+    /// Creates a ZK game. Player A commits to their board with the Noir
+    /// fleet hash only; every shot is answered with `respond_zk_attack`, so
+    /// neither grid ever holds more than the revealed cells. No shot can be
+    /// fired until both players prove their fleet with `verify_board`.
+    pub fn create_zk_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        rules: GameRules,
+        board_hash: [u8; 32],
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::zk::create_zk_game(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            rules,
+            board_hash,
+            slot,
+        )
+    }
+
+    /// Defender answers the pending shot in a ZK game with a proof from the
+    /// Noir shot circuit that the cell of the board behind their board hash
+    /// is `hit`, checked by the game's shot verifier.
+    pub fn respond_zk_attack<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        turn: GameTurn,
+        shot_proof: CircuitProof,
+        hit: bool,
+        session: Option<SessionAuth>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::zk::respond_zk_attack(ctx, proof, turn, shot_proof, hit, session, protocol)
+    }
+
+    /// Admin: registers version 0 of the verifying key for `backend` proofs
+    /// of `circuit_id`: the verifier program, and the account holding the
+    /// key if the backend reads one. Later versions come from
+    /// `rotate_verifier`.
+    pub fn register_verifier<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        circuit_id: u8,
+        backend: u8,
+        verifier_program: Pubkey,
+        verifying_key: Pubkey,
+    ) -> Result<()> {
+        instructions::zk::register_verifier(
+            ctx,
+            proof,
+            trees,
+            circuit_id,
+            backend,
+            verifier_program,
+            verifying_key,
+        )
+    }
+
+    /// Admin: retires `current_verifier` and registers the next version of
+    /// its circuit and backend. New games can only pin active versions;
+    /// games that already pinned the old one keep verifying against it.
+    pub fn rotate_verifier<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        current_verifier: CircuitVerifier,
+        account_meta: CompressedAccountMeta,
+        verifier_program: Pubkey,
+        verifying_key: Pubkey,
+    ) -> Result<()> {
+        instructions::zk::rotate_verifier(
+            ctx,
+            proof,
+            trees,
+            current_verifier,
+            account_meta,
+            verifier_program,
+            verifying_key,
+        )
+    }
+
+    /// Proves the signer's `board_hash` commits to a legal placement by
+    /// running `board_proof` through its registered verifier: the fleet
+    /// circuit against the signer's `rules` fleet in ZK games, the board
+    /// circuit otherwise. Sets the signer's bit in `boards_verified`.
+    /// The first proof in a game pins its backend and verifier.
+    pub fn verify_board<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        board_proof: CircuitProof,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::zk::verify_board(ctx, proof, game, board_proof, protocol)
+    }
+
+    /// Optional liveness check for ZK games: the defender proves their
+    /// committed fleet still has a cell the opponent hasn't fired at. A
+    /// defender who has been claiming misses on ship cells can't produce
+    /// one, so an attacker who doesn't see `FleetAfloat` events knows to
+    /// stop trusting a game that should have ended.
+    pub fn prove_fleet_afloat<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        afloat_proof: CircuitProof,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::zk::prove_fleet_afloat(ctx, proof, game, afloat_proof, protocol)
+    }
+
+    /// First phase of a split verification: runs `statement.zk_proof`
+    /// through the verifier and records a `ProofAttestation` for the signer. A game
+    /// instruction in a later transaction takes it as
+    /// `ProofSource::Attested` in place of the proof and closes it, so the
+    /// verifier's compute doesn't share a budget with the game's Light CPI.
+    pub fn attest_proof<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        attestation_id: u64,
+        statement: ProofStatement,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::zk::attest_proof(ctx, proof, trees, attestation_id, statement, protocol)
+    }
+
+    /// Admin: creates the `OracleConfig` naming the oracle key that pairs
+    /// encrypted matchmaking tickets. The oracle is trusted, not MPC: it
+    /// decrypts every ticket's preferences off chain and picks the pairs.
+    pub fn init_oracle_config<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        instructions::oracle::init_oracle_config(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            oracle,
+        )
+    }
+
+    /// Admin: points new matchmaking tickets at another oracle key. Queued
+    /// tickets keep the oracle they were created with.
+    pub fn update_oracle_config<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: OracleConfig,
+        account_meta: CompressedAccountMeta,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        instructions::oracle::update_oracle_config(ctx, proof, current_config, account_meta, oracle)
+    }
+
+    /// Admin: creates the `ProtocolConfig` setting each wager mode's fee and
+    /// wager limits, the treasury fees go to, and the stake ranked games
+    /// take
+    pub fn init_protocol_config<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        rakes: [ModeRake; WAGER_MODE_COUNT],
+        treasury: Pubkey,
+        ranked_stake: u64,
+    ) -> Result<()> {
+        instructions::admin::init_protocol_config(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            rakes,
+            treasury,
+            ranked_stake,
+        )
+    }
+
+    /// Admin: changes the mode rakes, treasury or ranked stake. Games keep
+    /// the fee and stake they were created with.
+    pub fn update_protocol_config<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        rakes: [ModeRake; WAGER_MODE_COUNT],
+        treasury: Pubkey,
+        ranked_stake: u64,
+    ) -> Result<()> {
+        instructions::admin::update_protocol_config(
+            ctx,
+            proof,
+            current_config,
+            account_meta,
+            rakes,
+            treasury,
+            ranked_stake,
+        )
+    }
+
+    /// Admin: bans `player` from wagered games and ranked queues. Games
+    /// they are already in can still be played out and claimed.
+    pub fn ban_player<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        player: Pubkey,
+    ) -> Result<()> {
+        instructions::admin::ban_player(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            player,
+        )
+    }
+
+    /// Admin: lifts a ban by closing it
+    pub fn unban_player<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        ban: Ban,
+        account_meta: CompressedAccountMeta,
+    ) -> Result<()> {
+        instructions::admin::unban_player(ctx, proof, ban, account_meta)
+    }
+
+    /// Admin: hands the pause key to `admin` and sets the timeouts games
+    /// created with the config pin; 0 keeps `TURN_TIMEOUT_SLOTS` and
+    /// `OPEN_GAME_EXPIRY_SLOTS`. Fees stay with `update_protocol_config`.
+    pub fn update_config<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        admin: Pubkey,
+        turn_timeout_slots: u64,
+        open_game_expiry_slots: u64,
+    ) -> Result<()> {
+        instructions::admin::update_config(
+            ctx,
+            proof,
+            current_config,
+            account_meta,
+            admin,
+            turn_timeout_slots,
+            open_game_expiry_slots,
+        )
+    }
+
+    /// Admin: ratings of profiles with no ranked game for `decay_idle_slots`
+    /// lose `decay_points` per idle period through `decay_rating`, down to
+    /// `decay_floor` (0 = `INITIAL_RATING`). An idle span of 0 turns decay
+    /// off.
+    pub fn set_rating_decay<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        decay_idle_slots: u64,
+        decay_points: u16,
+        decay_floor: u16,
+    ) -> Result<()> {
+        instructions::admin::set_rating_decay(
+            ctx,
+            proof,
+            current_config,
+            account_meta,
+            decay_idle_slots,
+            decay_points,
+            decay_floor,
+        )
+    }
+
+    /// Decays the public rating of a profile that has played no ranked game
+    /// for the config's `decay_idle_slots`, and restarts its idle clock.
+    /// Anyone can crank this.
+    pub fn decay_rating<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_profile: PlayerProfile,
+        profile_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::decay_rating(ctx, proof, current_profile, profile_meta, protocol)
+    }
+
+    /// Emergency switch for the config's `admin`, who needn't hold the
+    /// upgrade authority: while paused, every instruction refuses to run
+    /// until it's unpaused, except the admin's own and the refunds and
+    /// payouts of escrowed funds: cancelling, expiring or reclaiming an open
+    /// game, leaving a queue, and claiming deposits, token wagers, side bets
+    /// and market winnings, so a pause can't trap anyone's stake.
+    pub fn pause_protocol<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        paused: bool,
+    ) -> Result<()> {
+        instructions::admin::pause_protocol(ctx, proof, current_config, account_meta, paused)
+    }
+
+    /// Admin: sets the compressed reward token winners of unwagered games
+    /// can claim, and how many base units each win earns. The mint's
+    /// authority must be the `REWARD_AUTHORITY_SEED` PDA; a zero rate turns
+    /// rewards off.
+    pub fn set_reward_token<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        reward_mint: Pubkey,
+        reward_per_win: u64,
+    ) -> Result<()> {
+        instructions::admin::set_reward_token(
+            ctx,
+            proof,
+            current_config,
+            account_meta,
+            reward_mint,
+            reward_per_win,
+        )
+    }
+
+    /// Admin: lets each profile hold up to `max_active_games` unfinished
+    /// games, created or joined; 0 falls back to `DEFAULT_MAX_ACTIVE_GAMES`.
+    /// The limit may sit below the default.
+    pub fn set_active_game_limit<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_config: ProtocolConfig,
+        account_meta: CompressedAccountMeta,
+        max_active_games: u16,
+    ) -> Result<()> {
+        instructions::admin::set_active_game_limit(
+            ctx,
+            proof,
+            current_config,
+            account_meta,
+            max_active_games,
+        )
+    }
+
+    /// Creates a batch game: Player A commits to their whole game up front,
+    /// a fleet and an order of shots, with `batch_commitment`. Once B joins
+    /// with their own commitment, both reveal with `reveal_batch_plan` and the
+    /// program plays the shots out (A, B, A, ...). Suited to async play: one
+    /// round trip per game instead of one per shot. Plans stay sealed only
+    /// until both players commit: each reveal publishes that player's fleet
+    /// and shot order, unlike the MPC mode this stands in for, which would
+    /// write only the winner.
+    pub fn create_batch_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        commitment: [u8; 32],
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::batch::create_batch_game(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            commitment,
+            slot,
+        )
+    }
+
+    /// Join a batch game as Player B with a commitment to B's plan. Both
+    /// plans are then due within the game's turn timeout.
+    pub fn join_batch_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        plans: BatchPlansRef,
+        commitment: [u8; 32],
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::batch::join_batch_game(ctx, proof, game, plans, commitment, slot)
+    }
+
+    /// Opens the signer's batch commitment. The plan must fit the game's
+    /// rules. The second reveal places both fleets and plays the whole
+    /// game out on chain: sunk fleet wins, a draw if both orders run out.
+    pub fn reveal_batch_plan<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        plans: BatchPlansRef,
+        plan: BatchPlan,
+        salt: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::batch::reveal_batch_plan(ctx, proof, game, plans, plan, salt, protocol)
+    }
+
+    /// Permissionless crank: once a batch game's plans are overdue, the
+    /// player who revealed wins; if neither did, the game is voided.
+    pub fn forfeit_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        plans: BatchPlans,
+        plans_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::batch::forfeit_batch(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            plans,
+            plans_meta,
+            protocol,
+        )
+    }
+
+    /// Commits the signer to a random value for round `commit.round_id` of a game
+    /// with `rand_commitment`. The first commit opens the round; the second
+    /// passes it as `current_round`. Opening `RAND_FIRST_TURN` before the
+    /// first shot holds the game until both values are revealed.
+    /// `RAND_REMATCH` is drawn on a finished game, every other round on an
+    /// active one.
+    pub fn commit_rand<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameRef,
+        commit: RandCommitment,
+        current_round: Option<RandRoundRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::randomness::commit_rand(
+            ctx,
+            proof,
+            trees,
+            game,
+            commit,
+            current_round,
+            protocol,
+        )
+    }
+
+    /// Opens the signer's commitment once both players have committed.
+    /// The second reveal fixes the round's result, the hash of both values,
+    /// so neither player controls it alone; for `RAND_FIRST_TURN` its low
+    /// bit picks who fires first and the turn clock starts. The second
+    /// revealer can compute the result before sending their reveal. In an
+    /// active game, withholding it forfeits the game through `forfeit_rand`,
+    /// so they can refuse an unwanted first turn but only by losing.
+    pub fn reveal_rand<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        round: RandRoundRef,
+        reveal: RandReveal,
+        views: Option<PlayerViews>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::randomness::reveal_rand(ctx, proof, game, round, reveal, views, protocol)
+    }
+
+    /// Ends an active game whose randomness round stalled past its
+    /// deadline, one turn timeout after the last commit or reveal. A player who never committed, or never revealed after
+    /// both committed, forfeits; if both stalled the game is voided.
+    pub fn forfeit_rand<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        round: RandRound,
+        round_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::randomness::forfeit_rand(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            round,
+            round_meta,
+            protocol,
+        )
+    }
+
+    /// Joins the matchmaking queue with preferences (stake range, rating
+    /// band) encrypted to the oracle. The oracle pairs compatible tickets
+    /// and posts pairs with `match_callback`, so other players can't pick a
+    /// victim from the queue. This is trusted pairing, not MPC: the oracle
+    /// reads every ticket's preferences and chooses who plays whom, and the
+    /// program only checks that it signed.
+    pub fn enqueue_match<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        ticket: OracleTicket,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::enqueue_match(ctx, proof, trees, ticket, protocol)
+    }
+
+    /// Joins the ranked queue: like `enqueue_match`, plus a rating circuit
+    /// proof that the signer's sealed rating is inside `RATING_BRACKETS[bracket]`.
+    /// Ranked tickets only pair within their bracket, and the rating
+    /// itself is never revealed.
+    pub fn enqueue_ranked_match<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        ticket: OracleTicket,
+        rating: RatingProof,
+        bracket: u8,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::enqueue_ranked_match(
+            ctx, proof, trees, ticket, rating, bracket, protocol,
+        )
+    }
+
+    /// Oracle callback: the oracle pairs two queued tickets. The first ticket's
+    /// owner hosts with `create_matched_game`, reserved for the second. The
+    /// program can't see the preferences, so it takes the pairing on trust;
+    /// it only enforces the signer, distinct owners and matching brackets.
+    pub fn match_callback<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        host_ticket: MatchTicket,
+        host_meta: CompressedAccountMeta,
+        guest_ticket: MatchTicket,
+        guest_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::match_callback(
+            ctx,
+            proof,
+            host_ticket,
+            host_meta,
+            guest_ticket,
+            guest_meta,
+            protocol,
+        )
+    }
+
+    /// Leaves the queue, closing a ticket that hasn't been matched
+    pub fn cancel_match<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        ticket: MatchTicket,
+        ticket_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::cancel_match(ctx, proof, ticket, ticket_meta, protocol)
+    }
+
+    /// Opens the queue for one `RATING_BRACKETS` bucket. Anyone can open
+    /// each bucket once.
+    pub fn open_bucket_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        bucket: u8,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::open_bucket_queue(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            bucket,
+            protocol,
+        )
+    }
+
+    /// Joins a rating bucket's queue with only a rating circuit proof that
+    /// the signer's sealed rating is in the bucket; no oracle and no rating on
+    /// chain. If someone is waiting, the two are matched at once with the
+    /// waiting player as host (pass their ticket as `seat.waiting`); otherwise
+    /// the signer waits for the next joiner.
+    pub fn join_bucket_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        seat: BucketSeat,
+        rating: RatingProof,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::join_bucket_queue(ctx, proof, trees, seat, rating, protocol)
+    }
+
+    /// Leaves a bucket queue before being matched, closing the ticket
+    pub fn leave_bucket_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_queue: BucketQueue,
+        queue_meta: CompressedAccountMeta,
+        ticket: MatchTicket,
+        ticket_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::leave_bucket_queue(
+            ctx,
+            proof,
+            current_queue,
+            queue_meta,
+            ticket,
+            ticket_meta,
+            protocol,
+        )
+    }
+
+    /// Issues the signer's credential (proof of humanity, KYC or the like)
+    /// to `holder`, valid until `expires_slot` (0 = no expiry). Anyone can
+    /// issue; verified queues name the one issuer they trust.
+    pub fn issue_credential<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        holder: Pubkey,
+        expires_slot: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::issue_credential(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            holder,
+            expires_slot,
+            protocol,
+        )
+    }
 
-#[error_code]
-pub enum BattleshipError {
-    #[msg("Invalid position: coordinates out of bounds")]
-    InvalidPosition,
-    #[msg("Ship placement out of bounds")]
-    ShipOutOfBounds,
-    #[msg("Cell already attacked")]
-    AlreadyAttacked,
-    #[msg("Game is already over")]
-    GameOver,
-    #[msg("Not player's turn")]
-    NotPlayerTurn,
+    /// Lets a credential's issuer withdraw it
+    pub fn revoke_credential<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        credential: Credential,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::revoke_credential(ctx, proof, credential, account_meta, protocol)
+    }
+
+    /// Opens the play queue for `mode`, WAGER_MODE_CASUAL or
+    /// WAGER_MODE_RANKED. Anyone can open each once, and once more per
+    /// `issuer` as a verified queue that only holders of that issuer's
+    /// `Credential` can join.
+    pub fn open_play_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        mode: u8,
+        issuer: Option<Pubkey>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::open_play_queue(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            mode,
+            issuer,
+            protocol,
+        )
+    }
+
+    /// Queues the signer for a classic game with their ship placed.
+    /// With nobody waiting, the signer waits in the queue, spending their
+    /// profile's next game id and locking their deposit in the vault of the
+    /// game it will become. Otherwise this creates that game at once
+    /// between the waiting player (Player A, who starts) and the signer,
+    /// with no create/join handshake. The ranked queue locks the ranked
+    /// stake the first player waited with.
+    /// Players are only paired when their `entry.tags` match. Verified queues also
+    /// take the signer's unexpired `credential` from the queue's issuer.
+    pub fn join_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        queue: PlayQueueRef,
+        entry: QueueEntry,
+        slot: ActiveGameSlot,
+        credential: Option<CredentialRef>,
+    ) -> Result<()> {
+        instructions::matchmaking::join_queue(ctx, proof, trees, queue, entry, slot, credential)
+    }
+
+    /// Leaves the play queue before being paired, refunding the deposit
+    /// locked by `join_queue` and releasing the active game it held in the
+    /// signer's `profile`
+    pub fn leave_queue<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        queue: PlayQueueRef,
+        profile: PlayerProfile,
+        profile_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::matchmaking::leave_queue(
+            ctx,
+            proof,
+            address_tree_info,
+            queue,
+            profile,
+            profile_meta,
+            protocol,
+        )
+    }
+
+    /// Host side of a match: spends the matched ticket and creates a classic
+    /// game that only the matched opponent can join. Ranked tickets make a
+    /// ranked game, where both players lock the `ranked_stake` of the config
+    /// passed in `slot`.
+    pub fn create_matched_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        ticket: MatchTicket,
+        ticket_meta: CompressedAccountMeta,
+        board: ShipBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::matchmaking::create_matched_game(
+            ctx,
+            proof,
+            trees,
+            ticket,
+            ticket_meta,
+            board,
+            slot,
+        )
+    }
+
+    /// Permissionless crank: once the player who owes an action has been
+    /// idle for the game's turn timeout, anyone can award the game to the
+    /// opponent. With a pending hidden-board shot the defender is the idle one;
+    /// a first-turn or obstacle draw still open a turn after its round timed
+    /// out voids the game instead.
+    pub fn finalize_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::finalize_game(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// In games with `rules.skip_on_timeout`, lets the waiting player pass
+    /// an idle opponent's turn once the turn clock runs out. The skip is
+    /// logged and counts toward the move limit.
+    pub fn force_skip_turn<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        turn: GameTurn,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::force_skip_turn(ctx, proof, turn, protocol)
+    }
+
+    /// Registers a hot key that may sign `attack`/`respond_attack` for the
+    /// signer until `expires_at_slot`
+    pub fn authorize_session_key<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        session_key: Pubkey,
+        expires_at_slot: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::session::authorize_session_key(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            session_key,
+            expires_at_slot,
+            protocol,
+        )
+    }
+
+    /// Closes a session key account so the hot key can no longer play
+    pub fn revoke_session_key<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_session: SessionKey,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::session::revoke_session_key(
+            ctx,
+            proof,
+            current_session,
+            account_meta,
+            protocol,
+        )
+    }
+
+    /// Re-simulates a game from the players' revealed placements using the
+    /// move log and checks the result against the stored `GameState`. A
+    /// placement that isn't a legal fleet, or isn't the one the game was
+    /// played with, fails the replay.
+    /// Both accounts are passed read-only so the Light system program proves
+    /// they are the current on-chain versions.
+    pub fn verify_replay<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        log: MoveLogReadOnlyRef,
+        ships_a: Vec<ShipPlacement>,
+        ships_b: Vec<ShipPlacement>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::settlement::verify_replay(ctx, proof, game, log, ships_a, ships_b, protocol)
+    }
+
+    /// Settles a game that was played off-chain. Both players sign
+    /// `settlement_message(game_address, final_status, moves)` with ed25519
+    /// program instructions earlier in the same transaction; the transcript
+    /// is replayed from the committed placements and written in one go.
+    /// The result only becomes final once `CHALLENGE_WINDOW_SLOTS` pass
+    /// without a longer co-signed transcript being submitted.
+    pub fn settle_offchain_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        log: MoveLogRef,
+        final_status: u8,
+        moves: Vec<MoveRecord>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::settlement::settle_offchain_game(
+            ctx,
+            proof,
+            game,
+            log,
+            final_status,
+            moves,
+            protocol,
+        )
+    }
+
+    /// Replaces an open off-chain settlement with a longer co-signed
+    /// transcript, e.g. when the opponent settled from an older state.
+    /// The challenge window is not extended.
+    pub fn challenge_settlement<'info>(
+        ctx: Context<'_, '_, '_, 'info, SettleAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameRef,
+        log: MoveLogRef,
+        final_status: u8,
+        moves: Vec<MoveRecord>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::settlement::challenge_settlement(
+            ctx,
+            proof,
+            game,
+            log,
+            final_status,
+            moves,
+            protocol,
+        )
+    }
+
+    /// Pays out the deposits of a finished game. Clean finishes refund both
+    /// players; a game lost on time forfeits the loser's deposit to the
+    /// winner and their ranked stake to the treasury. Wagers go to the
+    /// winner, less the fee and `JACKPOT_BPS` for the jackpot, and are
+    /// refunded on a draw. Anyone can crank this.
+    pub fn claim_deposits<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimDepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::claim_deposits(ctx, proof, game, game_meta, protocol)
+    }
+
+    /// Pays out a finished game's escrowed tokens: both stakes to the winner,
+    /// or each stake back on a draw, void or cancellation. `stake` spends the escrow's
+    /// token accounts. Anyone can crank this.
+    pub fn claim_token_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, TokenClaimAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        stake: TokenInputs,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::token_wager::claim_token_wager(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            stake,
+            protocol,
+        )
+    }
+
+    /// Mints the protocol's reward token to the winner of a won PvP game
+    /// that had no wager, at the config's current `reward_per_win`. Each
+    /// game pays one reward. The compressed token program's `mint_to`
+    /// accounts start at `token_accounts_offset` in the remaining accounts.
+    /// Anyone can crank this.
+    pub fn claim_reward_tokens<'info>(
+        ctx: Context<'_, '_, '_, 'info, RewardMintAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+        token_accounts_offset: u8,
+    ) -> Result<()> {
+        instructions::token_wager::claim_reward_tokens(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            protocol,
+            token_accounts_offset,
+        )
+    }
+
+    /// Pays the progressive jackpot to the winner of a wagered game who sank
+    /// the whole fleet without a single miss. The vault keeps its
+    /// rent-exempt minimum; each game pays at most once. Anyone can crank
+    /// this.
+    pub fn claim_jackpot<'info>(
+        ctx: Context<'_, '_, '_, 'info, JackpotAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::claim_jackpot(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// Player A calls off a game nobody has joined, getting back the deposit
+    /// and any lamport wager. A token wager is refunded by
+    /// `claim_token_wager`.
+    pub fn cancel_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        stats: Option<GlobalStatsRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::cancel_game(ctx, proof, current_game, account_meta, stats, protocol)
+    }
+
+    /// Permissionless crank: cancels a game that has waited
+    /// `OPEN_GAME_EXPIRY_SLOTS`, or the expiry pinned at creation, for
+    /// Player B, refunding Player A as `cancel_game` does
+    pub fn expire_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        stats: Option<GlobalStatsRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::expire_game(ctx, proof, current_game, account_meta, stats, protocol)
+    }
+
+    /// Permissionless crank: cancels a wagered game nobody joined within
+    /// `STALE_WAGER_SLOTS`, refunding Player A's deposit and lamport wager.
+    /// A token wager is then refunded by `claim_token_wager`.
+    pub fn reclaim_stale_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        stats: Option<GlobalStatsRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::reclaim_stale_wager(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            stats,
+            protocol,
+        )
+    }
+
+    /// Concedes an active game: the opponent wins at once
+    pub fn resign<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::game::resign(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// Offers to double a wagered game's stakes, on the signer's turn. The
+    /// offer locks the signer's half of the raise and hands the turn to the
+    /// opponent, who must `accept_raise` or `decline_raise` before play
+    /// goes on. After a raise only the player who accepted it may raise
+    /// again, as with a backgammon doubling cube.
+    pub fn offer_raise<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::offer_raise(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// Accepts a raise, matching it so the wager doubles. The turn goes
+    /// back to the player who offered it.
+    pub fn accept_raise<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::accept_raise(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// Declines a raise, which resigns the game to the player who offered it
+    pub fn decline_raise<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::decline_raise(ctx, proof, current_game, account_meta, protocol)
+    }
+
+    /// Puts up a prize of `amount` lamports for the winner of a game that
+    /// hasn't finished. It goes into the game's vault and is paid by
+    /// `claim_deposits` on top of any wager, or split on a draw. A game has
+    /// one sponsor, who may add to the prize.
+    pub fn sponsor_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        amount: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::payouts::sponsor_game(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            amount,
+            protocol,
+        )
+    }
+
+    /// Succeeds only if `winner` won the game, for other programs (trophies,
+    /// rewards, tournaments) to CPI into via `battleship::cpi::verify_win`.
+    /// The game is passed read-only so the Light system program proves it is
+    /// the current state; open off-chain settlements don't count as final.
+    pub fn verify_win<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        winner: Pubkey,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::records::verify_win(ctx, proof, game, game_meta, winner, protocol)
+    }
+
+    /// Writes the immutable `GameResult` for a finished game. Permissionless
+    /// and one-shot: the address is derived from the game address, so a
+    /// second call fails. Nothing ever mutates the record afterwards, so it
+    /// outlives the game account. Passing both `updates.profiles` of a PvP game
+    /// settles their streaks and emits `AchievementUnlocked` per milestone.
+    /// Ranked games must pass them, and also move public ratings. Passing
+    /// `updates.rivalry` adds the game to the pair's head-to-head record.
+    pub fn record_game_result<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        updates: ResultUpdates,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::records::record_game_result(ctx, proof, trees, game, updates, protocol)
+    }
+
+    /// Appends a recorded `GameResult` the signer played to their match
+    /// history: to `page` if it has room, else to a new page after it,
+    /// and to page 0 when `page` is `None`. Pages are numbered from 0, so
+    /// clients find the latest by deriving page addresses until one is
+    /// missing.
+    pub fn append_match_history<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        result: GameResultRef,
+        page: Option<MatchHistoryRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::records::append_match_history(ctx, proof, trees, result, page, protocol)
+    }
+
+    /// Credits the signer loyalty points for a finished PvP game they
+    /// played, win or lose, creating their `LoyaltyPoints` if `loyalty` is
+    /// `None`. To stop farming, the game needs `LOYALTY_MIN_MOVES` shots and
+    /// an opponent outside the signer's last `LOYALTY_RECENT_OPPONENTS`, and
+    /// a `LoyaltyClaim` makes each game count once.
+    pub fn earn_loyalty_points<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        loyalty: Option<LoyaltyPointsRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::records::earn_loyalty_points(ctx, proof, trees, game, loyalty, protocol)
+    }
+
+    /// Records a finished game in `player`'s `Achievements`, creating it if
+    /// `achievements` is `None`, and mints a `Badge` for each badge the game
+    /// earns them: first win, `WIN_STREAK_BADGE_WINS` wins in a row, or a
+    /// perfect game. Games must be recorded in the order they ended.
+    /// Anyone can crank this, so opponents can record losses too.
+    pub fn record_achievements<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        player: Pubkey,
+        achievements: Option<AchievementsRef>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::records::record_achievements(
+            ctx,
+            proof,
+            trees,
+            game,
+            player,
+            achievements,
+            protocol,
+        )
+    }
+
+    /// Admin: publishes a `FleetPreset` that `create_preset_game` can
+    /// reference by id, so new setups ship without a program upgrade.
+    /// Gated on the program's upgrade authority.
+    pub fn create_fleet_preset<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        preset_id: u16,
+        rules: GameRules,
+    ) -> Result<()> {
+        instructions::presets::create_fleet_preset(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            preset_id,
+            rules,
+        )
+    }
+
+    /// Admin: replaces a preset's rules or retires it. Games already created
+    /// from the preset keep the rules they copied.
+    pub fn update_fleet_preset<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        current_preset: FleetPreset,
+        account_meta: CompressedAccountMeta,
+        rules: GameRules,
+        active: bool,
+    ) -> Result<()> {
+        instructions::presets::update_fleet_preset(
+            ctx,
+            proof,
+            current_preset,
+            account_meta,
+            rules,
+            active,
+        )
+    }
+
+    /// Creates a game using the rules of an active `FleetPreset`. The preset
+    /// is passed read-only so its contents are proven, then copied into the
+    /// game; Player B joins with `join_custom_game`.
+    pub fn create_preset_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        preset: FleetPreset,
+        preset_meta: CompressedAccountMetaReadOnly,
+        fleet: FleetBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::presets::create_preset_game(
+            ctx,
+            proof,
+            trees,
+            preset,
+            preset_meta,
+            fleet,
+            slot,
+        )
+    }
+
+    /// Creates the signer's `PlayerProfile` at `INITIAL_RATING`,
+    /// optionally naming who referred them
+    pub fn create_profile<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        referrer: Option<Pubkey>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::create_profile(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            referrer,
+            protocol,
+        )
+    }
+
+    /// Registers `name` as the signer's display name. Names are addressed
+    /// by their bytes, so each can be registered once; lowercase-only
+    /// keeps look-alikes from sharing a name.
+    pub fn register_name<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        name: String,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::register_name(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            name,
+            protocol,
+        )
+    }
+
+    /// Hands the signer's display name over to `new_owner`
+    pub fn transfer_name<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_name: PlayerName,
+        account_meta: CompressedAccountMeta,
+        new_owner: Pubkey,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::transfer_name(
+            ctx,
+            proof,
+            current_name,
+            account_meta,
+            new_owner,
+            protocol,
+        )
+    }
+
+    /// Founds the club `name` with the signer as its first member. Club
+    /// names follow the display-name rules and are unique the same way.
+    pub fn create_club<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        name: String,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::clubs::create_club(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            name,
+            protocol,
+        )
+    }
+
+    /// Makes the signer a member of `club`. A player belongs to one club
+    /// at a time, so it fails while they hold a membership.
+    pub fn join_club<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        club: ClubRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::clubs::join_club(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            club,
+            protocol,
+        )
+    }
+
+    /// Closes the signer's membership so they can join another club. The
+    /// club keeps the results they brought in.
+    pub fn leave_club<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        membership: ClubMember,
+        membership_meta: CompressedAccountMeta,
+        club: ClubRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::clubs::leave_club(ctx, proof, membership, membership_meta, club, protocol)
+    }
+
+    /// Adds a recorded game to the club of one of its players, as a win,
+    /// loss or draw for that member. Anyone can call this; a write-once
+    /// `ClubClaim` counts each member's game once, and games recorded
+    /// before the member joined don't count.
+    pub fn record_club_result<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        result: GameResultRef,
+        membership: ClubMemberRef,
+        club: ClubRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::clubs::record_club_result(
+            ctx, proof, trees, result, membership, club, protocol,
+        )
+    }
+
+    /// Pins a player's referrer from their profile into a wagered game
+    /// before it ends, so `claim_deposits` pays the referrer
+    /// `REFERRAL_SHARE_BPS` of the player's half of the fee. Anyone can
+    /// call this, the referrer included.
+    pub fn link_referrer<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        profile: PlayerProfile,
+        profile_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::link_referrer(
+            ctx,
+            proof,
+            current_game,
+            account_meta,
+            profile,
+            profile_meta,
+            protocol,
+        )
+    }
+
+    /// Withdraws the fee shares the signer has earned as a referrer. The
+    /// vault keeps its rent-exempt minimum so it stays open for new shares.
+    pub fn claim_referral_rewards(ctx: Context<DepositAccounts>) -> Result<()> {
+        instructions::profile::claim_referral_rewards(ctx)
+    }
+
+    /// Replaces the signer's public rating with a Pedersen commitment to
+    /// it. `rating_proof` is a rating circuit proof for the bracket
+    /// `[rating, rating]`, so the commitment opens to the current rating.
+    /// From then on the rating is only shown through range proofs.
+    pub fn seal_rating<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifierAccounts<'info>>,
+        proof: ValidityProof,
+        current_profile: PlayerProfile,
+        account_meta: CompressedAccountMeta,
+        rating_proof: CircuitProof,
+        commitment: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::profile::seal_rating(
+            ctx,
+            proof,
+            current_profile,
+            account_meta,
+            rating_proof,
+            commitment,
+            protocol,
+        )
+    }
+
+    /// Stores the signer's board (placements and salt) encrypted to their
+    /// own x25519 key next to the game, so a player who loses local state
+    /// can rebuild what their commitment opens to from chain data alone.
+    /// The program never sees the plaintext.
+    pub fn store_board_backup<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        backup: BackupCiphertext,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::store_board_backup(ctx, proof, trees, game, backup, protocol)
+    }
+
+    /// Opens the game's `GameChat`. Either player can call this once.
+    pub fn open_chat<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::open_chat(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            game,
+            game_meta,
+            protocol,
+        )
+    }
+
+    /// Posts a chat message of up to `MAX_CHAT_LEN` bytes. Each player gets
+    /// one message per move; the oldest message rotates out past
+    /// `MAX_CHAT_MESSAGES`.
+    pub fn send_chat<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        chat: GameChatRef,
+        text: String,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::send_chat(ctx, proof, game, chat, text, protocol)
+    }
+
+    /// Registers the signer's x25519 key on the game's `GameChat` so the
+    /// opponent can encrypt messages to it. Write-once, so earlier messages
+    /// stay decryptable.
+    pub fn register_chat_key<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_chat: GameChat,
+        chat_meta: CompressedAccountMeta,
+        chat_key: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::register_chat_key(
+            ctx,
+            proof,
+            current_chat,
+            chat_meta,
+            chat_key,
+            protocol,
+        )
+    }
+
+    /// Posts an end-to-end encrypted chat message: `ciphertext` is sealed
+    /// under the x25519 shared secret of both players' registered chat keys.
+    /// The program only checks the sender and the size, and shares the
+    /// one-message-per-move limit with `send_chat`.
+    pub fn send_sealed_chat<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        chat: GameChatRef,
+        nonce: [u8; 24],
+        ciphertext: Vec<u8>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::send_sealed_chat(ctx, proof, game, chat, nonce, ciphertext, protocol)
+    }
+
+    /// Emits a `Reaction` event for live emotes. Nothing is stored apart
+    /// from the sender's cooldown, which lives on the game's `GameChat`.
+    pub fn react<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_chat: GameChat,
+        chat_meta: CompressedAccountMeta,
+        emote_id: u8,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::react(ctx, proof, current_chat, chat_meta, emote_id, protocol)
+    }
+
+    /// Creates a fog-of-war `PlayerView` for each player. From then on every
+    /// `attack` and `respond_attack` must pass both views so they stay in
+    /// step with the game.
+    pub fn open_views<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        current_game: GameState,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::open_views(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            current_game,
+            account_meta,
+            protocol,
+        )
+    }
+
+    /// Permissionless refresh of both `PlayerView`s from the game, for state
+    /// changes that don't go through a move (timeouts, settlements).
+    pub fn sync_views<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        views: PlayerViews,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::sync_views(ctx, proof, game, game_meta, views, protocol)
+    }
+
+    /// Starts a `Campaign`: a chain of `CAMPAIGN_STAGES` games on growing
+    /// grids and fleets, scored as a whole
+    pub fn start_campaign<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        campaign_id: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::campaign::start_campaign(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            campaign_id,
+            protocol,
+        )
+    }
+
+    /// Creates the game for the campaign's current stage, with the owner as
+    /// Player A. Only one campaign game can be open at a time; Player B
+    /// joins with `join_custom_game`.
+    pub fn create_campaign_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        current_campaign: Campaign,
+        campaign_meta: CompressedAccountMeta,
+        fleet: FleetBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::campaign::create_campaign_game(
+            ctx,
+            proof,
+            trees,
+            current_campaign,
+            campaign_meta,
+            fleet,
+            slot,
+        )
+    }
+
+    /// Permissionless crank: scores the campaign's finished game and unlocks
+    /// the next stage
+    pub fn advance_campaign<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_campaign: Campaign,
+        campaign_meta: CompressedAccountMeta,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::campaign::advance_campaign(
+            ctx,
+            proof,
+            current_campaign,
+            campaign_meta,
+            game,
+            game_meta,
+            protocol,
+        )
+    }
+
+    /// Either player of a finished public PvP game asks for a rematch under
+    /// the same rules. The conditions come from the previous game's
+    /// `RAND_REMATCH` round, which both players commit to and reveal with
+    /// `commit_rand` / `reveal_rand`, so neither can predict them before
+    /// both have committed: which player takes seat A (and its fleet, salvo
+    /// size and any handicap bonus), who shoots first, and the obstacle map.
+    /// The round is commit-reveal, not MPC: the second revealer sees the
+    /// seed first and, since the finished game has nothing left to forfeit,
+    /// can withhold their value for free. The previous game has a single
+    /// `RAND_REMATCH` round, so withholding cancels the rematch; it cannot
+    /// re-roll the seed.
+    pub fn request_rematch<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        previous: GameState,
+        previous_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::rematch::request_rematch(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            previous,
+            previous_meta,
+            protocol,
+        )
+    }
+
+    /// The player the seed put in seat A opens the rematch, placing
+    /// `rules.fleet_a`. `draw.round` is the previous game's resolved
+    /// `RAND_REMATCH` round; the game uses its seed's obstacles and first turn,
+    /// is reserved for the other player (who joins with `join_custom_game`),
+    /// and consumes the `Rematch`.
+    pub fn create_rematch_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        draw: RematchDraw,
+        fleet: FleetBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::rematch::create_rematch_game(ctx, proof, trees, draw, fleet, slot)
+    }
+
+    /// Player A opens a sealed wager on a waiting game: only a commitment
+    /// to the amount goes on chain (see `wager_commitment`), backed by the
+    /// fixed `WAGER_COLLATERAL_LAMPORTS`. Amounts are revealed once the game
+    /// is over and only the net payout moves.
+    pub fn seal_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        commitment: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::sealed_wager::seal_wager(ctx, proof, trees, game, commitment, protocol)
+    }
+
+    /// Player B matches a sealed wager with their own commitment and
+    /// collateral. Only possible before the first shot.
+    pub fn match_sealed_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        wager: SealedWagerRef,
+        commitment: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::sealed_wager::match_sealed_wager(
+            ctx, proof, game, wager, commitment, protocol,
+        )
+    }
+
+    /// Opens the signer's wager commitment once the game is over
+    pub fn reveal_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        wager: SealedWagerRef,
+        amount: u64,
+        salt: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::sealed_wager::reveal_wager(ctx, proof, game, wager, amount, salt, protocol)
+    }
+
+    /// Settles a sealed wager and returns the collateral. The stake is the
+    /// smaller revealed amount and moves from loser to winner. A player who
+    /// hasn't revealed by the end of the reveal window (the game's pinned
+    /// turn timeout, or `WAGER_REVEAL_SLOTS` on the default) forfeits their
+    /// collateral to one who has.
+    pub fn settle_sealed_wager<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimDepositAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        wager: SealedWager,
+        wager_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::sealed_wager::settle_sealed_wager(
+            ctx, proof, game, game_meta, wager, wager_meta, protocol,
+        )
+    }
+
+    /// Counts the signer as a viewer of an unfinished game, creating its
+    /// `GameViewers` if `viewers` is `None`. The counter lives outside the
+    /// game so watching never races the players' moves. With `register`,
+    /// a write-once `Watcher` also records the signer, counting them once
+    /// among `watchers` and letting clients filter events by their games.
+    pub fn watch_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        viewers: Option<GameViewersRef>,
+        register: bool,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::social::watch_game(ctx, proof, trees, game, viewers, register, protocol)
+    }
+
+    /// Opens the spectator side-bet pool of an active game. Anyone can
+    /// open it, once.
+    pub fn open_side_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        game: GameState,
+        game_meta: CompressedAccountMetaReadOnly,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::side_bets::open_side_pool(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            game,
+            game_meta,
+            protocol,
+        )
+    }
+
+    /// A spectator stakes `SIDE_BET_STAKE_LAMPORTS` on an active game. Only
+    /// a commitment to the side goes on chain (see `side_bet_commitment`).
+    /// One bet per spectator per game; players can't bet on their own game.
+    pub fn place_side_bet<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        pool: SideBetPoolRef,
+        commitment: [u8; 32],
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::side_bets::place_side_bet(ctx, proof, trees, game, pool, commitment, protocol)
+    }
+
+    /// Opens a side bet's commitment once the game's result is final, within
+    /// `SIDE_BET_REVEAL_SLOTS`. Anyone holding the salt can reveal it.
+    pub fn reveal_side_bet<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        pool: SideBetPoolRef,
+        bet: SideBetRef,
+        reveal: SideBetReveal,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::side_bets::reveal_side_bet(ctx, proof, game, pool, bet, reveal, protocol)
+    }
+
+    /// Permissionless: pays a revealed side bet once every bet is revealed
+    /// or the reveal window closes, and closes the bet. Revealed winners
+    /// split the whole pool; on a draw, or a win no revealed bet backed, the
+    /// revealed bets split it instead. Unrevealed bets are forfeited unless
+    /// none was revealed, in which case each takes its stake back. The last
+    /// claim also takes whatever rounding left in the vault.
+    pub fn claim_side_bet<'info>(
+        ctx: Context<'_, '_, '_, 'info, SideBetPayoutAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        pool: SideBetPoolRef,
+        bet: SideBetRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::side_bets::claim_side_bet(ctx, proof, game, pool, bet, protocol)
+    }
+
+    /// Opens a public parimutuel market on an active game: spectators back
+    /// a player until the game reaches `close_move` moves, and the winning
+    /// side shares the whole pool. Anyone can open it, once.
+    pub fn open_market<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        close_move: u16,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::markets::open_market(ctx, proof, trees, game, close_move, protocol)
+    }
+
+    /// Backs `stake.side` (1 = A, 2 = B) with `stake.amount` lamports, held in the
+    /// market's vault. One bet per spectator per game.
+    pub fn place_bet<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        game: GameReadOnlyRef,
+        market: BetMarketRef,
+        stake: BetStake,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::markets::place_bet(ctx, proof, trees, game, market, stake, protocol)
+    }
+
+    /// Permissionless: pays a winning bet its share of the market pool once
+    /// the game is over, and closes the bet. Draws refund every bet, as
+    /// does a win nobody backed. The last bet paid also takes whatever
+    /// rounding left in the vault.
+    pub fn claim_winnings<'info>(
+        ctx: Context<'_, '_, '_, 'info, SideBetPayoutAccounts<'info>>,
+        proof: ValidityProof,
+        game: GameReadOnlyRef,
+        market: BetMarketRef,
+        bet: MarketBetRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::markets::claim_winnings(ctx, proof, game, market, bet, protocol)
+    }
+
+    /// Opens a prize pool that entrants buy into for `entry_fee` lamports.
+    /// `split_bps` is each place's share of the pot, first place first, and
+    /// must add up to 10,000.
+    pub fn create_prize_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        pool_id: u64,
+        entry_fee: u64,
+        split_bps: Vec<u16>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::prize_pools::create_prize_pool(
+            ctx, proof, trees, pool_id, entry_fee, split_bps, protocol,
+        )
+    }
+
+    /// Registers the signer in a prize pool, locking the entry fee in the
+    /// pool's vault
+    pub fn register_for_prize_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_pool: PrizePool,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::prize_pools::register_for_prize_pool(
+            ctx,
+            proof,
+            current_pool,
+            account_meta,
+            protocol,
+        )
+    }
+
+    /// Adds `amount` lamports to a prize pool's pot, on top of entry fees
+    pub fn sponsor_prize_pool<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_pool: PrizePool,
+        account_meta: CompressedAccountMeta,
+        amount: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::prize_pools::sponsor_prize_pool(
+            ctx,
+            proof,
+            current_pool,
+            account_meta,
+            amount,
+            protocol,
+        )
+    }
+
+    /// Organizer: pays the pot out to the final `standings`, best first,
+    /// by the pool's split. Each place's account is passed in order at the
+    /// start of the remaining accounts, ahead of the Light accounts. Shares
+    /// of places nobody finished in, and rounding, go to first place.
+    pub fn distribute_prizes<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_pool: PrizePool,
+        account_meta: CompressedAccountMeta,
+        standings: Vec<Pubkey>,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::prize_pools::distribute_prizes(
+            ctx,
+            proof,
+            current_pool,
+            account_meta,
+            standings,
+            protocol,
+        )
+    }
+
+    /// Opens a single-elimination tournament for up to `max_players`
+    /// players, with the signer as organizer
+    pub fn create_tournament<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        tournament_id: u64,
+        max_players: u8,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::tournaments::create_tournament(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            tournament_id,
+            max_players,
+            protocol,
+        )
+    }
+
+    /// Registers the signer for a tournament that has not started
+    pub fn register_for_tournament<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_tournament: Tournament,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::tournaments::register_for_tournament(
+            ctx,
+            proof,
+            current_tournament,
+            account_meta,
+            protocol,
+        )
+    }
+
+    /// Organizer: closes registration and draws the first round, pairing
+    /// players in registration order. An odd player out gets a bye.
+    pub fn start_tournament<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        current_tournament: Tournament,
+        account_meta: CompressedAccountMeta,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::tournaments::start_tournament(
+            ctx,
+            proof,
+            current_tournament,
+            account_meta,
+            protocol,
+        )
+    }
+
+    /// Creates the classic game for one of the current round's matches.
+    /// The match's first player hosts, the game only admits their
+    /// opponent, and the match points at it until a result is reported.
+    pub fn create_tournament_game<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        trees: NewAccountTrees,
+        tournament: TournamentRef,
+        match_index: u8,
+        board: ShipBoard,
+        slot: ActiveGameSlot,
+    ) -> Result<()> {
+        instructions::tournaments::create_tournament_game(
+            ctx,
+            proof,
+            trees,
+            tournament,
+            match_index,
+            board,
+            slot,
+        )
+    }
+
+    /// Reports a match's `GameResult`, advancing the winner. A draw frees
+    /// the match for a replay game. Once every match of the round has a
+    /// winner the next round is drawn, or the last winner is champion.
+    /// Anyone can crank this.
+    pub fn report_tournament_result<'info>(
+        ctx: Context<'_, '_, '_, 'info, GameAccounts<'info>>,
+        proof: ValidityProof,
+        tournament: TournamentRef,
+        match_index: u8,
+        result: GameResultRef,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::tournaments::report_tournament_result(
+            ctx,
+            proof,
+            tournament,
+            match_index,
+            result,
+            protocol,
+        )
+    }
+
+    /// Admin: ends the current season once its `end_slot` has passed,
+    /// archiving its leaderboard as final, and starts the next season of
+    /// `season_slots` with an empty leaderboard. Pass `None` to start
+    /// season 1.
+    pub fn rollover_season<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        current: Option<SeasonRollover>,
+        season_slots: u64,
+    ) -> Result<()> {
+        instructions::seasons::rollover_season(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            current,
+            season_slots,
+        )
+    }
+
+    /// Admin: snapshots a rolled-over season's final standings into a
+    /// write-once `SeasonArchive`, then closes its leaderboard. Nothing
+    /// mutates or closes archives, so past seasons stay queryable, and
+    /// `distribute_season_rewards` pays from either.
+    pub fn reset_season<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        current: SeasonRollover,
+    ) -> Result<()> {
+        instructions::seasons::reset_season(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            current,
+        )
+    }
+
+    /// Admin: opens the rewards pool for `season`, paid out to the
+    /// season's top finishers by `split_bps` (as for prize pools)
+    pub fn create_season_rewards<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        season: u32,
+        split_bps: Vec<u16>,
+    ) -> Result<()> {
+        instructions::seasons::create_season_rewards(
+            ctx,
+            proof,
+            address_tree_info,
+            output_state_tree_index,
+            season,
+            split_bps,
+        )
+    }
+
+    /// Adds `amount` lamports to a season's rewards pool. Anyone can fund it.
+    pub fn fund_season_rewards<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositAccounts<'info>>,
+        proof: ValidityProof,
+        current_rewards: SeasonRewards,
+        account_meta: CompressedAccountMeta,
+        amount: u64,
+        protocol: ProtocolConfigRef,
+    ) -> Result<()> {
+        instructions::seasons::fund_season_rewards(
+            ctx,
+            proof,
+            current_rewards,
+            account_meta,
+            amount,
+            protocol,
+        )
+    }
+
+    /// Admin: pays a season's rewards pool out to the top of the season's
+    /// final `standings`, best first, by the pool's split. As with
+    /// `distribute_prizes`, each place's account leads the remaining
+    /// accounts.
+    pub fn distribute_season_rewards<'info>(
+        ctx: Context<'_, '_, '_, 'info, AdminPayoutAccounts<'info>>,
+        proof: ValidityProof,
+        current_rewards: SeasonRewards,
+        account_meta: CompressedAccountMeta,
+        final_standings: SeasonStandings,
+    ) -> Result<()> {
+        instructions::seasons::distribute_season_rewards(
+            ctx,
+            proof,
+            current_rewards,
+            account_meta,
+            final_standings,
+        )
+    }
 }
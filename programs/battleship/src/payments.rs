@@ -0,0 +1,125 @@
+//! Lamport escrow, fees and prize payouts
+
+use crate::*;
+
+/// Moves the signer's `GAME_DEPOSIT_LAMPORTS` into the game's vault
+pub(crate) fn lock_deposit(accounts: &DepositAccounts, game_address: &[u8; 32]) -> Result<()> {
+    lock_lamports(
+        accounts,
+        derive_vault_address(game_address).0,
+        GAME_DEPOSIT_LAMPORTS,
+    )
+}
+
+/// Moves `amount` from the signer into `vault`, which must be the expected PDA
+pub(crate) fn lock_lamports(accounts: &DepositAccounts, vault: Pubkey, amount: u64) -> Result<()> {
+    if accounts.vault.key() != vault {
+        return Err(BattleshipError::WrongVault.into());
+    }
+    system_program::transfer(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.signer.to_account_info(),
+                to: accounts.vault.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+/// A prize split pays 1 to `MAX_PRIZE_PLACES` places and adds up to the
+/// whole pot
+pub(crate) fn validate_prize_split(split_bps: &[u16]) -> Result<()> {
+    if split_bps.is_empty()
+        || split_bps.len() > MAX_PRIZE_PLACES
+        || split_bps.iter().map(|&bps| bps as u32).sum::<u32>() != 10_000
+    {
+        msg!(
+            "Prize split must pay 1 to {} places and total 10,000 bps",
+            MAX_PRIZE_PLACES
+        );
+        return Err(BattleshipError::InvalidPrizeSplit.into());
+    }
+    Ok(())
+}
+
+/// Pays a vault PDA signed with `seeds` out to `standings` by `split_bps`.
+/// `winners` are the standings' accounts in the same order. Shares of
+/// unfilled places, and rounding, go to first place. Returns the pot.
+pub(crate) fn pay_standings<'info>(
+    vault: &SystemAccount<'info>,
+    system_program: &Program<'info, System>,
+    seeds: &[&[u8]],
+    winners: &[AccountInfo<'info>],
+    standings: &[Pubkey],
+    split_bps: &[u16],
+) -> Result<u64> {
+    let pot = vault.lamports();
+    let mut prizes: Vec<u64> = split_bps[..standings.len()]
+        .iter()
+        .map(|&bps| (pot as u128 * bps as u128 / 10_000) as u64)
+        .collect();
+    prizes[0] += pot - prizes.iter().sum::<u64>();
+
+    for ((winner, player), prize) in winners.iter().zip(standings).zip(prizes) {
+        if winner.key() != *player {
+            return Err(BattleshipError::WrongPlayerAccount.into());
+        }
+        if prize == 0 {
+            continue;
+        }
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: vault.to_account_info(),
+                    to: winner.clone(),
+                },
+                &[seeds],
+            ),
+            prize,
+        )?;
+    }
+    Ok(pot)
+}
+
+/// The configured rake for wager `mode`
+pub(crate) fn mode_rake(config: &ProtocolConfig, mode: u8) -> Result<ModeRake> {
+    config
+        .rakes
+        .get(mode as usize)
+        .cloned()
+        .ok_or(BattleshipError::WrongGameMode.into())
+}
+
+/// The protocol's cut of a won `pot` at `fee_bps`
+pub fn protocol_fee(pot: u64, fee_bps: u16) -> u64 {
+    (pot as u128 * fee_bps as u128 / 10_000) as u64
+}
+
+/// Pays both players out of a vault PDA signed with `seeds`
+pub(crate) fn pay_out(
+    accounts: &ClaimDepositAccounts,
+    seeds: &[&[u8]],
+    to_a: u64,
+    to_b: u64,
+) -> Result<()> {
+    for (player, amount) in [(&accounts.player_a, to_a), (&accounts.player_b, to_b)] {
+        if amount == 0 {
+            continue;
+        }
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: accounts.vault.to_account_info(),
+                    to: player.to_account_info(),
+                },
+                &[seeds],
+            ),
+            amount,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,263 @@
+//! Proof verification, attestations, board commitments and Merkle proofs
+
+use crate::*;
+
+/// Runs `proof` through the circuit's verifier program. Verifiers take the
+/// proof bytes followed by each public input as a 32-byte big-endian field
+/// element, and fail the transaction unless the proof holds.
+pub(crate) fn verify_proof(
+    accounts: &VerifierAccounts,
+    verifier: &CircuitVerifier,
+    proof: &[u8],
+    public_inputs: &[[u8; 32]],
+) -> Result<()> {
+    let verifier_program = &accounts.verifier_program;
+    if verifier_program.key() != verifier.verifier_program {
+        msg!(
+            "Circuit {} is verified by another program",
+            verifier.circuit_id
+        );
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+
+    let mut metas = vec![];
+    let mut infos = vec![verifier_program.to_account_info()];
+    if verifier.backend == PROOF_BACKEND_ULTRA_HONK {
+        let verifying_key = accounts
+            .verifying_key
+            .as_ref()
+            .filter(|vk| vk.key() == verifier.verifying_key)
+            .ok_or(BattleshipError::VerifierMismatch)?;
+        metas.push(AccountMeta::new_readonly(verifying_key.key(), false));
+        infos.push(verifying_key.to_account_info());
+    }
+
+    let mut data = proof.to_vec();
+    for input in public_inputs {
+        data.extend_from_slice(input);
+    }
+    invoke(
+        &Instruction {
+            program_id: verifier.verifier_program,
+            accounts: metas,
+            data,
+        },
+        &infos,
+    )?;
+    Ok(())
+}
+
+/// Checks a proof of `public_inputs` for `verifier`, the `CircuitVerifier`
+/// at `verifier_address`. Inline proofs run through the verifier now; an
+/// attestation must be the signer's, unexpired, and made by the same
+/// verifier for the same inputs. Callers close it with `with_attestation`.
+pub(crate) fn check_proof(
+    accounts: &VerifierAccounts,
+    verifier: &CircuitVerifier,
+    verifier_address: &[u8; 32],
+    source: &ProofSource,
+    public_inputs: &[[u8; 32]],
+) -> Result<()> {
+    match source {
+        ProofSource::Inline(proof) => verify_proof(accounts, verifier, proof, public_inputs),
+        ProofSource::Attested(attested) => {
+            let attestation = &attested.attestation;
+            if attestation.owner != accounts.signer.key()
+                || attestation.verifier != *verifier_address
+                || attestation.inputs_hash != public_inputs_hash(public_inputs)
+            {
+                return Err(BattleshipError::AttestationMismatch.into());
+            }
+            if Clock::get()?.slot > attestation.expires_slot {
+                msg!("Attestation expired at slot {}", attestation.expires_slot);
+                return Err(BattleshipError::AttestationExpired.into());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Closes the attestation a proof was checked against, so it is used once
+pub(crate) fn with_attestation(
+    cpi: LightSystemProgramCpi,
+    source: ProofSource,
+) -> Result<LightSystemProgramCpi> {
+    let ProofSource::Attested(attested) = source else {
+        return Ok(cpi);
+    };
+    let attestation = LightAccount::<ProofAttestation>::new_close(
+        &crate::ID,
+        &attested.meta,
+        attested.attestation,
+    )?;
+    Ok(cpi.with_light_account(attestation)?)
+}
+
+/// Hash binding an attestation to the public inputs it was verified with
+pub fn public_inputs_hash(public_inputs: &[[u8; 32]]) -> [u8; 32] {
+    let inputs: Vec<&[u8]> = public_inputs.iter().map(|input| input.as_slice()).collect();
+    hashv(&inputs).to_bytes()
+}
+
+/// An integer as a 32-byte big-endian public input
+pub(crate) fn field_from_u64(value: u64) -> [u8; 32] {
+    let mut field = [0u8; 32];
+    field[24..].copy_from_slice(&value.to_be_bytes());
+    field
+}
+
+/// Checks `rating_proof` shows the rating behind `commitment` lies in
+/// `min..=max`. Only active verifier versions are accepted, since no game
+/// pins them.
+pub(crate) fn verify_rating_range(
+    accounts: &VerifierAccounts,
+    verifier: &CircuitVerifier,
+    verifier_address: &[u8; 32],
+    commitment: [u8; 32],
+    min: u16,
+    max: u16,
+    rating_proof: &ProofSource,
+) -> Result<()> {
+    if verifier.circuit_id != CIRCUIT_RATING {
+        return Err(BattleshipError::VerifierMismatch.into());
+    }
+    if verifier.retired {
+        return Err(BattleshipError::VerifierRetired.into());
+    }
+    check_proof(
+        accounts,
+        verifier,
+        verifier_address,
+        rating_proof,
+        &[
+            commitment,
+            field_from_u64(min.into()),
+            field_from_u64(max.into()),
+        ],
+    )
+}
+
+/// Hash commitment to a sealed wager, opened with `reveal_wager`
+pub fn wager_commitment(
+    game_address: &[u8; 32],
+    player: &Pubkey,
+    amount: u64,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    hashv(&[
+        b"wager",
+        game_address,
+        player.as_ref(),
+        &amount.to_le_bytes(),
+        salt,
+    ])
+    .to_bytes()
+}
+
+/// Hash commitment to a side bet's side (1 = A, 2 = B), opened with
+/// `reveal_side_bet`
+pub fn side_bet_commitment(
+    game_address: &[u8; 32],
+    bettor: &Pubkey,
+    side: u8,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    hashv(&[b"side_bet", game_address, bettor.as_ref(), &[side], salt]).to_bytes()
+}
+
+/// Hash commitment to a randomness round value, opened with `reveal_rand`
+pub fn rand_commitment(
+    round_address: &[u8; 32],
+    player: &Pubkey,
+    value: &[u8; 32],
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    hashv(&[b"rand", round_address, player.as_ref(), value, salt]).to_bytes()
+}
+
+/// Hash commitment to a batch game plan, opened with `reveal_batch_plan`.
+/// Ships are encoded as `[x, y, is_horizontal]` each.
+pub fn batch_commitment(
+    game_address: &[u8; 32],
+    player: &Pubkey,
+    plan: &BatchPlan,
+    salt: &[u8; 32],
+) -> [u8; 32] {
+    let ships: Vec<u8> = plan
+        .ships
+        .iter()
+        .flat_map(|ship| [ship.x, ship.y, ship.is_horizontal as u8])
+        .collect();
+    hashv(&[
+        b"batch",
+        game_address,
+        player.as_ref(),
+        &ships,
+        &plan.shots,
+        salt,
+    ])
+    .to_bytes()
+}
+
+/// Message both players sign to settle an off-chain game:
+/// sha256("settle" || game_address || final_status || moves), with each
+/// move encoded as `[player, x, y, result]`.
+pub fn settlement_message(
+    game_address: &[u8; 32],
+    final_status: u8,
+    moves: &[MoveRecord],
+) -> [u8; 32] {
+    let encoded: Vec<u8> = moves
+        .iter()
+        .flat_map(|m| [m.player, m.x, m.y, m.result])
+        .collect();
+    hashv(&[b"settle", game_address, &[final_status], &encoded]).to_bytes()
+}
+
+/// Leaf of the board Merkle tree: sha256("cell" || index || value || salt).
+/// Each cell gets its own salt so unrevealed cells can't be brute-forced.
+pub fn board_leaf(index: u8, value: u8, salt: &[u8; 32]) -> [u8; 32] {
+    hashv(&[b"cell", &[index], &[value], salt]).to_bytes()
+}
+
+/// Computes the board root over all cells. Leaves past `GRID_CELLS` are zero.
+pub fn board_merkle_root(cells: &[u8; GRID_CELLS], salts: &[[u8; 32]; GRID_CELLS]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = (0..1usize << BOARD_TREE_DEPTH)
+        .map(|i| {
+            if i < GRID_CELLS {
+                board_leaf(i as u8, cells[i], &salts[i])
+            } else {
+                [0u8; 32]
+            }
+        })
+        .collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| hashv(&[&pair[0], &pair[1]]).to_bytes())
+            .collect();
+    }
+    level[0]
+}
+
+/// Checks a cell reveal against a board root. `path` lists siblings from
+/// the leaf up to the root.
+pub fn verify_cell_proof(
+    root: &[u8; 32],
+    index: u8,
+    value: u8,
+    salt: &[u8; 32],
+    path: &[[u8; 32]; BOARD_TREE_DEPTH],
+) -> bool {
+    let mut node = board_leaf(index, value, salt);
+    let mut position = index as usize;
+    for sibling in path {
+        node = if position & 1 == 0 {
+            hashv(&[&node, sibling]).to_bytes()
+        } else {
+            hashv(&[sibling, &node]).to_bytes()
+        };
+        position /= 2;
+    }
+    node == *root
+}
@@ -0,0 +1,202 @@
+//! Protocol config, bans, global stats, active-game slots and lobby checks
+
+use crate::*;
+
+/// Charges one active game to the signer's profile, failing once it holds
+/// the limit, and spends its next game id when `creates_game`. The config
+/// is added read-only to prove its limit and that the protocol is unpaused.
+pub(crate) fn with_active_game_slot(
+    cpi: LightSystemProgramCpi,
+    slot: ActiveGameSlot,
+    signer: &Pubkey,
+    creates_game: bool,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    if slot.profile.owner != *signer {
+        return Err(BattleshipError::WrongProfile.into());
+    }
+    require_unpaused(&slot.protocol.config)?;
+    let limit = active_game_limit(&slot.protocol.config);
+    if slot.profile.active_games >= limit {
+        msg!("Profile already holds {} active games", limit);
+        return Err(BattleshipError::ActiveGameLimit.into());
+    }
+
+    let mut profile_account =
+        LightAccount::<PlayerProfile>::new_mut(&crate::ID, &slot.profile_meta, slot.profile)?;
+    profile_account.active_games += 1;
+    if creates_game {
+        profile_account.next_game_id += 1;
+    }
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    with_protocol_config(
+        cpi.with_light_account(profile_account)?,
+        slot.protocol,
+        &tree_pubkeys,
+    )
+}
+
+/// Starts the CPI of an instruction that changes state, failing while the
+/// protocol is paused. The config goes in read-only, so leaving it out
+/// can't skip the check.
+pub(crate) fn new_unpaused_cpi(
+    proof: ValidityProof,
+    protocol: ProtocolConfigRef,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    require_unpaused(&protocol.config)?;
+    new_withdrawal_cpi(proof, protocol, light_cpi_accounts)
+}
+
+/// Like `new_unpaused_cpi`, for instructions that only pay out or refund
+/// escrowed funds: those keep running while the protocol is paused, so a
+/// pause can't trap anyone's lamports or tokens.
+pub(crate) fn new_withdrawal_cpi(
+    proof: ValidityProof,
+    protocol: ProtocolConfigRef,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    let tree_pubkeys = light_cpi_accounts
+        .tree_pubkeys()
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    with_protocol_config(
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof),
+        protocol,
+        &tree_pubkeys,
+    )
+}
+
+/// Applies `update` to the `GlobalStats`, if passed. It's optional so
+/// busy periods don't serialize every game on the one account.
+pub(crate) fn with_global_stats(
+    cpi: LightSystemProgramCpi,
+    stats: Option<GlobalStatsRef>,
+    update: impl FnOnce(&mut GlobalStats),
+) -> Result<LightSystemProgramCpi> {
+    let Some(stats) = stats else {
+        return Ok(cpi);
+    };
+    let mut stats_account =
+        LightAccount::<GlobalStats>::new_mut(&crate::ID, &stats.meta, stats.stats)?;
+    update(&mut stats_account);
+    Ok(cpi.with_light_account(stats_account)?)
+}
+
+/// Proves `player` isn't banned: their `Ban` address goes in as a
+/// read-only address, which the system program checks does not exist
+pub(crate) fn with_no_ban(
+    cpi: LightSystemProgramCpi,
+    player: &Pubkey,
+    address_tree_info: PackedAddressTreeInfo,
+    light_cpi_accounts: &CpiAccounts<'_, '_>,
+) -> Result<LightSystemProgramCpi> {
+    let address_tree_pubkey = address_tree_info
+        .get_tree_pubkey(light_cpi_accounts)
+        .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+    if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+        msg!("Invalid address tree");
+        return Err(ProgramError::InvalidAccountData.into());
+    }
+    let (address, _) = derive_ban_address(player, &address_tree_pubkey);
+    Ok(cpi.with_read_only_addresses(&[PackedReadOnlyAddress {
+        address,
+        address_merkle_tree_root_index: address_tree_info.root_index,
+        address_merkle_tree_account_index: address_tree_info.address_merkle_tree_pubkey_index,
+    }]))
+}
+
+/// Fails while the config's admin has the protocol paused
+pub(crate) fn require_unpaused(config: &ProtocolConfig) -> Result<()> {
+    if config.paused {
+        msg!("Protocol is paused");
+        return Err(BattleshipError::ProtocolPaused.into());
+    }
+    Ok(())
+}
+
+/// Display and club names: `MIN_NAME_LEN`-`MAX_NAME_LEN` bytes of a-z,
+/// 0-9 and _, so look-alikes can't share a name
+pub(crate) fn validate_name(name: &str) -> Result<()> {
+    if name.len() < MIN_NAME_LEN
+        || name.len() > MAX_NAME_LEN
+        || !name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+    {
+        msg!(
+            "Names are {}-{} bytes of a-z, 0-9 and _",
+            MIN_NAME_LEN,
+            MAX_NAME_LEN
+        );
+        return Err(BattleshipError::InvalidName.into());
+    }
+    Ok(())
+}
+
+/// A pair of players in rivalry order, lower pubkey first
+pub(crate) fn rivalry_pair(a: &Pubkey, b: &Pubkey) -> (Pubkey, Pubkey) {
+    if a < b {
+        (*a, *b)
+    } else {
+        (*b, *a)
+    }
+}
+
+/// Checks client-supplied lobby tags against the allow-lists and the
+/// grid the game is actually played on
+pub(crate) fn validate_lobby_tags(tags: &LobbyTags, grid_size: u8) -> Result<()> {
+    if tags.region >= REGION_COUNT || tags.speed >= SPEED_COUNT || tags.grid_size != grid_size {
+        msg!(
+            "Tags need a region below {}, a speed below {} and grid size {}",
+            REGION_COUNT,
+            SPEED_COUNT,
+            grid_size
+        );
+        return Err(BattleshipError::InvalidLobbyTags.into());
+    }
+    Ok(())
+}
+
+/// Whether an entry's tags satisfy a filter; REGION_ANY on either side
+/// matches every region
+pub(crate) fn lobby_tags_match(entry: &LobbyTags, filter: &LobbyTags) -> bool {
+    (entry.region == filter.region || entry.region == REGION_ANY || filter.region == REGION_ANY)
+        && entry.speed == filter.speed
+        && entry.grid_size == filter.grid_size
+}
+
+/// The config a ranked game is created under, which must set a stake
+pub(crate) fn ranked_config(protocol: &ProtocolConfigRef) -> Result<ProtocolConfig> {
+    if protocol.config.ranked_stake == 0 {
+        msg!("Ranked play needs a ranked stake");
+        return Err(BattleshipError::InvalidWager.into());
+    }
+    Ok(protocol.config.clone())
+}
+
+/// Adds the config read-only, proving the values taken from it
+pub(crate) fn with_protocol_config(
+    cpi: LightSystemProgramCpi,
+    protocol: ProtocolConfigRef,
+    tree_pubkeys: &[Pubkey],
+) -> Result<LightSystemProgramCpi> {
+    let config_account = LightAccount::<ProtocolConfig>::new_read_only(
+        &crate::ID,
+        &protocol.meta,
+        protocol.config,
+        tree_pubkeys,
+    )?;
+    Ok(cpi.with_light_account(config_account)?)
+}
+
+/// Active games a profile may hold under `config`, whose 0 means
+/// `DEFAULT_MAX_ACTIVE_GAMES`
+pub(crate) fn active_game_limit(config: &ProtocolConfig) -> u16 {
+    if config.max_active_games == 0 {
+        DEFAULT_MAX_ACTIVE_GAMES
+    } else {
+        config.max_active_games
+    }
+}
@@ -0,0 +1,1271 @@
+//! Compressed account types and the instruction data built from them
+
+use crate::*;
+
+/// The game state stored as a compressed account
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct GameState {
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+    pub reserved_for: Pubkey,     // Only this key may join, default = anyone
+    pub current_turn: u8,         // 1 = A, 2 = B
+    pub game_status: u8, // 0 = Waiting, 1 = Active, 2 = A Won, 3 = B Won, 4 = Draw, 5 = Cancelled
+    pub game_mode: u8,   // 0 = PvP, 1 = PvE (bot plays B)
+    pub move_count: u16, // Shots fired by both players
+    pub board_mode: u8,  // 0 = Public, 1 = Merkle, 3 = Noir, 4 = Batch
+    pub pending_shot: Option<u8>, // Hidden boards: cell index awaiting reveal
+    pub last_action_slot: u64, // Slot of the last create/join/move, for timeouts
+    pub end_reason: u8, // 0 = In progress, 1 = Sunk, 2 = Move limit, 3 = Timeout, 4 = Survived, 5 = Void, 6 = Resigned, 7 = Cancelled
+    pub challenge_deadline: u64, // Off-chain settlement challengeable until this slot, 0 = none
+    pub win_condition: u8, // 0 = Sink the fleet, 1 = Blockade
+    pub blockade_attacker: u8, // Blockade: the only side that fires (1 = A, 2 = B)
+    pub rules: GameRules,
+    pub shots_this_turn: u8, // Salvo shots already fired by `current_turn`
+    pub views_open: bool,    // PlayerView accounts exist and must be passed with every move
+    pub pending_draw: Option<u8>, // RandRound the first shot waits on
+    pub first_turn: u8,      // Coin flip result (1 = A, 2 = B); 0 = A started by default
+    pub boards_verified: u8, // Bit 0 = A, bit 1 = B: board hash proven with the board circuit
+    pub proof_backend: u8,   // Proof system every proof in this game uses
+    pub verifiers: [[u8; 32]; CIRCUIT_COUNT], // Pinned CircuitVerifier per circuit, 0 = not yet used
+    pub wager: u64, // Lamports each player stakes in the vault on top of the deposit, 0 = none
+    pub raise_offered_by: u8, // Seat whose doubling offer awaits an answer, 0 = none
+    pub raise_holder: u8, // Seat that accepted the last raise and alone may raise next, 0 = either
+    pub prize: u64, // Sponsored lamports in the vault for the winner, split on a draw
+    pub sponsor: Pubkey, // Who put up `prize`, refunded if the game is cancelled
+    pub token_mint: Pubkey, // Mint of the compressed-token wager
+    pub token_wager: u64, // Compressed tokens each player escrows, 0 = none
+    pub tokens_paid: bool, // The token escrow has been paid out
+    pub wager_mode: u8, // Wagers: WAGER_MODE_* the game was created in
+    pub ranked_stake: u64, // Ranked: lamports each player locks, refunded unless they time out
+    pub fee_bps: u16, // Wagers: protocol fee pinned at creation
+    pub treasury: Pubkey, // Wagers: where the fee goes
+    pub referrers: [Pubkey; 2], // Wagers: A's and B's linked referrers, default = none
+    pub reward_claimed: bool, // Unwagered wins: the reward token has been minted
+    pub jackpot_claimed: bool, // Perfect wagered wins: the jackpot has been paid
+    pub slots_held: u8, // Seats (1 = A, 2 = B) whose profile still counts this game as active
+    pub turn_timeout_slots: u64, // Pinned from the protocol config, 0 = TURN_TIMEOUT_SLOTS
+    pub open_game_expiry_slots: u64, // Pinned from the protocol config, 0 = OPEN_GAME_EXPIRY_SLOTS
+    pub in_stats: bool, // Created with GlobalStats, so its end leaves the active count
+    pub tags: LobbyTags, // Set by create_game and join_queue, default elsewhere
+
+    // Most recent shot, so clients can render it without the move log
+    pub last_attack_x: u8,
+    pub last_attack_y: u8,
+    pub last_attack_result: u8, // CELL_HIT, CELL_MISS or CELL_BLOCKED
+    pub last_attack_by: u8,     // 0 = No shots yet, 1 = A, 2 = B
+
+    // Player A
+    pub grid_a: [u8; GRID_CELLS],
+    pub board_hash_a: [u8; 32], // Noir Pedersen Hash (bytes), or Merkle root for hidden boards
+    pub hits_a: u8,
+    pub stats_a: ShotStats, // Shots fired by A
+    pub targeted_a: u32,    // Hidden boards: bit i is set once A fires at cell i
+
+    // Player B
+    pub grid_b: [u8; GRID_CELLS],
+    pub board_hash_b: [u8; 32], // Noir Pedersen Hash (bytes), or Merkle root for hidden boards
+    pub hits_b: u8,
+    pub stats_b: ShotStats, // Shots fired by B
+    pub targeted_b: u32,    // Hidden boards: bit i is set once B fires at cell i
+}
+
+/// A `GameState` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameRef {
+    pub game: GameState,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A `GameState` passed read-only
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameReadOnlyRef {
+    pub game: GameState,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// The trees a new compressed account goes in: the address tree its address
+/// is derived in and the state tree it is written to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct NewAccountTrees {
+    pub address_tree_info: PackedAddressTreeInfo,
+    pub output_state_tree_index: u8,
+}
+
+/// Client-supplied lobby labels so players can filter open games and
+/// queue entries
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct LobbyTags {
+    pub region: u8,    // REGION_*
+    pub speed: u8,     // SPEED_*
+    pub grid_size: u8, // Must be the grid the game is played on
+}
+
+/// How `create_game` lists a game: its wager mode, lobby tags and whether
+/// it is reserved for a friend
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LobbyOptions {
+    pub mode: u8,
+    pub tags: LobbyTags,
+    pub challenge: Option<FriendChallenge>,
+}
+
+/// How Player B joins with `join_game`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct JoinOptions {
+    pub coin_flip: bool,
+    pub filter: Option<LobbyTags>,
+    pub ban_check: Option<PackedAddressTreeInfo>,
+}
+
+/// Fleets and salvo sizes for a game. The default is the classic setup:
+/// one `SHIP_LENGTH` ship and one shot per turn each.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct GameRules {
+    pub grid_size: u8,    // Playable width and height, up to GRID_SIZE
+    pub fleet_a: Vec<u8>, // Ship lengths A places
+    pub fleet_b: Vec<u8>, // Ship lengths B places
+    pub shots_per_turn_a: u8,
+    pub shots_per_turn_b: u8,
+    pub handicap: bool, // Underdog gets bonus shots from the rating gap at join
+    pub obstacles: u8,  // Blocked cells scattered from the game address, up to MAX_OBSTACLES
+    pub skip_on_timeout: bool, // Idle turns are skipped with force_skip_turn instead of forfeited
+    pub sealed_obstacles: bool, // Obstacles are drawn in a RAND_OBSTACLES round once both fleets are placed
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            grid_size: GRID_SIZE as u8,
+            fleet_a: vec![SHIP_LENGTH as u8],
+            fleet_b: vec![SHIP_LENGTH as u8],
+            shots_per_turn_a: 1,
+            shots_per_turn_b: 1,
+            handicap: false,
+            obstacles: 0,
+            skip_on_timeout: false,
+            sealed_obstacles: false,
+        }
+    }
+}
+
+/// One ship's position in a custom fleet
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct ShipPlacement {
+    pub x: u8,
+    pub y: u8,
+    pub is_horizontal: bool,
+}
+
+/// A classic one-ship board: the ship's position and the board hash the
+/// player commits to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ShipBoard {
+    pub ship: ShipPlacement,
+    pub board_hash: [u8; 32],
+}
+
+/// A custom fleet board: one placement per ship, in fleet order, and the
+/// board hash the player commits to
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FleetBoard {
+    pub ships: Vec<ShipPlacement>,
+    pub board_hash: [u8; 32],
+}
+
+/// A hidden-board cell opened against the board's Merkle root
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CellOpening {
+    pub cell_value: u8,
+    pub salt: [u8; 32],
+    pub merkle_path: [[u8; 32]; BOARD_TREE_DEPTH],
+}
+
+/// Per-player accuracy counters
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ShotStats {
+    pub shots: u8,
+    pub hits: u8,
+    pub misses: u8,
+}
+
+/// A player's board encrypted to their own x25519 key: the shared secret
+/// of `ephemeral_key` and the owner's key seals `ciphertext` under `nonce`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct BoardBackup {
+    pub game_address: [u8; 32],
+    pub owner: Pubkey,
+    pub ephemeral_key: [u8; 32],
+    pub nonce: [u8; 24],
+    pub ciphertext: Vec<u8>, // Up to MAX_BACKUP_LEN bytes
+}
+
+/// A board backup encrypted to its owner: the ephemeral x25519 key, the
+/// nonce and the ciphertext
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BackupCiphertext {
+    pub ephemeral_key: [u8; 32],
+    pub nonce: [u8; 24],
+    pub ciphertext: Vec<u8>,
+}
+
+/// Short per-game chat log, capped at `MAX_CHAT_MESSAGES`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct GameChat {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+    pub messages: Vec<ChatMessage>,
+    pub last_move_a: Option<u16>, // move_count A last posted at
+    pub last_move_b: Option<u16>, // move_count B last posted at
+    pub last_reaction_slot_a: u64,
+    pub last_reaction_slot_b: u64,
+    pub chat_key_a: Option<[u8; 32]>, // A's x25519 key for sealed messages
+    pub chat_key_b: Option<[u8; 32]>,
+    pub sealed_messages: Vec<SealedChatMessage>, // Also capped at MAX_CHAT_MESSAGES
+}
+
+/// A `GameChat` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameChatRef {
+    pub chat: GameChat,
+    pub meta: CompressedAccountMeta,
+}
+
+/// One player's fog-of-war view of a game, so clients can subscribe to
+/// exactly what that player is allowed to see
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct PlayerView {
+    pub game_address: [u8; 32],
+    pub owner: Pubkey,
+    pub seat: u8, // 1 = A, 2 = B
+    pub own_grid: [u8; GRID_CELLS],
+    pub target_grid: [u8; GRID_CELLS], // Opponent board, unhit ships shown as empty
+    pub current_turn: u8,
+    pub game_status: u8,
+    pub move_count: u16,
+}
+
+/// Both players' views, passed with moves once `open_views` has run
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PlayerViews {
+    pub view_a: PlayerView,
+    pub meta_a: CompressedAccountMeta,
+    pub view_b: PlayerView,
+    pub meta_b: CompressedAccountMeta,
+}
+
+/// The accounts a move updates: the game, its move log and, once
+/// `open_views` has run, both players' views
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameTurn {
+    pub game: GameState,
+    pub game_meta: CompressedAccountMeta,
+    pub log: MoveLog,
+    pub log_meta: CompressedAccountMeta,
+    pub views: Option<PlayerViews>,
+}
+
+/// The trusted oracle that pairs encrypted matchmaking tickets. Players have
+/// to trust this key: it reads every ticket's preferences.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct OracleConfig {
+    pub oracle: Pubkey,
+}
+
+/// Fee the protocol takes from won wagers in each mode, and where it goes,
+/// plus the reward token unwagered wins earn
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct ProtocolConfig {
+    pub rakes: [ModeRake; WAGER_MODE_COUNT], // Indexed by WAGER_MODE_*
+    pub treasury: Pubkey,
+    pub ranked_stake: u64, // Lamports each player of a ranked game locks, slashed on timeout
+    pub reward_mint: Pubkey, // Compressed token minted to winners of unwagered games
+    pub reward_per_win: u64, // Reward base units per win, 0 = rewards off
+    pub max_active_games: u16, // Per-profile active-game limit, 0 = DEFAULT_MAX_ACTIVE_GAMES
+    pub admin: Pubkey,     // Holds the pause switch
+    pub paused: bool,      // Only the admin's instructions, refunds and payouts run
+    pub turn_timeout_slots: u64, // Pinned into games created with the config, 0 = TURN_TIMEOUT_SLOTS
+    pub open_game_expiry_slots: u64, // Likewise, 0 = OPEN_GAME_EXPIRY_SLOTS
+    pub decay_idle_slots: u64,   // Ranked idleness before decay_rating applies, 0 = off
+    pub decay_points: u16,       // Rating lost per idle period
+    pub decay_floor: u16,        // No decay below, 0 = INITIAL_RATING
+}
+
+/// One wager mode's fee and the wagers it allows
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct ModeRake {
+    pub fee_bps: u16,
+    pub min_wager: u64, // Lamports; never below MIN_WAGER_LAMPORTS
+    pub max_wager: u64, // Lamports, 0 = no cap
+}
+
+/// One version of the verifying key for a circuit and backend, registered
+/// by the admin
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct CircuitVerifier {
+    pub circuit_id: u8, // CIRCUIT_BOARD or CIRCUIT_SHOT
+    pub backend: u8,    // PROOF_BACKEND_*
+    pub version: u16,
+    pub retired: bool, // Rotated out: new games can't pin it
+    pub verifier_program: Pubkey,
+    pub verifying_key: Pubkey, // Account the verifier reads the key from, default = none
+}
+
+/// A proof `attest_proof` already verified, waiting to be consumed by one
+/// game instruction before `expires_slot`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct ProofAttestation {
+    pub owner: Pubkey,
+    pub verifier: [u8; 32], // CircuitVerifier address that checked the proof
+    pub inputs_hash: [u8; 32], // public_inputs_hash of the proven inputs
+    pub expires_slot: u64,
+}
+
+/// A `ProofAttestation` with its meta
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ProofAttestationRef {
+    pub attestation: ProofAttestation,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A proof to check inline, or one verified earlier by `attest_proof`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum ProofSource {
+    Inline(Vec<u8>),
+    Attested(ProofAttestationRef),
+}
+
+/// A proof and the registered verifier it is checked against
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CircuitProof {
+    pub verifier: CircuitVerifier,
+    pub verifier_meta: CompressedAccountMetaReadOnly,
+    pub proof: ProofSource,
+}
+
+/// A proof for `attest_proof` to check: the verifier, the public inputs
+/// and the proof bytes
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ProofStatement {
+    pub verifier: CircuitVerifier,
+    pub verifier_meta: CompressedAccountMetaReadOnly,
+    pub public_inputs: Vec<[u8; 32]>,
+    pub zk_proof: Vec<u8>,
+}
+
+/// Compressed token accounts to spend in a token wager transfer, with their
+/// validity proof. The compressed token program's accounts start at
+/// `token_accounts_offset` in the remaining accounts, and the packed
+/// indices in `accounts` and `output_tree_index` are relative to them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TokenInputs {
+    pub proof: ValidityProof,
+    pub accounts: Vec<TokenInputData>,
+    pub output_tree_index: u8,
+    pub token_accounts_offset: u8,
+}
+
+/// The lamport stake of a wagered game and the wager mode it is played in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct WagerTerms {
+    pub wager: u64,
+    pub mode: u8,
+}
+
+/// The token stake of a token wager game: `amount` tokens of `mint`, and the
+/// wager mode it is played in
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TokenWagerTerms {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub mode: u8,
+}
+
+/// The compressed token program's `InputTokenDataWithContext`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TokenInputData {
+    pub amount: u64,
+    pub delegate_index: Option<u8>,
+    pub merkle_tree_pubkey_index: u8,
+    pub queue_pubkey_index: u8,
+    pub leaf_index: u32,
+    pub prove_by_index: bool,
+    pub root_index: u16,
+    pub lamports: Option<u64>,
+    pub tlv: Option<Vec<u8>>,
+}
+
+/// A value encrypted to the oracle. `encryption_key` is the sender's x25519
+/// key for the shared secret.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct OracleCiphertext {
+    pub ciphertext: [u8; 32],
+    pub encryption_key: [u8; 32],
+    pub nonce: u128,
+}
+
+/// A requested rematch, waiting on the previous game's `RAND_REMATCH` round
+/// and then on the seat-A player to create the game
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Rematch {
+    pub previous_game: [u8; 32],
+    pub player_a: Pubkey, // Seats of the previous game
+    pub player_b: Pubkey,
+    pub rules: GameRules,
+}
+
+/// The rematch being opened and the resolved `RAND_REMATCH` round whose
+/// seed sets its seats, obstacles and first turn
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RematchDraw {
+    pub rematch: Rematch,
+    pub rematch_meta: CompressedAccountMeta,
+    pub round: RandRound,
+    pub round_meta: CompressedAccountMetaReadOnly,
+}
+
+/// Both players' plans in a batch game: each is committed with
+/// `batch_commitment` at create or join and revealed with `reveal_batch_plan`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct BatchPlans {
+    pub game_address: [u8; 32],
+    pub commitment_a: [u8; 32],
+    pub commitment_b: [u8; 32],
+    pub plan_a: Option<BatchPlan>,
+    pub plan_b: Option<BatchPlan>,
+}
+
+/// `BatchPlans` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchPlansRef {
+    pub plans: BatchPlans,
+    pub meta: CompressedAccountMeta,
+}
+
+/// One player's whole batch game: their fleet and an order of up to
+/// `GRID_CELLS` distinct cell indices (`y * GRID_SIZE + x`), fired in turn
+/// until a fleet is sunk
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchPlan {
+    pub ships: Vec<ShipPlacement>,
+    pub shots: Vec<u8>,
+}
+
+/// Side bet whose amounts stay hidden until the game is over. A zero
+/// `commitment_b` means B hasn't matched it.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SealedWager {
+    pub game_address: [u8; 32],
+    pub commitment_a: [u8; 32],
+    pub commitment_b: [u8; 32],
+    pub revealed_a: Option<u64>,
+    pub revealed_b: Option<u64>,
+}
+
+/// A `SealedWager` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SealedWagerRef {
+    pub wager: SealedWager,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Public parimutuel betting on one game's winner
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct BetMarket {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub close_move: u16, // Bets are taken while the game has fewer moves
+    pub pool_a: u64,     // Lamports backing Player A
+    pub pool_b: u64,     // Lamports backing Player B
+    pub claimed: u64,    // Stakes paid out so far; the last claim drains the vault
+}
+
+/// A `BetMarket` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BetMarketRef {
+    pub market: BetMarket,
+    pub meta: CompressedAccountMeta,
+}
+
+/// One spectator's stake in a `BetMarket`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct MarketBet {
+    pub game_address: [u8; 32],
+    pub bettor: Pubkey,
+    pub side: u8, // 1 = A, 2 = B
+    pub amount: u64,
+}
+
+/// The side a `place_bet` backs and the lamports staked on it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BetStake {
+    pub side: u8, // 1 = A, 2 = B
+    pub amount: u64,
+}
+
+/// A `MarketBet` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MarketBetRef {
+    pub bet: MarketBet,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Entry fees paid into a pot and split between the final standings
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct PrizePool {
+    pub organizer: Pubkey,
+    pub pool_id: u64,
+    pub entry_fee: u64,
+    pub split_bps: Vec<u16>, // Share of the pot per place, first place first
+    pub entrants: Vec<Pubkey>,
+    pub distributed: bool,
+    pub sponsored: u64, // Lamports added to the pot by sponsors
+}
+
+/// Single-elimination tournament. `matches` holds the current round.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Tournament {
+    pub organizer: Pubkey,
+    pub tournament_id: u64,
+    pub max_players: u8,
+    pub players: Vec<Pubkey>, // In registration order
+    pub status: u8,           // TOURNAMENT_*
+    pub round: u8,            // 1-based once running
+    pub round_started_at: i64,
+    pub matches: Vec<TournamentMatch>,
+    pub champion: Pubkey, // Set once finished
+}
+
+/// A `Tournament` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct TournamentRef {
+    pub tournament: Tournament,
+    pub meta: CompressedAccountMeta,
+}
+
+/// One pairing in a tournament round
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct TournamentMatch {
+    pub player_a: Pubkey,       // Hosts the game
+    pub player_b: Pubkey,       // Default for a bye
+    pub game_address: [u8; 32], // Game being played, zero = none yet
+    pub winner: Pubkey,         // Default until reported
+}
+
+/// A ranked season's window. Games that end inside it score on its
+/// leaderboard.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Season {
+    pub season: u32,
+    pub start_slot: u64,
+    pub end_slot: u64, // Exclusive
+}
+
+/// A season's top `LEADERBOARD_SIZE` players by wins, frozen once archived
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Leaderboard {
+    pub season: u32,
+    pub entries: Vec<LeaderboardEntry>, // Most wins first
+    pub archived: bool,                 // Season rolled over: standings are final
+}
+
+/// Write-once final standings of a season, kept after `reset_season`
+/// closes its leaderboard
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SeasonArchive {
+    pub season: u32,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub standings: Vec<LeaderboardEntry>, // The leaderboard's final entries, most wins first
+    pub archived_slot: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LeaderboardRef {
+    pub leaderboard: Leaderboard,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SeasonArchiveRef {
+    pub archive: SeasonArchive,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// A season's final standings: its rolled-over leaderboard, or the
+/// archive that replaced it
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub enum SeasonStandings {
+    Leaderboard(LeaderboardRef),
+    Archive(SeasonArchiveRef),
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct LeaderboardEntry {
+    pub player: Pubkey,
+    pub wins: u32,
+}
+
+/// A player's wins in one season, on the leaderboard or not
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SeasonScore {
+    pub season: u32,
+    pub player: Pubkey,
+    pub wins: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SeasonScoreRef {
+    pub score: SeasonScore,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Accounts `record_game_result` updates to score a win for the current
+/// season: the winner's score (`None` for their first win) and the
+/// leaderboard
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SeasonUpdate {
+    pub season: Season,
+    pub season_meta: CompressedAccountMetaReadOnly,
+    pub leaderboard: Leaderboard,
+    pub leaderboard_meta: CompressedAccountMeta,
+    pub score: Option<SeasonScoreRef>,
+}
+
+/// The optional accounts `record_game_result` folds a result into
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ResultUpdates {
+    pub season: Option<SeasonUpdate>,
+    pub profiles: Option<SettledProfiles>,
+    pub rivalry: Option<RivalryUpdate>,
+    pub stats: Option<GlobalStatsRef>,
+}
+
+/// The season `rollover_season` ends
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SeasonRollover {
+    pub season: Season,
+    pub season_meta: CompressedAccountMetaReadOnly,
+    pub leaderboard: Leaderboard,
+    pub leaderboard_meta: CompressedAccountMeta,
+}
+
+/// Rewards for a season's top finishers, funded by anyone
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SeasonRewards {
+    pub season: u32,
+    pub split_bps: Vec<u16>, // Share of the pool per place, first place first
+    pub funded: u64,
+    pub distributed: bool,
+}
+
+/// Spectator betting on one game. Sides stay sealed until the game is
+/// over; only revealed bets are tallied. This is commit-reveal, not an MPC
+/// tally: every stake is the same `SIDE_BET_STAKE_LAMPORTS`, so sizes never
+/// leak, but each side becomes public when it is revealed.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SideBetPool {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub bets: u16,
+    pub revealed_a: u16, // Revealed bets backing A
+    pub revealed_b: u16,
+    pub claimed: u16, // Bets paid out; the last one drains the vault
+}
+
+/// A `SideBetPool` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SideBetPoolRef {
+    pub pool: SideBetPool,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A game's live viewers metric, kept apart from the game so spectators
+/// never touch its state
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct GameViewers {
+    pub game_address: [u8; 32],
+    pub views: u32,    // Every watch_game call
+    pub watchers: u32, // Distinct registered watchers
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameViewersRef {
+    pub viewers: GameViewers,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Write-once marker that `watcher` registered to watch a game
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Watcher {
+    pub game_address: [u8; 32],
+    pub watcher: Pubkey,
+}
+
+/// One spectator's sealed bet of `SIDE_BET_STAKE_LAMPORTS`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SideBet {
+    pub game_address: [u8; 32],
+    pub bettor: Pubkey,
+    pub commitment: [u8; 32], // See `side_bet_commitment`
+    pub side: u8,             // 0 until revealed, 1 = A, 2 = B
+}
+
+/// A `SideBet` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SideBetRef {
+    pub bet: SideBet,
+    pub meta: CompressedAccountMeta,
+}
+
+/// The side and salt a bettor opens their side bet commitment with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SideBetReveal {
+    pub side: u8,
+    pub salt: [u8; 32],
+}
+
+/// Commit-reveal randomness shared by both players of a game. A zero
+/// commitment means that player hasn't committed yet. This is not MPC
+/// randomness: whoever reveals second knows the result first and can
+/// withhold their value instead, so a round is only as fair as the penalty
+/// for stalling it.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct RandRound {
+    pub game_address: [u8; 32],
+    pub round_id: u8,
+    pub commitment_a: [u8; 32],
+    pub commitment_b: [u8; 32],
+    pub revealed_a: Option<[u8; 32]>,
+    pub revealed_b: Option<[u8; 32]>,
+    pub result: Option<[u8; 32]>, // Hash of both values once both revealed
+    pub deadline: u64,            // Next commit or reveal is due by this slot
+}
+
+/// A commitment to a `RandRound` value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RandCommitment {
+    pub round_id: u8,
+    pub commitment: [u8; 32],
+}
+
+/// The value and salt a player opens their `RandRound` commitment with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RandReveal {
+    pub value: [u8; 32],
+    pub salt: [u8; 32],
+}
+
+/// A `RandRound` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RandRoundRef {
+    pub round: RandRound,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A player's place in the encrypted matchmaking queue
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct MatchTicket {
+    pub owner: Pubkey,
+    pub ticket_id: u64,
+    pub preferences: OracleCiphertext, // Stake range and rating band, only the oracle can read them
+    pub oracle: Pubkey,                // Default for bucket queue tickets, which the program pairs
+    pub status: u8,                    // TICKET_QUEUED or TICKET_MATCHED
+    pub opponent: Pubkey,              // Set once matched
+    pub is_host: bool,                 // Matched host creates the game
+    pub bracket: Option<u8>,           // Ranked queue: the proven RATING_BRACKETS entry
+}
+
+/// A new encrypted matchmaking ticket: its id, the oracle config the
+/// preferences are encrypted to, and the preferences
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct OracleTicket {
+    pub ticket_id: u64,
+    pub oracle_config: OracleConfigRef,
+    pub preferences: OracleCiphertext,
+}
+
+/// A proof of the signer's sealed rating, checked against their profile's
+/// rating commitment
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RatingProof {
+    pub profile: PlayerProfile,
+    pub profile_meta: CompressedAccountMetaReadOnly,
+    pub verifier: CircuitVerifier,
+    pub verifier_meta: CompressedAccountMetaReadOnly,
+    pub proof: ProofSource,
+}
+
+/// Queue for one rating bucket, holding at most one waiting ticket
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct BucketQueue {
+    pub bucket: u8,                // Index into RATING_BRACKETS
+    pub waiting: Option<[u8; 32]>, // Address of the waiting ticket
+}
+
+/// The signer's seat in a `BucketQueue`: their new ticket's id, the queue,
+/// and the ticket waiting in it, if any
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BucketSeat {
+    pub ticket_id: u64,
+    pub queue: BucketQueue,
+    pub queue_meta: CompressedAccountMeta,
+    pub waiting: Option<MatchTicketRef>,
+}
+
+/// A lobby's play queue, holding at most one player waiting to be paired
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct PlayQueue {
+    pub mode: u8, // WAGER_MODE_CASUAL or WAGER_MODE_RANKED
+    pub waiting: Option<QueuedPlayer>,
+    pub issuer: Option<Pubkey>, // Verified queue: joiners need this issuer's Credential
+}
+
+/// A `PlayQueue` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PlayQueueRef {
+    pub queue: PlayQueue,
+    pub meta: CompressedAccountMeta,
+}
+
+/// An attestation `issuer` vouches for about `holder`, such as proof of
+/// humanity or KYC, gating verified queues
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Credential {
+    pub issuer: Pubkey,
+    pub holder: Pubkey,
+    pub issued_slot: u64,
+    pub expires_slot: u64, // 0 = no expiry
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CredentialRef {
+    pub credential: Credential,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// A player waiting in the `PlayQueue`, with the board they will play
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct QueuedPlayer {
+    pub player: Pubkey,
+    pub game_id: u64, // Id of the game they will host; their deposit is in its vault
+    pub grid: [u8; GRID_CELLS],
+    pub board_hash: [u8; 32],
+    pub ranked_stake: u64, // Locked with the deposit in the ranked queue
+    pub tags: LobbyTags,
+}
+
+/// The board a player joins the `PlayQueue` with and the lobby tags they
+/// want
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct QueueEntry {
+    pub ship: ShipPlacement,
+    pub board_hash: [u8; 32],
+    pub tags: LobbyTags,
+}
+
+/// A `MatchTicket` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MatchTicketRef {
+    pub ticket: MatchTicket,
+    pub meta: CompressedAccountMeta,
+}
+
+/// An `OracleConfig` passed read-only
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct OracleConfigRef {
+    pub config: OracleConfig,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// The protocol config, passed read-only. Every instruction that changes
+/// state takes it, directly or in its `ActiveGameSlot`, so pausing stops
+/// them all; only the admin's own instructions run without it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ProtocolConfigRef {
+    pub config: ProtocolConfig,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// One line in a `GameChat`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ChatMessage {
+    pub sender: u8, // 1 = A, 2 = B
+    pub move_count: u16,
+    pub text: String,
+}
+
+/// One end-to-end encrypted line in a `GameChat`, sealed under the shared
+/// secret of `chat_key_a` and `chat_key_b`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SealedChatMessage {
+    pub sender: u8, // 1 = A, 2 = B
+    pub move_count: u16,
+    pub nonce: [u8; 24],
+    pub ciphertext: Vec<u8>, // Up to MAX_SEALED_CHAT_LEN bytes
+}
+
+/// Per-player profile holding the rating used for handicaps
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct PlayerProfile {
+    pub owner: Pubkey,
+    pub rating: u16,                 // 0 once sealed
+    pub rating_commitment: [u8; 32], // Pedersen commitment to a sealed rating, zero = public
+    pub referrer: Pubkey,            // Who referred this player, default = nobody
+    pub active_games: u16, // Unfinished games created or joined, see DEFAULT_MAX_ACTIVE_GAMES
+    pub games_played: u32, // Settled by record_game_result
+    pub wins: u32,
+    pub streak: u16, // Wins in a row, reset by a loss or draw
+    pub best_streak: u16,
+    pub milestones: u16,   // Bit per MILESTONE_* unlocked
+    pub next_game_id: u64, // Id create_game gives the owner's next game
+    pub rated_slot: u64,   // Last ranked result or decay, see decay_rating
+}
+
+/// The signer's profile, charged one active game by every instruction that
+/// creates or joins a game (creating also spends its next game id), and the
+/// protocol config, which sets the limit
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ActiveGameSlot {
+    pub profile: PlayerProfile,
+    pub profile_meta: CompressedAccountMeta,
+    pub protocol: ProtocolConfigRef,
+}
+
+/// Both players' profiles, whose streaks `record_game_result` updates
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SettledProfiles {
+    pub profile_a: PlayerProfile,
+    pub meta_a: CompressedAccountMeta,
+    pub profile_b: PlayerProfile,
+    pub meta_b: CompressedAccountMeta,
+}
+
+/// Head-to-head record of two players across every recorded game
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Rivalry {
+    pub player_one: Pubkey, // Lower pubkey
+    pub player_two: Pubkey,
+    pub games: u32,
+    pub wins_one: u32,
+    pub wins_two: u32,
+    pub draws: u32,
+    pub streak_holder: Pubkey, // Won the last `streak` games in a row, default after a draw
+    pub streak: u16,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RivalryRef {
+    pub rivalry: Rivalry,
+    pub meta: CompressedAccountMeta,
+}
+
+/// The pair's `Rivalry` that `record_game_result` updates, `None` for
+/// their first recorded game
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RivalryUpdate {
+    pub rivalry: Option<RivalryRef>,
+}
+
+/// Player A's profile, passed read-only when joining a handicap game
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct OpponentProfile {
+    pub profile: PlayerProfile,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// Protocol-wide counters for dashboards, kept by the lifecycle
+/// instructions that are passed it
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct GlobalStats {
+    pub total_games: u64,
+    pub total_wagered: u64, // Lamports staked by both players
+    pub active_games: u64,  // Created and not yet settled or cancelled
+}
+
+/// `GlobalStats` with its meta
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GlobalStatsRef {
+    pub stats: GlobalStats,
+    pub meta: CompressedAccountMeta,
+}
+
+/// An admin ban: while it exists, `player` can't create or join wagered
+/// games or enter ranked queues
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Ban {
+    pub player: Pubkey,
+    pub banned_slot: u64,
+}
+
+/// Admin-published game setup that games can be created from by id
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct FleetPreset {
+    pub preset_id: u16,
+    pub rules: GameRules,
+    pub active: bool, // Retired presets can't start new games
+}
+
+/// Write-once record of how a game ended. Winner and loser are the default
+/// pubkey for draws.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct GameResult {
+    pub game_address: [u8; 32],
+    pub game_id: u64,
+    pub winner: Pubkey,
+    pub loser: Pubkey,
+    pub game_status: u8,
+    pub end_reason: u8,
+    pub move_count: u16,
+    pub timestamp: i64,
+    pub player_a: Pubkey,
+    pub player_b: Pubkey,
+}
+
+/// A `GameResult` passed read-only
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct GameResultRef {
+    pub result: GameResult,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// One page of a player's match history: the addresses of up to
+/// `MAX_HISTORY_ENTRIES` `GameResult`s, oldest first
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct MatchHistory {
+    pub owner: Pubkey,
+    pub page: u32,
+    pub results: Vec<[u8; 32]>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MatchHistoryRef {
+    pub history: MatchHistory,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A club and its members' combined record, for club leaderboards
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Club {
+    pub founder: Pubkey,
+    pub name: String, // Display-name rules
+    pub members: u32,
+    pub wins: u32, // Summed over members, so a game between two members counts both ways
+    pub losses: u32,
+    pub draws: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ClubRef {
+    pub club: Club,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A player's membership of one club
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct ClubMember {
+    pub player: Pubkey,
+    pub club: [u8; 32], // Club address
+    pub joined_at: i64, // Results recorded earlier don't count for the club
+}
+
+/// A `ClubMember` passed read-only
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ClubMemberRef {
+    pub membership: ClubMember,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// Write-once marker that a member's game was added to their club
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct ClubClaim {
+    pub result_address: [u8; 32],
+    pub member: Pubkey,
+}
+
+/// A display name and the player it resolves to
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct PlayerName {
+    pub owner: Pubkey,
+    pub name: String, // See MIN_NAME_LEN and MAX_NAME_LEN
+}
+
+/// A player's friends and the invites waiting for their answer
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct FriendList {
+    pub owner: Pubkey,
+    pub friends: Vec<Pubkey>, // Up to MAX_FRIENDS
+    pub pending: Vec<Pubkey>, // Players who invited the owner, up to MAX_PENDING_INVITES
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FriendListRef {
+    pub list: FriendList,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A friend `create_game` reserves the game for, shown on the creator's
+/// `FriendList`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct FriendChallenge {
+    pub friend: Pubkey,
+    pub list: FriendList,
+    pub list_meta: CompressedAccountMetaReadOnly,
+}
+
+/// A player's running record for awarding badges
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Achievements {
+    pub owner: Pubkey,
+    pub wins: u32,
+    pub streak: u16,         // Wins in a row among recorded games
+    pub badges: u32,         // Bit BADGE_* is set once that badge is earned
+    pub last_game_slot: u64, // Last action slot of the last recorded game
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AchievementsRef {
+    pub achievements: Achievements,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Write-once badge, one per player and kind, so indexers can list a
+/// player's badges by owner or derive each address
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Badge {
+    pub owner: Pubkey,
+    pub kind: u8,               // BADGE_*
+    pub game_address: [u8; 32], // Game that earned it
+    pub earned_slot: u64,
+}
+
+/// Loyalty points a player has earned by finishing games, for reward
+/// programs to read or spend
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct LoyaltyPoints {
+    pub owner: Pubkey,
+    pub points: u64,
+    pub games_counted: u32,
+    pub recent_opponents: [Pubkey; LOYALTY_RECENT_OPPONENTS], // Ring buffer indexed by games_counted
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LoyaltyPointsRef {
+    pub points: LoyaltyPoints,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Write-once marker that a player's loyalty points for a game were paid
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct LoyaltyClaim {
+    pub game_address: [u8; 32],
+    pub owner: Pubkey,
+}
+
+/// Chain of games with escalating rules. `current_game` is zero while no
+/// stage is being played.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct Campaign {
+    pub owner: Pubkey,
+    pub campaign_id: u64,
+    pub stage: u8, // Stages finished so far, up to CAMPAIGN_STAGES
+    pub current_game: [u8; 32],
+    pub games_won: u8,
+    pub games_lost: u8,
+    pub score: u32,
+}
+
+/// Hot key allowed to make moves for `owner` until `expires_at_slot`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct SessionKey {
+    pub owner: Pubkey,
+    pub session_key: Pubkey,
+    pub expires_at_slot: u64,
+}
+
+/// A session account presented in place of the player's own signature
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SessionAuth {
+    pub account: SessionKey,
+    pub meta: CompressedAccountMetaReadOnly,
+}
+
+/// A single shot recorded in the move log
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct MoveRecord {
+    pub player: u8, // 1 = A, 2 = B
+    pub x: u8,
+    pub y: u8,
+    pub result: u8, // CELL_HIT, CELL_MISS, CELL_BLOCKED or RESULT_SKIPPED
+}
+
+/// Append-only list of every shot in a game, stored as a compressed account
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct MoveLog {
+    pub game_address: [u8; 32],
+    pub moves: Vec<MoveRecord>,
+}
+
+/// A `MoveLog` being updated
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MoveLogRef {
+    pub log: MoveLog,
+    pub meta: CompressedAccountMeta,
+}
+
+/// A `MoveLog` passed read-only
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MoveLogReadOnlyRef {
+    pub log: MoveLog,
+    pub meta: CompressedAccountMetaReadOnly,
+}
@@ -0,0 +1,46 @@
+#![allow(unexpected_cfgs)]
+#![allow(deprecated)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+
+declare_id!("F9718vuphmtuNeQ2Ws2CfPkoFhZ7D7tqwMEgHALKfpnG");
+
+/// Bytes of a mock proof
+pub const PROOF_LEN: usize = 32;
+
+/// Stand-in for a circuit's verifier program, registered with
+/// `register_verifier` in tests. It takes the same instruction data as a
+/// real verifier, the proof followed by each 32-byte public input, and
+/// accepts the proof only if it is the sha256 of the public inputs.
+#[program]
+pub mod mock_verifier {
+    use super::*;
+
+    pub fn fallback<'info>(
+        _program_id: &Pubkey,
+        _accounts: &'info [AccountInfo<'info>],
+        data: &[u8],
+    ) -> Result<()> {
+        if data.len() < PROOF_LEN {
+            return Err(MockVerifierError::MalformedInput.into());
+        }
+        let (proof, public_inputs) = data.split_at(PROOF_LEN);
+        if !public_inputs.chunks_exact(32).remainder().is_empty() {
+            return Err(MockVerifierError::MalformedInput.into());
+        }
+        if hashv(&[public_inputs]).to_bytes() != proof {
+            msg!("Proof does not verify");
+            return Err(MockVerifierError::InvalidProof.into());
+        }
+        Ok(())
+    }
+}
+
+#[error_code]
+pub enum MockVerifierError {
+    #[msg("Expected a 32-byte proof and 32-byte public inputs")]
+    MalformedInput,
+    #[msg("Proof does not verify")]
+    InvalidProof,
+}
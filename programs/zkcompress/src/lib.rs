@@ -1,7 +1,7 @@
 #![allow(unexpected_cfgs)]
 #![allow(deprecated)]
 
-use anchor_lang::{prelude::*, AnchorDeserialize, AnchorSerialize};
+use anchor_lang::{prelude::*, system_program, AnchorDeserialize, AnchorSerialize};
 use light_sdk::{
     account::LightAccount,
     address::v2::derive_address,
@@ -20,6 +20,42 @@ declare_id!("4XoRG4B6A7iJ5Vppi2EDD127imWR14USu2CBuEPHXiKK");
 pub const LIGHT_CPI_SIGNER: CpiSigner =
     derive_light_cpi_signer!("4XoRG4B6A7iJ5Vppi2EDD127imWR14USu2CBuEPHXiKK");
 
+/// Most accounts `update_accounts_batch` takes in one CPI
+pub const MAX_BATCH_UPDATES: usize = 4;
+/// Most accounts `create_accounts_batch` creates in one CPI, and the
+/// longest seed suffix each can take
+pub const MAX_BATCH_CREATES: usize = 4;
+pub const MAX_SEED_SUFFIX_LEN: usize = 32;
+
+/// Ceiling for the admin-set `MessageConfig::max_message_len`, which starts
+/// here. Every byte rides in instruction data and is rehashed on each
+/// update, and `update_account` carries both the old and the new message,
+/// so much past this an update stops fitting in one transaction.
+pub const MAX_MESSAGE_LEN: usize = 256;
+pub const MESSAGE_CONFIG_SEED: &[u8] = b"message_config";
+
+/// Longest `RawDataAccount` payload in bytes; same cost tradeoff as
+/// `MAX_MESSAGE_LEN`
+pub const MAX_RAW_DATA_LEN: usize = 512;
+
+/// Longest KV key in bytes, the most one address seed can hold. Values
+/// share `MAX_RAW_DATA_LEN`.
+pub const MAX_KV_KEY_LEN: usize = 32;
+
+/// Entries one `LogAccount` page holds before `append_entry` spills into
+/// the next page, and the longest entry in bytes
+pub const MAX_LOG_ENTRIES: usize = 8;
+pub const MAX_LOG_ENTRY_LEN: usize = 64;
+
+/// Seed of the system account escrowing an account's `crank_reward`,
+/// next to the compressed address
+pub const CRANK_REWARD_SEED: &[u8] = b"crank_reward";
+
+/// Delegate permission bits `set_delegate` can grant
+pub const PERMISSION_UPDATE: u8 = 1 << 0;
+pub const PERMISSION_CLOSE: u8 = 1 << 1; // Covers close_account and burn_account
+pub const PERMISSION_ALL: u8 = PERMISSION_UPDATE | PERMISSION_CLOSE;
+
 #[program]
 pub mod update {
 
@@ -28,14 +64,41 @@ pub mod update {
         v2::LightSystemProgramCpi, InvokeLightSystemProgram, LightCpiInstruction,
     };
 
+    /// Creates the program-wide `MessageConfig` at its `MAX_MESSAGE_LEN`
+    /// ceiling. Admin only: the signer must be the upgrade authority.
+    pub fn init_message_config(ctx: Context<InitMessageConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.max_message_len = MAX_MESSAGE_LEN as u16;
+        config.bump = ctx.bumps.config;
+
+        msg!("Message limit starts at {} bytes", config.max_message_len);
+        Ok(())
+    }
+
+    /// Sets the longest message create and update paths accept, from 1 up
+    /// to `MAX_MESSAGE_LEN`. Existing longer messages stay readable but
+    /// must shrink on their next update. Admin only.
+    pub fn set_message_limit(ctx: Context<SetMessageLimit>, max_message_len: u16) -> Result<()> {
+        if max_message_len == 0 || max_message_len as usize > MAX_MESSAGE_LEN {
+            msg!("Limits are 1-{} bytes", MAX_MESSAGE_LEN);
+            return Err(ZkCompressError::InvalidMessageLimit.into());
+        }
+        ctx.accounts.config.max_message_len = max_message_len;
+
+        msg!("Message limit set to {} bytes", max_message_len);
+        Ok(())
+    }
+
     /// Setup: Creates a compressed account
     pub fn create_account<'info>(
-        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        ctx: Context<'_, '_, '_, 'info, MessageAccounts<'info>>,
         proof: ValidityProof,
         address_tree_info: PackedAddressTreeInfo,
         output_state_tree_index: u8,
         message: String,
     ) -> Result<()> {
+        check_message(&message, &ctx.accounts.config)?;
+
         let light_cpi_accounts = CpiAccounts::new(
             ctx.accounts.signer.as_ref(),
             ctx.remaining_accounts,
@@ -81,14 +144,92 @@ pub mod update {
         Ok(())
     }
 
-    /// Updates an existing compressed account's message
+    /// Creates several compressed accounts under one validity proof and
+    /// Light CPI. Each lives at `[b"message", signer, seed_suffix]`, so a
+    /// signer can hold one per suffix beside the one `create_account` makes.
+    pub fn create_accounts_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, MessageAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        creates: Vec<AccountCreate>,
+    ) -> Result<()> {
+        if creates.is_empty() || creates.len() > MAX_BATCH_CREATES {
+            msg!("Batches hold 1-{} accounts", MAX_BATCH_CREATES);
+            return Err(ProgramError::InvalidInstructionData.into());
+        }
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let address_tree_pubkey = address_tree_info
+            .get_tree_pubkey(&light_cpi_accounts)
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+            msg!("Invalid address tree");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+
+        let count = creates.len();
+        let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+        let mut new_addresses = Vec::with_capacity(count);
+        for (i, create) in creates.into_iter().enumerate() {
+            check_seed_suffix(&create.seed_suffix)?;
+            check_message(&create.message, &ctx.accounts.config)?;
+
+            let (address, address_seed) = derive_address(
+                &[
+                    b"message",
+                    ctx.accounts.signer.key().as_ref(),
+                    &create.seed_suffix,
+                ],
+                &address_tree_pubkey,
+                &crate::ID,
+            );
+            let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_init(
+                &crate::ID,
+                Some(address),
+                output_state_tree_index,
+            );
+            my_compressed_account.owner = ctx.accounts.signer.key();
+            my_compressed_account.message = create.message;
+
+            // Account i is output i
+            cpi = cpi.with_light_account(my_compressed_account)?;
+            new_addresses.push(
+                address_tree_info
+                    .into_new_address_params_assigned_packed(address_seed, Some(i as u8)),
+            );
+        }
+
+        msg!("Created {} compressed accounts", count);
+
+        cpi.with_new_addresses(&new_addresses)
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Updates an existing compressed account's message. The owner, or a
+    /// delegate with `PERMISSION_UPDATE`, signs for this.
     pub fn update_account<'info>(
-        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        ctx: Context<'_, '_, '_, 'info, MessageAccounts<'info>>,
         proof: ValidityProof,
         current_account: MyCompressedAccount,
         account_meta: CompressedAccountMeta,
         new_message: String,
     ) -> Result<()> {
+        require_authority(
+            &current_account,
+            &ctx.accounts.signer.key(),
+            PERMISSION_UPDATE,
+        )?;
+        check_message(&new_message, &ctx.accounts.config)?;
+
         let light_cpi_accounts = CpiAccounts::new(
             ctx.accounts.signer.as_ref(),
             ctx.remaining_accounts,
@@ -115,13 +256,169 @@ pub mod update {
         Ok(())
     }
 
-    /// Close compressed account
+    /// Grants `delegate` the `permissions` bits (PERMISSION_*) on the
+    /// signer's account, replacing any earlier delegate. Pass no bits to
+    /// revoke.
+    pub fn set_delegate<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        current_account: MyCompressedAccount,
+        account_meta: CompressedAccountMeta,
+        delegate: Pubkey,
+        permissions: u8,
+    ) -> Result<()> {
+        if current_account.owner != ctx.accounts.signer.key() {
+            msg!("Signer does not own this account");
+            return Err(ZkCompressError::NotOwner.into());
+        }
+        if permissions & !PERMISSION_ALL != 0 {
+            return Err(ZkCompressError::InvalidPermissions.into());
+        }
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_mut(
+            &crate::ID,
+            &account_meta,
+            current_account,
+        )?;
+        if permissions == 0 {
+            my_compressed_account.delegate = Pubkey::default();
+        } else {
+            my_compressed_account.delegate = delegate;
+        }
+        my_compressed_account.permissions = permissions;
+
+        msg!(
+            "Delegate {:?} holds permissions {:#04b}",
+            my_compressed_account.delegate,
+            permissions
+        );
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Hands a compressed account to `new_owner`. The current owner signs;
+    /// the address stays the one derived from the original creator, and
+    /// any delegate is dropped. An escrowed crank reward is refunded to the
+    /// outgoing owner who funded it, and the expiry stays for the new owner
+    /// to keep or clear.
+    pub fn transfer_ownership<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExpiryAccounts<'info>>,
+        proof: ValidityProof,
+        current_account: MyCompressedAccount,
+        account_meta: CompressedAccountMeta,
+        new_owner: Pubkey,
+    ) -> Result<()> {
+        let previous_owner = current_account.owner;
+        if previous_owner != ctx.accounts.signer.key() {
+            msg!("Signer does not own this account");
+            return Err(ZkCompressError::NotOwner.into());
+        }
+        let bump = check_reward_vault(&ctx.accounts.vault, &account_meta.address)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let refund = current_account.crank_reward > 0;
+        let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_mut(
+            &crate::ID,
+            &account_meta,
+            current_account,
+        )?;
+        my_compressed_account.owner = new_owner;
+        my_compressed_account.delegate = Pubkey::default();
+        my_compressed_account.permissions = 0;
+        my_compressed_account.crank_reward = 0;
+
+        msg!("Transferred compressed account to {:?}", new_owner);
+        emit!(OwnershipTransferred {
+            address: account_meta.address,
+            previous_owner,
+            new_owner,
+        });
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .invoke(light_cpi_accounts)?;
+
+        if refund {
+            refund_reward(ctx.accounts, &account_meta.address, bump)?;
+        }
+
+        Ok(())
+    }
+
+    /// Updates several compressed accounts' messages in a single Light CPI,
+    /// so every input shares one validity proof. The signer needs update
+    /// rights on each, as in `update_account`.
+    pub fn update_accounts_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, MessageAccounts<'info>>,
+        proof: ValidityProof,
+        updates: Vec<AccountUpdate>,
+    ) -> Result<()> {
+        if updates.is_empty() || updates.len() > MAX_BATCH_UPDATES {
+            msg!("Batches hold 1-{} accounts", MAX_BATCH_UPDATES);
+            return Err(ProgramError::InvalidInstructionData.into());
+        }
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let count = updates.len();
+        let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof);
+        for update in updates {
+            require_authority(
+                &update.current_account,
+                &ctx.accounts.signer.key(),
+                PERMISSION_UPDATE,
+            )?;
+            check_message(&update.new_message, &ctx.accounts.config)?;
+            let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_mut(
+                &crate::ID,
+                &update.account_meta,
+                update.current_account,
+            )?;
+            my_compressed_account.message = update.new_message;
+            cpi = cpi.with_light_account(my_compressed_account)?;
+        }
+
+        msg!("Updated {} compressed accounts", count);
+
+        cpi.invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Close compressed account. Takes the account's real current state;
+    /// the owner, or a delegate with `PERMISSION_CLOSE`, signs for this.
     pub fn close_account<'info>(
         ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
         proof: ValidityProof,
         account_meta: CompressedAccountMeta,
-        current_message: String,
+        current_account: MyCompressedAccount,
     ) -> Result<()> {
+        require_authority(
+            &current_account,
+            &ctx.accounts.signer.key(),
+            PERMISSION_CLOSE,
+        )?;
+        require_no_escrow(&current_account)?;
+
         let light_cpi_accounts = CpiAccounts::new(
             ctx.accounts.signer.as_ref(),
             ctx.remaining_accounts,
@@ -131,10 +428,7 @@ pub mod update {
         let my_compressed_account = LightAccount::<MyCompressedAccount>::new_close(
             &crate::ID,
             &account_meta,
-            MyCompressedAccount {
-                owner: ctx.accounts.signer.key(),
-                message: current_message,
-            },
+            current_account,
         )?;
 
         msg!("Close compressed account.");
@@ -146,20 +440,30 @@ pub mod update {
         Ok(())
     }
 
-    /// Reinitialize closed compressed account
+    /// Reinitialize closed compressed account. A closed account keeps no
+    /// owner, so only the signer its address derives from can reopen it:
+    /// `seed_suffix` is empty for `create_account`'s address, or the suffix
+    /// `create_accounts_batch` used. The signer owns the reopened account.
     pub fn reinit_account<'info>(
         ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
         proof: ValidityProof,
         account_meta: CompressedAccountMeta,
+        seed_suffix: Vec<u8>,
     ) -> Result<()> {
+        if message_address(&ctx.accounts.signer.key(), &seed_suffix) != account_meta.address {
+            msg!("Account address isn't derived from the signer");
+            return Err(ZkCompressError::NotOwner.into());
+        }
+
         let light_cpi_accounts = CpiAccounts::new(
             ctx.accounts.signer.as_ref(),
             ctx.remaining_accounts,
             crate::LIGHT_CPI_SIGNER,
         );
 
-        let my_compressed_account =
+        let mut my_compressed_account =
             LightAccount::<MyCompressedAccount>::new_empty(&crate::ID, &account_meta)?;
+        my_compressed_account.owner = ctx.accounts.signer.key();
 
         msg!("Reinitializing closed compressed account");
 
@@ -170,13 +474,22 @@ pub mod update {
         Ok(())
     }
 
-    /// Burns a compressed account permanently
+    /// Burns a compressed account permanently. Like `close_account`, it
+    /// takes the real current state and needs the owner or a delegate with
+    /// `PERMISSION_CLOSE`.
     pub fn burn_account<'info>(
         ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
         proof: ValidityProof,
         account_meta: CompressedAccountMetaBurn,
-        current_message: String,
+        current_account: MyCompressedAccount,
     ) -> Result<()> {
+        require_authority(
+            &current_account,
+            &ctx.accounts.signer.key(),
+            PERMISSION_CLOSE,
+        )?;
+        require_no_escrow(&current_account)?;
+
         let light_cpi_accounts = CpiAccounts::new(
             ctx.accounts.signer.as_ref(),
             ctx.remaining_accounts,
@@ -186,10 +499,7 @@ pub mod update {
         let my_compressed_account = LightAccount::<MyCompressedAccount>::new_burn(
             &crate::ID,
             &account_meta,
-            MyCompressedAccount {
-                owner: ctx.accounts.signer.key(),
-                message: current_message,
-            },
+            current_account,
         )?;
 
         msg!("Burning compressed account permanently");
@@ -200,17 +510,1013 @@ pub mod update {
 
         Ok(())
     }
-}
 
-#[derive(Accounts)]
-pub struct GenericAnchorAccounts<'info> {
-    #[account(mut)]
-    pub signer: Signer<'info>,
-}
+    /// Creates a byte-blob account at `[b"raw", signer, schema]`, one per
+    /// schema per owner. `schema` is the app's tag for how to decode `data`.
+    pub fn create_raw_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        schema: u16,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        check_raw_data(&data)?;
 
-#[event]
-#[derive(Clone, Debug, Default, LightDiscriminator)]
-pub struct MyCompressedAccount {
-    pub owner: Pubkey,
-    pub message: String,
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let address_tree_pubkey = address_tree_info
+            .get_tree_pubkey(&light_cpi_accounts)
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+            msg!("Invalid address tree");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+
+        let (address, address_seed) = derive_address(
+            &[
+                b"raw",
+                ctx.accounts.signer.key().as_ref(),
+                &schema.to_le_bytes(),
+            ],
+            &address_tree_pubkey,
+            &crate::ID,
+        );
+
+        let mut raw_account = LightAccount::<RawDataAccount>::new_init(
+            &crate::ID,
+            Some(address),
+            output_state_tree_index,
+        );
+        raw_account.owner = ctx.accounts.signer.key();
+        raw_account.data = data;
+        raw_account.schema = schema;
+
+        msg!(
+            "Created raw data account: schema {}, {} bytes",
+            schema,
+            raw_account.data.len()
+        );
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(raw_account)?
+            .with_new_addresses(&[
+                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+            ])
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Replaces a raw data account's payload. The schema is part of the
+    /// address, so it stays fixed; only the owner signs for this.
+    pub fn update_raw_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        current_account: RawDataAccount,
+        account_meta: CompressedAccountMeta,
+        new_data: Vec<u8>,
+    ) -> Result<()> {
+        require_owner(&current_account.owner, &ctx.accounts.signer.key())?;
+        check_raw_data(&new_data)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let mut raw_account =
+            LightAccount::<RawDataAccount>::new_mut(&crate::ID, &account_meta, current_account)?;
+        raw_account.data = new_data;
+
+        msg!("Updated raw data account: {} bytes", raw_account.data.len());
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(raw_account)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Closes a raw data account. Takes the real current state and the
+    /// owner's signature.
+    pub fn close_raw_account<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        account_meta: CompressedAccountMeta,
+        current_account: RawDataAccount,
+    ) -> Result<()> {
+        require_owner(&current_account.owner, &ctx.accounts.signer.key())?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let raw_account =
+            LightAccount::<RawDataAccount>::new_close(&crate::ID, &account_meta, current_account)?;
+
+        msg!("Close raw data account.");
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(raw_account)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Creates a KV entry at `[b"kv", signer, key]`. Each signer keeps as
+    /// many entries as it has distinct keys; a live key fails here, so use
+    /// `kv_overwrite` to change its value. A key `kv_delete` removed keeps
+    /// its closed account, so pass that as `deleted` to set the key again.
+    pub fn kv_create<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        deleted: Option<CompressedAccountMeta>,
+    ) -> Result<()> {
+        check_kv_key(&key)?;
+        check_raw_data(&value)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        if let Some(deleted) = deleted {
+            let (address, _) = derive_address(
+                &[b"kv", ctx.accounts.signer.key().as_ref(), &key],
+                &Pubkey::new_from_array(ADDRESS_TREE_V2),
+                &crate::ID,
+            );
+            if deleted.address != address {
+                msg!("Deleted entry isn't the signer's entry for this key");
+                return Err(ZkCompressError::InvalidKey.into());
+            }
+
+            // The proof only holds for the closed, empty account
+            let mut entry = LightAccount::<KvEntry>::new_empty(&crate::ID, &deleted)?;
+            entry.owner = ctx.accounts.signer.key();
+            entry.key = key;
+            entry.value = value;
+
+            msg!("Recreated KV entry: {} byte value", entry.value.len());
+
+            LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+                .with_light_account(entry)?
+                .invoke(light_cpi_accounts)?;
+            return Ok(());
+        }
+
+        let address_tree_pubkey = address_tree_info
+            .get_tree_pubkey(&light_cpi_accounts)
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+            msg!("Invalid address tree");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+
+        let (address, address_seed) = derive_address(
+            &[b"kv", ctx.accounts.signer.key().as_ref(), &key],
+            &address_tree_pubkey,
+            &crate::ID,
+        );
+
+        let mut entry =
+            LightAccount::<KvEntry>::new_init(&crate::ID, Some(address), output_state_tree_index);
+        entry.owner = ctx.accounts.signer.key();
+        entry.key = key;
+        entry.value = value;
+
+        msg!("Created KV entry: {} byte value", entry.value.len());
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(entry)?
+            .with_new_addresses(&[
+                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+            ])
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Replaces a KV entry's value. The key is part of the address and
+    /// stays fixed; only the owner signs for this.
+    pub fn kv_overwrite<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        current_entry: KvEntry,
+        account_meta: CompressedAccountMeta,
+        new_value: Vec<u8>,
+    ) -> Result<()> {
+        require_owner(&current_entry.owner, &ctx.accounts.signer.key())?;
+        check_raw_data(&new_value)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let mut entry = LightAccount::<KvEntry>::new_mut(&crate::ID, &account_meta, current_entry)?;
+        entry.value = new_value;
+
+        msg!("Overwrote KV entry: {} byte value", entry.value.len());
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(entry)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Deletes a KV entry. Its address keeps a closed, empty account that
+    /// `kv_create` reopens if the key is set again.
+    pub fn kv_delete<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        account_meta: CompressedAccountMeta,
+        current_entry: KvEntry,
+    ) -> Result<()> {
+        require_owner(&current_entry.owner, &ctx.accounts.signer.key())?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let entry = LightAccount::<KvEntry>::new_close(&crate::ID, &account_meta, current_entry)?;
+
+        msg!("Deleted KV entry.");
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(entry)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Appends a timestamped entry to the signer's log. `page` is the
+    /// latest page, or `None` to start page 0. A full page is left as is
+    /// and the entry opens the next page at `[b"log", signer, page + 1]`,
+    /// so older entries stay readable instead of being dropped.
+    pub fn append_entry<'info>(
+        ctx: Context<'_, '_, '_, 'info, GenericAnchorAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+        page: Option<LogPageRef>,
+        data: Vec<u8>,
+    ) -> Result<()> {
+        if data.len() > MAX_LOG_ENTRY_LEN {
+            msg!(
+                "Entry is {} bytes, the limit is {}",
+                data.len(),
+                MAX_LOG_ENTRY_LEN
+            );
+            return Err(ZkCompressError::LogEntryTooLong.into());
+        }
+        let owner = ctx.accounts.signer.key();
+        let entry = LogEntry {
+            timestamp: Clock::get()?.unix_timestamp,
+            data,
+        };
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let (full_page, next_page) = match page {
+            Some(page) => {
+                require_owner(&page.log.owner, &owner)?;
+                if page.log.entries.len() < MAX_LOG_ENTRIES {
+                    let mut log_account =
+                        LightAccount::<LogAccount>::new_mut(&crate::ID, &page.meta, page.log)?;
+                    log_account.entries.push(entry);
+                    msg!(
+                        "Log page {} holds {} entries",
+                        log_account.page,
+                        log_account.entries.len()
+                    );
+
+                    LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+                        .with_light_account(log_account)?
+                        .invoke(light_cpi_accounts)?;
+                    return Ok(());
+                }
+                let next_page = page.log.page + 1;
+                // Carried over unchanged, proving the page is full
+                let full_page =
+                    LightAccount::<LogAccount>::new_mut(&crate::ID, &page.meta, page.log)?;
+                (Some(full_page), next_page)
+            }
+            None => (None, 0),
+        };
+
+        let address_tree_pubkey = address_tree_info
+            .get_tree_pubkey(&light_cpi_accounts)
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+            msg!("Invalid address tree");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+
+        let (address, address_seed) = derive_address(
+            &[b"log", owner.as_ref(), &next_page.to_le_bytes()],
+            &address_tree_pubkey,
+            &crate::ID,
+        );
+        let mut log_account = LightAccount::<LogAccount>::new_init(
+            &crate::ID,
+            Some(address),
+            output_state_tree_index,
+        );
+        log_account.owner = owner;
+        log_account.page = next_page;
+        log_account.entries.push(entry);
+
+        msg!("Started log page {}", next_page);
+
+        // The new page goes first so it is output 0
+        let mut cpi = LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(log_account)?;
+        if let Some(full_page) = full_page {
+            cpi = cpi.with_light_account(full_page)?;
+        }
+        cpi.with_new_addresses(&[
+            address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+        ])
+        .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Creates a `MessagePda` directly, rent paid by the signer, for state
+    /// that starts out in a regular account. It's keyed by the address
+    /// `compress_pda` later gives it, from the same `seed_suffix`.
+    pub fn create_message_pda(
+        ctx: Context<CreateMessagePdaAccounts>,
+        seed_suffix: Vec<u8>,
+        message: String,
+    ) -> Result<()> {
+        check_seed_suffix(&seed_suffix)?;
+        check_message(&message, &ctx.accounts.config)?;
+
+        let pda = &mut ctx.accounts.pda;
+        pda.owner = ctx.accounts.signer.key();
+        pda.address = message_address(&pda.owner, &seed_suffix);
+        pda.seed_suffix = seed_suffix;
+        pda.message = message;
+        pda.bump = ctx.bumps.pda;
+
+        msg!("Created PDA {}", pda.key());
+        Ok(())
+    }
+
+    /// Migrates one of the signer's `MessagePda`s into a compressed account
+    /// at `[b"message", signer, pda.seed_suffix]`, delegate included, and
+    /// closes the PDA, refunding its rent to the signer. The suffix is fixed
+    /// when the PDA is created, so the migration can't land anywhere else.
+    pub fn compress_pda<'info>(
+        ctx: Context<'_, '_, '_, 'info, CompressPdaAccounts<'info>>,
+        proof: ValidityProof,
+        address_tree_info: PackedAddressTreeInfo,
+        output_state_tree_index: u8,
+    ) -> Result<()> {
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let address_tree_pubkey = address_tree_info
+            .get_tree_pubkey(&light_cpi_accounts)
+            .map_err(|_| ErrorCode::AccountNotEnoughKeys)?;
+
+        if address_tree_pubkey.to_bytes() != ADDRESS_TREE_V2 {
+            msg!("Invalid address tree");
+            return Err(ProgramError::InvalidAccountData.into());
+        }
+
+        let (address, address_seed) = derive_address(
+            &[
+                b"message",
+                ctx.accounts.signer.key().as_ref(),
+                &ctx.accounts.pda.seed_suffix,
+            ],
+            &address_tree_pubkey,
+            &crate::ID,
+        );
+
+        let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_init(
+            &crate::ID,
+            Some(address),
+            output_state_tree_index,
+        );
+        my_compressed_account.owner = ctx.accounts.pda.owner;
+        my_compressed_account.message = ctx.accounts.pda.message.clone();
+        my_compressed_account.delegate = ctx.accounts.pda.delegate;
+        my_compressed_account.permissions = ctx.accounts.pda.permissions;
+
+        msg!(
+            "Compressed PDA {} into compressed account",
+            ctx.accounts.pda.key()
+        );
+
+        // Anchor closes the PDA to the signer once this returns
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .with_new_addresses(&[
+                address_tree_info.into_new_address_params_assigned_packed(address_seed, Some(0))
+            ])
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Inverse of `compress_pda`: burns the signer's compressed account and
+    /// writes it into a new rent-exempt `MessagePda` keyed by its address,
+    /// paid by the signer. Owner only. The delegate moves with the message;
+    /// an expiry has nothing to enforce it on a PDA, so clear it first. The
+    /// burned address can't be reused, so `seed_suffix` names where
+    /// `compress_pda` puts it back.
+    pub fn decompress_to_pda<'info>(
+        ctx: Context<'_, '_, '_, 'info, DecompressPdaAccounts<'info>>,
+        proof: ValidityProof,
+        account_meta: CompressedAccountMetaBurn,
+        current_account: MyCompressedAccount,
+        seed_suffix: Vec<u8>,
+    ) -> Result<()> {
+        check_seed_suffix(&seed_suffix)?;
+        require_owner(&current_account.owner, &ctx.accounts.signer.key())?;
+        if current_account.expires_at_slot.is_some() {
+            msg!("Clear the expiry before decompressing");
+            return Err(ZkCompressError::InvalidExpiry.into());
+        }
+        require_no_escrow(&current_account)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let pda = &mut ctx.accounts.pda;
+        pda.owner = current_account.owner;
+        pda.address = account_meta.address;
+        pda.seed_suffix = seed_suffix;
+        pda.message = current_account.message.clone();
+        pda.delegate = current_account.delegate;
+        pda.permissions = current_account.permissions;
+        pda.bump = ctx.bumps.pda;
+
+        // Burned, not closed, so the data can't be restored in compressed
+        // state while the PDA holds it
+        let my_compressed_account = LightAccount::<MyCompressedAccount>::new_burn(
+            &crate::ID,
+            &account_meta,
+            current_account,
+        )?;
+
+        msg!("Decompressed account into PDA {}", pda.key());
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .invoke(light_cpi_accounts)?;
+
+        Ok(())
+    }
+
+    /// Sets or clears when an account expires, owner only. With a slot,
+    /// `crank_reward` lamports move from the signer into the account's
+    /// escrow at `[CRANK_REWARD_SEED, address]`, on top of any reward already
+    /// there; a first deposit must cover the escrow's rent exemption.
+    /// Clearing the expiry refunds the escrow to the signer.
+    pub fn set_expiry<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExpiryAccounts<'info>>,
+        proof: ValidityProof,
+        current_account: MyCompressedAccount,
+        account_meta: CompressedAccountMeta,
+        expires_at_slot: Option<u64>,
+        crank_reward: u64,
+    ) -> Result<()> {
+        require_owner(&current_account.owner, &ctx.accounts.signer.key())?;
+        match expires_at_slot {
+            Some(slot) if slot <= Clock::get()?.slot => {
+                msg!("Expiry slot {} has already passed", slot);
+                return Err(ZkCompressError::InvalidExpiry.into());
+            }
+            None if crank_reward > 0 => {
+                msg!("A crank reward needs an expiry slot");
+                return Err(ZkCompressError::InvalidExpiry.into());
+            }
+            _ => {}
+        }
+        let bump = check_reward_vault(&ctx.accounts.vault, &account_meta.address)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let refund = current_account.crank_reward > 0 && expires_at_slot.is_none();
+        let mut my_compressed_account = LightAccount::<MyCompressedAccount>::new_mut(
+            &crate::ID,
+            &account_meta,
+            current_account,
+        )?;
+        my_compressed_account.expires_at_slot = expires_at_slot;
+        my_compressed_account.crank_reward = if refund {
+            0
+        } else {
+            my_compressed_account
+                .crank_reward
+                .checked_add(crank_reward)
+                .ok_or(ProgramError::ArithmeticOverflow)?
+        };
+
+        msg!(
+            "Account expires at {:?} with a {} lamport crank reward",
+            my_compressed_account.expires_at_slot,
+            my_compressed_account.crank_reward
+        );
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .invoke(light_cpi_accounts)?;
+
+        if refund {
+            refund_reward(ctx.accounts, &account_meta.address, bump)?;
+        } else if crank_reward > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.signer.to_account_info(),
+                        to: ctx.accounts.vault.to_account_info(),
+                    },
+                ),
+                crank_reward,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Permissionless cleanup: anyone can close an account once its
+    /// `expires_at_slot` has passed, and the escrowed crank reward, if
+    /// any, goes to them.
+    pub fn close_expired<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExpiryAccounts<'info>>,
+        proof: ValidityProof,
+        account_meta: CompressedAccountMeta,
+        current_account: MyCompressedAccount,
+    ) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        match current_account.expires_at_slot {
+            Some(expires_at_slot) if slot >= expires_at_slot => {}
+            _ => {
+                msg!(
+                    "Account expires at {:?}, now {}",
+                    current_account.expires_at_slot,
+                    slot
+                );
+                return Err(ZkCompressError::NotExpired.into());
+            }
+        }
+        let bump = check_reward_vault(&ctx.accounts.vault, &account_meta.address)?;
+
+        let light_cpi_accounts = CpiAccounts::new(
+            ctx.accounts.signer.as_ref(),
+            ctx.remaining_accounts,
+            crate::LIGHT_CPI_SIGNER,
+        );
+
+        let has_reward = current_account.crank_reward > 0;
+        let my_compressed_account = LightAccount::<MyCompressedAccount>::new_close(
+            &crate::ID,
+            &account_meta,
+            current_account,
+        )?;
+
+        LightSystemProgramCpi::new_cpi(LIGHT_CPI_SIGNER, proof)
+            .with_light_account(my_compressed_account)?
+            .invoke(light_cpi_accounts)?;
+
+        let reward = if has_reward {
+            ctx.accounts.vault.lamports()
+        } else {
+            0
+        };
+        if reward > 0 {
+            system_program::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.signer.to_account_info(),
+                    },
+                    &[&[CRANK_REWARD_SEED, &account_meta.address, &[bump]]],
+                ),
+                reward,
+            )?;
+        }
+
+        emit!(AccountExpired {
+            address: account_meta.address,
+            cranker: ctx.accounts.signer.key(),
+            reward,
+        });
+
+        Ok(())
+    }
+}
+
+/// Rejects messages over the configured limit
+fn check_message(message: &str, config: &MessageConfig) -> Result<()> {
+    if message.len() > config.max_message_len as usize {
+        msg!(
+            "Message is {} bytes, the limit is {}",
+            message.len(),
+            config.max_message_len
+        );
+        return Err(ZkCompressError::MessageTooLong.into());
+    }
+    Ok(())
+}
+
+/// Suffixes must be non-empty, keeping clear of `create_account`'s
+/// address, and fit in one address seed
+fn check_seed_suffix(seed_suffix: &[u8]) -> Result<()> {
+    if seed_suffix.is_empty() || seed_suffix.len() > MAX_SEED_SUFFIX_LEN {
+        msg!("Seed suffixes are 1-{} bytes", MAX_SEED_SUFFIX_LEN);
+        return Err(ProgramError::InvalidInstructionData.into());
+    }
+    Ok(())
+}
+
+/// Keys must be non-empty and fit in one address seed
+fn check_kv_key(key: &[u8]) -> Result<()> {
+    if key.is_empty() || key.len() > MAX_KV_KEY_LEN {
+        msg!("Keys are 1-{} bytes", MAX_KV_KEY_LEN);
+        return Err(ZkCompressError::InvalidKey.into());
+    }
+    Ok(())
+}
+
+/// Rejects payloads over `MAX_RAW_DATA_LEN` bytes
+fn check_raw_data(data: &[u8]) -> Result<()> {
+    if data.len() > MAX_RAW_DATA_LEN {
+        msg!(
+            "Data is {} bytes, the limit is {}",
+            data.len(),
+            MAX_RAW_DATA_LEN
+        );
+        return Err(ZkCompressError::DataTooLong.into());
+    }
+    Ok(())
+}
+
+/// For account types with no delegate: only the owner gets through
+fn require_owner(owner: &Pubkey, signer: &Pubkey) -> Result<()> {
+    if owner != signer {
+        msg!("Signer is not the account's owner");
+        return Err(ZkCompressError::NotOwner.into());
+    }
+    Ok(())
+}
+
+/// Owners can't close away an account whose crank reward is still
+/// escrowed; clearing the expiry with `set_expiry` refunds it first
+fn require_no_escrow(account: &MyCompressedAccount) -> Result<()> {
+    if account.crank_reward > 0 {
+        msg!("Clear the expiry to refund the crank reward first");
+        return Err(ZkCompressError::RewardEscrowed.into());
+    }
+    Ok(())
+}
+
+/// Checks `vault` is the reward escrow for `address`, returning its bump
+fn check_reward_vault(vault: &SystemAccount, address: &[u8; 32]) -> Result<u8> {
+    let (expected, bump) = Pubkey::find_program_address(&[CRANK_REWARD_SEED, address], &crate::ID);
+    if vault.key() != expected {
+        return Err(ZkCompressError::WrongVault.into());
+    }
+    Ok(bump)
+}
+
+/// Empties `address`'s reward escrow back to the signer, its funder: only
+/// an owner deposits, and a transfer refunds before the owner changes
+fn refund_reward(accounts: &ExpiryAccounts, address: &[u8; 32], bump: u8) -> Result<()> {
+    system_program::transfer(
+        CpiContext::new_with_signer(
+            accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: accounts.vault.to_account_info(),
+                to: accounts.signer.to_account_info(),
+            },
+            &[&[CRANK_REWARD_SEED, address, &[bump]]],
+        ),
+        accounts.vault.lamports(),
+    )
+}
+
+/// Address of `owner`'s message account: `create_account`'s with an empty
+/// suffix, else the one `create_accounts_batch` derives
+fn message_address(owner: &Pubkey, seed_suffix: &[u8]) -> [u8; 32] {
+    let address_tree_pubkey = Pubkey::new_from_array(ADDRESS_TREE_V2);
+    let (address, _) = if seed_suffix.is_empty() {
+        derive_address(
+            &[b"message", owner.as_ref()],
+            &address_tree_pubkey,
+            &crate::ID,
+        )
+    } else {
+        derive_address(
+            &[b"message", owner.as_ref(), seed_suffix],
+            &address_tree_pubkey,
+            &crate::ID,
+        )
+    };
+    address
+}
+
+/// Lets the owner through, or the delegate if it holds `permission`
+fn require_authority(account: &MyCompressedAccount, signer: &Pubkey, permission: u8) -> Result<()> {
+    if account.owner == *signer
+        || (account.delegate == *signer && account.permissions & permission != 0)
+    {
+        return Ok(());
+    }
+    msg!("Signer is neither the owner nor a delegate with this permission");
+    Err(ZkCompressError::NotOwner.into())
+}
+
+#[derive(Accounts)]
+pub struct GenericAnchorAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+}
+
+/// Instructions that write a message, checked against the `MessageConfig`
+#[derive(Accounts)]
+pub struct MessageAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(seeds = [MESSAGE_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, MessageConfig>,
+}
+
+/// Admin instructions: the signer must be the program's upgrade authority
+#[derive(Accounts)]
+pub struct InitMessageConfig<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Update>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(signer.key())
+            @ ZkCompressError::NotAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + MessageConfig::INIT_SPACE,
+        seeds = [MESSAGE_CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, MessageConfig>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetMessageLimit<'info> {
+    pub signer: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::Update>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(signer.key())
+            @ ZkCompressError::NotAdmin
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(mut, seeds = [MESSAGE_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, MessageConfig>,
+}
+
+#[derive(Accounts)]
+#[instruction(seed_suffix: Vec<u8>)]
+pub struct CreateMessagePdaAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(seeds = [MESSAGE_CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, MessageConfig>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + MessagePda::INIT_SPACE,
+        seeds = [
+            b"message_pda",
+            signer.key().as_ref(),
+            &message_address(&signer.key(), &seed_suffix),
+        ],
+        bump,
+    )]
+    pub pda: Account<'info, MessagePda>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CompressPdaAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"message_pda", signer.key().as_ref(), &pda.address],
+        bump = pda.bump,
+        close = signer,
+    )]
+    pub pda: Account<'info, MessagePda>,
+}
+
+#[derive(Accounts)]
+#[instruction(proof: ValidityProof, account_meta: CompressedAccountMetaBurn)]
+pub struct DecompressPdaAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8 + MessagePda::INIT_SPACE,
+        seeds = [b"message_pda", signer.key().as_ref(), &account_meta.address],
+        bump,
+    )]
+    pub pda: Account<'info, MessagePda>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExpiryAccounts<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    /// Checked against the reward escrow address in the instruction
+    #[account(mut)]
+    pub vault: SystemAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// One account's part of a `create_accounts_batch` batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AccountCreate {
+    pub seed_suffix: Vec<u8>, // 1-MAX_SEED_SUFFIX_LEN bytes
+    pub message: String,
+}
+
+/// One account's part of an `update_accounts_batch` batch
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct AccountUpdate {
+    pub current_account: MyCompressedAccount,
+    pub account_meta: CompressedAccountMeta,
+    pub new_message: String,
+}
+
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct MyCompressedAccount {
+    pub owner: Pubkey,
+    pub message: String,
+    pub delegate: Pubkey,             // Default = none
+    pub permissions: u8,              // PERMISSION_* bits the delegate holds
+    pub expires_at_slot: Option<u64>, // None = never expires
+    pub crank_reward: u64,            // Lamports escrowed for whoever runs close_expired
+}
+
+/// Arbitrary application payload; `schema` tells readers how to decode
+/// `data`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct RawDataAccount {
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+    pub schema: u16,
+}
+
+/// One entry of the compressed KV store, addressed by `[b"kv", owner, key]`
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct KvEntry {
+    pub owner: Pubkey,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+}
+
+/// Program-wide message settings at `[MESSAGE_CONFIG_SEED]`
+#[account]
+#[derive(InitSpace)]
+pub struct MessageConfig {
+    pub max_message_len: u16, // At most MAX_MESSAGE_LEN
+    pub bump: u8,
+}
+
+/// A message held in a regular account at `[b"message_pda", owner,
+/// address]`, for readers that can't take validity proofs; `address` is the
+/// compressed account it came from or the one it's created for, so an owner
+/// can hold several. `create_message_pda` or `decompress_to_pda` creates it
+/// and `compress_pda` moves it into compressed state.
+#[account]
+#[derive(InitSpace)]
+pub struct MessagePda {
+    pub owner: Pubkey,
+    pub address: [u8; 32],
+    #[max_len(MAX_SEED_SUFFIX_LEN)]
+    pub seed_suffix: Vec<u8>, // Where compress_pda migrates it
+    #[max_len(MAX_MESSAGE_LEN)]
+    pub message: String,
+    pub delegate: Pubkey, // Carried to and from the compressed account
+    pub permissions: u8,
+    pub bump: u8,
+}
+
+/// One page of an append-only log at `[b"log", owner, page]`. Pages fill
+/// in order, so the highest page is the only one still taking entries.
+#[event]
+#[derive(Clone, Debug, Default, LightDiscriminator)]
+pub struct LogAccount {
+    pub owner: Pubkey,
+    pub page: u32,
+    pub entries: Vec<LogEntry>, // Oldest first, at most MAX_LOG_ENTRIES
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct LogEntry {
+    pub timestamp: i64, // Unix time of the append
+    pub data: Vec<u8>,
+}
+
+/// The latest log page and where it sits in the state tree
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LogPageRef {
+    pub log: LogAccount,
+    pub meta: CompressedAccountMeta,
+}
+
+/// Emitted when `close_expired` cleans up an account
+#[event]
+pub struct AccountExpired {
+    pub address: [u8; 32],
+    pub cranker: Pubkey,
+    pub reward: u64,
+}
+
+/// Emitted when `transfer_ownership` hands an account to a new owner
+#[event]
+pub struct OwnershipTransferred {
+    pub address: [u8; 32],
+    pub previous_owner: Pubkey,
+    pub new_owner: Pubkey,
+}
+
+#[error_code]
+pub enum ZkCompressError {
+    #[msg("Only the account's owner, or a delegate allowed to, can do this")]
+    NotOwner,
+    #[msg("Permissions hold bits outside PERMISSION_ALL")]
+    InvalidPermissions,
+    #[msg("Message exceeds the configured limit; each byte adds instruction data and hashing cost to every write")]
+    MessageTooLong,
+    #[msg("Data exceeds MAX_RAW_DATA_LEN")]
+    DataTooLong,
+    #[msg("KV keys must be 1-MAX_KV_KEY_LEN bytes")]
+    InvalidKey,
+    #[msg("Log entries must be at most MAX_LOG_ENTRY_LEN bytes")]
+    LogEntryTooLong,
+    #[msg("Expiry must be a future slot, and a crank reward needs one")]
+    InvalidExpiry,
+    #[msg("Account has no expiry or it hasn't passed yet")]
+    NotExpired,
+    #[msg("Vault isn't this account's crank reward escrow")]
+    WrongVault,
+    #[msg("Account still holds an escrowed crank reward")]
+    RewardEscrowed,
+    #[msg("Message limits must be 1-MAX_MESSAGE_LEN bytes")]
+    InvalidMessageLimit,
+    #[msg("Only the program's upgrade authority can do this")]
+    NotAdmin,
 }
@@ -15,9 +15,20 @@ import {
   sleep,
   SystemAccountMetaConfig,
   featureFlags,
+  selectStateTreeInfo,
   VERSION,
 } from "@lightprotocol/stateless.js";
+import {
+  CompressedTokenProgram,
+  createMint,
+  getTokenPoolInfos,
+  mintTo,
+  packCompressedTokenAccounts,
+  selectMinCompressedTokenAccountsForTransfer,
+  selectTokenPoolInfo,
+} from "@lightprotocol/compressed-token";
 import * as assert from "assert";
+import { createHash, randomBytes } from "crypto";
 
 // Force V2 mode
 (featureFlags as any).version = VERSION.V2;
@@ -47,6 +58,10 @@ const CELL_EMPTY = 0;
 const CELL_SHIP = 1;
 const CELL_HIT = 2;
 const CELL_MISS = 3;
+const CELL_BLOCKED = 4;
+
+// Lamports each player locks at create/join
+const GAME_DEPOSIT_LAMPORTS = 10_000_000;
 
 async function generateBoardProof(
   noir: any,
@@ -77,6 +92,446 @@ async function generateBoardProof(
   };
 }
 
+// The coder decodes with IDL (snake_case) field names, but instruction
+// arguments are encoded with camelCase names
+function camelizeKeys(decoded: any): any {
+  if (Array.isArray(decoded)) return decoded.map(camelizeKeys);
+  if (decoded === null || typeof decoded !== "object" || decoded.constructor !== Object) {
+    return decoded;
+  }
+  const result: any = {};
+  for (const [key, value] of Object.entries(decoded)) {
+    result[key.replace(/_([a-z0-9])/g, (_, c) => c.toUpperCase())] = camelizeKeys(value);
+  }
+  return result;
+}
+
+// Mirrors `derive_game_address` in the program: ["battleship", creator, game_id]
+function deriveGameAddress(
+  creator: web3.PublicKey,
+  gameId: number,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const gameIdBytes = Buffer.alloc(8);
+  gameIdBytes.writeBigUInt64LE(BigInt(gameId));
+  const seed = deriveAddressSeedV2([Buffer.from("battleship"), creator.toBytes(), gameIdBytes]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_move_log_address` in the program: ["move_log", game_address]
+function deriveMoveLogAddress(
+  gameAddress: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("move_log"), gameAddress.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_player_view_address` in the program: ["view", game_address, seat]
+function derivePlayerViewAddress(
+  gameAddress: web3.PublicKey,
+  seat: number,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("view"), gameAddress.toBytes(), Buffer.from([seat])]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_game_result_address` in the program: ["result", game_address]
+function deriveGameResultAddress(
+  gameAddress: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("result"), gameAddress.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_profile_address` in the program: ["profile", owner]
+function deriveProfileAddress(
+  owner: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("profile"), owner.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_protocol_config_address` in the program: ["protocol_config"]
+function deriveProtocolConfigAddress(addressTree: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return deriveAddressV2(deriveAddressSeedV2([Buffer.from("protocol_config")]), addressTree, programId);
+}
+
+// Mirrors `derive_vault_address` in the program: ["vault", game_address]
+function deriveVaultAddress(gameAddress: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("vault"), gameAddress.toBytes()], programId)[0];
+}
+
+// Builds the CompressedAccountMeta for an account fetched from the indexer
+function toAccountMeta(
+  account: any,
+  address: web3.PublicKey,
+  rootIndex: number,
+  remainingAccounts: PackedAccounts,
+  outputStateTree: web3.PublicKey
+) {
+  return {
+    treeInfo: {
+      rootIndex,
+      proveByIndex: false,
+      merkleTreePubkeyIndex: remainingAccounts.insertOrGet(account.treeInfo.tree),
+      queuePubkeyIndex: remainingAccounts.insertOrGet(account.treeInfo.queue),
+      leafIndex: account.leafIndex,
+    },
+    address: Array.from(address.toBytes()),
+    outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+  };
+}
+
+// Loads the ProtocolConfig, which every instruction but the admin's takes
+// read-only; `configInput` goes in the validity proof's inputs
+async function loadProtocolConfig(program: Program<Battleship>, rpc: Rpc) {
+  const configAddress = deriveProtocolConfigAddress(new web3.PublicKey(batchAddressTree), program.programId);
+  const configAccount = await rpc.getCompressedAccount(bn(configAddress.toBytes()));
+  return {
+    configAccount,
+    configInput: { hash: configAccount!.hash, tree: configAccount!.treeInfo.tree, queue: configAccount!.treeInfo.queue },
+  };
+}
+
+// Builds the read-only ProtocolConfigRef from the config as fetched
+function toProtocolConfigRef(
+  coder: anchor.BorshCoder,
+  configAccount: any,
+  rootIndex: number,
+  remainingAccounts: PackedAccounts,
+  outputStateTree: web3.PublicKey
+) {
+  const { outputStateTreeIndex: _, ...meta } = toAccountMeta(
+    configAccount, new web3.PublicKey(configAccount.address), rootIndex, remainingAccounts, outputStateTree
+  );
+  return { config: camelizeKeys(coder.types.decode("ProtocolConfig", configAccount.data.data)), meta };
+}
+
+// Creates the ProtocolConfig, fee-free, unless an earlier run left one
+async function sendInitProtocolConfig(
+  program: Program<Battleship>,
+  rpc: Rpc,
+  admin: web3.Keypair,
+  outputStateTree: web3.PublicKey
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const configAddress = deriveProtocolConfigAddress(addressTree, program.programId);
+  if (await rpc.getCompressedAccount(bn(configAddress.toBytes()))) return;
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [],
+    [{ tree: addressTree, queue: addressTree, address: bn(configAddress.toBytes()) }]
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const freeRake = { feeBps: 0, minWager: new anchor.BN(0), maxWager: new anchor.BN(0) };
+
+  const tx = await program.methods
+    .initProtocolConfig(
+      { 0: proofRpcResult.compressedProof },
+      { rootIndex: proofRpcResult.rootIndices[0], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+      remainingAccounts.insertOrGet(outputStateTree),
+      [freeRake, freeRake, freeRake],
+      admin.publicKey,
+      new anchor.BN(0)
+    )
+    .accounts({ signer: admin.publicKey, programData: programDataAddress(program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([admin])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(admin);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [admin]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Flips the config's pause switch as its admin
+async function sendPauseProtocol(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  paused: boolean
+): Promise<void> {
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+  const tx = await program.methods
+    .pauseProtocol(
+      { 0: proofRpcResult.compressedProof },
+      camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data)),
+      toAccountMeta(
+        configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      paused
+    )
+    .accounts({ signer: signer.publicKey })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Hands the pause key to `admin` and pins `turnTimeoutSlots` into games
+// created from now on, signed by the upgrade authority
+async function sendUpdateConfig(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  admin: web3.PublicKey,
+  turnTimeoutSlots: number
+): Promise<void> {
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+  const tx = await program.methods
+    .updateConfig(
+      { 0: proofRpcResult.compressedProof },
+      camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data)),
+      toAccountMeta(
+        configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      admin,
+      new anchor.BN(turnTimeoutSlots),
+      new anchor.BN(0)
+    )
+    .accounts({ signer: signer.publicKey, programData: programDataAddress(program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Every instruction but the admin's reads the config, so it's created once
+// before any suite runs
+before(async () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const rpc = createRpc("http://127.0.0.1:8899", "http://127.0.0.1:8784", "http://127.0.0.1:3001", { commitment: "confirmed" });
+  await sendInitProtocolConfig(program, rpc, loadAdminKeypair(), defaultTestStateTreeAccounts().merkleTree);
+});
+
+// Creates `owner`'s PlayerProfile, which games they create or join charge
+// an active game to, optionally naming the player who referred them
+async function sendCreateProfile(
+  program: Program<Battleship>,
+  rpc: Rpc,
+  owner: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  referrer: web3.PublicKey | null = null
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const profileAddress = deriveProfileAddress(owner.publicKey, addressTree, program.programId);
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [configInput],
+    [{ tree: addressTree, queue: addressTree, address: bn(profileAddress.toBytes()) }]
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const protocol = toProtocolConfigRef(
+    new anchor.BorshCoder(idl as anchor.Idl), configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[1],
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+  const tx = await program.methods
+    .createProfile({ 0: proofRpcResult.compressedProof }, packedAddressTreeInfo, outputStateTreeIndex, referrer, protocol)
+    .accounts({ signer: owner.publicKey })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([owner])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(owner);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [owner]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return profileAddress;
+}
+
+// Builds the ActiveGameSlot every game-creating and -joining instruction
+// charges from `loadProfile`'s accounts, whose `slotInputs` start at
+// `rootIndex` in the validity proof
+function toActiveGameSlot(
+  coder: anchor.BorshCoder,
+  loaded: { profileAccount: any; configAccount: any },
+  rootIndex: number,
+  remainingAccounts: PackedAccounts,
+  outputStateTree: web3.PublicKey
+) {
+  return {
+    profile: camelizeKeys(coder.types.decode("PlayerProfile", loaded.profileAccount.data.data)),
+    profileMeta: toAccountMeta(
+      loaded.profileAccount,
+      new web3.PublicKey(loaded.profileAccount.address),
+      rootIndex,
+      remainingAccounts,
+      outputStateTree
+    ),
+    protocol: toProtocolConfigRef(coder, loaded.configAccount, rootIndex + 1, remainingAccounts, outputStateTree),
+  };
+}
+
+// Loads `player`'s profile and the protocol config, which games they create
+// or join take as the ActiveGameSlot, and the address of the game they
+// create next
+async function loadProfile(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  player: web3.PublicKey
+) {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const profileAccount = await rpc.getCompressedAccount(
+    bn(deriveProfileAddress(player, addressTree, program.programId).toBytes())
+  );
+  // The program takes the id from the creator's profile
+  const gameId = coder.types.decode("PlayerProfile", profileAccount!.data!.data).next_game_id.toNumber();
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  return {
+    profileAccount,
+    configAccount,
+    slotInputs: [
+      { hash: profileAccount!.hash, tree: profileAccount!.treeInfo.tree, queue: profileAccount!.treeInfo.queue },
+      configInput,
+    ],
+    gameAddress: deriveGameAddress(player, gameId, addressTree, program.programId),
+  };
+}
+
+// Fires a shot, updating the game and its move log in one transaction
+async function sendAttack(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  attackX: number,
+  attackY: number,
+  session: { account: any; address: web3.PublicKey } | null = null,
+  withViews = false
+): Promise<string> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+  const viewAddresses = withViews
+    ? [1, 2].map((seat) => derivePlayerViewAddress(gameAddress, seat, addressTree, program.programId))
+    : [];
+  const viewAccounts = await Promise.all(
+    viewAddresses.map((address) => rpc.getCompressedAccount(bn(address.toBytes())))
+  );
+
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+  const state = camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  const log = camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data));
+
+  const inputs = [
+    { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+    { hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue },
+    ...viewAccounts.map((view) => ({ hash: view!.hash, tree: view!.treeInfo.tree, queue: view!.treeInfo.queue })),
+  ];
+  if (session) {
+    inputs.push({
+      hash: session.account.hash,
+      tree: session.account.treeInfo.tree,
+      queue: session.account.treeInfo.queue,
+    });
+  }
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  inputs.push(configInput);
+  const proofRpcResult = await rpc.getValidityProofV0(inputs, []);
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const logMeta = toAccountMeta(
+    logAccount, logAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+  const [viewMetaA, viewMetaB] = viewAccounts.map((view, i) =>
+    toAccountMeta(view, viewAddresses[i], proofRpcResult.rootIndices[2 + i], remainingAccounts, outputStateTree)
+  );
+  const views = withViews
+    ? {
+        viewA: camelizeKeys(coder.types.decode("PlayerView", viewAccounts[0]!.data!.data)),
+        metaA: viewMetaA,
+        viewB: camelizeKeys(coder.types.decode("PlayerView", viewAccounts[1]!.data!.data)),
+        metaB: viewMetaB,
+      }
+    : null;
+
+  let sessionAuth = null;
+  if (session) {
+    const { outputStateTreeIndex: _, ...meta } = toAccountMeta(
+      session.account, session.address, proofRpcResult.rootIndices[2 + viewAccounts.length], remainingAccounts, outputStateTree
+    );
+    sessionAuth = {
+      account: camelizeKeys(coder.types.decode("SessionKey", session.account.data.data)),
+      meta,
+    };
+  }
+  const protocol = toProtocolConfigRef(
+    coder, configAccount, proofRpcResult.rootIndices[inputs.length - 1], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .attack(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: state,
+        gameMeta: accountMeta,
+        log,
+        logMeta,
+        views,
+      },
+      attackX,
+      attackY,
+      sessionAuth,
+      protocol
+    )
+    .accounts({ signer: signer.publicKey })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  const sig = await rpc.sendTransaction(tx, [signer]);
+  await confirmTx(rpc, sig);
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return sig;
+}
+
 describe("battleship_1v1", () => {
   const program = anchor.workspace.Battleship as Program<Battleship>;
   const coder = new anchor.BorshCoder(idl as anchor.Idl);
@@ -93,7 +548,7 @@ describe("battleship_1v1", () => {
   let noir: any;
   let backend: any;
 
-  const GAME_ID = Date.now();
+  const GAME_ID = 0; // First id from A's fresh profile
 
   before(async () => {
     // Initialize Noir with UltraHonk
@@ -119,53 +574,45 @@ describe("battleship_1v1", () => {
     outputStateTree = defaultTestStateTreeAccounts().merkleTree;
     addressTree = new web3.PublicKey(batchAddressTree);
 
-    // Derive game address
-    const gameIdBytes = Buffer.alloc(8);
-    gameIdBytes.writeBigUInt64LE(BigInt(GAME_ID));
-    const seed = deriveAddressSeedV2([Buffer.from("battleship"), gameIdBytes]);
-    gameAddress = deriveAddressV2(seed, addressTree, new web3.PublicKey(program.idl.address));
+    // Derive game address (namespaced by creator)
+    gameAddress = deriveGameAddress(signerA.publicKey, GAME_ID, addressTree, program.programId);
+
+    // create_game and join_game charge each player's profile
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
 
     console.log("Game ID:", GAME_ID);
     console.log("Game Address:", gameAddress.toBase58());
   });
 
   // Helper to decode GameState
-  const decodeGameState = (data: Buffer) => {
-    const decoded = coder.types.decode("GameState", data);
-    return {
-      gameId: decoded.game_id,
-      playerA: decoded.player_a,
-      playerB: decoded.player_b,
-      currentTurn: decoded.current_turn,
-      gameStatus: decoded.game_status,
-      // Player A
-      gridA: decoded.grid_a,
-      boardHashA: decoded.board_hash_a,
-      hitsA: decoded.hits_a,
-      // Player B
-      gridB: decoded.grid_b,
-      boardHashB: decoded.board_hash_b,
-      hitsB: decoded.hits_b,
-    };
-  };
+  const decodeGameState = (data: Buffer) => camelizeKeys(coder.types.decode("GameState", data));
 
   it("1. Player A Creates Game", async () => {
     const shipStartX = 0;
     const shipStartY = 0;
     const isHorizontal = true;
 
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const creator = await loadProfile(program, coder, rpc, signerA.publicKey);
     const proofRpcResult = await rpc.getValidityProofV0(
-      [],
-      [{ tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) }]
+      creator.slotInputs,
+      [
+        { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
     );
 
     const systemAccountConfig = new SystemAccountMetaConfig(program.programId);
     const remainingAccounts = new PackedAccounts();
     remainingAccounts.addSystemAccountsV2(systemAccountConfig);
 
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
     const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
     const packedAddressTreeInfo = {
-      rootIndex: proofRpcResult.rootIndices[0],
+      rootIndex: proofRpcResult.rootIndices[2],
       addressMerkleTreePubkeyIndex,
       addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
     };
@@ -183,15 +630,17 @@ describe("battleship_1v1", () => {
     const tx = await program.methods
       .createGame(
         proof,
-        packedAddressTreeInfo,
-        outputStateTreeIndex,
-        new anchor.BN(GAME_ID),
-        shipStartX,
-        shipStartY,
-        isHorizontal,
-        boardHashA_val
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+        { ship: { x: shipStartX, y: shipStartY, isHorizontal }, boardHash: boardHashA_val },
+        {
+          mode: 0, // WAGER_MODE_CASUAL
+          tags: { region: 0, speed: 0, gridSize: 5 }, // Any region, standard speed, classic grid
+          challenge: null,
+        },
+        slot,
+        null
       )
-      .accounts({ signer: signerA.publicKey })
+      .accounts({ signer: signerA.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
       .preInstructions([computeBudgetIx])
       .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
       .signers([signerA])
@@ -225,8 +674,12 @@ describe("battleship_1v1", () => {
     const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
     const state = decodeGameState(account!.data!.data);
 
+    const joiner = await loadProfile(program, coder, rpc, signerB.publicKey);
     const proofRpcResult = await rpc.getValidityProofV0(
-      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
+      [
+        { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+        ...joiner.slotInputs,
+      ],
       []
     );
 
@@ -259,14 +712,19 @@ describe("battleship_1v1", () => {
     const tx = await program.methods
       .joinGame(
         { 0: proofRpcResult.compressedProof },
-        state,
-        accountMeta,
-        shipStartX,
-        shipStartY,
-        isHorizontal,
-        boardHashB_val
+        { game: state, meta: accountMeta },
+        { ship: { x: shipStartX, y: shipStartY, isHorizontal }, boardHash: boardHashB_val },
+        {
+          coinFlip: false, // Player A starts
+          filter: null, // No lobby filter
+          banCheck: null, // No wager, so no ban check
+        },
+        toActiveGameSlot(
+          coder, joiner, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+        ),
+        null
       )
-      .accounts({ signer: signerB.publicKey })
+      .accounts({ signer: signerB.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
       .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
       .signers([signerB])
       .transaction();
@@ -290,57 +748,9 @@ describe("battleship_1v1", () => {
 
   it("3. Player A Attacks B (Hit)", async () => {
     // A attacks (4,0) -> Should hit B's ship
-    const attackX = 4;
-    const attackY = 0;
-
-    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
-    const state = decodeGameState(account!.data!.data);
-
-    const proofRpcResult = await rpc.getValidityProofV0(
-      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
-      []
-    );
-
-    const systemAccountConfig = new SystemAccountMetaConfig(program.programId);
-    const remainingAccounts = new PackedAccounts();
-    remainingAccounts.addSystemAccountsV2(systemAccountConfig);
-    const merkleTreeIndex = remainingAccounts.insertOrGet(account!.treeInfo.tree);
-    const queueIndex = remainingAccounts.insertOrGet(account!.treeInfo.queue);
-    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
-
-    const accountMeta = {
-      treeInfo: {
-        rootIndex: proofRpcResult.rootIndices[0],
-        proveByIndex: false,
-        merkleTreePubkeyIndex: merkleTreeIndex,
-        queuePubkeyIndex: queueIndex,
-        leafIndex: account!.leafIndex,
-      },
-      address: Array.from(gameAddress.toBytes()),
-      outputStateTreeIndex,
-    };
-
-    const tx = await program.methods
-      .attack(
-        { 0: proofRpcResult.compressedProof },
-        state,
-        accountMeta,
-        attackX,
-        attackY
-      )
-      .accounts({ signer: signerA.publicKey })
-      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
-      .signers([signerA])
-      .transaction();
-
-    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
-    tx.sign(signerA);
-    const sig = await rpc.sendTransaction(tx, [signerA]);
-    await confirmTx(rpc, sig);
+    const sig = await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
     console.log("Attack (A->B) TX:", sig);
 
-    await rpc.confirmTransactionIndexed(await rpc.getSlot());
-
     const updatedAccount = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
     const newState = decodeGameState(updatedAccount!.data!.data);
 
@@ -352,61 +762,17 @@ describe("battleship_1v1", () => {
 
   it("4. Player B Attacks A (Miss)", async () => {
     // B attacks (4,4) -> Should miss A (A is at 0,0 - 3,0)
-    const attackX = 4;
-    const attackY = 4;
-
-    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
-    const state = decodeGameState(account!.data!.data);
-
-    const proofRpcResult = await rpc.getValidityProofV0(
-      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
-      []
-    );
-
-    const systemAccountConfig = new SystemAccountMetaConfig(program.programId);
-    const remainingAccounts = new PackedAccounts();
-    remainingAccounts.addSystemAccountsV2(systemAccountConfig);
-    const merkleTreeIndex = remainingAccounts.insertOrGet(account!.treeInfo.tree);
-    const queueIndex = remainingAccounts.insertOrGet(account!.treeInfo.queue);
-    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
-
-    const accountMeta = {
-      treeInfo: {
-        rootIndex: proofRpcResult.rootIndices[0],
-        proveByIndex: false,
-        merkleTreePubkeyIndex: merkleTreeIndex,
-        queuePubkeyIndex: queueIndex,
-        leafIndex: account!.leafIndex,
-      },
-      address: Array.from(gameAddress.toBytes()),
-      outputStateTreeIndex,
-    };
-
-    const tx = await program.methods
-      .attack(
-        { 0: proofRpcResult.compressedProof },
-        state,
-        accountMeta,
-        attackX,
-        attackY
-      )
-      .accounts({ signer: signerB.publicKey })
-      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
-      .signers([signerB])
-      .transaction();
-
-    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
-    tx.sign(signerB);
-    const sig = await rpc.sendTransaction(tx, [signerB]);
-    await confirmTx(rpc, sig);
+    const sig = await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 4);
     console.log("Attack (B->A) TX:", sig);
 
-    await rpc.confirmTransactionIndexed(await rpc.getSlot());
-
     const updatedAccount = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
     const newState = decodeGameState(updatedAccount!.data!.data);
 
     assert.strictEqual(newState.gridA[24], CELL_MISS); // (4,4) is index 24
+    assert.deepStrictEqual(
+      [newState.lastAttackBy, newState.lastAttackX, newState.lastAttackY, newState.lastAttackResult],
+      [2, 4, 4, CELL_MISS]
+    );
     assert.strictEqual(newState.currentTurn, 1); // Switched to A
     console.log("✅ B Missed A. Turn Switched to A.");
   });
@@ -421,110 +787,108 @@ describe("battleship_1v1", () => {
       const [targetX, targetY] = targets[i];
 
       // 1. Player A Attacks (Hit)
-      let account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
-      let state = decodeGameState(account!.data!.data);
-
-      let proofRpcResult = await rpc.getValidityProofV0(
-        [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
-        []
-      );
-      let systemAccountConfig = new SystemAccountMetaConfig(program.programId);
-      let remainingAccounts = new PackedAccounts();
-      remainingAccounts.addSystemAccountsV2(systemAccountConfig);
-      let merkleTreeIndex = remainingAccounts.insertOrGet(account!.treeInfo.tree);
-      let queueIndex = remainingAccounts.insertOrGet(account!.treeInfo.queue);
-      let outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
-      let accountMeta = {
-        treeInfo: {
-          rootIndex: proofRpcResult.rootIndices[0],
-          proveByIndex: false,
-          merkleTreePubkeyIndex: merkleTreeIndex,
-          queuePubkeyIndex: queueIndex,
-          leafIndex: account!.leafIndex,
-        },
-        address: Array.from(gameAddress.toBytes()),
-        outputStateTreeIndex,
-      };
-
-      let tx = await program.methods
-        .attack(
-          { 0: proofRpcResult.compressedProof },
-          state,
-          accountMeta,
-          targetX,
-          targetY
-        )
-        .accounts({ signer: signerA.publicKey })
-        .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
-        .signers([signerA])
-        .transaction();
-
-      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
-      tx.sign(signerA);
-      let sig = await rpc.sendTransaction(tx, [signerA]);
-      await confirmTx(rpc, sig);
-      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+      await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, targetX, targetY);
 
       // If this was the last hit, check Game Over
       if (i === targets.length - 1) {
         const finalAccount = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
         const finalState = decodeGameState(finalAccount!.data!.data);
         assert.strictEqual(finalState.gameStatus, 2); // 2 = A Won
+        assert.deepStrictEqual(
+          [finalState.statsA.shots, finalState.statsA.hits, finalState.statsA.misses],
+          [4, 4, 0]
+        );
+        assert.deepStrictEqual(
+          [finalState.statsB.shots, finalState.statsB.hits, finalState.statsB.misses],
+          [3, 0, 3]
+        );
         console.log("🎉 Player A Wins! Game Status = 2");
         return; // Done
       }
 
       // 2. Player B Attacks (Miss) - to give turn back to A
-      const missX = i + 1;
-      const missY = 4;
+      await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, i + 1, 4);
+    }
+  });
 
-      account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
-      state = decodeGameState(account!.data!.data);
+  it("6. Move Log Replays To The Final State", async () => {
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+    const log = camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data));
 
-      proofRpcResult = await rpc.getValidityProofV0(
-        [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
-        []
-      );
-      systemAccountConfig = new SystemAccountMetaConfig(program.programId);
-      remainingAccounts = new PackedAccounts();
-      remainingAccounts.addSystemAccountsV2(systemAccountConfig);
-      merkleTreeIndex = remainingAccounts.insertOrGet(account!.treeInfo.tree);
-      queueIndex = remainingAccounts.insertOrGet(account!.treeInfo.queue);
-      outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
-      accountMeta = {
-        treeInfo: {
-          rootIndex: proofRpcResult.rootIndices[0],
-          proveByIndex: false,
-          merkleTreePubkeyIndex: merkleTreeIndex,
-          queuePubkeyIndex: queueIndex,
-          leafIndex: account!.leafIndex,
-        },
-        address: Array.from(gameAddress.toBytes()),
-        outputStateTreeIndex,
-      };
+    // 4 hits by A, 3 misses by B
+    assert.strictEqual(log.moves.length, 7);
+    assert.strictEqual(decodeGameState(account!.data!.data).moveCount, 7);
+
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+        { hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue },
+        configInput,
+      ],
+      []
+    );
 
-      tx = await program.methods
-        .attack(
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _g, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const { outputStateTreeIndex: _l, ...logMeta } = toAccountMeta(
+      logAccount, logAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+
+    const sendReplay = async (shipsA: any[], shipsB: any[]) => {
+      const tx = await program.methods
+        .verifyReplay(
           { 0: proofRpcResult.compressedProof },
-          state,
-          accountMeta,
-          missX,
-          missY
+          { game: decodeGameState(account!.data!.data), meta: gameMeta },
+          { log, meta: logMeta },
+          shipsA,
+          shipsB,
+          protocol
         )
         .accounts({ signer: signerB.publicKey })
+        .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
         .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
         .signers([signerB])
         .transaction();
 
       tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
       tx.sign(signerB);
-      sig = await rpc.sendTransaction(tx, [signerB]);
+      const sig = await rpc.sendTransaction(tx, [signerB]);
       await confirmTx(rpc, sig);
-      await rpc.confirmTransactionIndexed(await rpc.getSlot());
-    }
+      return sig;
+    };
+
+    // The placements from tests 2 and 3
+    const shipsA = [{ x: 0, y: 0, isHorizontal: true }];
+    const shipsB = [{ x: 4, y: 0, isHorizontal: false }];
+
+    // Moving A's ship onto the row B fired at makes B's misses hits
+    await assert.rejects(sendReplay([{ x: 0, y: 4, isHorizontal: true }], shipsB));
+    // So is a fleet that doesn't fit the rules
+    await assert.rejects(sendReplay([], shipsB));
+
+    const sig = await sendReplay(shipsA, shipsB);
+    console.log("✅ Replay verified:", sig);
+  });
+
+  it("7. Same game_id Under Different Creators Does Not Collide", async () => {
+    const addressForA = deriveGameAddress(signerA.publicKey, GAME_ID, addressTree, program.programId);
+    const addressForB = deriveGameAddress(signerB.publicKey, GAME_ID, addressTree, program.programId);
+
+    assert.ok(addressForA.equals(gameAddress));
+    assert.ok(!addressForA.equals(addressForB), "Addresses must be namespaced per creator");
+    console.log("✅ Game addresses are namespaced per creator.");
   });
 
-  it("6. ZK Privacy Demo: Ship Coordinates Are Hidden", async () => {
+  it("8. ZK Privacy Demo: Ship Coordinates Are Hidden", async () => {
     console.log("\n" + "=".repeat(60));
     console.log("🔒 ZK PRIVACY DEMONSTRATION");
     console.log("=".repeat(60));
@@ -576,4 +940,9428 @@ describe("battleship_1v1", () => {
 
     console.log("✅ ZK Privacy Demo Complete!");
   });
+
+  it("9. Deposits Are Refunded After A Clean Finish", async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const vault = deriveVaultAddress(gameAddress, program.programId);
+    const balanceB = await rpc.getBalance(signerB.publicKey);
+    assert.strictEqual(await rpc.getBalance(vault), 2 * GAME_DEPOSIT_LAMPORTS);
+
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+
+    const tx = await program.methods
+      .claimDeposits({ 0: proofRpcResult.compressedProof }, decodeGameState(account!.data!.data), gameMeta, protocol)
+      .accounts({ signer: signerA.publicKey, vault, playerA: signerA.publicKey, playerB: signerB.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerA])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerA);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerA]));
+
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    assert.strictEqual(await rpc.getBalance(signerB.publicKey), balanceB + GAME_DEPOSIT_LAMPORTS);
+    console.log("✅ Both deposits refunded.");
+  });
+
+  it("10. Win Is Verifiable By Other Programs", async () => {
+    const sendVerifyWin = async (winner: web3.PublicKey) => {
+      const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+        []
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+        account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      );
+      const protocol = toProtocolConfigRef(
+        coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+      );
+
+      const tx = await program.methods
+        .verifyWin({ 0: proofRpcResult.compressedProof }, decodeGameState(account!.data!.data), gameMeta, winner, protocol)
+        .accounts({ signer: signerB.publicKey })
+        .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+        .signers([signerB])
+        .transaction();
+
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(signerB);
+      await confirmTx(rpc, await rpc.sendTransaction(tx, [signerB]));
+    };
+
+    await sendVerifyWin(signerA.publicKey);
+    await assert.rejects(sendVerifyWin(signerB.publicKey));
+    console.log("✅ Only the real winner verifies.");
+  });
+
+  it("11. Game Result Is Recorded Once", async () => {
+    const resultAddress = deriveGameResultAddress(gameAddress, addressTree, program.programId);
+    const sendRecord = async () => {
+      const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+        [{ tree: addressTree, queue: addressTree, address: bn(resultAddress.toBytes()) }]
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const { outputStateTreeIndex, ...gameMeta } = toAccountMeta(
+        account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      );
+      const protocol = toProtocolConfigRef(
+        coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+      );
+      const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+      const packedAddressTreeInfo = {
+        rootIndex: proofRpcResult.rootIndices[2],
+        addressMerkleTreePubkeyIndex,
+        addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+      };
+
+      const tx = await program.methods
+        .recordGameResult(
+          { 0: proofRpcResult.compressedProof },
+          { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+          { game: decodeGameState(account!.data!.data), meta: gameMeta },
+          { season: null, profiles: null, rivalry: null, stats: null },
+          protocol
+        )
+        .accounts({ signer: signerB.publicKey })
+        .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+        .signers([signerB])
+        .transaction();
+
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(signerB);
+      await confirmTx(rpc, await rpc.sendTransaction(tx, [signerB]));
+      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    };
+
+    await sendRecord();
+    const resultAccount = await rpc.getCompressedAccount(bn(resultAddress.toBytes()));
+    const result = camelizeKeys(coder.types.decode("GameResult", resultAccount!.data!.data));
+    assert.ok(result.winner.equals(signerA.publicKey));
+    assert.ok(result.loser.equals(signerB.publicKey));
+    assert.strictEqual(result.moveCount, 7);
+
+    await assert.rejects(sendRecord());
+    console.log("✅ Result attestation written once.");
+  });
+});
+
+describe("battleship_pve", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let player: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  let hotKey: web3.Keypair;
+  let sessionAddress: web3.PublicKey;
+
+  before(async () => {
+    player = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(player.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, player, outputStateTree);
+  });
+
+  it("1. Player Creates PvE Game", async () => {
+    const creator = await loadProfile(program, coder, rpc, player.publicKey);
+    gameAddress = creator.gameAddress;
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      creator.slotInputs,
+      [
+        { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[2],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    const tx = await program.methods
+      .createPveGame(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array(32).fill(1) },
+        slot
+      )
+      .accounts({ signer: player.publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([player])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(player);
+    const sig = await rpc.sendTransaction(tx, [player]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = coder.types.decode("GameState", account!.data!.data);
+
+    assert.strictEqual(state.game_status, 1); // Active immediately
+    assert.strictEqual(state.game_mode, 1); // PvE
+    assert.ok(state.player_b.equals(program.programId)); // Bot seat
+    assert.strictEqual(
+      state.grid_b.filter((c: number) => c === CELL_SHIP).length,
+      SHIP_LENGTH
+    );
+    console.log("✅ PvE Game Created.");
+  });
+
+  it("2. Bot Fires Back After Player Attack", async () => {
+    await sendAttack(program, coder, rpc, player, gameAddress, outputStateTree, 2, 2);
+
+    const updated = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const newState = coder.types.decode("GameState", updated!.data!.data);
+
+    const botShots = newState.grid_a.filter((c: number) => c === CELL_HIT || c === CELL_MISS).length;
+    assert.strictEqual(botShots, 1, "Bot should have fired exactly once");
+    assert.strictEqual(newState.current_turn, 1, "Turn returns to the player");
+    console.log("✅ Bot fired back. Player's turn again.");
+  });
+
+  it("3. Session Key Attacks On The Player's Behalf", async () => {
+    hotKey = new web3.Keypair();
+    await rpc.requestAirdrop(hotKey.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    const seed = deriveAddressSeedV2([
+      Buffer.from("session"),
+      player.publicKey.toBytes(),
+      hotKey.publicKey.toBytes(),
+    ]);
+    sessionAddress = deriveAddressV2(seed, addressTree, program.programId);
+
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(sessionAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    const tx = await program.methods
+      .authorizeSessionKey(
+        { 0: proofRpcResult.compressedProof },
+        {
+          rootIndex: proofRpcResult.rootIndices[1],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        outputStateTreeIndex,
+        hotKey.publicKey,
+        new anchor.BN((await rpc.getSlot()) + 10_000),
+        protocol
+      )
+      .accounts({ signer: player.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([player])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(player);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [player]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const sessionAccount = await rpc.getCompressedAccount(bn(sessionAddress.toBytes()));
+    await sendAttack(program, coder, rpc, hotKey, gameAddress, outputStateTree, 3, 3, {
+      account: sessionAccount,
+      address: sessionAddress,
+    });
+
+    const updated = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = coder.types.decode("GameState", updated!.data!.data);
+    assert.notStrictEqual(state.grid_b[18], CELL_EMPTY); // (3,3) attacked
+    console.log("✅ Hot key played a move for the player.");
+  });
+
+  it("4. A Revoked Session Key Can't Play", async () => {
+    const sessionAccount = await rpc.getCompressedAccount(bn(sessionAddress.toBytes()));
+    const sendRevoke = async (signer: web3.Keypair) => {
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [
+          { hash: sessionAccount!.hash, tree: sessionAccount!.treeInfo.tree, queue: sessionAccount!.treeInfo.queue },
+          configInput,
+        ],
+        []
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const meta = toAccountMeta(
+        sessionAccount, sessionAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      );
+      const protocol = toProtocolConfigRef(
+        coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+      );
+
+      const tx = await program.methods
+        .revokeSessionKey(
+          { 0: proofRpcResult.compressedProof },
+          camelizeKeys(coder.types.decode("SessionKey", sessionAccount!.data!.data)),
+          meta,
+          protocol
+        )
+        .accounts({ signer: signer.publicKey })
+        .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+        .signers([signer])
+        .transaction();
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(signer);
+      await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    };
+
+    // The hot key can play for the player but not revoke itself
+    await rejectsWith(sendRevoke(hotKey), "InvalidSessionKey");
+    await sendRevoke(player);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(sessionAddress.toBytes())), null);
+
+    // The closed session can't be proven, and without it the hot key doesn't
+    // hold the player's turn
+    await assert.rejects(
+      sendAttack(program, coder, rpc, hotKey, gameAddress, outputStateTree, 4, 4, {
+        account: sessionAccount,
+        address: sessionAddress,
+      })
+    );
+    await rejectsWith(sendAttack(program, coder, rpc, hotKey, gameAddress, outputStateTree, 4, 4), "NotPlayerTurn");
+
+    // The player still moves with their own key
+    await sendAttack(program, coder, rpc, player, gameAddress, outputStateTree, 4, 4);
+    console.log("✅ Revoked hot key locked out.");
+  });
+});
+
+describe("battleship_finalize", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  // Short enough to wait out, pinned by the game on creation
+  const TIMEOUT = 20;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let cranker: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const loadGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  const sendFinalize = async (signer: web3.Keypair) => {
+    const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+      program, coder, rpc, gameAddress, outputStateTree
+    );
+    const tx = await program.methods
+      .finalizeGame(proof, game, meta, protocol)
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    cranker = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, cranker]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, TIMEOUT);
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    ).finally(() => sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, 0));
+    await sendJoinGame(program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+  });
+
+  it("1. An Idle Player Keeps The Game Until Their Turn Runs Out", async () => {
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
+    const state = await loadGame();
+    assert.strictEqual(state.currentTurn, 2);
+    assert.strictEqual(state.turnTimeoutSlots.toNumber(), TIMEOUT);
+
+    await rejectsWith(sendFinalize(cranker), "TurnNotExpired");
+    console.log("✅ B's turn is still open.");
+  });
+
+  it("2. Anyone Awards The Idle Player's Game To Their Opponent", async () => {
+    const { lastActionSlot } = await loadGame();
+    while ((await rpc.getSlot()) <= lastActionSlot.toNumber() + TIMEOUT) await sleep(1000);
+    await sendFinalize(cranker);
+
+    const state = await loadGame();
+    assert.strictEqual(state.gameStatus, 2); // A Won
+    assert.strictEqual(state.endReason, 3); // Timeout
+    console.log("✅ A won on B's timeout.");
+  });
+
+  it("3. An Ended Game Can't Be Finalized Again", async () => {
+    await rejectsWith(sendFinalize(cranker), "GameOver");
+    assert.strictEqual((await loadGame()).gameStatus, 2);
+    console.log("✅ The result stands.");
+  });
+});
+
+describe("battleship_blockade", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const BLOCKADE_TURNS = 12;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const loadGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Player A Creates A Blockade To Defend", async () => {
+    const creator = await loadProfile(program, coder, rpc, signerA.publicKey);
+    gameAddress = creator.gameAddress;
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      creator.slotInputs,
+      [gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[2],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+
+    const tx = await program.methods
+      .createBlockadeGame(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree) },
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        false, // A defends
+        slot
+      )
+      .accounts({ signer: signerA.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerA])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerA);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerA]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const state = await loadGame();
+    assert.strictEqual(state.winCondition, 1); // WIN_CONDITION_BLOCKADE
+    assert.strictEqual(state.blockadeAttacker, 2);
+    assert.strictEqual(state.currentTurn, 2);
+    console.log("✅ Blockade created with B attacking.");
+  });
+
+  it("2. Only The Attacker Fires, And Never Twice At A Cell", async () => {
+    await sendJoinGame(program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    await rejectsWith(sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, 4), "NotPlayerTurn");
+
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, 2);
+    const state = await loadGame();
+    assert.strictEqual(state.currentTurn, 2); // The attacker keeps the turn
+    assert.strictEqual(state.gridA[10], CELL_MISS);
+    await rejectsWith(sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, 2), "AlreadyAttacked");
+    console.log("✅ B kept firing; the repeat shot was rejected.");
+  });
+
+  it("3. The Defender Wins Once Their Ship Survives The Blockade", async () => {
+    // A's ship sits on row 0, so shots on rows 2 to 4 all miss
+    for (let shot = 1; shot < BLOCKADE_TURNS; shot++) {
+      assert.strictEqual((await loadGame()).gameStatus, 1);
+      await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, shot % GRID_SIZE, 2 + Math.floor(shot / GRID_SIZE));
+    }
+
+    const state = await loadGame();
+    assert.strictEqual(state.gameStatus, 2); // A Won
+    assert.strictEqual(state.endReason, 4); // Survived
+    assert.strictEqual(state.hitsA, 0);
+    console.log(`✅ A's ship survived ${BLOCKADE_TURNS} shots.`);
+  });
+});
+
+describe("battleship_social", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let outsider: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  let chatAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const loadChat = async () => decode("GameChat", await rpc.getCompressedAccount(bn(chatAddress.toBytes())));
+
+  // Proves the game read-only next to the chat it writes: the chat first,
+  // then the game, then the config
+  const loadChatAccounts = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const chatAccount = await rpc.getCompressedAccount(bn(chatAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(chatAccount), inputOf(account), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const chat = {
+      chat: decode("GameChat", chatAccount),
+      meta: toAccountMeta(chatAccount, chatAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+    };
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const game = { game: decode("GameState", account), meta: gameMeta };
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    return { proof: { 0: proofRpcResult.compressedProof }, chat, game, protocol, remainingAccounts };
+  };
+
+  const sendOpenChat = async (signer: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [inputOf(account), configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(chatAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.openChat(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        decode("GameState", account),
+        gameMeta,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendChat = async (signer: web3.Keypair, text: string) => {
+    const { proof, chat, game, protocol, remainingAccounts } = await loadChatAccounts();
+    await send(program.methods.sendChat(proof, game, chat, text, protocol), signer, remainingAccounts);
+  };
+
+  const sendReact = async (signer: web3.Keypair, emoteId: number) => {
+    const chatAccount = await rpc.getCompressedAccount(bn(chatAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(chatAccount), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const chatMeta = toAccountMeta(chatAccount, chatAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.react(
+        { 0: proofRpcResult.compressedProof }, decode("GameChat", chatAccount), chatMeta, emoteId, protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendRegisterChatKey = async (signer: web3.Keypair, chatKey: number[]) => {
+    const chatAccount = await rpc.getCompressedAccount(bn(chatAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(chatAccount), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const chatMeta = toAccountMeta(chatAccount, chatAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.registerChatKey(
+        { 0: proofRpcResult.compressedProof }, decode("GameChat", chatAccount), chatMeta, chatKey, protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendSealedChat = async (signer: web3.Keypair, nonce: number[], ciphertext: Buffer) => {
+    const { proof, chat, game, protocol, remainingAccounts } = await loadChatAccounts();
+    await send(
+      program.methods.sendSealedChat(proof, game, chat, nonce, ciphertext, protocol), signer, remainingAccounts
+    );
+  };
+
+  // Mirrors `derive_board_backup_address` in the program: ["board_backup", game_address, owner]
+  const deriveBackupAddress = (owner: web3.PublicKey) =>
+    deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("board_backup"), gameAddress.toBytes(), owner.toBytes()]),
+      addressTree,
+      program.programId
+    );
+
+  const sendStoreBackup = async (signer: web3.Keypair, backup: { ephemeralKey: number[]; nonce: number[]; ciphertext: Buffer }) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [inputOf(account), configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveBackupAddress(signer.publicKey).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.storeBoardBackup(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: gameMeta },
+        backup,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // Mirrors `derive_game_viewers_address` and `derive_watcher_address` in
+  // the program: ["viewers", game_address] and ["watcher", game_address, watcher]
+  const deriveViewersAddress = () =>
+    deriveAddressV2(deriveAddressSeedV2([Buffer.from("viewers"), gameAddress.toBytes()]), addressTree, program.programId);
+  const deriveWatcherAddress = (watcher: web3.PublicKey) =>
+    deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("watcher"), gameAddress.toBytes(), watcher.toBytes()]),
+      addressTree,
+      program.programId
+    );
+  const loadViewers = async () => decode("GameViewers", await rpc.getCompressedAccount(bn(deriveViewersAddress().toBytes())));
+
+  // The first watch creates the counter; a registered watch also creates
+  // the watcher's marker, after it
+  const sendWatchGame = async (signer: web3.Keypair, register: boolean) => {
+    const viewersAddress = deriveViewersAddress();
+    const viewersAccount = await rpc.getCompressedAccount(bn(viewersAddress.toBytes()));
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const inputs = [...(viewersAccount ? [inputOf(viewersAccount)] : []), inputOf(account), configInput];
+    const newAddresses = [
+      ...(viewersAccount ? [] : [viewersAddress]),
+      ...(register ? [deriveWatcherAddress(signer.publicKey)] : []),
+    ];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      inputs,
+      newAddresses.map((address) => ({ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const viewers = viewersAccount
+      ? {
+          viewers: decode("GameViewers", viewersAccount),
+          meta: toAccountMeta(viewersAccount, viewersAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        }
+      : null;
+    const gameIndex = inputs.length - 2;
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[gameIndex], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[gameIndex + 1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.watchGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[inputs.length] ?? 0, addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: gameMeta },
+        viewers,
+        register,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    outsider = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, outsider]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    chatAddress = deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("chat"), gameAddress.toBytes()]), addressTree, program.programId
+    );
+  });
+
+  it("1. Only A Player Opens The Game's Chat", async () => {
+    await rejectsWith(sendOpenChat(outsider), "NotAPlayer");
+    await sendOpenChat(signerB);
+
+    const chat = await loadChat();
+    assert.ok(chat.playerA.equals(signerA.publicKey));
+    assert.ok(chat.playerB.equals(signerB.publicKey));
+    assert.strictEqual(chat.messages.length, 0);
+
+    // The chat's address is taken, so it opens only once
+    await assert.rejects(sendOpenChat(signerA));
+    console.log("✅ Chat opened by B.");
+  });
+
+  it("2. Players Post One Bounded Message Per Move", async () => {
+    await rejectsWith(sendChat(outsider, "hello"), "NotAPlayer");
+    await rejectsWith(sendChat(signerA, "x".repeat(33)), "ChatTooLong"); // MAX_CHAT_LEN is 32
+    await sendChat(signerA, "good luck");
+    await rejectsWith(sendChat(signerA, "again"), "ChatRateLimited");
+    await sendChat(signerB, "you too");
+
+    // A new move lifts A's limit
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
+    await sendChat(signerA, "hit!");
+
+    const chat = await loadChat();
+    assert.deepStrictEqual(
+      chat.messages.map((m: any) => [m.sender, m.moveCount, m.text]),
+      [[1, 0, "good luck"], [2, 0, "you too"], [1, 1, "hit!"]]
+    );
+    console.log("✅ Messages rate-limited per move.");
+  });
+
+  it("3. Reactions Are Limited To Known Emotes And A Cooldown", async () => {
+    await rejectsWith(sendReact(signerA, 8), "InvalidEmote"); // EMOTE_COUNT is 8
+    await rejectsWith(sendReact(outsider, 0), "NotAPlayer");
+
+    await sendReact(signerA, 3);
+    const { lastReactionSlotA } = await loadChat();
+    assert.ok(Number(lastReactionSlotA) > 0);
+    await rejectsWith(sendReact(signerA, 4), "ReactionRateLimited");
+    // B's cooldown is tracked apart from A's
+    await sendReact(signerB, 4);
+
+    while ((await rpc.getSlot()) < Number(lastReactionSlotA) + 25) await sleep(1000); // REACTION_COOLDOWN_SLOTS
+    await sendReact(signerA, 5);
+    assert.ok(Number((await loadChat()).lastReactionSlotA) >= Number(lastReactionSlotA) + 25);
+    console.log("✅ Reactions rate-limited per player.");
+  });
+
+  it("4. Each Player Stores One Board Backup", async () => {
+    // The program stores the ciphertext as is, so any bytes stand in for it
+    const backupOf = (length: number) => ({
+      ephemeralKey: Array.from(randomBytes(32)),
+      nonce: Array.from(randomBytes(24)),
+      ciphertext: randomBytes(length),
+    });
+    await rejectsWith(sendStoreBackup(outsider, backupOf(64)), "NotAPlayer");
+    await rejectsWith(sendStoreBackup(signerA, backupOf(0)), "BackupTooLong");
+    await rejectsWith(sendStoreBackup(signerA, backupOf(97)), "BackupTooLong"); // MAX_BACKUP_LEN is 96
+
+    const backup = backupOf(96);
+    await sendStoreBackup(signerA, backup);
+    const stored = decode("BoardBackup", await rpc.getCompressedAccount(bn(deriveBackupAddress(signerA.publicKey).toBytes())));
+    assert.ok(stored.owner.equals(signerA.publicKey));
+    assert.deepStrictEqual(Array.from(stored.gameAddress), Array.from(gameAddress.toBytes()));
+    assert.deepStrictEqual(Array.from(stored.nonce), backup.nonce);
+    assert.ok(Buffer.from(stored.ciphertext).equals(backup.ciphertext));
+
+    // The backup's address is taken, so it can't be replaced
+    await assert.rejects(sendStoreBackup(signerA, backupOf(64)));
+    await sendStoreBackup(signerB, backupOf(64));
+    console.log("✅ One backup stored per player.");
+  });
+
+  it("5. Sealed Chat Opens Once Both Players Register A Key", async () => {
+    // Keys and ciphertexts are opaque to the program, so random bytes stand in
+    await rejectsWith(sendRegisterChatKey(outsider, Array.from(randomBytes(32))), "NotAPlayer");
+    await sendRegisterChatKey(signerA, Array.from(randomBytes(32)));
+    await rejectsWith(sendRegisterChatKey(signerA, Array.from(randomBytes(32))), "ChatKeyRegistered");
+    await rejectsWith(sendSealedChat(signerB, Array.from(randomBytes(24)), randomBytes(32)), "ChatKeyMissing");
+    await sendRegisterChatKey(signerB, Array.from(randomBytes(32)));
+
+    await rejectsWith(sendSealedChat(signerB, Array.from(randomBytes(24)), randomBytes(49)), "ChatTooLong"); // MAX_SEALED_CHAT_LEN is 48
+    const nonce = Array.from(randomBytes(24));
+    const ciphertext = randomBytes(48);
+    await sendSealedChat(signerB, nonce, ciphertext);
+    // A's plain message already used this move
+    await rejectsWith(sendSealedChat(signerA, Array.from(randomBytes(24)), randomBytes(32)), "ChatRateLimited");
+
+    const chat = await loadChat();
+    assert.ok(chat.chatKeyA && chat.chatKeyB);
+    assert.strictEqual(chat.sealedMessages.length, 1);
+    const [message] = chat.sealedMessages;
+    assert.deepStrictEqual([message.sender, message.moveCount], [2, 1]);
+    assert.deepStrictEqual(Array.from(message.nonce), nonce);
+    assert.ok(Buffer.from(message.ciphertext).equals(ciphertext));
+    console.log("✅ Sealed message stored once both keys were registered.");
+  });
+
+  it("6. Spectators Are Counted, And Registered Once", async () => {
+    await rejectsWith(sendWatchGame(signerA, false), "NotASpectator");
+
+    await sendWatchGame(outsider, false);
+    let viewers = await loadViewers();
+    assert.deepStrictEqual(Array.from(viewers.gameAddress), Array.from(gameAddress.toBytes()));
+    assert.deepStrictEqual([viewers.views, viewers.watchers], [1, 0]);
+
+    await sendWatchGame(outsider, true);
+    const watcher = decode("Watcher", await rpc.getCompressedAccount(bn(deriveWatcherAddress(outsider.publicKey).toBytes())));
+    assert.ok(watcher.watcher.equals(outsider.publicKey));
+    // The watcher's marker exists, so they register only once
+    await assert.rejects(sendWatchGame(outsider, true));
+    await sendWatchGame(outsider, false);
+
+    viewers = await loadViewers();
+    assert.deepStrictEqual([viewers.views, viewers.watchers], [3, 1]);
+    console.log("✅ Three views from one registered watcher.");
+  });
+});
+
+describe("battleship_views", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let outsider: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  let viewAddresses: web3.PublicKey[];
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const loadGame = async () => decode("GameState", await rpc.getCompressedAccount(bn(gameAddress.toBytes())));
+  const loadViews = async () =>
+    Promise.all(viewAddresses.map(async (address) => decode("PlayerView", await rpc.getCompressedAccount(bn(address.toBytes())))));
+
+  const sendOpenViews = async (signer: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [inputOf(account), configInput],
+      viewAddresses.map((address) => ({ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.openViews(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        decode("GameState", account),
+        accountMeta,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // The views are written and the game only read: views, then the game,
+  // then the config
+  const sendSyncViews = async (signer: web3.Keypair, swapped = false) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const viewAccounts = await Promise.all(viewAddresses.map((address) => rpc.getCompressedAccount(bn(address.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...viewAccounts.map(inputOf), inputOf(account), configInput], []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const [metaA, metaB] = viewAccounts.map((view, i) =>
+      toAccountMeta(view, viewAddresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[3], remainingAccounts, outputStateTree
+    );
+    const [viewA, viewB] = viewAccounts.map((view) => decode("PlayerView", view));
+    const views = swapped
+      ? { viewA: viewB, metaA: metaB, viewB: viewA, metaB: metaA }
+      : { viewA, metaA, viewB, metaB };
+    await send(
+      program.methods.syncViews(
+        { 0: proofRpcResult.compressedProof }, decode("GameState", account), gameMeta, views, protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    outsider = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, outsider]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    viewAddresses = [1, 2].map((seat) => derivePlayerViewAddress(gameAddress, seat, addressTree, program.programId));
+  });
+
+  it("1. A Player Opens Views That Hide Unhit Ships", async () => {
+    await rejectsWith(sendOpenViews(outsider), "NotAPlayer");
+    await sendOpenViews(signerA);
+
+    const game = await loadGame();
+    assert.strictEqual(game.viewsOpen, true);
+    const [viewA, viewB] = await loadViews();
+    assert.ok(viewA.owner.equals(signerA.publicKey));
+    assert.ok(viewB.owner.equals(signerB.publicKey));
+    assert.deepStrictEqual([viewA.seat, viewB.seat], [1, 2]);
+    assert.deepStrictEqual(Array.from(viewA.ownGrid), Array.from(game.gridA));
+    assert.ok(!Array.from(viewA.targetGrid).includes(CELL_SHIP));
+    assert.ok(!Array.from(viewB.targetGrid).includes(CELL_SHIP));
+
+    // The view addresses are taken, so views open only once
+    await assert.rejects(sendOpenViews(signerB));
+    console.log("✅ Views opened with the opponent's ships hidden.");
+  });
+
+  it("2. Moves Must Update Both Views", async () => {
+    await rejectsWith(
+      sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0),
+      "ViewsRequired"
+    );
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0, null, true);
+
+    const [viewA, viewB] = await loadViews();
+    assert.strictEqual(viewA.targetGrid[4], CELL_HIT); // (4,0) is index 4
+    assert.strictEqual(viewB.ownGrid[4], CELL_HIT);
+    assert.deepStrictEqual([viewA.moveCount, viewB.moveCount], [1, 1]);
+    assert.deepStrictEqual([viewA.currentTurn, viewB.currentTurn], [2, 2]);
+    console.log("✅ The hit shows in both views.");
+  });
+
+  it("3. Anyone Can Sync The Views, In Seat Order", async () => {
+    await rejectsWith(sendSyncViews(outsider, true), "ViewMismatch");
+    await sendSyncViews(outsider);
+
+    const game = await loadGame();
+    const [viewA, viewB] = await loadViews();
+    assert.deepStrictEqual(Array.from(viewA.ownGrid), Array.from(game.gridA));
+    assert.deepStrictEqual(Array.from(viewB.ownGrid), Array.from(game.gridB));
+    assert.strictEqual(viewA.moveCount, game.moveCount);
+    console.log("✅ Views synced.");
+  });
+});
+
+describe("battleship_campaign", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const campaignId = 1;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let stranger: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let campaignAddress: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const loadCampaign = async () => decode("Campaign", await rpc.getCompressedAccount(bn(campaignAddress.toBytes())));
+  const loadGame = async () => decode("GameState", await rpc.getCompressedAccount(bn(gameAddress.toBytes())));
+
+  const sendStartCampaign = async () => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(campaignAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.startCampaign(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        new anchor.BN(campaignId),
+        protocol
+      ),
+      signerA,
+      remainingAccounts
+    );
+  };
+
+  // The campaign comes before the creator's ActiveGameSlot inputs
+  const sendCreateCampaignGame = async (signer: web3.Keypair, ships: { x: number; y: number; isHorizontal: boolean }[]) => {
+    const campaignAccount = await rpc.getCompressedAccount(bn(campaignAddress.toBytes()));
+    const { gameAddress: nextGame, ...profile } = await loadProfile(program, coder, rpc, signer.publicKey);
+    const logAddress = deriveMoveLogAddress(nextGame, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [inputOf(campaignAccount), ...profile.slotInputs],
+      [
+        { tree: addressTree, queue: addressTree, address: bn(nextGame.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const campaignMeta = toAccountMeta(
+      campaignAccount, campaignAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const slot = toActiveGameSlot(coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createCampaignGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[3], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        decode("Campaign", campaignAccount),
+        campaignMeta,
+        { ships, boardHash: Array.from(randomBytes(32)) },
+        slot
+      ),
+      signer,
+      remainingAccounts,
+      { vault: deriveVaultAddress(nextGame, program.programId) }
+    );
+    return nextGame;
+  };
+
+  // The campaign is written and the game only read
+  const sendAdvanceCampaign = async () => {
+    const campaignAccount = await rpc.getCompressedAccount(bn(campaignAddress.toBytes()));
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(campaignAccount), inputOf(account), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const campaignMeta = toAccountMeta(
+      campaignAccount, campaignAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.advanceCampaign(
+        { 0: proofRpcResult.compressedProof },
+        decode("Campaign", campaignAccount),
+        campaignMeta,
+        decode("GameState", account),
+        gameMeta,
+        protocol
+      ),
+      signerA,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    stranger = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, stranger]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    for (const signer of [signerA, signerB, stranger]) {
+      await sendCreateProfile(program, rpc, signer, outputStateTree);
+    }
+    const campaignIdBytes = Buffer.alloc(8);
+    campaignIdBytes.writeBigUInt64LE(BigInt(campaignId));
+    campaignAddress = deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("campaign"), signerA.publicKey.toBytes(), campaignIdBytes]),
+      addressTree,
+      program.programId
+    );
+  });
+
+  it("1. The Owner Starts A Campaign And Opens Its First Stage", async () => {
+    await sendStartCampaign();
+    const campaign = await loadCampaign();
+    assert.ok(campaign.owner.equals(signerA.publicKey));
+    assert.strictEqual(campaign.stage, 0);
+
+    const ships = [{ x: 0, y: 0, isHorizontal: true }];
+    await rejectsWith(sendCreateCampaignGame(stranger, ships), "NotCampaignOwner");
+    gameAddress = await sendCreateCampaignGame(signerA, ships);
+    await rejectsWith(sendCreateCampaignGame(signerA, ships), "CampaignGameOpen");
+
+    const game = await loadGame();
+    assert.strictEqual(game.rules.gridSize, 3);
+    assert.deepStrictEqual(Array.from(game.rules.fleetA), [2]);
+    assert.deepStrictEqual((await loadCampaign()).currentGame, Array.from(gameAddress.toBytes()));
+    console.log("✅ Stage 1 opened on a 3x3 grid.");
+  });
+
+  it("2. A Won Stage Scores And Advances The Campaign", async () => {
+    await sendJoinCustomGame(program, coder, rpc, signerB, gameAddress, outputStateTree, [{ x: 0, y: 2, isHorizontal: true }]);
+    await rejectsWith(sendAdvanceCampaign(), "GameNotFinished");
+
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, 2);
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 2, 2);
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 1, 2);
+    assert.strictEqual((await loadGame()).gameStatus, 2); // A won
+
+    await sendAdvanceCampaign();
+    const campaign = await loadCampaign();
+    assert.strictEqual(campaign.stage, 1);
+    assert.strictEqual(campaign.gamesWon, 1);
+    assert.strictEqual(campaign.score, 100); // CAMPAIGN_WIN_POINTS for stage 1
+    assert.deepStrictEqual(campaign.currentGame, new Array(32).fill(0));
+
+    // The stage is settled once
+    await rejectsWith(sendAdvanceCampaign(), "CampaignMismatch");
+    console.log("✅ Stage 1 won for 100 points.");
+  });
+
+  it("3. The Next Stage Grows The Grid And The Fleet", async () => {
+    gameAddress = await sendCreateCampaignGame(signerA, [
+      { x: 0, y: 0, isHorizontal: true },
+      { x: 0, y: 2, isHorizontal: true },
+    ]);
+    const game = await loadGame();
+    assert.strictEqual(game.rules.gridSize, 4);
+    assert.deepStrictEqual(Array.from(game.rules.fleetA), [2, 2]);
+    assert.deepStrictEqual(Array.from(game.rules.fleetB), [2, 2]);
+    console.log("✅ Stage 2 opened on a 4x4 grid with two ships each.");
+  });
+});
+
+describe("battleship_loyalty", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let outsider: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let shortGame: web3.PublicKey;
+  let longGame: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  // Mirrors `derive_loyalty_address` in the program: ["loyalty", owner]
+  const deriveLoyaltyAddress = (owner: web3.PublicKey) =>
+    deriveAddressV2(deriveAddressSeedV2([Buffer.from("loyalty"), owner.toBytes()]), addressTree, program.programId);
+  const deriveClaimAddress = (game: web3.PublicKey, owner: web3.PublicKey) =>
+    deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("loyalty_claim"), game.toBytes(), owner.toBytes()]),
+      addressTree,
+      program.programId
+    );
+  const loadPoints = async (owner: web3.PublicKey) =>
+    decode("LoyaltyPoints", await rpc.getCompressedAccount(bn(deriveLoyaltyAddress(owner).toBytes())));
+
+  const newGame = async () => {
+    const game = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    return game;
+  };
+
+  // A first-time earner's points account is created with the claim; later
+  // claims update it, ahead of the game in the proof
+  const sendEarnLoyaltyPoints = async (signer: web3.Keypair, game: web3.PublicKey) => {
+    const loyaltyAddress = deriveLoyaltyAddress(signer.publicKey);
+    const loyaltyAccount = await rpc.getCompressedAccount(bn(loyaltyAddress.toBytes()));
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const newAddresses = [deriveClaimAddress(game, signer.publicKey), ...(loyaltyAccount ? [] : [loyaltyAddress])];
+    const inputs = [...(loyaltyAccount ? [inputOf(loyaltyAccount)] : []), inputOf(account), configInput];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      inputs,
+      newAddresses.map((address) => ({ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const loyalty = loyaltyAccount
+      ? {
+          points: decode("LoyaltyPoints", loyaltyAccount),
+          meta: toAccountMeta(loyaltyAccount, loyaltyAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        }
+      : null;
+    const gameIndex = inputs.length - 2;
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, game, proofRpcResult.rootIndices[gameIndex], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[gameIndex + 1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.earnLoyaltyPoints(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[inputs.length], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: gameMeta },
+        loyalty,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    outsider = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, outsider]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Only Finished PvP Games Of 20 Shots Or More Earn Points", async () => {
+    shortGame = await newGame();
+    await rejectsWith(sendEarnLoyaltyPoints(signerA, shortGame), "GameNotFinished");
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, shortGame, outputStateTree);
+    await rejectsWith(sendEarnLoyaltyPoints(signerA, shortGame), "LoyaltyIneligible"); // 7 shots
+
+    // Eight misses each on the empty rows 1 and 2, then A sinks B's ship
+    longGame = await newGame();
+    for (let i = 0; i < 8; i++) {
+      await sendAttack(program, coder, rpc, signerA, longGame, outputStateTree, i % 4, 1 + Math.floor(i / 4));
+      await sendAttack(program, coder, rpc, signerB, longGame, outputStateTree, i % 4, 1 + Math.floor(i / 4));
+    }
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, longGame, outputStateTree);
+    const game = decode("GameState", await rpc.getCompressedAccount(bn(longGame.toBytes())));
+    assert.strictEqual(game.gameStatus, 2);
+    assert.strictEqual(game.moveCount, 23);
+
+    await rejectsWith(sendEarnLoyaltyPoints(outsider, longGame), "NotAPlayer");
+    await sendEarnLoyaltyPoints(signerA, longGame);
+    await sendEarnLoyaltyPoints(signerB, longGame);
+    const pointsA = await loadPoints(signerA.publicKey);
+    assert.strictEqual(pointsA.points.toNumber(), 3); // Played plus the win bonus
+    assert.strictEqual(pointsA.gamesCounted, 1);
+    assert.ok(pointsA.recentOpponents[0].equals(signerB.publicKey));
+    assert.strictEqual((await loadPoints(signerB.publicKey)).points.toNumber(), 1);
+    console.log("✅ Points earned for the 23-shot game only.");
+  });
+
+  it("2. A Game Is Credited Once Per Player", async () => {
+    // The claim's address is taken, so no proof of its absence can be built
+    await assert.rejects(sendEarnLoyaltyPoints(signerA, longGame));
+    const points = await loadPoints(signerA.publicKey);
+    assert.strictEqual(points.points.toNumber(), 3);
+    assert.strictEqual(points.gamesCounted, 1);
+    console.log("✅ Second claim for the same game rejected.");
+  });
+});
+
+describe("battleship_achievements", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const BADGE_FIRST_WIN = 0;
+  const BADGE_PERFECT_GAME = 2;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let firstGame: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  // Mirrors `derive_achievements_address` and `derive_badge_address` in the
+  // program: ["achievements", owner] and ["badge", owner, kind]
+  const deriveAchievementsAddress = (owner: web3.PublicKey) =>
+    deriveAddressV2(deriveAddressSeedV2([Buffer.from("achievements"), owner.toBytes()]), addressTree, program.programId);
+  const deriveBadgeAddress = (owner: web3.PublicKey, kind: number) =>
+    deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("badge"), owner.toBytes(), Buffer.from([kind])]),
+      addressTree,
+      program.programId
+    );
+  const loadAchievements = async (owner: web3.PublicKey) =>
+    decode("Achievements", await rpc.getCompressedAccount(bn(deriveAchievementsAddress(owner).toBytes())));
+
+  const newWin = async () => {
+    const game = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, game, outputStateTree);
+    return game;
+  };
+
+  // `badges` are the kinds the game earns, which the proof must show are
+  // unclaimed; the new badges come before a first record
+  const sendRecordAchievements = async (player: web3.PublicKey, game: web3.PublicKey, badges: number[]) => {
+    const recordAddress = deriveAchievementsAddress(player);
+    const recordAccount = await rpc.getCompressedAccount(bn(recordAddress.toBytes()));
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const newAddresses = [
+      ...badges.map((kind) => deriveBadgeAddress(player, kind)),
+      ...(recordAccount ? [] : [recordAddress]),
+    ];
+    const inputs = [...(recordAccount ? [inputOf(recordAccount)] : []), inputOf(account), configInput];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      inputs,
+      newAddresses.map((address) => ({ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const achievements = recordAccount
+      ? {
+          achievements: decode("Achievements", recordAccount),
+          meta: toAccountMeta(recordAccount, recordAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        }
+      : null;
+    const gameIndex = inputs.length - 2;
+    const { outputStateTreeIndex: _, ...gameMeta } = toAccountMeta(
+      account, game, proofRpcResult.rootIndices[gameIndex], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[gameIndex + 1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.recordAchievements(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[inputs.length] ?? 0, addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: gameMeta },
+        player,
+        achievements,
+        protocol
+      ),
+      signerB,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. A First Perfect Win Earns Two Badges", async () => {
+    firstGame = await newWin();
+    // Anyone may record a player's game, here B records A's
+    await sendRecordAchievements(signerA.publicKey, firstGame, [BADGE_FIRST_WIN, BADGE_PERFECT_GAME]);
+
+    const record = await loadAchievements(signerA.publicKey);
+    assert.strictEqual(record.wins, 1);
+    assert.strictEqual(record.streak, 1);
+    assert.strictEqual(record.badges, (1 << BADGE_FIRST_WIN) | (1 << BADGE_PERFECT_GAME));
+    for (const kind of [BADGE_FIRST_WIN, BADGE_PERFECT_GAME]) {
+      const badge = decode("Badge", await rpc.getCompressedAccount(bn(deriveBadgeAddress(signerA.publicKey, kind).toBytes())));
+      assert.strictEqual(badge.kind, kind);
+      assert.deepStrictEqual(Array.from(badge.gameAddress), Array.from(firstGame.toBytes()));
+    }
+
+    // The loser's record starts without badges
+    await sendRecordAchievements(signerB.publicKey, firstGame, []);
+    const loser = await loadAchievements(signerB.publicKey);
+    assert.deepStrictEqual([loser.wins, loser.streak, loser.badges], [0, 0, 0]);
+    console.log("✅ First win and perfect game badges awarded.");
+  });
+
+  it("2. A Game Is Recorded Once And Badges Are Awarded Once", async () => {
+    await rejectsWith(sendRecordAchievements(signerA.publicKey, firstGame, []), "NothingToClaim");
+
+    // Another perfect win counts, but earns no second copy of either badge
+    const secondGame = await newWin();
+    await sendRecordAchievements(signerA.publicKey, secondGame, []);
+    const record = await loadAchievements(signerA.publicKey);
+    assert.strictEqual(record.wins, 2);
+    assert.strictEqual(record.streak, 2);
+    assert.strictEqual(record.badges, (1 << BADGE_FIRST_WIN) | (1 << BADGE_PERFECT_GAME));
+    const badge = decode("Badge", await rpc.getCompressedAccount(bn(deriveBadgeAddress(signerA.publicKey, BADGE_PERFECT_GAME).toBytes())));
+    assert.deepStrictEqual(Array.from(badge.gameAddress), Array.from(firstGame.toBytes()));
+    console.log("✅ Duplicate game and badges rejected.");
+  });
+});
+
+describe("battleship_active_games", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const admin = loadAdminKeypair();
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let signerC: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let firstGame: web3.PublicKey;
+  let secondGame: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const loadActiveGames = async (owner: web3.PublicKey) =>
+    decode(
+      "PlayerProfile",
+      await rpc.getCompressedAccount(bn(deriveProfileAddress(owner, addressTree, program.programId).toBytes()))
+    ).activeGames;
+
+  const sendSetActiveGameLimit = async (signer: web3.Keypair, maxActiveGames: number) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    await send(
+      program.methods.setActiveGameLimit(
+        { 0: proofRpcResult.compressedProof },
+        decode("ProtocolConfig", configAccount),
+        toAccountMeta(
+          configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+        ),
+        maxActiveGames
+      ),
+      signer,
+      remainingAccounts,
+      { programData: programDataAddress(program.programId) }
+    );
+  };
+
+  const sendReleaseActiveGame = async (game: web3.PublicKey, owner: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const { profileAccount, configAccount, slotInputs } = await loadProfile(program, coder, rpc, owner);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(account), ...slotInputs], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(account, game, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const { profile, profileMeta, protocol } = toActiveGameSlot(
+      coder, { profileAccount, configAccount }, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.releaseActiveGame(
+        { 0: proofRpcResult.compressedProof }, decode("GameState", account), accountMeta, profile, profileMeta, protocol
+      ),
+      signerC,
+      remainingAccounts
+    );
+  };
+
+  const sendCreate = (creator: web3.Keypair) =>
+    sendCreateGame(program, coder, rpc, creator, outputStateTree, 0, 0, true, Array.from(randomBytes(32)));
+  const sendJoin = (joiner: web3.Keypair, game: web3.PublicKey) =>
+    sendJoinGame(program, coder, rpc, joiner, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    signerC = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, signerC]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    for (const signer of [signerA, signerB, signerC]) {
+      await sendCreateProfile(program, rpc, signer, outputStateTree);
+    }
+  });
+
+  // The config is shared by every suite, so the default limit is restored
+  after(async () => {
+    await sendSetActiveGameLimit(admin, 0);
+  });
+
+  it("1. Profiles Can't Take On Games Past The Limit", async () => {
+    await rejectsWith(sendSetActiveGameLimit(signerA, 1), "NotAdmin");
+    await sendSetActiveGameLimit(admin, 1);
+
+    firstGame = await sendCreate(signerA);
+    await sendJoin(signerB, firstGame);
+    secondGame = await sendCreate(signerC);
+    await rejectsWith(sendJoin(signerB, secondGame), "ActiveGameLimit");
+    await rejectsWith(sendCreate(signerA), "ActiveGameLimit");
+    assert.deepStrictEqual(
+      [await loadActiveGames(signerA.publicKey), await loadActiveGames(signerB.publicKey)],
+      [1, 1]
+    );
+    console.log("✅ Both players held at one active game.");
+  });
+
+  it("2. Releasing A Finished Game Frees Its Slot Once", async () => {
+    await rejectsWith(sendReleaseActiveGame(firstGame, signerB.publicKey), "GameNotFinished");
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, firstGame, outputStateTree);
+    await rejectsWith(sendReleaseActiveGame(firstGame, signerC.publicKey), "WrongProfile");
+
+    // Anyone may release a finished game's slot
+    await sendReleaseActiveGame(firstGame, signerB.publicKey);
+    assert.strictEqual(await loadActiveGames(signerB.publicKey), 0);
+    await rejectsWith(sendReleaseActiveGame(firstGame, signerB.publicKey), "NothingToClaim");
+
+    await sendJoin(signerB, secondGame);
+    assert.strictEqual(
+      decode("GameState", await rpc.getCompressedAccount(bn(secondGame.toBytes()))).gameStatus,
+      1
+    );
+    console.log("✅ B's slot freed and reused.");
+  });
+});
+
+describe("battleship_global_stats", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  // Mirrors `derive_global_stats_address` in the program: ["global_stats"]
+  const statsAddress = deriveAddressV2(deriveAddressSeedV2([Buffer.from("global_stats")]), addressTree, program.programId);
+
+  let signerA: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const loadStats = async () => {
+    const stats = decode("GlobalStats", await rpc.getCompressedAccount(bn(statsAddress.toBytes())));
+    return [stats.totalGames.toNumber(), stats.totalWagered.toNumber(), stats.activeGames.toNumber()];
+  };
+  const toStatsRef = (statsAccount: any, rootIndex: number, remainingAccounts: PackedAccounts) => ({
+    stats: decode("GlobalStats", statsAccount),
+    meta: toAccountMeta(statsAccount, statsAddress, rootIndex, remainingAccounts, outputStateTree),
+  });
+
+  const sendInitGlobalStats = async () => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(statsAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.initGlobalStats(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        protocol
+      ),
+      signerA,
+      remainingAccounts
+    );
+  };
+
+  // The stats are written along with the creator's profile, so they sit
+  // between the profile and the config
+  const sendCreateCountedGame = async () => {
+    const { gameAddress: nextGame, profileAccount, configAccount, slotInputs } = await loadProfile(
+      program, coder, rpc, signerA.publicKey
+    );
+    const statsAccount = await rpc.getCompressedAccount(bn(statsAddress.toBytes()));
+    const logAddress = deriveMoveLogAddress(nextGame, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [slotInputs[0], inputOf(statsAccount), slotInputs[1]],
+      [
+        { tree: addressTree, queue: addressTree, address: bn(nextGame.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = {
+      profile: decode("PlayerProfile", profileAccount),
+      profileMeta: toAccountMeta(
+        profileAccount, new web3.PublicKey(profileAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      protocol: toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree),
+    };
+    const stats = toStatsRef(statsAccount, proofRpcResult.rootIndices[1], remainingAccounts);
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[3], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        { mode: 0, tags: { region: 0, speed: 0, gridSize: 5 }, challenge: null },
+        slot,
+        stats
+      ),
+      signerA,
+      remainingAccounts,
+      { vault: deriveVaultAddress(nextGame, program.programId) }
+    );
+    return nextGame;
+  };
+
+  const sendCancelCountedGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const statsAccount = await rpc.getCompressedAccount(bn(statsAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(account), inputOf(statsAccount), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const stats = toStatsRef(statsAccount, proofRpcResult.rootIndices[1], remainingAccounts);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.cancelGame(
+        { 0: proofRpcResult.compressedProof }, decode("GameState", account), accountMeta, stats, protocol
+      ),
+      signerA,
+      remainingAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId), playerA: signerA.publicKey, sponsor: null }
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+  });
+
+  it("1. The Stats Account Is Created Once, Empty", async () => {
+    await sendInitGlobalStats();
+    assert.deepStrictEqual(await loadStats(), [0, 0, 0]);
+
+    // There's one stats account, at a fixed address
+    await assert.rejects(sendInitGlobalStats());
+    console.log("✅ Global stats initialized.");
+  });
+
+  it("2. Games Passing The Stats Count In And Out Of The Active Total", async () => {
+    // Games that leave the stats out don't touch them
+    await sendCreateGame(program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)));
+    assert.deepStrictEqual(await loadStats(), [0, 0, 0]);
+
+    gameAddress = await sendCreateCountedGame();
+    assert.strictEqual(decode("GameState", await rpc.getCompressedAccount(bn(gameAddress.toBytes()))).inStats, true);
+    assert.deepStrictEqual(await loadStats(), [1, 0, 1]);
+
+    await sendCancelCountedGame();
+    assert.deepStrictEqual(await loadStats(), [1, 0, 0]);
+    console.log("✅ The game was counted and left the active total when cancelled.");
+  });
+});
+
+describe("battleship_names", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  // Names are global, so each run registers a fresh one
+  const name = `captain_${randomBytes(4).toString("hex")}`;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  // Mirrors `derive_name_address` in the program: ["name", name]
+  const deriveNameAddress = (name: string) =>
+    deriveAddressV2(deriveAddressSeedV2([Buffer.from("name"), Buffer.from(name)]), addressTree, program.programId);
+  const loadName = async () =>
+    camelizeKeys(coder.types.decode("PlayerName", (await rpc.getCompressedAccount(bn(deriveNameAddress(name).toBytes())))!.data!.data));
+
+  const sendRegisterName = async (signer: web3.Keypair, name: string) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveNameAddress(name).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.registerName(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        name,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendTransferName = async (signer: web3.Keypair, newOwner: web3.PublicKey) => {
+    const nameAddress = deriveNameAddress(name);
+    const account = await rpc.getCompressedAccount(bn(nameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(account, nameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.transferName(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("PlayerName", account!.data!.data)),
+        accountMeta,
+        newOwner,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+  });
+
+  it("1. A Name Is Registered Once, By Whoever Claims It First", async () => {
+    // 3-20 bytes of a-z, 0-9 and _
+    for (const invalid of ["ab", "Captain", "sea dog", "x".repeat(21)]) {
+      await rejectsWith(sendRegisterName(signerA, invalid), "InvalidName");
+    }
+    await sendRegisterName(signerA, name);
+    const registered = await loadName();
+    assert.strictEqual(registered.name, name);
+    assert.ok(registered.owner.equals(signerA.publicKey));
+
+    // The name's address is taken, so no proof of its absence can be built
+    await assert.rejects(sendRegisterName(signerB, name));
+    console.log(`✅ ${name} registered to A.`);
+  });
+
+  it("2. Only The Owner Transfers A Name", async () => {
+    await rejectsWith(sendTransferName(signerB, signerB.publicKey), "NotNameOwner");
+    await sendTransferName(signerA, signerB.publicKey);
+    assert.ok((await loadName()).owner.equals(signerB.publicKey));
+    await rejectsWith(sendTransferName(signerA, signerA.publicKey), "NotNameOwner");
+    console.log(`✅ ${name} transferred to B.`);
+  });
+});
+
+describe("battleship_match_history", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let outsider: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let results: web3.PublicKey[];
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  // Mirrors `derive_match_history_address` in the program: ["match_history", owner, page]
+  const deriveHistoryAddress = (owner: web3.PublicKey, page: number) =>
+    deriveAddressV2(
+      deriveAddressSeedV2([Buffer.from("match_history"), owner.toBytes(), new anchor.BN(page).toArrayLike(Buffer, "le", 4)]),
+      addressTree,
+      program.programId
+    );
+  const loadHistory = async (owner: web3.PublicKey) =>
+    decode("MatchHistory", await rpc.getCompressedAccount(bn(deriveHistoryAddress(owner, 0).toBytes())));
+
+  // Files `result` on `pageOwner`'s first page, which is created the first
+  // time. The page is written and the result only read.
+  const sendAppendMatchHistory = async (signer: web3.Keypair, result: web3.PublicKey, pageOwner = signer.publicKey) => {
+    const pageAddress = deriveHistoryAddress(pageOwner, 0);
+    const pageAccount = await rpc.getCompressedAccount(bn(pageAddress.toBytes()));
+    const resultAccount = await rpc.getCompressedAccount(bn(result.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const inputs = [...(pageAccount ? [inputOf(pageAccount)] : []), inputOf(resultAccount), configInput];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      inputs,
+      pageAccount ? [] : [{ tree: addressTree, queue: addressTree, address: bn(pageAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const page = pageAccount
+      ? {
+          history: decode("MatchHistory", pageAccount),
+          meta: toAccountMeta(pageAccount, pageAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        }
+      : null;
+    const resultIndex = inputs.length - 2;
+    const { outputStateTreeIndex: _, ...resultMeta } = toAccountMeta(
+      resultAccount, result, proofRpcResult.rootIndices[resultIndex], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[resultIndex + 1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.appendMatchHistory(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[inputs.length] ?? 0, addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { result: decode("GameResult", resultAccount), meta: resultMeta },
+        page,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    outsider = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, outsider]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    results = [];
+    for (let i = 0; i < 2; i++) {
+      const game = await sendCreateGame(
+        program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+      );
+      await sendJoinGame(program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+      await sendPerfectWin(program, coder, rpc, signerA, signerB, game, outputStateTree);
+      results.push(await sendRecordGameResult(program, coder, rpc, signerA, game, outputStateTree));
+    }
+  });
+
+  it("1. Players File Their Own Results, Each Once", async () => {
+    await rejectsWith(sendAppendMatchHistory(outsider, results[0]), "NotAPlayer");
+    await sendAppendMatchHistory(signerA, results[0]);
+    await rejectsWith(sendAppendMatchHistory(signerA, results[0]), "AlreadyInHistory");
+    await sendAppendMatchHistory(signerA, results[1]);
+
+    const history = await loadHistory(signerA.publicKey);
+    assert.ok(history.owner.equals(signerA.publicKey));
+    assert.strictEqual(history.page, 0);
+    assert.deepStrictEqual(
+      history.results.map((entry: number[]) => Buffer.from(entry).toString("hex")),
+      results.map((result) => result.toBuffer().toString("hex"))
+    );
+    console.log("✅ Two results filed on A's first page.");
+  });
+
+  it("2. Each Player Keeps Their Own History", async () => {
+    await sendAppendMatchHistory(signerB, results[1]);
+    // B can't file onto A's page
+    await rejectsWith(sendAppendMatchHistory(signerB, results[0], signerA.publicKey), "WrongProfile");
+
+    const history = await loadHistory(signerB.publicKey);
+    assert.strictEqual(history.results.length, 1);
+    assert.strictEqual((await loadHistory(signerA.publicKey)).results.length, 2);
+    console.log("✅ B's history kept apart from A's.");
+  });
+});
+
+// Mirrors `board_leaf` / `board_merkle_root` in the program
+const BOARD_TREE_DEPTH = 5;
+
+function boardLeaf(index: number, value: number, salt: Buffer): Buffer {
+  return createHash("sha256")
+    .update(Buffer.from("cell"))
+    .update(Buffer.from([index, value]))
+    .update(salt)
+    .digest();
+}
+
+// Returns every tree level, leaves first, so paths can be read off
+function buildBoardTree(cells: number[], salts: Buffer[]): Buffer[][] {
+  let level: Buffer[] = [];
+  for (let i = 0; i < 1 << BOARD_TREE_DEPTH; i++) {
+    level.push(i < cells.length ? boardLeaf(i, cells[i], salts[i]) : Buffer.alloc(32));
+  }
+  const levels = [level];
+  while (level.length > 1) {
+    const next: Buffer[] = [];
+    for (let i = 0; i < level.length; i += 2) {
+      next.push(createHash("sha256").update(level[i]).update(level[i + 1]).digest());
+    }
+    levels.push(next);
+    level = next;
+  }
+  return levels;
+}
+
+function boardMerklePath(levels: Buffer[][], index: number): number[][] {
+  const path: number[][] = [];
+  for (let depth = 0; depth < BOARD_TREE_DEPTH; depth++) {
+    path.push(Array.from(levels[depth][index ^ 1]));
+    index >>= 1;
+  }
+  return path;
+}
+
+describe("battleship_hidden_board", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  // Boards stay off-chain; only their roots are committed
+  const cellsA = Array(GRID_SIZE * GRID_SIZE).fill(CELL_EMPTY);
+  const cellsB = Array(GRID_SIZE * GRID_SIZE).fill(CELL_EMPTY);
+  [0, 1, 2, 3].forEach((i) => (cellsA[i] = CELL_SHIP)); // (0,0) horizontal
+  [4, 9, 14, 19].forEach((i) => (cellsB[i] = CELL_SHIP)); // (4,0) vertical
+  const saltsA = cellsA.map(() => randomBytes(32));
+  const saltsB = cellsB.map(() => randomBytes(32));
+  const treeA = buildBoardTree(cellsA, saltsA);
+  const treeB = buildBoardTree(cellsB, saltsB);
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    ({ gameAddress } = await loadProfile(program, coder, rpc, signerA.publicKey));
+  });
+
+  const sendRespond = async (signer: web3.Keypair, index: number, value: number, salt: Buffer, path: number[][]) => {
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+        { hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue },
+        configInput,
+      ],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+    const tx = await program.methods
+      .respondAttack(
+        { 0: proofRpcResult.compressedProof },
+        {
+          game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+          gameMeta: toAccountMeta(account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+          log: camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data)),
+          logMeta: toAccountMeta(logAccount, logAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree),
+          views: null,
+        },
+        { cellValue: value, salt: Array.from(salt), merklePath: path },
+        null,
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+      )
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    const sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  it("1. Player A Creates Hidden Game With Board Root", async () => {
+    const creator = await loadProfile(program, coder, rpc, signerA.publicKey);
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      creator.slotInputs,
+      [
+        { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    const tx = await program.methods
+      .createHiddenGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          rootIndex: proofRpcResult.rootIndices[2],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        outputStateTreeIndex,
+        Array.from(treeA[BOARD_TREE_DEPTH][0]),
+        slot
+      )
+      .accounts({ signer: signerA.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerA])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerA);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerA]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = coder.types.decode("GameState", account!.data!.data);
+    assert.strictEqual(state.board_mode, 1);
+    assert.ok(state.grid_a.every((c: number) => c === CELL_EMPTY), "Board must not be on chain");
+    console.log("✅ Hidden game created.");
+  });
+
+  it("2. Player B Joins With Board Root", async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const joiner = await loadProfile(program, coder, rpc, signerB.publicKey);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...joiner.slotInputs],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+    const tx = await program.methods
+      .joinHiddenGame(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        toAccountMeta(account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        Array.from(treeB[BOARD_TREE_DEPTH][0]),
+        toActiveGameSlot(coder, joiner, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree)
+      )
+      .accounts({ signer: signerB.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerB])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerB);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerB]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    console.log("✅ Player B joined hidden game.");
+  });
+
+  it("3. Defender Cannot Lie About An Attacked Cell", async () => {
+    // A fires at (4,0) -> index 4, a ship cell on B's board
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = coder.types.decode("GameState", account!.data!.data);
+    assert.strictEqual(state.pending_shot, 4);
+
+    await assert.rejects(
+      sendRespond(signerB, 4, CELL_EMPTY, saltsB[4], boardMerklePath(treeB, 4)),
+      "Revealing a ship cell as empty must fail"
+    );
+    console.log("✅ False reveal rejected.");
+  });
+
+  it("4. Defender Reveals Hit With Merkle Path", async () => {
+    await sendRespond(signerB, 4, CELL_SHIP, saltsB[4], boardMerklePath(treeB, 4));
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = coder.types.decode("GameState", account!.data!.data);
+    assert.strictEqual(state.grid_b[4], CELL_HIT);
+    assert.strictEqual(state.hits_b, 1);
+    assert.strictEqual(state.pending_shot, null);
+    assert.strictEqual(state.current_turn, 2);
+    console.log("✅ Hit revealed and verified on-chain.");
+  });
+});
+
+// Creates a public-board PvP game and its move log for `creator`
+async function sendCreateGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  creator: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  shipX: number,
+  shipY: number,
+  isHorizontal: boolean,
+  boardHash: number[]
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const { gameAddress, ...profile } = await loadProfile(
+    program, coder, rpc, creator.publicKey
+  );
+  const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    profile.slotInputs,
+    [
+      { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+      { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+    ]
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[2],
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+  const tx = await program.methods
+    .createGame(
+      { 0: proofRpcResult.compressedProof },
+      { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+      { ship: { x: shipX, y: shipY, isHorizontal }, boardHash },
+      {
+        mode: 0, // WAGER_MODE_CASUAL
+        tags: { region: 0, speed: 0, gridSize: 5 },
+        challenge: null,
+      },
+      slot,
+      null
+    )
+    .accounts({ signer: creator.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([creator])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(creator);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [creator]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return gameAddress;
+}
+
+// Joins a waiting public-board game as player B
+async function sendJoinGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  joiner: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  shipX: number,
+  shipY: number,
+  isHorizontal: boolean,
+  boardHash: number[],
+  coinFlip = false
+): Promise<void> {
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const profile = await loadProfile(program, coder, rpc, joiner.publicKey);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...profile.slotInputs],
+    []
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .joinGame(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      { ship: { x: shipX, y: shipY, isHorizontal }, boardHash },
+      { coinFlip, filter: null, banCheck: null },
+      slot,
+      null
+    )
+    .accounts({ signer: joiner.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([joiner])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(joiner);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [joiner]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Mirrors `derive_rand_round_address` in the program: ["rand", game_address, round_id]
+function deriveRandRoundAddress(
+  gameAddress: web3.PublicKey,
+  roundId: number,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("rand"), gameAddress.toBytes(), Buffer.from([roundId])]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `rand_commitment` in the program
+function randCommitment(roundAddress: web3.PublicKey, player: web3.PublicKey, value: Buffer, salt: Buffer): number[] {
+  return Array.from(
+    createHash("sha256")
+      .update(Buffer.from("rand"))
+      .update(roundAddress.toBytes())
+      .update(player.toBytes())
+      .update(value)
+      .update(salt)
+      .digest()
+  );
+}
+
+// Commits `player` to a randomness round, opening the round on the first commit
+async function sendCommitRand(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  player: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  roundId: number,
+  value: Buffer,
+  salt: Buffer
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const roundAddress = deriveRandRoundAddress(gameAddress, roundId, addressTree, program.programId);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const roundAccount = await rpc.getCompressedAccount(bn(roundAddress.toBytes()));
+  const hashes = [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }];
+  if (roundAccount) {
+    hashes.push({ hash: roundAccount.hash, tree: roundAccount.treeInfo.tree, queue: roundAccount.treeInfo.queue });
+  }
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  hashes.push(configInput);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    hashes,
+    roundAccount ? [] : [{ tree: addressTree, queue: addressTree, address: bn(roundAddress.toBytes()) }]
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[hashes.length] ?? 0,
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const currentRound = roundAccount
+    ? {
+        round: camelizeKeys(coder.types.decode("RandRound", roundAccount.data!.data)),
+        meta: toAccountMeta(
+          roundAccount, roundAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+        ),
+      }
+    : null;
+  const protocol = toProtocolConfigRef(
+    coder, configAccount, proofRpcResult.rootIndices[hashes.length - 1], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .commitRand(
+      { 0: proofRpcResult.compressedProof },
+      {
+        addressTreeInfo: packedAddressTreeInfo,
+        outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+      },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      { roundId, commitment: randCommitment(roundAddress, player.publicKey, value, salt) },
+      currentRound,
+      protocol
+    )
+    .accounts({ signer: player.publicKey })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([player])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(player);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [player]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Opens `player`'s commitment to a randomness round
+async function sendRevealRand(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  player: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  roundId: number,
+  value: Buffer,
+  salt: Buffer
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const roundAddress = deriveRandRoundAddress(gameAddress, roundId, addressTree, program.programId);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const roundAccount = await rpc.getCompressedAccount(bn(roundAddress.toBytes()));
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [
+      { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+      { hash: roundAccount!.hash, tree: roundAccount!.treeInfo.tree, queue: roundAccount!.treeInfo.queue },
+      configInput,
+    ],
+    []
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const roundMeta = toAccountMeta(
+    roundAccount, roundAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+  const protocol = toProtocolConfigRef(
+    coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .revealRand(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      {
+        round: camelizeKeys(coder.types.decode("RandRound", roundAccount!.data!.data)),
+        meta: roundMeta,
+      },
+      { value: Array.from(value), salt: Array.from(salt) },
+      null,
+      protocol
+    )
+    .accounts({ signer: player.publicKey })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([player])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(player);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [player]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Mirrors `settlement_message` in the program
+function settlementMessage(gameAddress: web3.PublicKey, finalStatus: number, moves: any[]): Buffer {
+  const encoded = Buffer.from(moves.flatMap((m) => [m.player, m.x, m.y, m.result]));
+  return createHash("sha256")
+    .update(Buffer.from("settle"))
+    .update(gameAddress.toBytes())
+    .update(Buffer.from([finalStatus]))
+    .update(encoded)
+    .digest();
+}
+
+describe("battleship_offchain_settlement", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  // A's ship on row 0, B's ship on column 4. A sinks B in 4 shots, B misses 3 times.
+  const transcript = [
+    { player: 1, x: 4, y: 0, result: CELL_HIT },
+    { player: 2, x: 0, y: 4, result: CELL_MISS },
+    { player: 1, x: 4, y: 1, result: CELL_HIT },
+    { player: 2, x: 1, y: 4, result: CELL_MISS },
+    { player: 1, x: 4, y: 2, result: CELL_HIT },
+    { player: 2, x: 2, y: 4, result: CELL_MISS },
+    { player: 1, x: 4, y: 3, result: CELL_HIT },
+  ];
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+  });
+
+  const sendSettle = async (
+    finalStatus: number,
+    signers: web3.Keypair[],
+    method: "settleOffchainGame" | "challengeSettlement" = "settleOffchainGame"
+  ) => {
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+        { hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue },
+        configInput,
+      ],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const logMeta = toAccountMeta(
+      logAccount, logAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+
+    const message = settlementMessage(gameAddress, finalStatus, transcript);
+    const signatureIxs = signers.map((kp) =>
+      web3.Ed25519Program.createInstructionWithPrivateKey({ privateKey: kp.secretKey, message })
+    );
+
+    const tx = await program.methods[method](
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      {
+        log: camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data)),
+        meta: logMeta,
+      },
+      finalStatus,
+      transcript,
+      protocol
+    )
+      .accounts({ signer: signerA.publicKey })
+      .preInstructions(signatureIxs)
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerA])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerA);
+    const sig = await rpc.sendTransaction(tx, [signerA]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    return sig;
+  };
+
+  it("1. Settlement Without B's Signature Is Rejected", async () => {
+    await assert.rejects(sendSettle(2, [signerA]));
+    console.log("✅ One-sided settlement rejected.");
+  });
+
+  it("2. Co-Signed Transcript Settles The Game", async () => {
+    const sig = await sendSettle(2, [signerA, signerB]);
+    console.log("Settle TX:", sig);
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+    assert.strictEqual(state.gameStatus, 2); // A Won
+    assert.strictEqual(state.moveCount, transcript.length);
+    assert.strictEqual(state.hitsB, SHIP_LENGTH);
+    assert.ok(state.challengeDeadline.toNumber() > 0);
+    console.log("✅ Off-chain game settled in one transaction.");
+  });
+
+  it("3. Challenge Must Supersede With A Longer Transcript", async () => {
+    await assert.rejects(sendSettle(2, [signerA, signerB], "challengeSettlement"));
+    console.log("✅ Same-length challenge rejected.");
+  });
+});
+
+describe("battleship_coin_flip", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const admin = loadAdminKeypair();
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const valueA = randomBytes(32);
+  const saltA = randomBytes(32);
+  const valueB = randomBytes(32);
+  const saltB = randomBytes(32);
+
+  const loadGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  const sendForfeitRand = async (game: web3.PublicKey, roundId: number) => {
+    const roundAddress = deriveRandRoundAddress(game, roundId, addressTree, program.programId);
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const roundAccount = await rpc.getCompressedAccount(bn(roundAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+        { hash: roundAccount!.hash, tree: roundAccount!.treeInfo.tree, queue: roundAccount!.treeInfo.queue },
+        configInput,
+      ],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(
+      account, game, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const { outputStateTreeIndex: _, ...roundMeta } = toAccountMeta(
+      roundAccount, roundAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+
+    const tx = await program.methods
+      .forfeitRand(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        accountMeta,
+        camelizeKeys(coder.types.decode("RandRound", roundAccount!.data!.data)),
+        roundMeta,
+        protocol
+      )
+      .accounts({ signer: signerB.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerB])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerB);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerB]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+  });
+
+  it("1. Joining With A Coin Flip Holds The First Shot", async () => {
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32)), true
+    );
+
+    const state = await loadGame();
+    assert.strictEqual(state.currentTurn, 0);
+    assert.strictEqual(state.pendingDraw, 0); // RAND_FIRST_TURN
+    await assert.rejects(sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0));
+    console.log("✅ Nobody can shoot before the draw.");
+  });
+
+  it("2. A Wrong Reveal Is Rejected", async () => {
+    await sendCommitRand(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, valueA, saltA);
+    await sendCommitRand(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, valueB, saltB);
+    await assert.rejects(
+      sendRevealRand(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, valueB, saltA)
+    );
+    console.log("✅ Reveal must open the commitment.");
+  });
+
+  it("3. Both Reveals Decide Who Starts", async () => {
+    await sendRevealRand(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, valueA, saltA);
+    assert.strictEqual((await loadGame()).currentTurn, 0);
+    await sendRevealRand(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, valueB, saltB);
+
+    const result = createHash("sha256").update(valueA).update(valueB).digest();
+    const first = 1 + (result[0] & 1);
+    const state = await loadGame();
+    assert.strictEqual(state.currentTurn, first);
+    assert.strictEqual(state.firstTurn, first);
+    assert.strictEqual(state.pendingDraw, null);
+    console.log(`✅ Player ${first === 1 ? "A" : "B"} starts.`);
+  });
+
+  it("4. A Player Who Withholds Their Reveal Forfeits After The Deadline", async () => {
+    // Games pin the config's turn timeout, which also bounds their rounds
+    const TIMEOUT = 20;
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, TIMEOUT);
+    const game = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    ).finally(() => sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, 0));
+    await sendJoinGame(
+      program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)), true
+    );
+    const [value, salt] = [randomBytes(32), randomBytes(32)];
+    await sendCommitRand(program, coder, rpc, signerA, game, outputStateTree, 0, valueA, saltA);
+    await sendCommitRand(program, coder, rpc, signerB, game, outputStateTree, 0, value, salt);
+
+    // B sees A's value and withholds their own; the round stays open for now
+    await sendRevealRand(program, coder, rpc, signerA, game, outputStateTree, 0, valueA, saltA);
+    await rejectsWith(sendForfeitRand(game, 0), "TurnNotExpired");
+
+    const roundAddress = deriveRandRoundAddress(game, 0, addressTree, program.programId);
+    const round = camelizeKeys(
+      coder.types.decode("RandRound", (await rpc.getCompressedAccount(bn(roundAddress.toBytes())))!.data!.data)
+    );
+    while ((await rpc.getSlot()) <= round.deadline.toNumber()) await sleep(1000);
+    await sendForfeitRand(game, 0);
+
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const state = camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+    assert.strictEqual(state.gameStatus, 2); // A Won
+    assert.strictEqual(state.endReason, 3); // Timeout
+    assert.strictEqual(state.pendingDraw, null);
+    console.log("✅ The silent revealer lost the game.");
+  });
+});
+
+describe("battleship_custom_rules", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  // A: big fleet, one shot per turn. B: a single small ship firing 2-shot salvos.
+  const rules = { gridSize: 5, fleetA: Buffer.from([3, 2, 2]), fleetB: Buffer.from([2]), shotsPerTurnA: 1, shotsPerTurnB: 2, handicap: false, obstacles: 0, skipOnTimeout: false, sealedObstacles: false };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    ({ gameAddress } = await loadProfile(program, coder, rpc, signerA.publicKey));
+  });
+
+  const decodeGameState = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  it("1. Player A Creates A Custom-Rule Game", async () => {
+    const creator = await loadProfile(program, coder, rpc, signerA.publicKey);
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      creator.slotInputs,
+      [
+        { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[2],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    // Rows 0, 1 and 2, all starting at the left edge
+    const ships = [
+      { x: 0, y: 0, isHorizontal: true },
+      { x: 0, y: 1, isHorizontal: true },
+      { x: 0, y: 2, isHorizontal: true },
+    ];
+
+    const tx = await program.methods
+      .createCustomGame(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+        rules,
+        { ships, boardHash: Array.from(randomBytes(32)) },
+        slot
+      )
+      .accounts({ signer: signerA.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerA])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerA);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerA]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const state = await decodeGameState();
+    assert.deepStrictEqual(Array.from(state.rules.fleetA), [3, 2, 2]);
+    assert.strictEqual(state.gridA.filter((c: number) => c === CELL_SHIP).length, 7);
+    console.log("✅ Custom game created with a 3-ship fleet.");
+  });
+
+  it("2. Player B Joins With A Single Small Ship", async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const joiner = await loadProfile(program, coder, rpc, signerB.publicKey);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...joiner.slotInputs],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const slot = toActiveGameSlot(
+      coder, joiner, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+
+    const tx = await program.methods
+      .joinCustomGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+          meta: accountMeta,
+        },
+        { ships: [{ x: 4, y: 3, isHorizontal: false }], boardHash: Array.from(randomBytes(32)) },
+        null,
+        slot
+      )
+      .accounts({ signer: signerB.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signerB])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signerB);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signerB]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    assert.strictEqual((await decodeGameState()).gameStatus, 1);
+    console.log("✅ Player B joined.");
+  });
+
+  it("3. Player B Fires A Two-Shot Salvo", async () => {
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 0, 4);
+    assert.strictEqual((await decodeGameState()).currentTurn, 2);
+
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, 0);
+    assert.strictEqual((await decodeGameState()).currentTurn, 2); // Salvo continues
+
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 1, 0);
+    const state = await decodeGameState();
+    assert.strictEqual(state.currentTurn, 1);
+    assert.strictEqual(state.hitsA, 2);
+    console.log("✅ Salvo used up, turn back to A.");
+  });
+
+  it("4. An Idle Turn Is Skipped Once The Clock Runs Out", async () => {
+    const sendForceSkipTurn = async (signer: web3.Keypair, game: web3.PublicKey) => {
+      const logAddress = deriveMoveLogAddress(game, addressTree, program.programId);
+      const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+      const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [
+          { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+          { hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue },
+          configInput,
+        ],
+        []
+      );
+
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const gameMeta = toAccountMeta(account, game, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+      const logMeta = toAccountMeta(logAccount, logAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+      const protocol = toProtocolConfigRef(
+        coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+      );
+
+      const tx = await program.methods
+        .forceSkipTurn(
+          { 0: proofRpcResult.compressedProof },
+          {
+            game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+            gameMeta,
+            log: camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data)),
+            logMeta,
+            views: null,
+          },
+          protocol
+        )
+        .accounts({ signer: signer.publicKey })
+        .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+        .signers([signer])
+        .transaction();
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(signer);
+      await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    };
+
+    // Without the rule an idle turn still loses on timeout
+    await rejectsWith(sendForceSkipTurn(signerB, gameAddress), "WrongGameMode");
+
+    // Games pin the config's turn timeout
+    const TIMEOUT = 20;
+    const skipRules = { ...rules, fleetA: Buffer.from([2]), shotsPerTurnB: 1, skipOnTimeout: true };
+    const ships = [{ x: 0, y: 0, isHorizontal: true }];
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, TIMEOUT);
+    const game = await sendCreateCustomGame(program, coder, rpc, signerA, outputStateTree, skipRules, ships).finally(
+      () => sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, 0)
+    );
+    await sendJoinCustomGame(program, coder, rpc, signerB, game, outputStateTree, [{ x: 4, y: 3, isHorizontal: false }]);
+
+    const loadState = async () => {
+      const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+      return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+    };
+    const joined = await loadState();
+    assert.strictEqual(joined.turnTimeoutSlots.toNumber(), TIMEOUT);
+    assert.strictEqual(joined.currentTurn, 1);
+
+    // Only A's opponent can skip A's turn, and only once it has run out
+    await rejectsWith(sendForceSkipTurn(signerB, game), "TurnNotExpired");
+    await rejectsWith(sendForceSkipTurn(signerA, game), "NotPlayerTurn");
+    while ((await rpc.getSlot()) <= joined.lastActionSlot.toNumber() + TIMEOUT) await sleep(1000);
+    await sendForceSkipTurn(signerB, game);
+
+    const skipped = await loadState();
+    assert.strictEqual(skipped.gameStatus, 1);
+    assert.strictEqual(skipped.currentTurn, 2);
+    assert.strictEqual(skipped.moveCount, joined.moveCount + 1);
+    const logAccount = await rpc.getCompressedAccount(
+      bn(deriveMoveLogAddress(game, addressTree, program.programId).toBytes())
+    );
+    const log = camelizeKeys(coder.types.decode("MoveLog", logAccount!.data!.data));
+    assert.deepStrictEqual(log.moves[log.moves.length - 1], { player: 1, x: 0, y: 0, result: 0 }); // RESULT_SKIPPED
+
+    // The skip restarts the clock for B
+    await rejectsWith(sendForceSkipTurn(signerA, game), "TurnNotExpired");
+    console.log("✅ A's idle turn passed to B.");
+  });
+});
+
+// Creates a custom-rule game as player A
+async function sendCreateCustomGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  creator: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  rules: any,
+  ships: { x: number; y: number; isHorizontal: boolean }[]
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const { gameAddress, ...profile } = await loadProfile(
+    program, coder, rpc, creator.publicKey
+  );
+  const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    profile.slotInputs,
+    [
+      { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+      { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+    ]
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[2],
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+  const tx = await program.methods
+    .createCustomGame(
+      { 0: proofRpcResult.compressedProof },
+      { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+      rules,
+      { ships, boardHash: Array.from(randomBytes(32)) },
+      slot
+    )
+    .accounts({ signer: creator.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([creator])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(creator);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [creator]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return gameAddress;
+}
+
+// Joins a waiting custom-rule game as player B
+async function sendJoinCustomGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  joiner: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  ships: { x: number; y: number; isHorizontal: boolean }[]
+): Promise<void> {
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const profile = await loadProfile(program, coder, rpc, joiner.publicKey);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...profile.slotInputs],
+    []
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .joinCustomGame(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      { ships, boardHash: Array.from(randomBytes(32)) },
+      null,
+      slot
+    )
+    .accounts({ signer: joiner.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([joiner])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(joiner);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [joiner]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Mirrors `scatter_obstacles` in the program for two grids without shared cells taken
+function expectedObstacles(gridA: number[], gridB: number[], seed: Buffer, rules: any): number[] {
+  const size = rules.gridSize;
+  const candidates = createHash("sha256").update(Buffer.from("obstacles")).update(seed).digest();
+  const blocked: number[] = [];
+  for (const byte of candidates) {
+    if (blocked.length >= rules.obstacles) break;
+    const cell = byte % (size * size);
+    const index = Math.floor(cell / size) * GRID_SIZE + (cell % size);
+    if (gridA[index] === CELL_EMPTY && gridB[index] === CELL_EMPTY && !blocked.includes(index)) {
+      blocked.push(index);
+    }
+  }
+  return blocked.sort((a, b) => a - b);
+}
+
+describe("battleship_sealed_obstacles", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const rules = { gridSize: 5, fleetA: Buffer.from([2]), fleetB: Buffer.from([2]), shotsPerTurnA: 1, shotsPerTurnB: 1, handicap: false, obstacles: 3, skipOnTimeout: false, sealedObstacles: true };
+
+  const valueA = randomBytes(32);
+  const saltA = randomBytes(32);
+  const valueB = randomBytes(32);
+  const saltB = randomBytes(32);
+
+  const decodeGameState = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+  const blockedCells = (grid: number[]) =>
+    grid.flatMap((cell: number, index: number) => (cell === CELL_BLOCKED ? [index] : []));
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. No Obstacles Exist Until Both Fleets Are Placed", async () => {
+    gameAddress = await sendCreateCustomGame(
+      program, coder, rpc, signerA, outputStateTree, rules, [{ x: 0, y: 0, isHorizontal: true }]
+    );
+    assert.deepStrictEqual(blockedCells((await decodeGameState()).gridA), []);
+
+    await sendJoinCustomGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, [{ x: 4, y: 3, isHorizontal: false }]
+    );
+    const state = await decodeGameState();
+    assert.strictEqual(state.currentTurn, 0);
+    assert.strictEqual(state.pendingDraw, 1); // RAND_OBSTACLES
+    assert.deepStrictEqual(blockedCells(state.gridB), []);
+    await assert.rejects(sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 4));
+    console.log("✅ Game held until the obstacle map is drawn.");
+  });
+
+  it("2. One Reveal Alone Places Nothing", async () => {
+    await sendCommitRand(program, coder, rpc, signerA, gameAddress, outputStateTree, 1, valueA, saltA);
+    await sendCommitRand(program, coder, rpc, signerB, gameAddress, outputStateTree, 1, valueB, saltB);
+    await sendRevealRand(program, coder, rpc, signerB, gameAddress, outputStateTree, 1, valueB, saltB);
+
+    const state = await decodeGameState();
+    assert.strictEqual(state.currentTurn, 0);
+    assert.deepStrictEqual(blockedCells(state.gridA), []);
+    console.log("✅ Neither player can steer the map alone.");
+  });
+
+  it("3. Both Reveals Scatter The Obstacles From The Combined Seed", async () => {
+    const before = await decodeGameState();
+    await sendRevealRand(program, coder, rpc, signerA, gameAddress, outputStateTree, 1, valueA, saltA);
+
+    const seed = createHash("sha256").update(valueA).update(valueB).digest();
+    const expected = expectedObstacles(before.gridA, before.gridB, seed, rules);
+    const state = await decodeGameState();
+    assert.deepStrictEqual(blockedCells(state.gridA), expected);
+    assert.deepStrictEqual(blockedCells(state.gridB), expected);
+    assert.strictEqual(state.currentTurn, 1);
+    assert.strictEqual(state.pendingDraw, null);
+    console.log(`✅ ${expected.length} obstacles placed, Player A to move.`);
+  });
+});
+
+// Mirrors `derive_rematch_address` in the program: ["rematch", previous_game]
+function deriveRematchAddress(
+  previousGame: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("rematch"), previousGame.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+describe("battleship_rematch", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let previousGame: web3.PublicKey;
+  let rematchAddress: web3.PublicKey;
+
+  const RAND_REMATCH = 2;
+  const valueA = randomBytes(32);
+  const saltA = randomBytes(32);
+  const valueB = randomBytes(32);
+  const saltB = randomBytes(32);
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+
+    // A sinks B's column-4 ship while B misses along row 4
+    previousGame = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, previousGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    for (let y = 0; y < SHIP_LENGTH; y++) {
+      await sendAttack(program, coder, rpc, signerA, previousGame, outputStateTree, 4, y);
+      if (y < SHIP_LENGTH - 1) {
+        await sendAttack(program, coder, rpc, signerB, previousGame, outputStateTree, y, 4);
+      }
+    }
+    rematchAddress = deriveRematchAddress(previousGame, addressTree, program.programId);
+  });
+
+  const sendRequestRematch = async (signer: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(previousGame.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(rematchAddress.toBytes()) }]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _, ...previousMeta } = toAccountMeta(
+      account, previousGame, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+
+    const tx = await program.methods
+      .requestRematch(
+        { 0: proofRpcResult.compressedProof },
+        {
+          rootIndex: proofRpcResult.rootIndices[2],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        remainingAccounts.insertOrGet(outputStateTree),
+        camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        previousMeta,
+        protocol
+      )
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const sendCreateRematchGame = async (signer: web3.Keypair) => {
+    const roundAddress = deriveRandRoundAddress(previousGame, RAND_REMATCH, addressTree, program.programId);
+    const { gameAddress, ...profile } = await loadProfile(
+      program, coder, rpc, signer.publicKey
+    );
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const rematchAccount = await rpc.getCompressedAccount(bn(rematchAddress.toBytes()));
+    const roundAccount = await rpc.getCompressedAccount(bn(roundAddress.toBytes()));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: rematchAccount!.hash, tree: rematchAccount!.treeInfo.tree, queue: rematchAccount!.treeInfo.queue },
+        { hash: roundAccount!.hash, tree: roundAccount!.treeInfo.tree, queue: roundAccount!.treeInfo.queue },
+        ...profile.slotInputs,
+      ],
+      [
+        { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+        { tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) },
+      ]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const rematchMeta = toAccountMeta(
+      rematchAccount, rematchAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const { outputStateTreeIndex: _, ...roundMeta } = toAccountMeta(
+      roundAccount, roundAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const slot = toActiveGameSlot(
+      coder, profile, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+
+    const tx = await program.methods
+      .createRematchGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[4],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        {
+          rematch: camelizeKeys(coder.types.decode("Rematch", rematchAccount!.data!.data)),
+          rematchMeta,
+          round: camelizeKeys(coder.types.decode("RandRound", roundAccount!.data!.data)),
+          roundMeta,
+        },
+        { ships: [{ x: 0, y: 0, isHorizontal: true }], boardHash: Array.from(randomBytes(32)) },
+        slot
+      )
+      .accounts({ signer: signer.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    return gameAddress;
+  };
+
+  it("1. Rematch Can't Open Before The Round Is Revealed", async () => {
+    await sendRequestRematch(signerB);
+    await sendCommitRand(program, coder, rpc, signerA, previousGame, outputStateTree, RAND_REMATCH, valueA, saltA);
+    await sendCommitRand(program, coder, rpc, signerB, previousGame, outputStateTree, RAND_REMATCH, valueB, saltB);
+
+    await assert.rejects(sendCreateRematchGame(signerA));
+    await assert.rejects(sendCreateRematchGame(signerB));
+    console.log("✅ No seat is assigned until both players reveal.");
+  });
+
+  it("2. The Revealed Round Picks Seats And First Turn", async () => {
+    await sendRevealRand(program, coder, rpc, signerA, previousGame, outputStateTree, RAND_REMATCH, valueA, saltA);
+    await sendRevealRand(program, coder, rpc, signerB, previousGame, outputStateTree, RAND_REMATCH, valueB, saltB);
+
+    const seed = createHash("sha256").update(valueA).update(valueB).digest();
+    const [seatA, seatB] = seed[0] & 1 ? [signerB, signerA] : [signerA, signerB];
+    const first = 1 + (seed[1] & 1);
+
+    await assert.rejects(sendCreateRematchGame(seatB));
+    const gameAddress = await sendCreateRematchGame(seatA);
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const state = camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+    assert.ok(state.playerA.equals(seatA.publicKey));
+    assert.ok(state.reservedFor.equals(seatB.publicKey));
+    assert.strictEqual(state.currentTurn, first);
+    assert.strictEqual(state.firstTurn, first);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(rematchAddress.toBytes())), null);
+    console.log(`✅ Rematch opened, Player ${first === 1 ? "A" : "B"} shoots first.`);
+  });
+});
+
+// Mirrors `derive_oracle_config_address` in the program: ["oracle_config"]
+function deriveOracleConfigAddress(addressTree: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return deriveAddressV2(deriveAddressSeedV2([Buffer.from("oracle_config")]), addressTree, programId);
+}
+
+// Points the singleton OracleConfig at `oracle`, creating it on first use
+async function sendSetOracle(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  admin: web3.Keypair,
+  oracle: web3.PublicKey,
+  outputStateTree: web3.PublicKey
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const configAddress = deriveOracleConfigAddress(addressTree, program.programId);
+  const configAccount = await rpc.getCompressedAccount(bn(configAddress.toBytes()));
+  const proofRpcResult = configAccount
+    ? await rpc.getValidityProofV0(
+        [{ hash: configAccount.hash, tree: configAccount.treeInfo.tree, queue: configAccount.treeInfo.queue }],
+        []
+      )
+    : await rpc.getValidityProofV0([], [{ tree: addressTree, queue: addressTree, address: bn(configAddress.toBytes()) }]);
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  let method;
+  if (configAccount) {
+    method = program.methods.updateOracleConfig(
+      { 0: proofRpcResult.compressedProof },
+      camelizeKeys(coder.types.decode("OracleConfig", configAccount.data!.data)),
+      toAccountMeta(configAccount, configAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+      oracle
+    );
+  } else {
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    method = program.methods.initOracleConfig(
+      { 0: proofRpcResult.compressedProof },
+      {
+        rootIndex: proofRpcResult.rootIndices[0],
+        addressMerkleTreePubkeyIndex,
+        addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+      },
+      remainingAccounts.insertOrGet(outputStateTree),
+      oracle
+    );
+  }
+
+  const tx = await method
+    .accounts({ signer: admin.publicKey, programData: programDataAddress(program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([admin])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(admin);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [admin]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Mirrors `derive_batch_plans_address` in the program: ["batch_plans", game_address]
+function deriveBatchPlansAddress(
+  gameAddress: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("batch_plans"), gameAddress.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `batch_commitment` in the program
+function batchCommitment(gameAddress: web3.PublicKey, player: web3.PublicKey, plan: any, salt: Buffer): number[] {
+  const ships = Buffer.from(plan.ships.flatMap((s: any) => [s.x, s.y, s.isHorizontal ? 1 : 0]));
+  return Array.from(
+    createHash("sha256")
+      .update(Buffer.from("batch"))
+      .update(gameAddress.toBytes())
+      .update(player.toBytes())
+      .update(ships)
+      .update(Buffer.from(plan.shots))
+      .update(salt)
+      .digest()
+  );
+}
+
+// Creates `creator`'s next game as a batch game committed to `commitment`
+// (see `loadProfile` for its address); returns its address
+async function sendCreateBatchGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  creator: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  commitment: number[]
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const { gameAddress, ...profile } = await loadProfile(
+    program, coder, rpc, creator.publicKey
+  );
+  const plansAddress = deriveBatchPlansAddress(gameAddress, addressTree, program.programId);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    profile.slotInputs,
+    [
+      { tree: addressTree, queue: addressTree, address: bn(gameAddress.toBytes()) },
+      { tree: addressTree, queue: addressTree, address: bn(plansAddress.toBytes()) },
+    ]
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+
+  const tx = await program.methods
+    .createBatchGame(
+      { 0: proofRpcResult.compressedProof },
+      {
+        rootIndex: proofRpcResult.rootIndices[2],
+        addressMerkleTreePubkeyIndex,
+        addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+      },
+      remainingAccounts.insertOrGet(outputStateTree),
+      commitment,
+      slot
+    )
+    .accounts({ signer: creator.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([creator])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(creator);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [creator]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return gameAddress;
+}
+
+async function sendJoinBatchGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  joiner: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  commitment: number[]
+): Promise<void> {
+  const plansAddress = deriveBatchPlansAddress(gameAddress, new web3.PublicKey(batchAddressTree), program.programId);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const plansAccount = await rpc.getCompressedAccount(bn(plansAddress.toBytes()));
+  const profile = await loadProfile(program, coder, rpc, joiner.publicKey);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [
+      { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+      { hash: plansAccount!.hash, tree: plansAccount!.treeInfo.tree, queue: plansAccount!.treeInfo.queue },
+      ...profile.slotInputs,
+    ],
+    []
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const plansMeta = toAccountMeta(
+    plansAccount, plansAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .joinBatchGame(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      {
+        plans: camelizeKeys(coder.types.decode("BatchPlans", plansAccount!.data!.data)),
+        meta: plansMeta,
+      },
+      commitment,
+      slot
+    )
+    .accounts({ signer: joiner.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([joiner])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(joiner);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [joiner]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+async function sendRevealBatchPlan(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  plan: any,
+  salt: Buffer
+): Promise<void> {
+  const plansAddress = deriveBatchPlansAddress(gameAddress, new web3.PublicKey(batchAddressTree), program.programId);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const plansAccount = await rpc.getCompressedAccount(bn(plansAddress.toBytes()));
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [
+      { hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue },
+      { hash: plansAccount!.hash, tree: plansAccount!.treeInfo.tree, queue: plansAccount!.treeInfo.queue },
+      configInput,
+    ],
+    []
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const plansMeta = toAccountMeta(
+    plansAccount, plansAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+
+  const tx = await program.methods
+    .revealBatchPlan(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      {
+        plans: camelizeKeys(coder.types.decode("BatchPlans", plansAccount!.data!.data)),
+        meta: plansMeta,
+      },
+      { ships: plan.ships, shots: Buffer.from(plan.shots) },
+      Array.from(salt),
+      toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+    )
+    .accounts({ signer: signer.publicKey })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+describe("battleship_batch", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const cell = (x: number, y: number) => y * GRID_SIZE + x;
+
+  // A's ship on row 0 and B's on column 4. A walks down column 4 and sinks B
+  // on its 4th shot; B sweeps row 4 and never hits.
+  const planA = {
+    ships: [{ x: 0, y: 0, isHorizontal: true }],
+    shots: [cell(4, 0), cell(4, 1), cell(4, 2), cell(4, 3), cell(4, 4)],
+  };
+  const planB = {
+    ships: [{ x: 4, y: 0, isHorizontal: false }],
+    shots: [cell(0, 4), cell(1, 4), cell(2, 4), cell(3, 4)],
+  };
+  const saltA = randomBytes(32);
+  const saltB = randomBytes(32);
+
+  const decodeGameState = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    ({ gameAddress } = await loadProfile(program, coder, rpc, signerA.publicKey));
+  });
+
+  it("1. Both Players Commit To Their Whole Game", async () => {
+    await sendCreateBatchGame(
+      program, coder, rpc, signerA, outputStateTree, batchCommitment(gameAddress, signerA.publicKey, planA, saltA)
+    );
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(logAddress.toBytes())), null);
+
+    await sendJoinBatchGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree,
+      batchCommitment(gameAddress, signerB.publicKey, planB, saltB)
+    );
+
+    const state = await decodeGameState();
+    assert.strictEqual(state.gameStatus, 1);
+    assert.strictEqual(state.currentTurn, 0);
+    console.log("✅ Batch game active with both plans committed.");
+  });
+
+  it("2. Plans That Don't Open The Commitment Are Rejected", async () => {
+    // Same ships, a different shot order
+    await assert.rejects(
+      sendRevealBatchPlan(
+        program, coder, rpc, signerB, gameAddress, outputStateTree, { ...planB, shots: [cell(0, 0), ...planB.shots] }, saltB
+      )
+    );
+    await assert.rejects(sendRevealBatchPlan(program, coder, rpc, signerB, gameAddress, outputStateTree, planB, saltA));
+    console.log("✅ Reveal must match the committed plan.");
+  });
+
+  it("3. The Winner Can't Be Claimed Before Both Plans Are Revealed", async () => {
+    await sendRevealBatchPlan(program, coder, rpc, signerB, gameAddress, outputStateTree, planB, saltB);
+    await assert.rejects(sendRevealBatchPlan(program, coder, rpc, signerB, gameAddress, outputStateTree, planB, saltB));
+    await assert.rejects(sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0));
+
+    const state = await decodeGameState();
+    assert.strictEqual(state.gameStatus, 1);
+    assert.strictEqual(state.moveCount, 0);
+    console.log("✅ One revealed plan leaves the game open.");
+  });
+
+  it("4. The Second Reveal Plays The Game Out On Chain", async () => {
+    await sendRevealBatchPlan(program, coder, rpc, signerA, gameAddress, outputStateTree, planA, saltA);
+
+    const state = await decodeGameState();
+    assert.strictEqual(state.gameStatus, 2); // A Won
+    assert.strictEqual(state.endReason, 1); // Sunk
+    assert.strictEqual(state.hitsB, SHIP_LENGTH);
+    assert.strictEqual(state.hitsA, 0);
+    assert.strictEqual(state.moveCount, 7); // A's 4 shots and B's 3 in between
+    assert.strictEqual(state.gridB[cell(4, 3)], CELL_HIT);
+    assert.strictEqual(state.gridA[cell(2, 4)], CELL_MISS);
+    assert.strictEqual(state.gridA[cell(3, 4)], CELL_EMPTY); // Never fired
+    console.log("✅ A sank B; the program, not a signer, decided the winner.");
+  });
+});
+
+// Mirrors `derive_side_pool_address` in the program: ["side_pool", game_address]
+function deriveSidePoolAddress(
+  gameAddress: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("side_pool"), gameAddress.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `derive_side_bet_address` in the program: ["side_bet", game_address, bettor]
+function deriveSideBetAddress(
+  gameAddress: web3.PublicKey,
+  bettor: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("side_bet"), gameAddress.toBytes(), bettor.toBytes()]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+// Mirrors `side_bet_commitment` in the program
+function sideBetCommitment(gameAddress: web3.PublicKey, bettor: web3.PublicKey, side: number, salt: Buffer): number[] {
+  return Array.from(
+    createHash("sha256")
+      .update(Buffer.from("side_bet"))
+      .update(gameAddress.toBytes())
+      .update(bettor.toBytes())
+      .update(Buffer.from([side]))
+      .update(salt)
+      .digest()
+  );
+}
+
+describe("battleship_side_bets", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let bettorC: web3.Keypair;
+  let bettorD: web3.Keypair;
+  let bettorE: web3.Keypair;
+  let bettorF: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const STAKE = 100_000_000; // SIDE_BET_STAKE_LAMPORTS
+  const salts = new Map<string, Buffer>();
+  const saltOf = (bettor: web3.Keypair) => {
+    const key = bettor.publicKey.toBase58();
+    if (!salts.has(key)) salts.set(key, randomBytes(32));
+    return salts.get(key)!;
+  };
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  // Fetches `addresses` with the protocol config behind them. Updated
+  // accounts go before the game, which is only read.
+  const accountsOf = async (addresses: web3.PublicKey[]) => {
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) =>
+      toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    return { accounts, proofRpcResult, remainingAccounts, metas, protocol };
+  };
+  const readOnly = (meta: any) => {
+    const { outputStateTreeIndex: _, ...rest } = meta;
+    return rest;
+  };
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const poolOf = (game: web3.PublicKey) => deriveSidePoolAddress(game, addressTree, program.programId);
+  const betOf = (game: web3.PublicKey, bettor: web3.Keypair) =>
+    deriveSideBetAddress(game, bettor.publicKey, addressTree, program.programId);
+  const vaultOf = (game: web3.PublicKey) =>
+    web3.PublicKey.findProgramAddressSync([Buffer.from("side_vault"), game.toBytes()], program.programId)[0];
+
+  const sendOpenSidePool = async (game: web3.PublicKey, signer: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(poolOf(game).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const gameMeta = toAccountMeta(account, game, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.openSidePool(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        decode("GameState", account),
+        readOnly(gameMeta),
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendPlaceSideBet = async (game: web3.PublicKey, bettor: web3.Keypair, side: number) => {
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const poolAccount = await rpc.getCompressedAccount(bn(poolOf(game).toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    // The updated pool goes before the game, which is only read
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        ...[poolAccount, account].map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })),
+        configInput,
+      ],
+      [{ tree: addressTree, queue: addressTree, address: bn(betOf(game, bettor).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const poolMeta = toAccountMeta(
+      poolAccount, poolOf(game), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const gameMeta = toAccountMeta(account, game, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.placeSideBet(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[3], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: readOnly(gameMeta) },
+        { pool: decode("SideBetPool", poolAccount), meta: poolMeta },
+        sideBetCommitment(game, bettor.publicKey, side, saltOf(bettor)),
+        protocol
+      ),
+      bettor,
+      remainingAccounts,
+      { vault: vaultOf(game) }
+    );
+  };
+
+  const sendRevealSideBet = async (game: web3.PublicKey, bettor: web3.Keypair, side: number, salt = saltOf(bettor)) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([
+      poolOf(game),
+      betOf(game, bettor),
+      game,
+    ]);
+    await send(
+      program.methods.revealSideBet(
+        { 0: proofRpcResult.compressedProof },
+        { game: decode("GameState", accounts[2]), meta: readOnly(metas[2]) },
+        { pool: decode("SideBetPool", accounts[0]), meta: metas[0] },
+        { bet: decode("SideBet", accounts[1]), meta: metas[1] },
+        { side, salt: Array.from(salt) },
+        protocol
+      ),
+      bettor,
+      remainingAccounts
+    );
+  };
+
+  const sendResign = async (game: web3.PublicKey, signer: web3.Keypair) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([game]);
+    await send(
+      program.methods.resign(
+        { 0: proofRpcResult.compressedProof },
+        decode("GameState", accounts[0]),
+        metas[0],
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // Anyone can claim; `payee` is the account the payout is sent to
+  const sendClaimSideBet = async (
+    game: web3.PublicKey,
+    bettor: web3.Keypair,
+    signer: web3.Keypair,
+    payee = bettor.publicKey
+  ) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([
+      poolOf(game),
+      betOf(game, bettor),
+      game,
+    ]);
+    await send(
+      program.methods.claimSideBet(
+        { 0: proofRpcResult.compressedProof },
+        { game: decode("GameState", accounts[2]), meta: readOnly(metas[2]) },
+        { pool: decode("SideBetPool", accounts[0]), meta: metas[0] },
+        { bet: decode("SideBet", accounts[1]), meta: metas[1] },
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { vault: vaultOf(game), bettor: payee }
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    bettorC = new web3.Keypair();
+    bettorD = new web3.Keypair();
+    bettorE = new web3.Keypair();
+    bettorF = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    for (const signer of [signerA, signerB, bettorC, bettorD, bettorE, bettorF]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+  });
+
+  it("1. Sides Stay Sealed While The Game Runs", async () => {
+    await sendOpenSidePool(gameAddress, bettorC);
+    await sendPlaceSideBet(gameAddress, bettorC, 1);
+    await sendPlaceSideBet(gameAddress, bettorD, 2);
+    await sendPlaceSideBet(gameAddress, bettorE, 1);
+    // Players can't bet on their own game
+    await assert.rejects(sendPlaceSideBet(gameAddress, signerA, 1));
+    // Nor can a bet be opened before the result is final
+    await assert.rejects(sendRevealSideBet(gameAddress, bettorC, 1));
+
+    const pool = decode("SideBetPool", await rpc.getCompressedAccount(bn(poolOf(gameAddress).toBytes())));
+    assert.strictEqual(pool.bets, 3);
+    assert.strictEqual(pool.revealedA + pool.revealedB, 0);
+    assert.strictEqual(await rpc.getBalance(vaultOf(gameAddress)), 3 * STAKE);
+    console.log("✅ Three sealed bets, no side on chain.");
+  });
+
+  it("2. Reveals Must Open The Commitment", async () => {
+    // B resigns, A wins
+    await sendResign(gameAddress, signerB);
+
+    // D backed B and can't switch sides after the fact
+    await assert.rejects(sendRevealSideBet(gameAddress, bettorD, 1));
+    await sendRevealSideBet(gameAddress, bettorC, 1);
+    await assert.rejects(sendRevealSideBet(gameAddress, bettorC, 1));
+    // Nobody is paid while a bet is still sealed and the window is open
+    await assert.rejects(sendClaimSideBet(gameAddress, bettorC, bettorC));
+
+    await sendRevealSideBet(gameAddress, bettorD, 2);
+    await sendRevealSideBet(gameAddress, bettorE, 1);
+    const pool = decode("SideBetPool", await rpc.getCompressedAccount(bn(poolOf(gameAddress).toBytes())));
+    assert.strictEqual(pool.revealedA, 2);
+    assert.strictEqual(pool.revealedB, 1);
+    console.log("✅ Revealed: two on A, one on B.");
+  });
+
+  it("3. Winners Split The Whole Pool", async () => {
+    // Paying C's bet to anyone but C is rejected
+    await assert.rejects(sendClaimSideBet(gameAddress, bettorC, signerA, bettorD.publicKey));
+    await assert.rejects(sendClaimSideBet(gameAddress, bettorD, signerA));
+
+    const before = await rpc.getBalance(bettorC.publicKey);
+    await sendClaimSideBet(gameAddress, bettorC, signerA);
+    assert.strictEqual((await rpc.getBalance(bettorC.publicKey)) - before, (3 * STAKE) / 2);
+    await assert.rejects(sendClaimSideBet(gameAddress, bettorC, signerA));
+
+    await sendClaimSideBet(gameAddress, bettorE, signerA);
+    assert.strictEqual(await rpc.getBalance(vaultOf(gameAddress)), 0);
+    console.log("✅ C and E shared D's stake.");
+  });
+
+  it("4. A Draw Refunds Revealed Bets", async () => {
+    const cell = (x: number, y: number) => y * GRID_SIZE + x;
+    // Both players fire one shot and miss: a draw
+    const planA = { ships: [{ x: 0, y: 0, isHorizontal: true }], shots: [cell(0, 4)] };
+    const planB = { ships: [{ x: 4, y: 0, isHorizontal: false }], shots: [cell(1, 4)] };
+    const saltA = randomBytes(32);
+    const saltB = randomBytes(32);
+    const { gameAddress: drawAddress } = await loadProfile(program, coder, rpc, signerA.publicKey);
+    await sendCreateBatchGame(
+      program, coder, rpc, signerA, outputStateTree, batchCommitment(drawAddress, signerA.publicKey, planA, saltA)
+    );
+    await sendJoinBatchGame(
+      program, coder, rpc, signerB, drawAddress, outputStateTree,
+      batchCommitment(drawAddress, signerB.publicKey, planB, saltB)
+    );
+
+    salts.clear();
+    await sendOpenSidePool(drawAddress, bettorC);
+    await sendPlaceSideBet(drawAddress, bettorC, 1);
+    await sendPlaceSideBet(drawAddress, bettorD, 2);
+    await sendRevealBatchPlan(program, coder, rpc, signerA, drawAddress, outputStateTree, planA, saltA);
+    await sendRevealBatchPlan(program, coder, rpc, signerB, drawAddress, outputStateTree, planB, saltB);
+    const game = decode("GameState", await rpc.getCompressedAccount(bn(drawAddress.toBytes())));
+    assert.strictEqual(game.gameStatus, 4); // Draw
+
+    await sendRevealSideBet(drawAddress, bettorC, 1);
+    await sendRevealSideBet(drawAddress, bettorD, 2);
+    for (const bettor of [bettorC, bettorD]) {
+      const before = await rpc.getBalance(bettor.publicKey);
+      await sendClaimSideBet(drawAddress, bettor, signerA);
+      assert.strictEqual((await rpc.getBalance(bettor.publicKey)) - before, STAKE);
+    }
+    assert.strictEqual(await rpc.getBalance(vaultOf(drawAddress)), 0);
+    console.log("✅ Draw refunded both stakes.");
+  });
+
+  it("5. The Last Winner Takes What Rounding Left", async () => {
+    const game = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    const bets = [[bettorC, 1], [bettorD, 2], [bettorE, 1], [bettorF, 1]] as const;
+    salts.clear();
+    await sendOpenSidePool(game, bettorC);
+    for (const [bettor, side] of bets) {
+      await sendPlaceSideBet(game, bettor, side);
+    }
+    await sendResign(game, signerB);
+    for (const [bettor, side] of bets) {
+      await sendRevealSideBet(game, bettor, side);
+    }
+
+    // Three winners can't split four stakes evenly
+    const share = Math.floor((4 * STAKE) / 3);
+    const [paidC, paidE, paidF] = await balanceChanges(
+      rpc,
+      [bettorC.publicKey, bettorE.publicKey, bettorF.publicKey],
+      async () => {
+        for (const bettor of [bettorC, bettorE, bettorF]) {
+          await sendClaimSideBet(game, bettor, signerA);
+        }
+      }
+    );
+    assert.deepStrictEqual([paidC, paidE], [share, share]);
+    assert.strictEqual(paidF, 4 * STAKE - 2 * share);
+    assert.strictEqual(await rpc.getBalance(vaultOf(game)), 0);
+    console.log("✅ F's claim emptied the vault.");
+  });
+});
+
+// Mirrors `wager_commitment` in the program
+function wagerCommitment(gameAddress: web3.PublicKey, player: web3.PublicKey, amount: number, salt: Buffer): number[] {
+  return Array.from(
+    createHash("sha256")
+      .update(Buffer.from("wager"))
+      .update(gameAddress.toBytes())
+      .update(player.toBytes())
+      .update(new anchor.BN(amount).toArrayLike(Buffer, "le", 8))
+      .update(salt)
+      .digest()
+  );
+}
+
+describe("battleship_sealed_wagers", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let settler: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+
+  const COLLATERAL = web3.LAMPORTS_PER_SOL; // WAGER_COLLATERAL_LAMPORTS
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+  // Fetches `addresses` with the protocol config behind them. The wager
+  // goes before the game, which is only read.
+  const accountsOf = async (addresses: web3.PublicKey[]) => {
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) =>
+      toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    return { accounts, proofRpcResult, remainingAccounts, metas, protocol };
+  };
+  const readOnly = (meta: any) => {
+    const { outputStateTreeIndex: _, ...rest } = meta;
+    return rest;
+  };
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+  const wagerOf = (game: web3.PublicKey) =>
+    deriveAddressV2(deriveAddressSeedV2([Buffer.from("sealed_wager"), game.toBytes()]), addressTree, program.programId);
+  const vaultOf = (game: web3.PublicKey) =>
+    web3.PublicKey.findProgramAddressSync([Buffer.from("sealed_vault"), game.toBytes()], program.programId)[0];
+
+  const sendSealWager = async (game: web3.PublicKey, signer: web3.Keypair, commitment: number[]) => {
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(wagerOf(game).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const gameMeta = toAccountMeta(account, game, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.sealWager(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: decode("GameState", account), meta: readOnly(gameMeta) },
+        commitment,
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { vault: vaultOf(game) }
+    );
+  };
+
+  const sendMatchSealedWager = async (game: web3.PublicKey, signer: web3.Keypair, commitment: number[]) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([wagerOf(game), game]);
+    await send(
+      program.methods.matchSealedWager(
+        { 0: proofRpcResult.compressedProof },
+        { game: decode("GameState", accounts[1]), meta: readOnly(metas[1]) },
+        { wager: decode("SealedWager", accounts[0]), meta: metas[0] },
+        commitment,
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { vault: vaultOf(game) }
+    );
+  };
+
+  const sendRevealWager = async (game: web3.PublicKey, signer: web3.Keypair, amount: number, salt: Buffer) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([wagerOf(game), game]);
+    await send(
+      program.methods.revealWager(
+        { 0: proofRpcResult.compressedProof },
+        { game: decode("GameState", accounts[1]), meta: readOnly(metas[1]) },
+        { wager: decode("SealedWager", accounts[0]), meta: metas[0] },
+        new anchor.BN(amount),
+        Array.from(salt),
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // Anyone can settle; the payouts go to the game's players
+  const sendSettleSealedWager = async (game: web3.PublicKey) => {
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await accountsOf([wagerOf(game), game]);
+    await send(
+      program.methods.settleSealedWager(
+        { 0: proofRpcResult.compressedProof },
+        decode("GameState", accounts[1]),
+        readOnly(metas[1]),
+        decode("SealedWager", accounts[0]),
+        metas[0],
+        protocol
+      ),
+      settler,
+      remainingAccounts,
+      { vault: vaultOf(game), playerA: signerA.publicKey, playerB: signerB.publicKey }
+    );
+  };
+
+  const sendCreate = () =>
+    sendCreateGame(program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)));
+  const sendJoin = (game: web3.PublicKey) =>
+    sendJoinGame(program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    settler = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, settler]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 5);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Both Reveals Move The Smaller Stake To The Winner", async () => {
+    const game = await sendCreate();
+    const [saltA, saltB] = [randomBytes(32), randomBytes(32)];
+    const [stakeA, stakeB] = [300_000_000, 500_000_000];
+
+    // Only A seals, and only before B joins; only B matches
+    await rejectsWith(sendSealWager(game, signerB, wagerCommitment(game, signerB.publicKey, stakeB, saltB)), "WagerClosed");
+    await sendSealWager(game, signerA, wagerCommitment(game, signerA.publicKey, stakeA, saltA));
+    await rejectsWith(sendMatchSealedWager(game, signerB, wagerCommitment(game, signerB.publicKey, stakeB, saltB)), "WagerClosed");
+    await sendJoin(game);
+    await rejectsWith(sendMatchSealedWager(game, signerA, wagerCommitment(game, signerA.publicKey, stakeA, saltA)), "WagerClosed");
+    await sendMatchSealedWager(game, signerB, wagerCommitment(game, signerB.publicKey, stakeB, saltB));
+    assert.strictEqual(await rpc.getBalance(vaultOf(game)), 2 * COLLATERAL);
+
+    // Nothing opens or settles mid-game
+    await rejectsWith(sendRevealWager(game, signerA, stakeA, saltA), "GameNotFinished");
+    await rejectsWith(sendSettleSealedWager(game), "GameNotFinished");
+
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, game, outputStateTree);
+    // A reveal must open the commitment
+    await rejectsWith(sendRevealWager(game, signerA, stakeB, saltA), "InvalidWagerReveal");
+    await sendRevealWager(game, signerA, stakeA, saltA);
+    await sendRevealWager(game, signerB, stakeB, saltB);
+
+    const changes = await balanceChanges(
+      rpc,
+      [signerA.publicKey, signerB.publicKey, vaultOf(game)],
+      () => sendSettleSealedWager(game)
+    );
+    assert.deepStrictEqual(changes, [COLLATERAL + stakeA, COLLATERAL - stakeA, -2 * COLLATERAL]);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(wagerOf(game).toBytes())), null, "Settling closes the wager");
+    console.log("✅ A won B's matched stake.");
+  });
+
+  it("2. An Unrevealed Wager Forfeits Its Collateral After The Window", async () => {
+    // Games pin the config's turn timeout, which bounds their reveal window
+    const WINDOW = 20;
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, WINDOW);
+    const game = await sendCreate().finally(() =>
+      sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, 0)
+    );
+    const [saltA, saltB] = [randomBytes(32), randomBytes(32)];
+    await sendSealWager(game, signerA, wagerCommitment(game, signerA.publicKey, 100_000_000, saltA));
+    await sendJoin(game);
+    await sendMatchSealedWager(game, signerB, wagerCommitment(game, signerB.publicKey, 100_000_000, saltB));
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, game, outputStateTree);
+
+    // B loses and never reveals, but keeps the collateral until the window ends
+    await sendRevealWager(game, signerA, 100_000_000, saltA);
+    await rejectsWith(sendSettleSealedWager(game), "TurnNotExpired");
+
+    const ended = decode("GameState", await rpc.getCompressedAccount(bn(game.toBytes())));
+    assert.strictEqual(ended.turnTimeoutSlots.toNumber(), WINDOW);
+    while ((await rpc.getSlot()) <= ended.lastActionSlot.toNumber() + WINDOW) {
+      await sleep(1000);
+    }
+    const changes = await balanceChanges(
+      rpc,
+      [signerA.publicKey, signerB.publicKey, vaultOf(game)],
+      () => sendSettleSealedWager(game)
+    );
+    assert.deepStrictEqual(changes, [2 * COLLATERAL, 0, -2 * COLLATERAL]);
+    console.log("✅ The only revealer took both collaterals.");
+  });
+
+  it("3. An Unmatched Wager Returns A's Collateral", async () => {
+    const game = await sendCreate();
+    await sendSealWager(game, signerA, wagerCommitment(game, signerA.publicKey, 100_000_000, randomBytes(32)));
+
+    const changes = await balanceChanges(
+      rpc,
+      [signerA.publicKey, signerB.publicKey, vaultOf(game)],
+      () => sendSettleSealedWager(game)
+    );
+    assert.deepStrictEqual(changes, [COLLATERAL, 0, -COLLATERAL]);
+    console.log("✅ Collateral refunded to A.");
+  });
+});
+
+// Upgrade authority of the deployed program, which gates admin instructions
+function loadAdminKeypair(): web3.Keypair {
+  const fs = require("fs");
+  return web3.Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(anchorWalletPath, "utf8"))));
+}
+
+function programDataAddress(programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync(
+    [programId.toBuffer()],
+    new web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  )[0];
+}
+
+// Mirrors `derive_fleet_preset_address` in the program: ["fleet_preset", preset_id]
+function deriveFleetPresetAddress(
+  presetId: number,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const idBytes = Buffer.alloc(2);
+  idBytes.writeUInt16LE(presetId);
+  const seed = deriveAddressSeedV2([Buffer.from("fleet_preset"), idBytes]);
+  return deriveAddressV2(seed, addressTree, programId);
+}
+
+describe("battleship_fleet_presets", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  let admin: web3.Keypair;
+  let stranger: web3.Keypair;
+  let joiner: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+
+  const PRESET_ID = Date.now() % 65_536;
+  // Small 4x4 board with two 2-cell ships each
+  const rules = { gridSize: 4, fleetA: Buffer.from([2, 2]), fleetB: Buffer.from([2, 2]), shotsPerTurnA: 1, shotsPerTurnB: 1, handicap: false, obstacles: 0, skipOnTimeout: false, sealedObstacles: false };
+
+  before(async () => {
+    admin = loadAdminKeypair();
+    stranger = new web3.Keypair();
+    joiner = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(stranger.publicKey, web3.LAMPORTS_PER_SOL);
+    await rpc.requestAirdrop(joiner.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, stranger, outputStateTree);
+    await sendCreateProfile(program, rpc, joiner, outputStateTree);
+  });
+
+  const loadPreset = async () => {
+    const presetAddress = deriveFleetPresetAddress(PRESET_ID, addressTree, program.programId);
+    return { presetAddress, presetAccount: await rpc.getCompressedAccount(bn(presetAddress.toBytes())) };
+  };
+  const decode = (type: string, account: any) => camelizeKeys(coder.types.decode(type, account!.data!.data));
+
+  const sendUpdatePreset = async (signer: web3.Keypair, newRules: any, active: boolean) => {
+    const { presetAddress, presetAccount } = await loadPreset();
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: presetAccount!.hash, tree: presetAccount!.treeInfo.tree, queue: presetAccount!.treeInfo.queue }],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+    const tx = await program.methods
+      .updateFleetPreset(
+        { 0: proofRpcResult.compressedProof },
+        decode("FleetPreset", presetAccount),
+        toAccountMeta(presetAccount, presetAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        newRules,
+        active
+      )
+      .accounts({ signer: signer.publicKey, programData: programDataAddress(program.programId) })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  // The stranger creates a game from the preset, which is proven read-only
+  const sendCreatePresetGame = async (ships: { x: number; y: number; isHorizontal: boolean }[]) => {
+    const { gameAddress, ...profile } = await loadProfile(program, coder, rpc, stranger.publicKey);
+    const { presetAddress, presetAccount } = await loadPreset();
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const [profileInput, configInput] = profile.slotInputs;
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        profileInput,
+        { hash: presetAccount!.hash, tree: presetAccount!.treeInfo.tree, queue: presetAccount!.treeInfo.queue },
+        configInput,
+      ],
+      [gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = {
+      profile: decode("PlayerProfile", profile.profileAccount),
+      profileMeta: toAccountMeta(
+        profile.profileAccount, new web3.PublicKey(profile.profileAccount!.address!), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      protocol: toProtocolConfigRef(coder, profile.configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree),
+    };
+    const { outputStateTreeIndex: _, ...presetMeta } = toAccountMeta(
+      presetAccount, presetAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[3],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+
+    const tx = await program.methods
+      .createPresetGame(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree) },
+        decode("FleetPreset", presetAccount),
+        presetMeta,
+        { ships, boardHash: Array.from(randomBytes(32)) },
+        slot
+      )
+      .accounts({ signer: stranger.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([stranger])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(stranger);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [stranger]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    return gameAddress;
+  };
+  const loadGameRules = async (game: web3.PublicKey) =>
+    decode("GameState", await rpc.getCompressedAccount(bn(game.toBytes()))).rules;
+
+  const sendCreatePreset = async (signer: web3.Keypair) => {
+    const presetAddress = deriveFleetPresetAddress(PRESET_ID, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(presetAddress.toBytes()) }]
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[0],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    const tx = await program.methods
+      .createFleetPreset(
+        { 0: proofRpcResult.compressedProof },
+        packedAddressTreeInfo,
+        outputStateTreeIndex,
+        PRESET_ID,
+        rules
+      )
+      .accounts({ signer: signer.publicKey, programData: programDataAddress(program.programId) })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+    return presetAddress;
+  };
+
+  it("1. Non-Admin Cannot Publish A Preset", async () => {
+    await assert.rejects(sendCreatePreset(stranger));
+    console.log("✅ Stranger rejected.");
+  });
+
+  it("2. Admin Publishes A 4x4 Preset", async () => {
+    const presetAddress = await sendCreatePreset(admin);
+    const account = await rpc.getCompressedAccount(bn(presetAddress.toBytes()));
+    const preset = camelizeKeys(coder.types.decode("FleetPreset", account!.data!.data));
+    assert.strictEqual(preset.presetId, PRESET_ID);
+    assert.strictEqual(preset.rules.gridSize, 4);
+    assert.ok(preset.active);
+    console.log("✅ Preset published.");
+  });
+
+  let presetGame: web3.PublicKey;
+
+  it("3. A Preset Game Plays With The Stored Fleet", async () => {
+    // Two 2-cell ships on rows 0 and 2 of the 4x4 board
+    presetGame = await sendCreatePresetGame([
+      { x: 0, y: 0, isHorizontal: true },
+      { x: 0, y: 2, isHorizontal: true },
+    ]);
+    const gameRules = await loadGameRules(presetGame);
+    assert.strictEqual(gameRules.gridSize, 4);
+    assert.deepStrictEqual(Array.from(gameRules.fleetA), [2, 2]);
+    assert.deepStrictEqual(Array.from(gameRules.fleetB), [2, 2]);
+
+    await sendJoinCustomGame(program, coder, rpc, joiner, presetGame, outputStateTree, [
+      { x: 3, y: 0, isHorizontal: false },
+      { x: 1, y: 3, isHorizontal: true },
+    ]);
+    const state = decode("GameState", await rpc.getCompressedAccount(bn(presetGame.toBytes())));
+    assert.strictEqual(state.gameStatus, 1);
+    assert.strictEqual(state.gridB.filter((c: number) => c === CELL_SHIP).length, 4);
+    console.log("✅ Both fleets follow the preset.");
+  });
+
+  it("4. Preset Updates Apply To New Games Only", async () => {
+    const bigger = { ...rules, fleetA: Buffer.from([3]), fleetB: Buffer.from([3]) };
+    await rejectsWith(sendUpdatePreset(stranger, bigger, true), "NotAdmin");
+    await sendUpdatePreset(admin, bigger, true);
+
+    // The game created earlier keeps the fleet it copied
+    assert.deepStrictEqual(Array.from((await loadGameRules(presetGame)).fleetA), [2, 2]);
+    const newer = await sendCreatePresetGame([{ x: 0, y: 0, isHorizontal: true }]);
+    assert.deepStrictEqual(Array.from((await loadGameRules(newer)).fleetA), [3]);
+    console.log("✅ Only the new game uses the updated fleet.");
+  });
+
+  it("5. A Retired Preset Starts No Games", async () => {
+    await sendUpdatePreset(admin, rules, false);
+    assert.strictEqual(decode("FleetPreset", (await loadPreset()).presetAccount).active, false);
+    await rejectsWith(
+      sendCreatePresetGame([
+        { x: 0, y: 0, isHorizontal: true },
+        { x: 0, y: 2, isHorizontal: true },
+      ]),
+      "PresetInactive"
+    );
+    console.log("✅ Retired preset rejected.");
+  });
+});
+
+const CIRCUIT_FLEET = 2;
+const CIRCUIT_SHOT = 1;
+const CIRCUIT_RATING = 3;
+const CIRCUIT_AFLOAT = 4;
+const PROOF_BACKEND_GROTH16 = 0;
+const MAX_SHIPS = 4;
+
+// Mirrors `derive_verifier_address` in the program: ["verifier", circuit, backend, version]
+function deriveVerifierAddress(
+  circuitId: number,
+  backend: number,
+  version: number,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const versionBytes = Buffer.alloc(2);
+  versionBytes.writeUInt16LE(version);
+  const seed = deriveAddressSeedV2([
+    Buffer.from("verifier"),
+    Buffer.from([circuitId]),
+    Buffer.from([backend]),
+    versionBytes,
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_attestation_address` in the program: ["attestation", owner, id]
+function deriveAttestationAddress(
+  owner: web3.PublicKey,
+  attestationId: number,
+  programId: web3.PublicKey
+): web3.PublicKey {
+  const seed = deriveAddressSeedV2([
+    Buffer.from("attestation"),
+    owner.toBytes(),
+    new anchor.BN(attestationId).toArrayLike(Buffer, "le", 8),
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `field_from_u64` in the program
+function fieldFromU64(value: number): Buffer {
+  return new anchor.BN(value).toArrayLike(Buffer, "be", 32);
+}
+
+// The only proof programs/mock_verifier accepts: the sha256 of the inputs
+function mockProof(publicInputs: Buffer[]): Buffer {
+  return createHash("sha256").update(Buffer.concat(publicInputs)).digest();
+}
+
+// Points the active Groth16 verifier of `circuitId` at `verifierProgram`,
+// registering or rotating it as needed; returns its address
+async function sendUseVerifier(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  admin: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  circuitId: number,
+  verifierProgram: web3.PublicKey
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  let version = 0;
+  let address = deriveVerifierAddress(circuitId, PROOF_BACKEND_GROTH16, version, program.programId);
+  let account = await rpc.getCompressedAccount(bn(address.toBytes()));
+  while (account && coder.types.decode("CircuitVerifier", account.data!.data).retired) {
+    version += 1;
+    address = deriveVerifierAddress(circuitId, PROOF_BACKEND_GROTH16, version, program.programId);
+    account = await rpc.getCompressedAccount(bn(address.toBytes()));
+  }
+  const current = account ? camelizeKeys(coder.types.decode("CircuitVerifier", account.data!.data)) : null;
+  if (current && current.verifierProgram.equals(verifierProgram)) {
+    return address;
+  }
+
+  const nextAddress = current
+    ? deriveVerifierAddress(circuitId, PROOF_BACKEND_GROTH16, version + 1, program.programId)
+    : address;
+  const proofRpcResult = await rpc.getValidityProofV0(
+    account ? [{ hash: account.hash, tree: account.treeInfo.tree, queue: account.treeInfo.queue }] : [],
+    [{ tree: addressTree, queue: addressTree, address: bn(nextAddress.toBytes()) }]
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = account
+    ? toAccountMeta(account, address, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree)
+    : null;
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[account ? 1 : 0],
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+  const method = current
+    ? program.methods.rotateVerifier(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+        current,
+        accountMeta,
+        verifierProgram,
+        web3.PublicKey.default
+      )
+    : program.methods.registerVerifier(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+        circuitId,
+        PROOF_BACKEND_GROTH16,
+        verifierProgram,
+        web3.PublicKey.default
+      );
+
+  const tx = await method
+    .accounts({ signer: admin.publicKey, programData: programDataAddress(program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([admin])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(admin);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [admin]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return nextAddress;
+}
+
+// Asserts `promise` fails with the program error `name`
+async function rejectsWith(promise: Promise<unknown>, name: string): Promise<void> {
+  await assert.rejects(promise, (err: any) => {
+    const logs: string[] = err.logs ?? err.transactionLogs ?? [];
+    return `${err.message}\n${logs.join("\n")}`.includes(name);
+  });
+}
+
+describe("battleship_zk_proofs", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const mockVerifier: web3.PublicKey = anchor.workspace.MockVerifier.programId;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  const verifiers: { [circuit: number]: web3.PublicKey } = {};
+
+  const hashA = randomBytes(32);
+  const hashB = randomBytes(32);
+  const rules = { gridSize: 5, fleetA: Buffer.from([SHIP_LENGTH]), fleetB: Buffer.from([SHIP_LENGTH]), shotsPerTurnA: 1, shotsPerTurnB: 1, handicap: false, obstacles: 0, skipOnTimeout: false, sealedObstacles: false };
+  const fleetInputs = (boardHash: Buffer) => [
+    boardHash,
+    fieldFromU64(rules.gridSize),
+    ...Array.from({ length: MAX_SHIPS }, (_, i) => fieldFromU64(i === 0 ? SHIP_LENGTH : 0)),
+  ];
+  const shotInputs = (boardHash: Buffer, cell: number, hit: boolean) => [
+    boardHash,
+    fieldFromU64(cell),
+    fieldFromU64(hit ? 1 : 0),
+  ];
+
+  const loadGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  // Fetches `addresses` in one validity proof, the protocol config after
+  // them; the last `readOnly` of `addresses` get read-only metas
+  const fetchAccounts = async (addresses: web3.PublicKey[], readOnly: number) => {
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) => {
+      const meta = toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree);
+      if (i < addresses.length - readOnly) return meta;
+      const { outputStateTreeIndex: _, ...readOnlyMeta } = meta;
+      return readOnlyMeta;
+    });
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    return { accounts, proofRpcResult, remainingAccounts, metas, configAccount, protocol };
+  };
+
+  const sendVerifyBoard = async (
+    signer: web3.Keypair,
+    boardProof: any,
+    verifierProgram = mockVerifier
+  ) => {
+    const verifierAddress = verifiers[CIRCUIT_FLEET];
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await fetchAccounts(
+      [gameAddress, verifierAddress],
+      1
+    );
+    await send(
+      program.methods.verifyBoard(
+        { 0: proofRpcResult.compressedProof },
+        {
+          game: camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+          meta: metas[0],
+        },
+        {
+          verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[1]!.data!.data)),
+          verifierMeta: metas[1],
+          proof: boardProof,
+        },
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { verifierProgram, verifyingKey: null }
+    );
+  };
+
+  const sendRespondZkAttack = async (signer: web3.Keypair, hit: boolean, shotProof: any) => {
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const verifierAddress = verifiers[CIRCUIT_SHOT];
+    const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await fetchAccounts(
+      [gameAddress, logAddress, verifierAddress],
+      1
+    );
+    await send(
+      program.methods.respondZkAttack(
+        { 0: proofRpcResult.compressedProof },
+        {
+          game: camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+          gameMeta: metas[0],
+          log: camelizeKeys(coder.types.decode("MoveLog", accounts[1]!.data!.data)),
+          logMeta: metas[1],
+          views: null,
+        },
+        {
+          verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[2]!.data!.data)),
+          verifierMeta: metas[2],
+          proof: shotProof,
+        },
+        hit,
+        null,
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { verifierProgram: mockVerifier, verifyingKey: null }
+    );
+  };
+
+  const sendAttestProof = async (signer: web3.Keypair, attestationId: number, circuitId: number, inputs: Buffer[]) => {
+    const attestationAddress = deriveAttestationAddress(signer.publicKey, attestationId, program.programId);
+    const verifierAddress = verifiers[circuitId];
+    const verifierAccount = await rpc.getCompressedAccount(bn(verifierAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: verifierAccount!.hash, tree: verifierAccount!.treeInfo.tree, queue: verifierAccount!.treeInfo.queue },
+        configInput,
+      ],
+      [{ tree: addressTree, queue: addressTree, address: bn(attestationAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { outputStateTreeIndex: _, ...verifierMeta } = toAccountMeta(
+      verifierAccount, verifierAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.attestProof(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        new anchor.BN(attestationId),
+        {
+          verifier: camelizeKeys(coder.types.decode("CircuitVerifier", verifierAccount!.data!.data)),
+          verifierMeta,
+          publicInputs: inputs.map((input) => Array.from(input)),
+          zkProof: mockProof(inputs),
+        },
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { verifierProgram: mockVerifier, verifyingKey: null }
+    );
+    return attestationAddress;
+  };
+
+  const loadAttestation = async (attestationAddress: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(attestationAddress.toBytes()));
+    return { account, attestation: camelizeKeys(coder.types.decode("ProofAttestation", account!.data!.data)) };
+  };
+
+  // Answers the pending shot with an attestation in place of the proof
+  const sendRespondAttested = async (
+    signer: web3.Keypair,
+    hit: boolean,
+    attestationAddress: web3.PublicKey,
+    { account, attestation }: { account: any; attestation: any }
+  ) => {
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const verifierAddress = verifiers[CIRCUIT_SHOT];
+    const addresses = [gameAddress, logAddress, verifierAddress];
+    // `account` is the attestation as it was loaded, even if since closed
+    const accounts = [...(await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))))), account];
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = [...addresses, attestationAddress].map((address, i) =>
+      toAccountMeta(accounts[i], address, proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const { outputStateTreeIndex: _, ...verifierMeta } = metas[2];
+    await send(
+      program.methods.respondZkAttack(
+        { 0: proofRpcResult.compressedProof },
+        {
+          game: camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+          gameMeta: metas[0],
+          log: camelizeKeys(coder.types.decode("MoveLog", accounts[1]!.data!.data)),
+          logMeta: metas[1],
+          views: null,
+        },
+        {
+          verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[2]!.data!.data)),
+          verifierMeta,
+          proof: { attested: { 0: { attestation, meta: metas[3] } } },
+        },
+        hit,
+        null,
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[4], remainingAccounts, outputStateTree)
+      ),
+      signer,
+      remainingAccounts,
+      { verifierProgram: mockVerifier, verifyingKey: null }
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    const creator = await loadProfile(program, coder, rpc, signerA.publicKey);
+    gameAddress = creator.gameAddress;
+
+    const admin = loadAdminKeypair();
+    for (const circuit of [CIRCUIT_FLEET, CIRCUIT_SHOT, CIRCUIT_AFLOAT]) {
+      verifiers[circuit] = await sendUseVerifier(program, coder, rpc, admin, outputStateTree, circuit, mockVerifier);
+    }
+
+    // A creates a ZK game, B joins with their own board hash
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      creator.slotInputs,
+      [gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, creator, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createZkGame(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        rules,
+        Array.from(hashA),
+        slot
+      ),
+      signerA,
+      remainingAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId) }
+    );
+
+    const { accounts, proofRpcResult: joinProof, remainingAccounts: joinAccounts, metas, configAccount } = await fetchAccounts(
+      [gameAddress, deriveProfileAddress(signerB.publicKey, addressTree, program.programId)],
+      0
+    );
+    await send(
+      program.methods.joinHiddenGame(
+        { 0: joinProof.compressedProof },
+        camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+        metas[0],
+        Array.from(hashB),
+        toActiveGameSlot(
+          coder, { profileAccount: accounts[1], configAccount }, joinProof.rootIndices[1], joinAccounts, outputStateTree
+        )
+      ),
+      signerB,
+      joinAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId) }
+    );
+  });
+
+  it("1. Fleet Proofs Only Pass Through The Registered Verifier", async () => {
+    const proofA = { inline: { 0: mockProof(fleetInputs(hashA)) } };
+    // Any other program in the verifier's place is refused
+    await rejectsWith(
+      sendVerifyBoard(signerA, proofA, anchor.workspace.Zkcompress.programId),
+      "VerifierMismatch"
+    );
+    // A proof of someone else's board doesn't verify
+    await assert.rejects(sendVerifyBoard(signerA, { inline: { 0: mockProof(fleetInputs(hashB)) } }));
+    await assert.rejects(sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0));
+
+    await sendVerifyBoard(signerA, proofA);
+    await sendVerifyBoard(signerB, { inline: { 0: mockProof(fleetInputs(hashB)) } });
+    const state = await loadGame();
+    assert.strictEqual(state.boardsVerified, 3);
+    assert.ok(new web3.PublicKey(state.verifiers[CIRCUIT_FLEET]).equals(verifiers[CIRCUIT_FLEET]));
+    console.log("✅ Both fleets proven through the mock verifier.");
+  });
+
+  it("2. Shot Answers Must Be Proven", async () => {
+    // A fires at (4, 0); B claims a miss but only holds a proof of the hit
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
+    const proof = { inline: { 0: mockProof(shotInputs(hashB, 4, true)) } };
+    await assert.rejects(sendRespondZkAttack(signerB, false, proof));
+    await assert.rejects(sendRespondZkAttack(signerA, true, proof));
+
+    await sendRespondZkAttack(signerB, true, proof);
+    const state = await loadGame();
+    assert.strictEqual(state.gridB[4], CELL_HIT);
+    assert.strictEqual(state.hitsB, 1);
+    assert.strictEqual(state.pendingShot, null);
+    console.log("✅ Proven hit recorded.");
+  });
+
+  it("3. An Attestation Stands In For The Proof Once", async () => {
+    // B fires at (0, 4); A attests the miss in one transaction, answers in another
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, 4);
+    const attestationAddress = await sendAttestProof(signerA, 1, CIRCUIT_SHOT, shotInputs(hashA, 20, false));
+    const loaded = await loadAttestation(attestationAddress);
+    assert.ok(new web3.PublicKey(loaded.attestation.verifier).equals(verifiers[CIRCUIT_SHOT]));
+
+    // The attestation is tied to its inputs: it can't back a hit
+    await rejectsWith(sendRespondAttested(signerA, true, attestationAddress, loaded), "AttestationMismatch");
+    await sendRespondAttested(signerA, false, attestationAddress, loaded);
+    assert.strictEqual((await loadGame()).gridA[20], CELL_MISS);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(attestationAddress.toBytes())), null);
+
+    // A fires at (4, 1) and B answers the hit, then B fires at (1, 4), the
+    // same miss for A as far as the circuit cares
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 1);
+    await sendRespondZkAttack(signerB, true, { inline: { 0: mockProof(shotInputs(hashB, 9, true)) } });
+    await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, 1, 4);
+    // Closed when it was used, so it can't answer a second shot
+    await assert.rejects(sendRespondAttested(signerA, false, attestationAddress, loaded));
+    console.log("✅ Attestation consumed by one answer.");
+  });
+
+  it("4. Attestations Expire", async () => {
+    // (1, 4) is pending against A
+    const inputs = shotInputs(hashA, 21, false);
+    const attestationAddress = await sendAttestProof(signerA, 2, CIRCUIT_SHOT, inputs);
+    const loaded = await loadAttestation(attestationAddress);
+    while ((await rpc.getSlot()) <= loaded.attestation.expiresSlot.toNumber()) {
+      await sleep(2000);
+    }
+    await rejectsWith(sendRespondAttested(signerA, false, attestationAddress, loaded), "AttestationExpired");
+
+    // The inline proof still answers the shot
+    await sendRespondZkAttack(signerA, false, { inline: { 0: mockProof(inputs) } });
+    assert.strictEqual((await loadGame()).gridA[21], CELL_MISS);
+    console.log("✅ Expired attestation refused.");
+  });
+
+  it("5. Defenders Prove Their Fleet Is Still Afloat", async () => {
+    // A has fired at B's (4, 0) and (4, 1)
+    const state = await loadGame();
+    assert.strictEqual(state.targetedA, (1 << 4) | (1 << 9));
+    const verifierAddress = verifiers[CIRCUIT_AFLOAT];
+    const prove = async (proof: Buffer) => {
+      const { accounts, proofRpcResult, remainingAccounts, metas, protocol } = await fetchAccounts(
+        [gameAddress, verifierAddress],
+        1
+      );
+      await send(
+        program.methods.proveFleetAfloat(
+          { 0: proofRpcResult.compressedProof },
+          {
+            game: camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+            meta: metas[0],
+          },
+          {
+            verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[1]!.data!.data)),
+            verifierMeta: metas[1],
+            proof: { inline: { 0: proof } },
+          },
+          protocol
+        ),
+        signerB,
+        remainingAccounts,
+        { verifierProgram: mockVerifier, verifyingKey: null }
+      );
+    };
+    // Proven against a mask that ignores A's shots
+    await assert.rejects(prove(mockProof([hashB, fieldFromU64(0)])));
+    await prove(mockProof([hashB, fieldFromU64(state.targetedA)]));
+    console.log("✅ Liveness proof accepted.");
+  });
+});
+
+const RATING_BRACKETS = [[0, 1099], [1100, 1399], [1400, 1799], [1800, 65_535]];
+const INITIAL_RATING = 1200;
+const TICKET_QUEUED = 0;
+const TICKET_MATCHED = 1;
+
+// Mirrors `derive_bucket_queue_address` in the program: ["bucket_queue", bucket]
+function deriveBucketQueueAddress(bucket: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("bucket_queue"), Buffer.from([bucket])]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_match_ticket_address` in the program: ["match_ticket", owner, id]
+function deriveMatchTicketAddress(owner: web3.PublicKey, ticketId: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([
+    Buffer.from("match_ticket"),
+    owner.toBytes(),
+    new anchor.BN(ticketId).toArrayLike(Buffer, "le", 8),
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_ban_address` in the program: ["ban", player]
+function deriveBanAddress(player: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("ban"), player.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Public inputs of a rating circuit proof that `commitment` opens to a
+// rating in [min, max]
+function rangeInputs(commitment: Buffer, [min, max]: number[]): Buffer[] {
+  return [commitment, fieldFromU64(min), fieldFromU64(max)];
+}
+
+// Seals `signer`'s rating behind `commitment`, proven with a mock verifier proof
+async function sendSealRating(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  verifierAddress: web3.PublicKey,
+  commitment: Buffer,
+  proof: Buffer
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const profileAddress = deriveProfileAddress(signer.publicKey, addressTree, program.programId);
+  const addresses = [profileAddress, verifierAddress];
+  const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+    []
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const profileMeta = toAccountMeta(accounts[0], profileAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+  const { outputStateTreeIndex: _, ...verifierMeta } = toAccountMeta(
+    accounts[1], verifierAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+  const protocol = toProtocolConfigRef(
+    coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+  );
+  const tx = await program.methods
+    .sealRating(
+      { 0: proofRpcResult.compressedProof },
+      camelizeKeys(coder.types.decode("PlayerProfile", accounts[0]!.data!.data)),
+      profileMeta,
+      {
+        verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[1]!.data!.data)),
+        verifierMeta,
+        proof: { inline: { 0: proof } },
+      },
+      Array.from(commitment),
+      protocol
+    )
+    .accounts({
+      signer: signer.publicKey,
+      verifierProgram: anchor.workspace.MockVerifier.programId,
+      verifyingKey: null,
+    })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+describe("battleship_bucket_queues", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const mockVerifier: web3.PublicKey = anchor.workspace.MockVerifier.programId;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let verifierAddress: web3.PublicKey;
+  let queueAddress: web3.PublicKey;
+
+  const BUCKET = 1;
+  const commitmentA = randomBytes(32);
+  const commitmentB = randomBytes(32);
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decode = async (typeName: string, address: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    return account ? camelizeKeys(coder.types.decode(typeName, account.data!.data)) : null;
+  };
+
+  // Joins the bucket with `proof`; `waiting` is the waiting ticket's address
+  // when someone is already in the queue
+  const sendJoinBucketQueue = async (
+    signer: web3.Keypair,
+    ticketId: number,
+    proof: Buffer,
+    waiting: web3.PublicKey | null,
+    verifierProgram = mockVerifier
+  ) => {
+    const profileAddress = deriveProfileAddress(signer.publicKey, addressTree, program.programId);
+    const ticketAddress = deriveMatchTicketAddress(signer.publicKey, ticketId, program.programId);
+    const addresses = [queueAddress, ...(waiting ? [waiting] : []), profileAddress, verifierAddress];
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    // The new ticket, then the ban the system program checks is absent
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      [ticketAddress, deriveBanAddress(signer.publicKey, program.programId)].map((a) => ({
+        tree: addressTree,
+        queue: addressTree,
+        address: bn(a.toBytes()),
+      }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) =>
+      toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const { outputStateTreeIndex: _p, ...profileMeta } = metas[metas.length - 2];
+    const { outputStateTreeIndex: _v, ...verifierMeta } = metas[metas.length - 1];
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.joinBucketQueue(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[accounts.length + 1],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        {
+          ticketId: new anchor.BN(ticketId),
+          queue: camelizeKeys(coder.types.decode("BucketQueue", accounts[0]!.data!.data)),
+          queueMeta: metas[0],
+          waiting: waiting
+          ? { ticket: camelizeKeys(coder.types.decode("MatchTicket", accounts[1]!.data!.data)), meta: metas[1] }
+          : null,
+        },
+        {
+          profile: camelizeKeys(coder.types.decode("PlayerProfile", accounts[accounts.length - 2]!.data!.data)),
+          profileMeta,
+          verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[accounts.length - 1]!.data!.data)),
+          verifierMeta,
+          proof: { inline: { 0: proof } },
+        },
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { verifierProgram, verifyingKey: null }
+    );
+    return ticketAddress;
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    queueAddress = deriveBucketQueueAddress(BUCKET, program.programId);
+
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    verifierAddress = await sendUseVerifier(
+      program, coder, rpc, loadAdminKeypair(), outputStateTree, CIRCUIT_RATING, mockVerifier
+    );
+
+    // Each bucket opens once for the whole validator
+    if (!(await rpc.getCompressedAccount(bn(queueAddress.toBytes())))) {
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [configInput],
+        [{ tree: addressTree, queue: addressTree, address: bn(queueAddress.toBytes()) }]
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const protocol = toProtocolConfigRef(
+        coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      );
+      const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+      await send(
+        program.methods.openBucketQueue(
+          { 0: proofRpcResult.compressedProof },
+          { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          remainingAccounts.insertOrGet(outputStateTree),
+          BUCKET,
+          protocol
+        ),
+        signerA,
+        remainingAccounts
+      );
+    }
+  });
+
+  it("1. Ratings Are Sealed Against A Proof Of The Current Rating", async () => {
+    // A proof for any other rating doesn't open the commitment
+    await assert.rejects(
+      sendSealRating(
+        program, coder, rpc, signerA, outputStateTree, verifierAddress,
+        commitmentA, mockProof(rangeInputs(commitmentA, [1500, 1500]))
+      )
+    );
+    await sendSealRating(
+      program, coder, rpc, signerA, outputStateTree, verifierAddress,
+      commitmentA, mockProof(rangeInputs(commitmentA, [INITIAL_RATING, INITIAL_RATING]))
+    );
+    await sendSealRating(
+      program, coder, rpc, signerB, outputStateTree, verifierAddress,
+      commitmentB, mockProof(rangeInputs(commitmentB, [INITIAL_RATING, INITIAL_RATING]))
+    );
+
+    const profile = await decode("PlayerProfile", deriveProfileAddress(signerA.publicKey, addressTree, program.programId));
+    assert.strictEqual(profile.rating, 0);
+    assert.deepStrictEqual(Buffer.from(profile.ratingCommitment), commitmentA);
+    // Sealed once
+    await rejectsWith(
+      sendSealRating(
+        program, coder, rpc, signerA, outputStateTree, verifierAddress,
+        commitmentB, mockProof(rangeInputs(commitmentB, [0, 0]))
+      ),
+      "WrongProfile"
+    );
+    console.log("✅ Ratings sealed.");
+  });
+
+  it("2. Joining Needs A Proof Of The Bucket", async () => {
+    const proof = mockProof(rangeInputs(commitmentA, RATING_BRACKETS[BUCKET]));
+    await rejectsWith(
+      sendJoinBucketQueue(signerA, 1, proof, null, anchor.workspace.Zkcompress.programId),
+      "VerifierMismatch"
+    );
+    // A proof for the next bucket up doesn't get A into this one
+    await assert.rejects(
+      sendJoinBucketQueue(signerA, 1, mockProof(rangeInputs(commitmentA, RATING_BRACKETS[BUCKET + 1])), null)
+    );
+
+    const ticketAddress = await sendJoinBucketQueue(signerA, 1, proof, null);
+    const ticket = await decode("MatchTicket", ticketAddress);
+    assert.strictEqual(ticket.status, TICKET_QUEUED);
+    assert.strictEqual(ticket.bracket, BUCKET);
+    assert.ok(ticket.oracle.equals(web3.PublicKey.default));
+    const queue = await decode("BucketQueue", queueAddress);
+    assert.ok(new web3.PublicKey(queue.waiting).equals(ticketAddress));
+    console.log("✅ A waits in the bucket.");
+  });
+
+  it("3. A Waiting Player Can Leave", async () => {
+    const ticketAddress = deriveMatchTicketAddress(signerA.publicKey, 1, program.programId);
+    const leave = async (signer: web3.Keypair) => {
+      const addresses = [queueAddress, ticketAddress];
+      const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+        []
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const metas = accounts.map((a, i) =>
+        toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+      );
+      await send(
+        program.methods.leaveBucketQueue(
+          { 0: proofRpcResult.compressedProof },
+          camelizeKeys(coder.types.decode("BucketQueue", accounts[0]!.data!.data)),
+          metas[0],
+          camelizeKeys(coder.types.decode("MatchTicket", accounts[1]!.data!.data)),
+          metas[1],
+          toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+        ),
+        signer,
+        remainingAccounts
+      );
+    };
+    await rejectsWith(leave(signerB), "NotTicketOwner");
+    await leave(signerA);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(ticketAddress.toBytes())), null);
+    assert.strictEqual((await decode("BucketQueue", queueAddress)).waiting, null);
+
+    await sendJoinBucketQueue(signerA, 2, mockProof(rangeInputs(commitmentA, RATING_BRACKETS[BUCKET])), null);
+    console.log("✅ A left and queued again.");
+  });
+
+  it("4. The Next Joiner Is Matched With The Waiting Player", async () => {
+    const hostTicket = deriveMatchTicketAddress(signerA.publicKey, 2, program.programId);
+    const proof = mockProof(rangeInputs(commitmentB, RATING_BRACKETS[BUCKET]));
+    // B has to take the waiting ticket rather than queue behind it
+    await rejectsWith(sendJoinBucketQueue(signerB, 1, proof, null), "TicketNotQueued");
+
+    const guestTicket = await sendJoinBucketQueue(signerB, 1, proof, hostTicket);
+    const host = await decode("MatchTicket", hostTicket);
+    const guest = await decode("MatchTicket", guestTicket);
+    assert.strictEqual(host.status, TICKET_MATCHED);
+    assert.strictEqual(guest.status, TICKET_MATCHED);
+    assert.ok(host.isHost);
+    assert.ok(!guest.isHost);
+    assert.ok(host.opponent.equals(signerB.publicKey));
+    assert.ok(guest.opponent.equals(signerA.publicKey));
+    assert.strictEqual((await decode("BucketQueue", queueAddress)).waiting, null);
+    console.log("✅ A and B matched in the bucket.");
+  });
+});
+
+describe("battleship_matchmaking", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const mockVerifier: web3.PublicKey = anchor.workspace.MockVerifier.programId;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let signerC: web3.Keypair;
+  let oracle: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let verifierAddress: web3.PublicKey;
+
+  const commitmentA = randomBytes(32);
+  const commitmentB = randomBytes(32);
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decode = async (typeName: string, address: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    return account ? camelizeKeys(coder.types.decode(typeName, account.data!.data)) : null;
+  };
+
+  // Queues `signer` with preferences the program never reads. Ranked tickets
+  // prove the signer's sealed rating is inside `ranked.bracket`.
+  const sendEnqueue = async (
+    signer: web3.Keypair,
+    ticketId: number,
+    ranked: { commitment: Buffer; bracket: number } | null = null
+  ) => {
+    const configAddress = deriveOracleConfigAddress(addressTree, program.programId);
+    const ticketAddress = deriveMatchTicketAddress(signer.publicKey, ticketId, program.programId);
+    const addresses = [
+      configAddress,
+      ...(ranked ? [deriveProfileAddress(signer.publicKey, addressTree, program.programId), verifierAddress] : []),
+    ];
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    // The new ticket, then for ranked tickets the ban the system program
+    // checks is absent
+    const newAddresses = ranked ? [ticketAddress, deriveBanAddress(signer.publicKey, program.programId)] : [ticketAddress];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      newAddresses.map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) => {
+      const { outputStateTreeIndex: _, ...meta } = toAccountMeta(
+        a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree
+      );
+      return meta;
+    });
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const trees = {
+      addressTreeInfo: {
+        rootIndex: proofRpcResult.rootIndices[accounts.length + 1],
+        addressMerkleTreePubkeyIndex,
+        addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+      },
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+    const ticket = {
+      ticketId: new anchor.BN(ticketId),
+      oracleConfig: {
+        config: camelizeKeys(coder.types.decode("OracleConfig", accounts[0]!.data!.data)),
+        meta: metas[0],
+      },
+      preferences: {
+        ciphertext: Array.from(randomBytes(32)),
+        encryptionKey: Array.from(randomBytes(32)),
+        nonce: new anchor.BN(0),
+      },
+    };
+    if (!ranked) {
+      await send(
+        program.methods.enqueueMatch({ 0: proofRpcResult.compressedProof }, trees, ticket, protocol),
+        signer,
+        remainingAccounts
+      );
+    } else {
+      await send(
+        program.methods.enqueueRankedMatch(
+          { 0: proofRpcResult.compressedProof },
+          trees,
+          ticket,
+          {
+            profile: camelizeKeys(coder.types.decode("PlayerProfile", accounts[1]!.data!.data)),
+            profileMeta: metas[1],
+            verifier: camelizeKeys(coder.types.decode("CircuitVerifier", accounts[2]!.data!.data)),
+            verifierMeta: metas[2],
+            proof: { inline: { 0: mockProof(rangeInputs(ranked.commitment, RATING_BRACKETS[ranked.bracket])) } },
+          },
+          ranked.bracket,
+          protocol
+        ),
+        signer,
+        remainingAccounts,
+        { verifierProgram: mockVerifier, verifyingKey: null }
+      );
+    }
+    return ticketAddress;
+  };
+
+  // The tickets an instruction updates, then the config, as proof inputs
+  const loadTickets = async (addresses: web3.PublicKey[]) => {
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const inputs = [
+      ...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })),
+      configInput,
+    ];
+    return { accounts, configAccount, inputs };
+  };
+
+  const sendMatch = async (signer: web3.Keypair, host: web3.PublicKey, guest: web3.PublicKey) => {
+    const { accounts, configAccount, inputs } = await loadTickets([host, guest]);
+    const proofRpcResult = await rpc.getValidityProofV0(inputs, []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const metas = accounts.map((a, i) =>
+      toAccountMeta(a, [host, guest][i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    await send(
+      program.methods.matchCallback(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("MatchTicket", accounts[0]!.data!.data)),
+        metas[0],
+        camelizeKeys(coder.types.decode("MatchTicket", accounts[1]!.data!.data)),
+        metas[1],
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendCancel = async (signer: web3.Keypair, ticketAddress: web3.PublicKey) => {
+    const { accounts, configAccount, inputs } = await loadTickets([ticketAddress]);
+    const proofRpcResult = await rpc.getValidityProofV0(inputs, []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    await send(
+      program.methods.cancelMatch(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("MatchTicket", accounts[0]!.data!.data)),
+        toAccountMeta(accounts[0], ticketAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree)
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // Spends `signer`'s matched ticket on a game with A's ship at (0, 0)
+  const sendCreateMatchedGame = async (signer: web3.Keypair, ticketAddress: web3.PublicKey) => {
+    const profile = await loadProfile(program, coder, rpc, signer.publicKey);
+    const logAddress = deriveMoveLogAddress(profile.gameAddress, addressTree, program.programId);
+    const ticketAccount = await rpc.getCompressedAccount(bn(ticketAddress.toBytes()));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: ticketAccount!.hash, tree: ticketAccount!.treeInfo.tree, queue: ticketAccount!.treeInfo.queue },
+        ...profile.slotInputs,
+      ],
+      [profile.gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const ticketMeta = toAccountMeta(
+      ticketAccount, ticketAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const slot = toActiveGameSlot(coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createMatchedGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[3],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        camelizeKeys(coder.types.decode("MatchTicket", ticketAccount!.data!.data)),
+        ticketMeta,
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        slot
+      ),
+      signer,
+      remainingAccounts,
+      { vault: deriveVaultAddress(profile.gameAddress, program.programId) }
+    );
+    return profile.gameAddress;
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    signerC = new web3.Keypair();
+    oracle = new web3.Keypair();
+
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+
+    for (const signer of [signerA, signerB, signerC, oracle]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+
+    for (const signer of [signerA, signerB, signerC]) {
+      await sendCreateProfile(program, rpc, signer, outputStateTree);
+    }
+    await sendSetOracle(program, coder, rpc, loadAdminKeypair(), oracle.publicKey, outputStateTree);
+    verifierAddress = await sendUseVerifier(
+      program, coder, rpc, loadAdminKeypair(), outputStateTree, CIRCUIT_RATING, mockVerifier
+    );
+  });
+
+  it("1. Only The Oracle Pairs Queued Tickets", async () => {
+    const hostTicket = await sendEnqueue(signerA, 1);
+    const guestTicket = await sendEnqueue(signerB, 1);
+    const queued = await decode("MatchTicket", hostTicket);
+    assert.strictEqual(queued.status, TICKET_QUEUED);
+    assert.ok(queued.oracle.equals(oracle.publicKey));
+    assert.strictEqual(queued.bracket, null);
+
+    await rejectsWith(sendMatch(signerC, hostTicket, guestTicket), "NotOracle");
+    await sendMatch(oracle, hostTicket, guestTicket);
+
+    const host = await decode("MatchTicket", hostTicket);
+    const guest = await decode("MatchTicket", guestTicket);
+    assert.strictEqual(host.status, TICKET_MATCHED);
+    assert.strictEqual(guest.status, TICKET_MATCHED);
+    assert.ok(host.isHost);
+    assert.ok(!guest.isHost);
+    assert.ok(host.opponent.equals(signerB.publicKey));
+    assert.ok(guest.opponent.equals(signerA.publicKey));
+    // Matched tickets are out of the queue
+    await rejectsWith(sendMatch(oracle, hostTicket, guestTicket), "TicketNotQueued");
+    console.log("✅ A and B paired by the oracle.");
+  });
+
+  it("2. Ranked Tickets Only Pair Within Their Bracket", async () => {
+    await sendSealRating(
+      program, coder, rpc, signerA, outputStateTree, verifierAddress,
+      commitmentA, mockProof(rangeInputs(commitmentA, [INITIAL_RATING, INITIAL_RATING]))
+    );
+    await sendSealRating(
+      program, coder, rpc, signerB, outputStateTree, verifierAddress,
+      commitmentB, mockProof(rangeInputs(commitmentB, [INITIAL_RATING, INITIAL_RATING]))
+    );
+    const ticketA = await sendEnqueue(signerA, 2, { commitment: commitmentA, bracket: 1 });
+    const ticketB = await sendEnqueue(signerB, 2, { commitment: commitmentB, bracket: 2 });
+    const unranked = await sendEnqueue(signerC, 1);
+    assert.strictEqual((await decode("MatchTicket", ticketA)).bracket, 1);
+
+    await rejectsWith(sendMatch(oracle, ticketA, ticketB), "BracketMismatch");
+    // Nor does a ranked ticket pair with an unranked one
+    await rejectsWith(sendMatch(oracle, unranked, ticketA), "BracketMismatch");
+    assert.strictEqual((await decode("MatchTicket", ticketA)).status, TICKET_QUEUED);
+    assert.strictEqual((await decode("MatchTicket", ticketB)).status, TICKET_QUEUED);
+    console.log("✅ Cross-bracket pairing rejected.");
+  });
+
+  it("3. Owners Cancel Tickets That Were Not Matched", async () => {
+    const ticketA = deriveMatchTicketAddress(signerA.publicKey, 2, program.programId);
+    await rejectsWith(sendCancel(signerB, ticketA), "NotTicketOwner");
+    // A matched ticket is spent on the game, not cancelled
+    await rejectsWith(
+      sendCancel(signerA, deriveMatchTicketAddress(signerA.publicKey, 1, program.programId)),
+      "TicketNotQueued"
+    );
+
+    // Tickets hold no stake, so cancelling just closes them
+    await sendCancel(signerA, ticketA);
+    await sendCancel(signerB, deriveMatchTicketAddress(signerB.publicKey, 2, program.programId));
+    await sendCancel(signerC, deriveMatchTicketAddress(signerC.publicKey, 1, program.programId));
+    assert.strictEqual(await rpc.getCompressedAccount(bn(ticketA.toBytes())), null);
+    console.log("✅ Unmatched tickets cancelled.");
+  });
+
+  it("4. The Host Opens A Game Only The Matched Guest Can Join", async () => {
+    const hostTicket = deriveMatchTicketAddress(signerA.publicKey, 1, program.programId);
+    const guestTicket = deriveMatchTicketAddress(signerB.publicKey, 1, program.programId);
+    await rejectsWith(sendCreateMatchedGame(signerB, guestTicket), "TicketNotQueued");
+    await rejectsWith(sendCreateMatchedGame(signerC, hostTicket), "NotTicketOwner");
+
+    const gameAddress = await sendCreateMatchedGame(signerA, hostTicket);
+    assert.strictEqual(await rpc.getCompressedAccount(bn(hostTicket.toBytes())), null);
+    const state = await decode("GameState", gameAddress);
+    assert.strictEqual(state.gameStatus, 0);
+    assert.ok(state.playerA.equals(signerA.publicKey));
+    assert.ok(state.reservedFor.equals(signerB.publicKey));
+
+    await rejectsWith(
+      sendJoinGame(program, coder, rpc, signerC, gameAddress, outputStateTree, 0, 0, true, Array.from(randomBytes(32))),
+      "GameReserved"
+    );
+    await sendJoinGame(program, coder, rpc, signerB, gameAddress, outputStateTree, 0, 0, true, Array.from(randomBytes(32)));
+    assert.strictEqual((await decode("GameState", gameAddress)).gameStatus, 1);
+    console.log("✅ Matched game created and joined by the guest.");
+  });
+});
+
+describe("battleship_pause", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let latecomer: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  let openGame: web3.PublicKey;
+  let endedGame: web3.PublicKey;
+
+  const loadConfig = async () => {
+    const { configAccount } = await loadProtocolConfig(program, rpc);
+    return camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data));
+  };
+  const isPaused = async () => (await loadConfig()).paused;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const sendCancelGame = async (game: web3.PublicKey) => {
+    const loaded = await loadGameWithConfig(program, coder, rpc, game, outputStateTree);
+    await send(
+      program.methods.cancelGame(loaded.proof, loaded.game, loaded.meta, null, loaded.protocol),
+      signerA,
+      loaded.remainingAccounts,
+      { vault: deriveVaultAddress(game, program.programId), playerA: signerA.publicKey, sponsor: null }
+    );
+  };
+
+  const sendClaimDeposits = async (game: web3.PublicKey) => {
+    const loaded = await loadGameWithConfig(program, coder, rpc, game, outputStateTree);
+    const { outputStateTreeIndex: _, ...gameMeta } = loaded.meta;
+    await send(
+      program.methods.claimDeposits(loaded.proof, loaded.game, gameMeta, loaded.protocol),
+      signerA,
+      loaded.remainingAccounts,
+      { vault: deriveVaultAddress(game, program.programId), playerA: signerA.publicKey, playerB: signerB.publicKey }
+    );
+  };
+
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    latecomer = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, latecomer]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    openGame = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    endedGame = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, endedGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, endedGame, outputStateTree);
+  });
+
+  // The config is shared by every suite, so it's never left paused
+  after(async () => {
+    if (await isPaused()) {
+      await sendPauseProtocol(program, coder, rpc, admin, outputStateTree, false);
+    }
+  });
+
+  it("1. Only The Admin Can Pause", async () => {
+    await rejectsWith(sendPauseProtocol(program, coder, rpc, signerA, outputStateTree, true), "NotAdmin");
+    assert.strictEqual(await isPaused(), false);
+    console.log("✅ Players can't pause the protocol.");
+  });
+
+  it("2. Play Stops While Paused", async () => {
+    await sendPauseProtocol(program, coder, rpc, admin, outputStateTree, true);
+    assert.strictEqual(await isPaused(), true);
+
+    // Moves, new games and new profiles alike
+    await rejectsWith(
+      sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0),
+      "ProtocolPaused"
+    );
+    await rejectsWith(
+      sendCreateGame(program, coder, rpc, signerB, outputStateTree, 0, 0, true, Array.from(randomBytes(32))),
+      "ProtocolPaused"
+    );
+    await rejectsWith(sendCreateProfile(program, rpc, latecomer, outputStateTree), "ProtocolPaused");
+    // Nor can a player lift the pause
+    await rejectsWith(sendPauseProtocol(program, coder, rpc, signerA, outputStateTree, false), "NotAdmin");
+    console.log("✅ Paused protocol refuses moves, games and profiles.");
+  });
+
+  it("3. Refunds And Payouts Run While Paused", async () => {
+    assert.strictEqual(await isPaused(), true);
+
+    // A's open game refunds the deposit
+    const [refunded] = await balanceChanges(rpc, [signerA.publicKey], () => sendCancelGame(openGame));
+    assert.ok(refunded > 0, "Cancelling should refund A's deposit");
+    const cancelled = await rpc.getCompressedAccount(bn(openGame.toBytes()));
+    assert.strictEqual(camelizeKeys(coder.types.decode("GameState", cancelled!.data!.data)).gameStatus, 5);
+
+    // The finished game pays out its deposits
+    const vault = deriveVaultAddress(endedGame, program.programId);
+    const [, toB] = await balanceChanges(
+      rpc,
+      [signerA.publicKey, signerB.publicKey],
+      () => sendClaimDeposits(endedGame)
+    );
+    assert.strictEqual(toB, GAME_DEPOSIT_LAMPORTS, "B's deposit should come back after a clean finish");
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    console.log("✅ Escrowed funds can leave a paused protocol.");
+  });
+
+  it("4. Play Resumes Once Unpaused", async () => {
+    await sendPauseProtocol(program, coder, rpc, admin, outputStateTree, false);
+    assert.strictEqual(await isPaused(), false);
+
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, 0);
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    assert.strictEqual(camelizeKeys(coder.types.decode("GameState", account!.data!.data)).currentTurn, 2);
+    await sendCreateProfile(program, rpc, latecomer, outputStateTree);
+    console.log("✅ Unpaused protocol plays on.");
+  });
+
+  it("5. Config Updates Hand Over The Pause Key And Pin New Timeouts", async () => {
+    const newAdmin = new web3.Keypair();
+    await rpc.requestAirdrop(newAdmin.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    // Only the upgrade authority updates the config
+    await rejectsWith(sendUpdateConfig(program, coder, rpc, signerA, outputStateTree, signerA.publicKey, 0), "NotAdmin");
+
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, newAdmin.publicKey, 50);
+    const config = await loadConfig();
+    assert.ok(new web3.PublicKey(config.admin).equals(newAdmin.publicKey));
+    assert.strictEqual(config.turnTimeoutSlots.toNumber(), 50);
+
+    // Games created from now on pin the new timeout
+    const pinnedGame = await sendCreateGame(
+      program, coder, rpc, signerB, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    const pinned = await rpc.getCompressedAccount(bn(pinnedGame.toBytes()));
+    assert.strictEqual(camelizeKeys(coder.types.decode("GameState", pinned!.data!.data)).turnTimeoutSlots.toNumber(), 50);
+
+    // The pause key moved with it
+    await rejectsWith(sendPauseProtocol(program, coder, rpc, admin, outputStateTree, true), "NotAdmin");
+    await sendPauseProtocol(program, coder, rpc, newAdmin, outputStateTree, true);
+    await sendPauseProtocol(program, coder, rpc, newAdmin, outputStateTree, false);
+
+    // Hand it back with the default timeouts for the suites that follow
+    await sendUpdateConfig(program, coder, rpc, admin, outputStateTree, admin.publicKey, 0);
+    const restored = await loadConfig();
+    assert.ok(new web3.PublicKey(restored.admin).equals(admin.publicKey));
+    assert.strictEqual(restored.turnTimeoutSlots.toNumber(), 0);
+    console.log("✅ Config update moved the pause key and pinned the timeout.");
+  });
+});
+
+describe("battleship_rating_decay", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  const IDLE_SLOTS = 20;
+  const DECAY_POINTS = 50;
+  const DECAY_FLOOR = 1_100;
+
+  let player: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const loadPlayerProfile = async () => {
+    const { profileAccount } = await loadProfile(program, coder, rpc, player.publicKey);
+    return camelizeKeys(coder.types.decode("PlayerProfile", profileAccount!.data!.data));
+  };
+
+  const sendSetRatingDecay = async (signer: web3.Keypair, idleSlots: number, points: number, floor: number) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    await send(
+      program.methods.setRatingDecay(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data)),
+        toAccountMeta(
+          configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+        ),
+        new anchor.BN(idleSlots),
+        points,
+        floor
+      ),
+      signer,
+      remainingAccounts,
+      { programData: programDataAddress(program.programId) }
+    );
+  };
+
+  // Anyone can crank a decay, so it's sent by the admin rather than the player
+  const sendDecayRating = async () => {
+    const loaded = await loadProfile(program, coder, rpc, player.publicKey);
+    const proofRpcResult = await rpc.getValidityProofV0(loaded.slotInputs, []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const { profile, profileMeta, protocol } = toActiveGameSlot(
+      coder, loaded, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.decayRating({ 0: proofRpcResult.compressedProof }, profile, profileMeta, protocol),
+      admin,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    player = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(player.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    await sendCreateProfile(program, rpc, player, outputStateTree);
+  });
+
+  it("1. Ratings Decay Only After The Idle Span And Restart Its Clock", async () => {
+    // Decay is off until the admin configures it
+    await rejectsWith(sendDecayRating(), "RatingNotIdle");
+    await rejectsWith(sendSetRatingDecay(player, IDLE_SLOTS, DECAY_POINTS, DECAY_FLOOR), "NotAdmin");
+
+    // The config is shared by every suite, so decay is switched off again
+    await sendSetRatingDecay(admin, IDLE_SLOTS, DECAY_POINTS, DECAY_FLOOR);
+    try {
+      const created = await loadPlayerProfile();
+      assert.strictEqual(created.rating, 1_200); // INITIAL_RATING
+      while ((await rpc.getSlot()) <= created.ratedSlot.toNumber() + IDLE_SLOTS) await sleep(1000);
+      await sendDecayRating();
+      const decayed = await loadPlayerProfile();
+      assert.strictEqual(decayed.rating, 1_200 - DECAY_POINTS);
+      assert.ok(decayed.ratedSlot.toNumber() > created.ratedSlot.toNumber() + IDLE_SLOTS);
+
+      // The decay itself counts as activity, so a profile active within the
+      // span can't decay
+      await rejectsWith(sendDecayRating(), "RatingNotIdle");
+      while ((await rpc.getSlot()) <= decayed.ratedSlot.toNumber() + IDLE_SLOTS) await sleep(1000);
+      await sendDecayRating();
+      assert.strictEqual((await loadPlayerProfile()).rating, DECAY_FLOOR);
+
+      // Ratings never decay below the floor
+      const floored = await loadPlayerProfile();
+      while ((await rpc.getSlot()) <= floored.ratedSlot.toNumber() + IDLE_SLOTS) await sleep(1000);
+      await sendDecayRating();
+      assert.strictEqual((await loadPlayerProfile()).rating, DECAY_FLOOR);
+    } finally {
+      await sendSetRatingDecay(admin, 0, 0, 0);
+    }
+    console.log("✅ The idle rating decayed to the floor, one span at a time.");
+  });
+});
+
+// Mirrors `derive_friend_list_address` in the program: ["friend_list", owner]
+function deriveFriendListAddress(owner: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("friend_list"), owner.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+describe("battleship_friends", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const addressTree = new web3.PublicKey(batchAddressTree);
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let outsider: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const accountOf = (owner: web3.PublicKey) =>
+    rpc.getCompressedAccount(bn(deriveFriendListAddress(owner, program.programId).toBytes()));
+  const inputOf = (account: any) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue });
+  const decodeList = (account: any) => camelizeKeys(coder.types.decode("FriendList", account!.data!.data));
+  const loadList = async (owner: web3.PublicKey) => decodeList(await accountOf(owner));
+
+  const sendCreateFriendList = async (owner: web3.Keypair) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveFriendListAddress(owner.publicKey, program.programId).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createFriendList(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        protocol
+      ),
+      owner,
+      remainingAccounts
+    );
+  };
+
+  const sendInvite = async (sender: web3.Keypair, recipient: web3.PublicKey) => {
+    const account = await accountOf(recipient);
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([inputOf(account), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(
+      account, deriveFriendListAddress(recipient, program.programId), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.sendInvite({ 0: proofRpcResult.compressedProof }, decodeList(account), meta, protocol),
+      sender,
+      remainingAccounts
+    );
+  };
+
+  // Answers `inviter`'s invite on `owner`'s list; accepting also updates the
+  // inviter's list
+  const sendAnswer = async (signer: web3.Keypair, owner: web3.PublicKey, inviter: web3.PublicKey, accept: boolean) => {
+    const account = await accountOf(owner);
+    const inviterAccount = accept ? await accountOf(inviter) : null;
+    const accounts = inviterAccount ? [inviterAccount, account] : [account];
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([...accounts.map(inputOf), configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const refs = accounts.map((a, i) => ({
+      list: decodeList(a),
+      meta: toAccountMeta(
+        a, new web3.PublicKey(a!.address!), proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree
+      ),
+    }));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[accounts.length], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.acceptInvite(
+        { 0: proofRpcResult.compressedProof },
+        refs[refs.length - 1],
+        inviter,
+        inviterAccount ? refs[0] : null,
+        accept,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // Creates a game reserved for `friend`, proving A's list names them
+  const sendChallenge = async (friend: web3.PublicKey) => {
+    const { gameAddress, ...profile } = await loadProfile(program, coder, rpc, signerA.publicKey);
+    const listAccount = await accountOf(signerA.publicKey);
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const [profileInput, configInput] = profile.slotInputs;
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [profileInput, inputOf(listAccount), configInput],
+      [gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = {
+      profile: camelizeKeys(coder.types.decode("PlayerProfile", profile.profileAccount!.data!.data)),
+      profileMeta: toAccountMeta(
+        profile.profileAccount, new web3.PublicKey(profile.profileAccount!.address!), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      protocol: toProtocolConfigRef(coder, profile.configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree),
+    };
+    const { outputStateTreeIndex: _, ...listMeta } = toAccountMeta(
+      listAccount, deriveFriendListAddress(signerA.publicKey, program.programId), proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const packedAddressTreeInfo = {
+      rootIndex: proofRpcResult.rootIndices[3],
+      addressMerkleTreePubkeyIndex,
+      addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+    };
+
+    await send(
+      program.methods.createGame(
+        { 0: proofRpcResult.compressedProof },
+        { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree) },
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        {
+          mode: 0, // WAGER_MODE_CASUAL
+          tags: { region: 0, speed: 0, gridSize: 5 },
+          challenge: { friend, list: decodeList(listAccount), listMeta },
+        },
+        slot,
+        null
+      ),
+      signerA,
+      remainingAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId) }
+    );
+    return gameAddress;
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    outsider = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, outsider]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    for (const signer of [signerA, signerB, outsider]) {
+      await sendCreateProfile(program, rpc, signer, outputStateTree);
+    }
+    await sendCreateFriendList(signerA);
+    await sendCreateFriendList(signerB);
+  });
+
+  it("1. An Invite Waits On The Recipient's List", async () => {
+    await sendInvite(signerA, signerB.publicKey);
+    const list = await loadList(signerB.publicKey);
+    assert.ok(list.owner.equals(signerB.publicKey));
+    assert.deepStrictEqual(list.pending.map((p: web3.PublicKey) => p.toBase58()), [signerA.publicKey.toBase58()]);
+
+    // Neither a repeat invite nor one to yourself is pending twice
+    await rejectsWith(sendInvite(signerA, signerB.publicKey), "NotFriends");
+    await rejectsWith(sendInvite(signerA, signerA.publicKey), "NotFriends");
+    console.log("✅ B holds A's invite.");
+  });
+
+  it("2. Only The Owner Answers, And Only Pending Invites", async () => {
+    // The outsider never invited B, and can't answer on B's behalf
+    await rejectsWith(sendAnswer(signerB, signerB.publicKey, outsider.publicKey, false), "NotFriends");
+    await rejectsWith(sendAnswer(outsider, signerB.publicKey, signerA.publicKey, true), "WrongProfile");
+
+    await sendAnswer(signerB, signerB.publicKey, signerA.publicKey, true);
+    const listA = await loadList(signerA.publicKey);
+    const listB = await loadList(signerB.publicKey);
+    assert.deepStrictEqual(listA.friends.map((p: web3.PublicKey) => p.toBase58()), [signerB.publicKey.toBase58()]);
+    assert.deepStrictEqual(listB.friends.map((p: web3.PublicKey) => p.toBase58()), [signerA.publicKey.toBase58()]);
+    assert.strictEqual(listB.pending.length, 0);
+
+    // Friends can't invite each other again
+    await rejectsWith(sendInvite(signerA, signerB.publicKey), "NotFriends");
+    console.log("✅ A and B are friends.");
+  });
+
+  it("3. A Challenge Is Reserved For The Friend", async () => {
+    await rejectsWith(sendChallenge(outsider.publicKey), "NotFriends");
+    const game = await sendChallenge(signerB.publicKey);
+    const account = await rpc.getCompressedAccount(bn(game.toBytes()));
+    assert.ok(camelizeKeys(coder.types.decode("GameState", account!.data!.data)).reservedFor.equals(signerB.publicKey));
+
+    await rejectsWith(
+      sendJoinGame(program, coder, rpc, outsider, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32))),
+      "GameReserved"
+    );
+    await sendJoinGame(program, coder, rpc, signerB, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+    const joined = await rpc.getCompressedAccount(bn(game.toBytes()));
+    assert.strictEqual(camelizeKeys(coder.types.decode("GameState", joined!.data!.data)).gameStatus, 1);
+    console.log("✅ Only B could take A's challenge.");
+  });
+});
+
+const MIN_WAGER_LAMPORTS = 1_000_000;
+const WAGER_MODE_CASUAL = 0;
+const WAGER_MODE_RANKED = 1;
+const JACKPOT_BPS = 50;
+const REFERRAL_SHARE_BPS = 2_000;
+
+// Mirrors `derive_jackpot_vault_address` in the program: ["jackpot_vault"]
+function deriveJackpotVaultAddress(programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("jackpot_vault")], programId)[0];
+}
+
+// Mirrors `derive_referral_vault_address` in the program: ["referral_vault", referrer]
+function deriveReferralVaultAddress(referrer: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("referral_vault"), referrer.toBytes()], programId)[0];
+}
+
+// Lamports each of `keys` gained across `action`
+async function balanceChanges(rpc: Rpc, keys: web3.PublicKey[], action: () => Promise<void>): Promise<number[]> {
+  const before = await Promise.all(keys.map((key) => rpc.getBalance(key)));
+  await action();
+  const after = await Promise.all(keys.map((key) => rpc.getBalance(key)));
+  return after.map((balance, i) => balance - before[i]);
+}
+
+// Loads a game and the config for the instructions that take both: the
+// proof over them, the decoded game and its meta, and the ProtocolConfigRef
+async function loadGameWithConfig(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey
+) {
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+    []
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  return {
+    proof: { 0: proofRpcResult.compressedProof },
+    game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+    meta: toAccountMeta(account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+    protocol: toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree),
+    remainingAccounts,
+  };
+}
+
+// A sinks B's ship down column 4 without a miss while B fires along row 4,
+// missing A's ship on row 0
+async function sendPerfectWin(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signerA: web3.Keypair,
+  signerB: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey
+): Promise<void> {
+  for (let y = 0; y < SHIP_LENGTH; y++) {
+    await sendAttack(program, coder, rpc, signerA, gameAddress, outputStateTree, 4, y);
+    if (y < SHIP_LENGTH - 1) {
+      await sendAttack(program, coder, rpc, signerB, gameAddress, outputStateTree, y, 4);
+    }
+  }
+}
+
+// Sets the config's mode rakes, treasury and ranked stake as its admin
+async function sendUpdateProtocolConfig(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  admin: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  rakes: any[],
+  treasury: web3.PublicKey,
+  rankedStake: number
+): Promise<void> {
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+
+  const tx = await program.methods
+    .updateProtocolConfig(
+      { 0: proofRpcResult.compressedProof },
+      camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data)),
+      toAccountMeta(
+        configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+      ),
+      rakes,
+      treasury,
+      new anchor.BN(rankedStake)
+    )
+    .accounts({ signer: admin.publicKey, programData: programDataAddress(program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([admin])
+    .transaction();
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(admin);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [admin]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+// Creates a public-board game staking `wager` lamports in wager `mode`
+async function sendCreateWagerGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  creator: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  shipX: number,
+  shipY: number,
+  isHorizontal: boolean,
+  boardHash: number[],
+  wager: number,
+  mode = WAGER_MODE_CASUAL
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const { gameAddress, ...profile } = await loadProfile(
+    program, coder, rpc, creator.publicKey
+  );
+  const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+  // The game and its log, then the ban the system program checks is absent
+  const proofRpcResult = await rpc.getValidityProofV0(
+    profile.slotInputs,
+    [gameAddress, logAddress, deriveBanAddress(creator.publicKey, program.programId)].map((a) => ({
+      tree: addressTree,
+      queue: addressTree,
+      address: bn(a.toBytes()),
+    }))
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+  const packedAddressTreeInfo = {
+    rootIndex: proofRpcResult.rootIndices[2],
+    addressMerkleTreePubkeyIndex,
+    addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+  };
+  const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+  const tx = await program.methods
+    .createGameWithWager(
+      { 0: proofRpcResult.compressedProof },
+      { addressTreeInfo: packedAddressTreeInfo, outputStateTreeIndex },
+      { ship: { x: shipX, y: shipY, isHorizontal }, boardHash },
+      { wager: new anchor.BN(wager), mode },
+      slot,
+      null
+    )
+    .accounts({ signer: creator.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([creator])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(creator);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [creator]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return gameAddress;
+}
+
+// Joins a waiting wagered game as player B, matching the creator's stakes
+async function sendJoinWagerGame(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  joiner: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  shipX: number,
+  shipY: number,
+  isHorizontal: boolean,
+  boardHash: number[]
+): Promise<void> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const profile = await loadProfile(program, coder, rpc, joiner.publicKey);
+  // Stakers prove they aren't banned
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...profile.slotInputs],
+    [{ tree: addressTree, queue: addressTree, address: bn(deriveBanAddress(joiner.publicKey, program.programId).toBytes()) }]
+  );
+
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const accountMeta = toAccountMeta(
+    account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+  );
+  const slot = toActiveGameSlot(
+    coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+
+  const tx = await program.methods
+    .joinGame(
+      { 0: proofRpcResult.compressedProof },
+      {
+        game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)),
+        meta: accountMeta,
+      },
+      { ship: { x: shipX, y: shipY, isHorizontal }, boardHash },
+      {
+        coinFlip: false,
+        filter: null,
+        banCheck: {
+          rootIndex: proofRpcResult.rootIndices[3],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+      },
+      slot,
+      null
+    )
+    .accounts({ signer: joiner.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([joiner])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(joiner);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [joiner]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+}
+
+describe("battleship_wagers", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+  const freeRake = { feeBps: 0, minWager: new anchor.BN(0), maxWager: new anchor.BN(0) };
+
+  // Large enough that the jackpot cut and the referral share clear rent
+  // exemption on their own
+  const WAGER = 100_000_000;
+  const RAISE_WAGER = 2_000_000;
+  const RANKED_STAKE = 5_000_000;
+  const FEE_BPS = 1_000;
+  const PRIZE = 3_000_000;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let referrer: web3.Keypair;
+  let sponsor: web3.Keypair;
+  let treasury: web3.PublicKey;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let rentExempt: number;
+  let jackpotVault: web3.PublicKey;
+  let referralVault: web3.PublicKey;
+  let rankedGame: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decodeGame = async (gameAddress: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  // `signer` pays the fees, so only the payouts move the players' balances
+  const sendClaimDeposits = async (
+    signer: web3.Keypair,
+    gameAddress: web3.PublicKey,
+    [playerA, playerB]: web3.PublicKey[],
+    accounts: any = {}
+  ) => {
+    const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+      program, coder, rpc, gameAddress, outputStateTree
+    );
+    const { outputStateTreeIndex: _, ...gameMeta } = meta;
+    await send(program.methods.claimDeposits(proof, game, gameMeta, protocol), signer, remainingAccounts, {
+      vault: deriveVaultAddress(gameAddress, program.programId),
+      playerA,
+      playerB,
+      jackpot: jackpotVault,
+      ...accounts,
+    });
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    referrer = new web3.Keypair();
+    sponsor = new web3.Keypair();
+    treasury = new web3.Keypair().publicKey;
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, referrer, sponsor]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    rentExempt = await rpc.getMinimumBalanceForRentExemption(0);
+    jackpotVault = deriveJackpotVaultAddress(program.programId);
+    referralVault = deriveReferralVaultAddress(referrer.publicKey, program.programId);
+
+    // A was referred, so the referrer shares in the fees A's games pay
+    await sendCreateProfile(program, rpc, signerA, outputStateTree, referrer.publicKey);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Both Players' Stakes Are Locked At The Rake Pinned On Creation", async () => {
+    await sendUpdateProtocolConfig(
+      program, coder, rpc, admin, outputStateTree, [freeRake, { ...freeRake, feeBps: FEE_BPS }, freeRake], treasury, RANKED_STAKE
+    );
+    try {
+      await rejectsWith(
+        sendCreateWagerGame(
+          program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), MIN_WAGER_LAMPORTS - 1, WAGER_MODE_RANKED
+        ),
+        "InvalidWager"
+      );
+      rankedGame = await sendCreateWagerGame(
+        program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), WAGER, WAGER_MODE_RANKED
+      );
+    } finally {
+      // The config is shared by every suite, so later games are fee-free again
+      await sendUpdateProtocolConfig(
+        program, coder, rpc, admin, outputStateTree, [freeRake, freeRake, freeRake], admin.publicKey, 0
+      );
+    }
+    await sendJoinWagerGame(
+      program, coder, rpc, signerB, rankedGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+
+    const game = await decodeGame(rankedGame);
+    assert.strictEqual(game.gameStatus, 1);
+    assert.strictEqual(game.wager.toNumber(), WAGER);
+    assert.strictEqual(game.feeBps, FEE_BPS);
+    assert.ok(game.treasury.equals(treasury));
+    assert.strictEqual(game.rankedStake.toNumber(), RANKED_STAKE);
+    assert.strictEqual(
+      await rpc.getBalance(deriveVaultAddress(rankedGame, program.programId)),
+      2 * (GAME_DEPOSIT_LAMPORTS + WAGER + RANKED_STAKE)
+    );
+    console.log("✅ Deposits, wagers and ranked stakes locked in the vault.");
+  });
+
+  it("2. A Referred Player Links Their Referrer To The Game", async () => {
+    const sendLinkReferrer = async (player: web3.Keypair) => {
+      const profileAddress = deriveProfileAddress(player.publicKey, addressTree, program.programId);
+      const addresses = [rankedGame, profileAddress];
+      const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+      const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+        []
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const gameMeta = toAccountMeta(accounts[0], rankedGame, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+      const { outputStateTreeIndex: _, ...profileMeta } = toAccountMeta(
+        accounts[1], profileAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+      );
+      await send(
+        program.methods.linkReferrer(
+          { 0: proofRpcResult.compressedProof },
+          camelizeKeys(coder.types.decode("GameState", accounts[0]!.data!.data)),
+          gameMeta,
+          camelizeKeys(coder.types.decode("PlayerProfile", accounts[1]!.data!.data)),
+          profileMeta,
+          toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+        ),
+        player,
+        remainingAccounts
+      );
+    };
+    // Nobody referred B
+    await rejectsWith(sendLinkReferrer(signerB), "WrongProfile");
+    await sendLinkReferrer(signerA);
+
+    const game = await decodeGame(rankedGame);
+    assert.ok(game.referrers[0].equals(referrer.publicKey));
+    assert.ok(game.referrers[1].equals(web3.PublicKey.default));
+    console.log("✅ A's referrer linked.");
+  });
+
+  it("3. The Winner Takes The Pot Less The Fee, Jackpot Cut And Referral Share", async () => {
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, rankedGame, outputStateTree);
+    assert.strictEqual((await decodeGame(rankedGame)).gameStatus, 2);
+
+    const players = [signerA.publicKey, signerB.publicKey];
+    const payees = { treasury, referralVaultA: referralVault };
+    // Paid to the game's own players only
+    await rejectsWith(
+      sendClaimDeposits(sponsor, rankedGame, [signerA.publicKey, sponsor.publicKey], payees),
+      "WrongPlayerAccount"
+    );
+
+    const vault = deriveVaultAddress(rankedGame, program.programId);
+    const pot = await rpc.getBalance(vault);
+    const changes = await balanceChanges(
+      rpc,
+      [...players, treasury, jackpotVault, referralVault],
+      () => sendClaimDeposits(sponsor, rankedGame, players, payees)
+    );
+    // B, who lost cleanly, gets the deposit and ranked stake back
+    const refund = GAME_DEPOSIT_LAMPORTS + RANKED_STAKE;
+    const fee = (2 * WAGER * FEE_BPS) / 10_000;
+    const jackpot = (2 * WAGER * JACKPOT_BPS) / 10_000;
+    const share = ((fee / 2) * REFERRAL_SHARE_BPS) / 10_000;
+    assert.deepStrictEqual(changes, [pot - refund - fee - jackpot, refund, fee - share, jackpot, share]);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+
+    await rejectsWith(sendClaimDeposits(sponsor, rankedGame, players, payees), "NothingToClaim");
+    console.log("✅ Pot paid out once.");
+  });
+
+  it("4. A Perfect Wagered Win Takes The Jackpot Once", async () => {
+    const sendClaimJackpot = async (winner: web3.PublicKey) => {
+      const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+        program, coder, rpc, rankedGame, outputStateTree
+      );
+      await send(program.methods.claimJackpot(proof, game, meta, protocol), sponsor, remainingAccounts, {
+        vault: jackpotVault,
+        winner,
+      });
+    };
+    await rejectsWith(sendClaimJackpot(signerB.publicKey), "WrongPlayerAccount");
+
+    const jackpot = (await rpc.getBalance(jackpotVault)) - rentExempt;
+    const [won] = await balanceChanges(rpc, [signerA.publicKey], () => sendClaimJackpot(signerA.publicKey));
+    assert.strictEqual(won, jackpot);
+    // The vault stays open for the next jackpot
+    assert.strictEqual(await rpc.getBalance(jackpotVault), rentExempt);
+    assert.ok((await decodeGame(rankedGame)).jackpotClaimed);
+
+    await rejectsWith(sendClaimJackpot(signerA.publicKey), "NothingToClaim");
+    console.log("✅ Jackpot paid to A.");
+  });
+
+  it("5. Referrers Claim Their Share Once", async () => {
+    const sendClaimReferralRewards = (signer: web3.Keypair, vault: web3.PublicKey) =>
+      send(program.methods.claimReferralRewards(), signer, new PackedAccounts(), { vault });
+
+    // Each referrer claims from their own vault only
+    await rejectsWith(sendClaimReferralRewards(signerB, referralVault), "WrongVault");
+
+    const share = ((2 * WAGER * FEE_BPS) / 10_000 / 2) * REFERRAL_SHARE_BPS / 10_000;
+    const [paid] = await balanceChanges(rpc, [referralVault], () => sendClaimReferralRewards(referrer, referralVault));
+    // The vault keeps its rent
+    assert.strictEqual(-paid, share - rentExempt);
+    assert.strictEqual(await rpc.getBalance(referralVault), rentExempt);
+
+    await rejectsWith(sendClaimReferralRewards(referrer, referralVault), "NothingToClaim");
+    console.log("✅ Referral share claimed.");
+  });
+
+  it("6. An Accepted Raise Doubles The Wager Paid Out On Resignation", async () => {
+    const raisedGame = await sendCreateWagerGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), RAISE_WAGER
+    );
+    await sendJoinWagerGame(
+      program, coder, rpc, signerB, raisedGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    const vault = deriveVaultAddress(raisedGame, program.programId);
+
+    const sendRaise = async (method: "offerRaise" | "acceptRaise", signer: web3.Keypair) => {
+      const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+        program, coder, rpc, raisedGame, outputStateTree
+      );
+      await send(program.methods[method](proof, game, meta, protocol), signer, remainingAccounts, { vault });
+    };
+    // Raises are offered on your own turn and answered by the opponent
+    await rejectsWith(sendRaise("offerRaise", signerB), "NotPlayerTurn");
+    await sendRaise("offerRaise", signerA);
+    await rejectsWith(sendRaise("acceptRaise", signerA), "NotPlayerTurn");
+    await sendRaise("acceptRaise", signerB);
+
+    const game = await decodeGame(raisedGame);
+    assert.strictEqual(game.wager.toNumber(), 2 * RAISE_WAGER);
+    assert.strictEqual(game.currentTurn, 1); // Back to A, who raised
+    const pot = 2 * (GAME_DEPOSIT_LAMPORTS + 2 * RAISE_WAGER);
+    assert.strictEqual(await rpc.getBalance(vault), pot);
+
+    const { proof, game: current, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+      program, coder, rpc, raisedGame, outputStateTree
+    );
+    await send(program.methods.resign(proof, current, meta, protocol), signerB, remainingAccounts);
+    assert.strictEqual((await decodeGame(raisedGame)).gameStatus, 2);
+
+    const players = [signerA.publicKey, signerB.publicKey];
+    const changes = await balanceChanges(
+      rpc,
+      [...players, jackpotVault],
+      () => sendClaimDeposits(sponsor, raisedGame, players)
+    );
+    const jackpot = (2 * 2 * RAISE_WAGER * JACKPOT_BPS) / 10_000;
+    assert.deepStrictEqual(changes, [pot - GAME_DEPOSIT_LAMPORTS - jackpot, GAME_DEPOSIT_LAMPORTS, jackpot]);
+
+    await rejectsWith(sendClaimDeposits(sponsor, raisedGame, players), "NothingToClaim");
+    console.log("✅ Raised pot paid to A.");
+  });
+
+  it("7. A Cancelled Wager Refunds Player A And The Sponsor", async () => {
+    const openGame = await sendCreateWagerGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), RAISE_WAGER
+    );
+    const vault = deriveVaultAddress(openGame, program.programId);
+
+    const sendSponsorGame = async (signer: web3.Keypair) => {
+      const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+        program, coder, rpc, openGame, outputStateTree
+      );
+      await send(
+        program.methods.sponsorGame(proof, game, meta, new anchor.BN(PRIZE), protocol),
+        signer,
+        remainingAccounts,
+        { vault }
+      );
+    };
+    // Players can't sponsor their own game
+    await rejectsWith(sendSponsorGame(signerA), "SponsorMismatch");
+    await sendSponsorGame(sponsor);
+    assert.strictEqual((await decodeGame(openGame)).prize.toNumber(), PRIZE);
+
+    const sendRefund = async (method: "cancelGame" | "reclaimStaleWager", signer: web3.Keypair) => {
+      const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+        program, coder, rpc, openGame, outputStateTree
+      );
+      await send(program.methods[method](proof, game, meta, null, protocol), signer, remainingAccounts, {
+        vault,
+        playerA: signerA.publicKey,
+        sponsor: sponsor.publicKey,
+      });
+    };
+    // Only A cancels, and the wager isn't stale yet for anyone else
+    await rejectsWith(sendRefund("cancelGame", signerB), "NotAPlayer");
+    await rejectsWith(sendRefund("reclaimStaleWager", signerB), "TurnNotExpired");
+
+    const refund = (await rpc.getBalance(vault)) - PRIZE;
+    assert.strictEqual(refund, GAME_DEPOSIT_LAMPORTS + RAISE_WAGER);
+    const [refunded] = await balanceChanges(rpc, [sponsor.publicKey], () => sendRefund("cancelGame", signerA));
+    assert.strictEqual(refunded, PRIZE);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    assert.strictEqual((await decodeGame(openGame)).gameStatus, 5);
+
+    // Cancelled games stay cancelled
+    await assert.rejects(sendRefund("cancelGame", signerA));
+    console.log("✅ Stakes and prize refunded.");
+  });
+
+  it("8. Banned Players Finish Their Games But Can't Stake Until Unbanned", async () => {
+    const banned = new web3.Keypair();
+    await rpc.requestAirdrop(banned.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+    await sendCreateProfile(program, rpc, banned, outputStateTree);
+    const banAddress = deriveBanAddress(banned.publicKey, program.programId);
+
+    const sendBan = async (signer: web3.Keypair) => {
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [],
+        [{ tree: addressTree, queue: addressTree, address: bn(banAddress.toBytes()) }]
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+      await send(
+        program.methods.banPlayer(
+          { 0: proofRpcResult.compressedProof },
+          { rootIndex: proofRpcResult.rootIndices[0], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+          remainingAccounts.insertOrGet(outputStateTree),
+          banned.publicKey
+        ),
+        signer,
+        remainingAccounts,
+        { programData: programDataAddress(program.programId) }
+      );
+    };
+    const sendUnban = async () => {
+      const account = await rpc.getCompressedAccount(bn(banAddress.toBytes()));
+      const proofRpcResult = await rpc.getValidityProofV0(
+        [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
+        []
+      );
+      const remainingAccounts = new PackedAccounts();
+      remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      await send(
+        program.methods.unbanPlayer(
+          { 0: proofRpcResult.compressedProof },
+          camelizeKeys(coder.types.decode("Ban", account!.data!.data)),
+          toAccountMeta(account, banAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree)
+        ),
+        admin,
+        remainingAccounts,
+        { programData: programDataAddress(program.programId) }
+      );
+    };
+    const sendCreate = (creator: web3.Keypair) =>
+      sendCreateWagerGame(
+        program, coder, rpc, creator, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), RAISE_WAGER
+      );
+    const sendJoin = (joiner: web3.Keypair, game: web3.PublicKey) =>
+      sendJoinWagerGame(program, coder, rpc, joiner, game, outputStateTree, 4, 0, false, Array.from(randomBytes(32)));
+
+    const inFlight = await sendCreate(banned);
+    await sendJoin(signerB, inFlight);
+    await rejectsWith(sendBan(signerA), "NotAdmin");
+    await sendBan(admin);
+    const ban = camelizeKeys(coder.types.decode("Ban", (await rpc.getCompressedAccount(bn(banAddress.toBytes())))!.data!.data));
+    assert.ok(ban.player.equals(banned.publicKey));
+
+    // The ban's address exists, so no proof of its absence can be built
+    await assert.rejects(sendCreate(banned));
+    const openGame = await sendCreate(signerA);
+    await assert.rejects(sendJoin(banned, openGame));
+
+    // The game started before the ban still plays out and pays
+    await sendPerfectWin(program, coder, rpc, banned, signerB, inFlight, outputStateTree);
+    const [won] = await balanceChanges(rpc, [banned.publicKey], () =>
+      sendClaimDeposits(sponsor, inFlight, [banned.publicKey, signerB.publicKey])
+    );
+    assert.ok(won > 0);
+
+    await sendUnban();
+    assert.strictEqual(await rpc.getCompressedAccount(bn(banAddress.toBytes())), null);
+    await sendJoin(banned, openGame);
+    assert.strictEqual((await decodeGame(openGame)).gameStatus, 1);
+    console.log("✅ The ban blocked new stakes only until it was lifted.");
+  });
+
+  it("9. A Declined Raise Resigns The Game To The Raiser", async () => {
+    const raisedGame = await sendCreateWagerGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32)), RAISE_WAGER
+    );
+    await sendJoinWagerGame(
+      program, coder, rpc, signerB, raisedGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    const vault = deriveVaultAddress(raisedGame, program.programId);
+
+    const sendRaise = async (method: "offerRaise" | "declineRaise", signer: web3.Keypair) => {
+      const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+        program, coder, rpc, raisedGame, outputStateTree
+      );
+      await send(program.methods[method](proof, game, meta, protocol), signer, remainingAccounts, { vault });
+    };
+    await rejectsWith(sendRaise("declineRaise", signerB), "RaiseNotAllowed");
+    await sendRaise("offerRaise", signerA);
+    await rejectsWith(sendRaise("declineRaise", signerA), "NotPlayerTurn");
+    await sendRaise("declineRaise", signerB);
+
+    const game = await decodeGame(raisedGame);
+    assert.strictEqual(game.gameStatus, 2); // A won
+    assert.strictEqual(game.endReason, 6); // Resigned
+    assert.strictEqual(game.raiseOfferedBy, 0);
+    assert.strictEqual(game.wager.toNumber(), RAISE_WAGER);
+    // A's unmatched half of the raise is still in the vault
+    const pot = 2 * GAME_DEPOSIT_LAMPORTS + 3 * RAISE_WAGER;
+    assert.strictEqual(await rpc.getBalance(vault), pot);
+
+    // The winnings are the original wager, and the raiser gets their half back
+    const players = [signerA.publicKey, signerB.publicKey];
+    const changes = await balanceChanges(
+      rpc,
+      [...players, jackpotVault],
+      () => sendClaimDeposits(sponsor, raisedGame, players)
+    );
+    const jackpot = (2 * RAISE_WAGER * JACKPOT_BPS) / 10_000;
+    assert.deepStrictEqual(changes, [pot - GAME_DEPOSIT_LAMPORTS - jackpot, GAME_DEPOSIT_LAMPORTS, jackpot]);
+    console.log("✅ Declining the raise resigned the game to A.");
+  });
+});
+
+// Mirrors `derive_prize_pool_address` in the program: ["prize_pool", organizer, pool_id]
+function derivePrizePoolAddress(organizer: web3.PublicKey, poolId: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([
+    Buffer.from("prize_pool"),
+    organizer.toBytes(),
+    new anchor.BN(poolId).toArrayLike(Buffer, "le", 8),
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_prize_vault_address` in the program: ["prize_vault", pool_address]
+function derivePrizeVaultAddress(poolAddress: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("prize_vault"), poolAddress.toBytes()], programId)[0];
+}
+
+describe("battleship_prize_pools", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  const POOL_ID = 0;
+  const ENTRY_FEE = 5_000_000;
+  const SPONSORED = 2_000_000;
+  const SPLIT_BPS = [7_000, 3_000];
+
+  let organizer: web3.Keypair;
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let poolAddress: web3.PublicKey;
+  let vault: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: web3.AccountMeta[], accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  // The proof, pool and config every instruction on the pool takes
+  const loadPool = async () => {
+    const account = await rpc.getCompressedAccount(bn(poolAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    return {
+      proof: { 0: proofRpcResult.compressedProof },
+      pool: camelizeKeys(coder.types.decode("PrizePool", account!.data!.data)),
+      meta: toAccountMeta(account, poolAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+      protocol: toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree),
+      remainingAccounts,
+    };
+  };
+
+  before(async () => {
+    organizer = new web3.Keypair();
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [organizer, signerA, signerB]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    poolAddress = derivePrizePoolAddress(organizer.publicKey, POOL_ID, program.programId);
+    vault = derivePrizeVaultAddress(poolAddress, program.programId);
+  });
+
+  it("1. Entry Fees And Sponsorship Fill The Pool's Vault", async () => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(poolAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createPrizePool(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[1],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        new anchor.BN(POOL_ID),
+        new anchor.BN(ENTRY_FEE),
+        SPLIT_BPS,
+        protocol
+      ),
+      organizer,
+      remainingAccounts.toAccountMetas().remainingAccounts
+    );
+
+    const sendRegister = async (entrant: web3.Keypair) => {
+      const { proof, pool, meta, protocol, remainingAccounts } = await loadPool();
+      await send(
+        program.methods.registerForPrizePool(proof, pool, meta, protocol),
+        entrant,
+        remainingAccounts.toAccountMetas().remainingAccounts,
+        { vault }
+      );
+    };
+    await sendRegister(signerA);
+    await sendRegister(signerB);
+    // One entry each
+    await rejectsWith(sendRegister(signerB), "PrizePoolMismatch");
+
+    const { proof, pool, meta, protocol: current, remainingAccounts: sponsorAccounts } = await loadPool();
+    await send(
+      program.methods.sponsorPrizePool(proof, pool, meta, new anchor.BN(SPONSORED), current),
+      organizer,
+      sponsorAccounts.toAccountMetas().remainingAccounts,
+      { vault }
+    );
+
+    const { pool: filled } = await loadPool();
+    assert.deepStrictEqual(
+      filled.entrants.map((entrant: web3.PublicKey) => entrant.toBase58()),
+      [signerA.publicKey.toBase58(), signerB.publicKey.toBase58()]
+    );
+    assert.strictEqual(filled.sponsored.toNumber(), SPONSORED);
+    assert.strictEqual(await rpc.getBalance(vault), 2 * ENTRY_FEE + SPONSORED);
+    console.log("✅ Pool filled.");
+  });
+
+  it("2. The Organizer Pays The Standings Once", async () => {
+    const sendDistribute = async (signer: web3.Keypair, standings: web3.PublicKey[]) => {
+      const { proof, pool, meta, protocol, remainingAccounts } = await loadPool();
+      // The placed players' accounts go first, in standings order
+      const winners = standings.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+      await send(
+        program.methods.distributePrizes(proof, pool, meta, standings, protocol),
+        signer,
+        [...winners, ...remainingAccounts.toAccountMetas().remainingAccounts],
+        { vault }
+      );
+    };
+    const standings = [signerB.publicKey, signerA.publicKey];
+    await rejectsWith(sendDistribute(signerA, standings), "NotOrganizer");
+    // Only entrants place
+    await rejectsWith(sendDistribute(organizer, [signerB.publicKey, organizer.publicKey]), "PrizePoolMismatch");
+
+    const pot = await rpc.getBalance(vault);
+    const changes = await balanceChanges(rpc, standings, () => sendDistribute(organizer, standings));
+    const second = (pot * SPLIT_BPS[1]) / 10_000;
+    assert.deepStrictEqual(changes, [pot - second, second]);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    assert.ok((await loadPool()).pool.distributed);
+
+    await rejectsWith(sendDistribute(organizer, standings), "NothingToClaim");
+    console.log("✅ B and A paid for first and second.");
+  });
+});
+
+// Mirrors `derive_market_address` in the program: ["bet_market", game_address]
+function deriveMarketAddress(gameAddress: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("bet_market"), gameAddress.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_market_bet_address` in the program: ["market_bet", game_address, bettor]
+function deriveMarketBetAddress(gameAddress: web3.PublicKey, bettor: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("market_bet"), gameAddress.toBytes(), bettor.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_market_vault_address` in the program: ["market_vault", game_address]
+function deriveMarketVaultAddress(gameAddress: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("market_vault"), gameAddress.toBytes()], programId)[0];
+}
+
+describe("battleship_markets", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  const CLOSE_MOVE = 4;
+  const STAKE_A = 3_000_000;
+  const STAKE_B = 1_000_000;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let backerA: web3.Keypair;
+  let backerB: web3.Keypair;
+  let backerC: web3.Keypair;
+  let backerD: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+  let marketAddress: web3.PublicKey;
+  let vault: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const readOnlyMeta = (account: any, address: web3.PublicKey, rootIndex: number, remainingAccounts: PackedAccounts) => {
+    const { outputStateTreeIndex: _, ...meta } = toAccountMeta(account, address, rootIndex, remainingAccounts, outputStateTree);
+    return meta;
+  };
+
+  const decodeMarket = async () => {
+    const account = await rpc.getCompressedAccount(bn(marketAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("BetMarket", account!.data!.data));
+  };
+
+  const sendPlaceBet = async (bettor: web3.Keypair, side: number, amount: number) => {
+    const betAddress = deriveMarketBetAddress(gameAddress, bettor.publicKey, program.programId);
+    // The updated market goes before the accounts read
+    const accounts = await Promise.all([marketAddress, gameAddress].map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(betAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const marketMeta = toAccountMeta(accounts[0], marketAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const gameMeta = readOnlyMeta(accounts[1], gameAddress, proofRpcResult.rootIndices[1], remainingAccounts);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.placeBet(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[3],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: camelizeKeys(coder.types.decode("GameState", accounts[1]!.data!.data)), meta: gameMeta },
+        { market: camelizeKeys(coder.types.decode("BetMarket", accounts[0]!.data!.data)), meta: marketMeta },
+        { side, amount: new anchor.BN(amount) },
+        protocol
+      ),
+      bettor,
+      remainingAccounts,
+      { vault }
+    );
+  };
+
+  // `bet` overrides the bet as currently indexed
+  const sendClaimWinnings = async (bettor: web3.PublicKey, payee: web3.PublicKey, bet: any = null) => {
+    const betAddress = deriveMarketBetAddress(gameAddress, bettor, program.programId);
+    // The market and the closed bet go before the game, which is only read
+    const accounts = [
+      await rpc.getCompressedAccount(bn(marketAddress.toBytes())),
+      bet ?? (await rpc.getCompressedAccount(bn(betAddress.toBytes()))),
+      await rpc.getCompressedAccount(bn(gameAddress.toBytes())),
+    ];
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const marketMeta = toAccountMeta(accounts[0], marketAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const betMeta = toAccountMeta(accounts[1], betAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+    const gameMeta = readOnlyMeta(accounts[2], gameAddress, proofRpcResult.rootIndices[2], remainingAccounts);
+    await send(
+      program.methods.claimWinnings(
+        { 0: proofRpcResult.compressedProof },
+        { game: camelizeKeys(coder.types.decode("GameState", accounts[2]!.data!.data)), meta: gameMeta },
+        { market: camelizeKeys(coder.types.decode("BetMarket", accounts[0]!.data!.data)), meta: marketMeta },
+        { bet: camelizeKeys(coder.types.decode("MarketBet", accounts[1]!.data!.data)), meta: betMeta },
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[3], remainingAccounts, outputStateTree)
+      ),
+      signerA,
+      remainingAccounts,
+      { vault, bettor: payee }
+    );
+  };
+
+  // Starts a new A-vs-B game and opens its market, which the other helpers
+  // then act on
+  const sendOpenMarket = async () => {
+    gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    marketAddress = deriveMarketAddress(gameAddress, program.programId);
+    vault = deriveMarketVaultAddress(gameAddress, program.programId);
+
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(marketAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const gameMeta = readOnlyMeta(account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.openMarket(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[2],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)), meta: gameMeta },
+        CLOSE_MOVE,
+        protocol
+      ),
+      backerA,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    backerA = new web3.Keypair();
+    backerB = new web3.Keypair();
+    backerC = new web3.Keypair();
+    backerD = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, backerA, backerB, backerC, backerD]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Anyone But The Players Backs A Side", async () => {
+    await sendOpenMarket();
+    await rejectsWith(sendPlaceBet(signerA, 1, STAKE_A), "MarketClosed");
+    await sendPlaceBet(backerA, 1, STAKE_A);
+    await sendPlaceBet(backerB, 2, STAKE_B);
+
+    const market = await decodeMarket();
+    assert.deepStrictEqual([market.poolA.toNumber(), market.poolB.toNumber()], [STAKE_A, STAKE_B]);
+    assert.strictEqual(await rpc.getBalance(vault), STAKE_A + STAKE_B);
+    console.log("✅ Both sides backed.");
+  });
+
+  it("2. The Winning Side Takes The Whole Pool Once", async () => {
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+
+    // Paid to the bet's own bettor, and only on the winning side
+    await rejectsWith(sendClaimWinnings(backerA.publicKey, backerB.publicKey), "WrongPlayerAccount");
+    await rejectsWith(sendClaimWinnings(backerB.publicKey, backerB.publicKey), "NothingToClaim");
+
+    const betAddress = deriveMarketBetAddress(gameAddress, backerA.publicKey, program.programId);
+    const bet = await rpc.getCompressedAccount(bn(betAddress.toBytes()));
+    const [won] = await balanceChanges(
+      rpc,
+      [backerA.publicKey],
+      () => sendClaimWinnings(backerA.publicKey, backerA.publicKey)
+    );
+    assert.strictEqual(won, STAKE_A + STAKE_B);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+
+    // The claim closed the bet, so it can't be proven again
+    assert.strictEqual(await rpc.getCompressedAccount(bn(betAddress.toBytes())), null);
+    await assert.rejects(sendClaimWinnings(backerA.publicKey, backerA.publicKey, bet));
+    console.log("✅ A's backer took the pool.");
+  });
+
+  it("3. The Last Winning Claim Empties The Vault", async () => {
+    await sendOpenMarket();
+    const STAKE = 1_000_000;
+    for (const backer of [backerA, backerC, backerD]) {
+      await sendPlaceBet(backer, 1, STAKE);
+    }
+    await sendPlaceBet(backerB, 2, STAKE);
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+
+    // Three equal winning stakes can't split four evenly
+    const share = Math.floor((4 * STAKE) / 3);
+    const paid = await balanceChanges(
+      rpc,
+      [backerA.publicKey, backerC.publicKey, backerD.publicKey],
+      async () => {
+        for (const backer of [backerA, backerC, backerD]) {
+          await sendClaimWinnings(backer.publicKey, backer.publicKey);
+        }
+      }
+    );
+    assert.deepStrictEqual(paid, [share, share, 4 * STAKE - 2 * share]);
+    assert.strictEqual((await decodeMarket()).claimed.toNumber(), 3 * STAKE);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    console.log("✅ The third winner took the rounding remainder.");
+  });
+});
+
+// Mirrors `derive_token_escrow_address` in the program: ["token_escrow", game_address]
+function deriveTokenEscrowAddress(gameAddress: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("token_escrow"), gameAddress.toBytes()], programId)[0];
+}
+
+// Mirrors `derive_reward_authority_address` in the program: ["reward_authority"]
+function deriveRewardAuthorityAddress(programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("reward_authority")], programId)[0];
+}
+
+// Packs `owner`'s compressed `mint` tokens covering `amount` as the
+// TokenInputs a token wager transfer spends. The compressed token
+// program's accounts go after `lightAccounts`, the program's own.
+async function packTokenStake(
+  rpc: Rpc,
+  payer: web3.PublicKey,
+  owner: web3.PublicKey,
+  mint: web3.PublicKey,
+  amount: number,
+  lightAccounts: web3.AccountMeta[]
+) {
+  const { items } = await rpc.getCompressedTokenAccountsByOwner(owner, { mint });
+  const [inputs] = selectMinCompressedTokenAccountsForTransfer(items, bn(amount));
+  const proofRpcResult = await rpc.getValidityProofV0(
+    inputs.map((a) => ({
+      hash: a.compressedAccount.hash,
+      tree: a.compressedAccount.treeInfo.tree,
+      queue: a.compressedAccount.treeInfo.queue,
+    })),
+    []
+  );
+  // The SDK's own transfer lists the accounts the token program expects
+  // after the fee payer and authority
+  const transfer = await CompressedTokenProgram.transfer({
+    payer,
+    inputCompressedTokenAccounts: inputs,
+    toAddress: owner,
+    amount: bn(amount),
+    recentInputStateRootIndices: proofRpcResult.rootIndices,
+    recentValidityProof: proofRpcResult.compressedProof,
+  });
+  const { inputTokenDataWithContext, packedOutputTokenData } = packCompressedTokenAccounts({
+    inputCompressedTokenAccounts: inputs,
+    rootIndices: proofRpcResult.rootIndices,
+    tokenTransferOutputs: [{ owner, amount: bn(amount), lamports: null, tlv: null }],
+  });
+  return {
+    stake: {
+      proof: { 0: proofRpcResult.compressedProof },
+      accounts: inputTokenDataWithContext.map((input) => ({
+        amount: input.amount,
+        delegateIndex: input.delegateIndex,
+        merkleTreePubkeyIndex: input.merkleContext.merkleTreePubkeyIndex,
+        queuePubkeyIndex: input.merkleContext.queuePubkeyIndex,
+        leafIndex: input.merkleContext.leafIndex,
+        proveByIndex: input.merkleContext.proveByIndex,
+        rootIndex: input.rootIndex,
+        lamports: input.lamports,
+        tlv: input.tlv,
+      })),
+      outputTreeIndex: packedOutputTokenData[0].merkleTreeIndex,
+      tokenAccountsOffset: lightAccounts.length,
+    },
+    accounts: [...lightAccounts, ...transfer.keys.slice(2)],
+  };
+}
+
+describe("battleship_token_wagers", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  const MINTED = 1_000;
+  const STAKE = 400;
+  const REWARD_PER_WIN = 25;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let mint: web3.PublicKey;
+  let gameAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: web3.AccountMeta[], accounts: any) => {
+    const tx = await method
+      .accounts(accounts)
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 })])
+      .remainingAccounts(remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decodeGame = async (address: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  const tokenBalance = async (owner: web3.PublicKey, tokenMint = mint) => {
+    const { items } = await rpc.getCompressedTokenAccountsByOwner(owner, { mint: tokenMint });
+    return items.reduce((total, account) => total + account.parsed.amount.toNumber(), 0);
+  };
+
+  const sendCreateTokenWagerGame = async () => {
+    const { gameAddress, ...profile } = await loadProfile(program, coder, rpc, signerA.publicKey);
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    // The game and its log, then the ban the system program checks is absent
+    const proofRpcResult = await rpc.getValidityProofV0(
+      profile.slotInputs,
+      [gameAddress, logAddress, deriveBanAddress(signerA.publicKey, program.programId)].map((a) => ({
+        tree: addressTree,
+        queue: addressTree,
+        address: bn(a.toBytes()),
+      }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const slot = toActiveGameSlot(
+      coder, profile, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+    const { stake, accounts } = await packTokenStake(
+      rpc, signerA.publicKey, signerA.publicKey, mint, STAKE, remainingAccounts.toAccountMetas().remainingAccounts
+    );
+    await send(
+      program.methods.createGameWithTokenWager(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[2],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex,
+        },
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        { mint, amount: new anchor.BN(STAKE), mode: WAGER_MODE_CASUAL },
+        stake,
+        slot
+      ),
+      signerA,
+      accounts,
+      { deposit: { signer: signerA.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) } }
+    );
+    return gameAddress;
+  };
+
+  const sendJoinTokenWagerGame = async () => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    const profile = await loadProfile(program, coder, rpc, signerB.publicKey);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...profile.slotInputs],
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveBanAddress(signerB.publicKey, program.programId).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(
+      account, gameAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const slot = toActiveGameSlot(
+      coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    const { stake, accounts } = await packTokenStake(
+      rpc, signerB.publicKey, signerB.publicKey, mint, STAKE, remainingAccounts.toAccountMetas().remainingAccounts
+    );
+    await send(
+      program.methods.joinTokenWagerGame(
+        { 0: proofRpcResult.compressedProof },
+        { game: camelizeKeys(coder.types.decode("GameState", account!.data!.data)), meta: accountMeta },
+        { ship: { x: 4, y: 0, isHorizontal: false }, boardHash: Array.from(randomBytes(32)) },
+        stake,
+        {
+          rootIndex: proofRpcResult.rootIndices[3],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        slot
+      ),
+      signerB,
+      accounts,
+      { deposit: { signer: signerB.publicKey, vault: deriveVaultAddress(gameAddress, program.programId) } }
+    );
+  };
+
+  // `forge` edits the game before it's submitted
+  const sendClaimTokenWager = async (forge: (game: any) => void = () => {}) => {
+    const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+      program, coder, rpc, gameAddress, outputStateTree
+    );
+    const escrow = deriveTokenEscrowAddress(gameAddress, program.programId);
+    const lightAccounts = remainingAccounts.toAccountMetas().remainingAccounts;
+    // Once paid out, the escrow has no tokens left to spend
+    const { stake, accounts } = game.tokensPaid
+      ? {
+        stake: { proof: { 0: null }, accounts: [], outputTreeIndex: 0, tokenAccountsOffset: lightAccounts.length },
+        accounts: lightAccounts,
+      }
+      : await packTokenStake(rpc, signerB.publicKey, escrow, mint, 2 * STAKE, lightAccounts);
+    forge(game);
+    await send(
+      program.methods.claimTokenWager(proof, game, meta, stake, protocol),
+      signerB,
+      accounts,
+      { signer: signerB.publicKey, escrow }
+    );
+  };
+
+  const sendSetRewardToken = async (rewardMint: web3.PublicKey, rewardPerWin: number) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0([configInput], []);
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    await send(
+      program.methods.setRewardToken(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("ProtocolConfig", configAccount!.data!.data)),
+        toAccountMeta(
+          configAccount, new web3.PublicKey(configAccount!.address), proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+        ),
+        rewardMint,
+        new anchor.BN(rewardPerWin)
+      ),
+      admin,
+      remainingAccounts.toAccountMetas().remainingAccounts,
+      { signer: admin.publicKey, programData: programDataAddress(program.programId) }
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+
+    ({ mint } = await createMint(rpc, signerA, signerA.publicKey, 0));
+    await mintTo(rpc, signerA, mint, signerA.publicKey, signerA, MINTED);
+    await mintTo(rpc, signerA, mint, signerB.publicKey, signerA, MINTED);
+  });
+
+  it("1. Both Stakes Are Escrowed Under The Game's PDA", async () => {
+    gameAddress = await sendCreateTokenWagerGame();
+    await sendJoinTokenWagerGame();
+
+    const game = await decodeGame(gameAddress);
+    assert.strictEqual(game.gameStatus, 1);
+    assert.ok(game.tokenMint.equals(mint));
+    assert.strictEqual(game.tokenWager.toNumber(), STAKE);
+    assert.strictEqual(await tokenBalance(deriveTokenEscrowAddress(gameAddress, program.programId)), 2 * STAKE);
+    assert.strictEqual(await tokenBalance(signerA.publicKey), MINTED - STAKE);
+    assert.strictEqual(await tokenBalance(signerB.publicKey), MINTED - STAKE);
+    console.log("✅ Token stakes escrowed.");
+  });
+
+  it("2. The Winner Takes The Escrow Once", async () => {
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+
+    // The payout follows the game as proven, so B can't name themselves A
+    await assert.rejects(sendClaimTokenWager((game) => { game.playerA = signerB.publicKey; }));
+
+    await sendClaimTokenWager();
+    assert.strictEqual(await tokenBalance(signerA.publicKey), MINTED + STAKE);
+    assert.strictEqual(await tokenBalance(signerB.publicKey), MINTED - STAKE);
+    assert.strictEqual(await tokenBalance(deriveTokenEscrowAddress(gameAddress, program.programId)), 0);
+    assert.ok((await decodeGame(gameAddress)).tokensPaid);
+
+    await rejectsWith(sendClaimTokenWager(), "NothingToClaim");
+    console.log("✅ Escrow paid to A.");
+  });
+
+  it("3. An Unwagered Win Mints The Reward Token Once", async () => {
+    const rewardAuthority = deriveRewardAuthorityAddress(program.programId);
+    const { mint: rewardMint } = await createMint(rpc, signerA, rewardAuthority, 0);
+    await sendSetRewardToken(rewardMint, REWARD_PER_WIN);
+    try {
+      const rewardGame = await sendCreateGame(
+        program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+      );
+      await sendJoinGame(
+        program, coder, rpc, signerB, rewardGame, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+      );
+      await sendPerfectWin(program, coder, rpc, signerA, signerB, rewardGame, outputStateTree);
+
+      // `forge` edits the game before it's submitted
+      const sendClaimRewardTokens = async (forge: (game: any) => void = () => {}) => {
+        const { proof, game, meta, protocol, remainingAccounts } = await loadGameWithConfig(
+          program, coder, rpc, rewardGame, outputStateTree
+        );
+        forge(game);
+        const lightAccounts = remainingAccounts.toAccountMetas().remainingAccounts;
+        // The SDK's own mint lists the accounts the token program expects
+        // after the fee payer and authority
+        const mintToIx = await CompressedTokenProgram.mintTo({
+          feePayer: signerB.publicKey,
+          mint: rewardMint,
+          authority: rewardAuthority,
+          amount: REWARD_PER_WIN,
+          toPubkey: signerA.publicKey,
+          outputStateTreeInfo: selectStateTreeInfo(await rpc.getStateTreeInfos()),
+          tokenPoolInfo: selectTokenPoolInfo(await getTokenPoolInfos(rpc, rewardMint)),
+        });
+        await send(
+          program.methods.claimRewardTokens(proof, game, meta, protocol, lightAccounts.length),
+          signerB,
+          [...lightAccounts, ...mintToIx.keys.slice(2)],
+          { signer: signerB.publicKey, mintAuthority: rewardAuthority }
+        );
+      };
+      // The reward goes to the winner as proven, so B can't claim the win
+      await assert.rejects(sendClaimRewardTokens((game) => { game.gameStatus = 3; }));
+
+      await sendClaimRewardTokens();
+      assert.strictEqual(await tokenBalance(signerA.publicKey, rewardMint), REWARD_PER_WIN);
+      assert.strictEqual(await tokenBalance(signerB.publicKey, rewardMint), 0);
+      assert.ok((await decodeGame(rewardGame)).rewardClaimed);
+
+      await rejectsWith(sendClaimRewardTokens(), "NothingToClaim");
+    } finally {
+      // The config is shared by every suite, so later wins mint nothing
+      await sendSetRewardToken(web3.PublicKey.default, 0);
+    }
+    console.log("✅ Reward minted to A.");
+  });
+});
+
+// Records a finished game's GameResult, scoring the winner on `season`'s
+// leaderboard if set
+async function sendRecordGameResult(
+  program: Program<Battleship>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  signer: web3.Keypair,
+  gameAddress: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  season: number | null = null
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const resultAddress = deriveGameResultAddress(gameAddress, addressTree, program.programId);
+  const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+  const game = camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+
+  // The season's accounts, updated ones first, then the winner's score
+  // if they already have one
+  const seasonAddresses = season === null ? [] : [deriveLeaderboardAddress(season, program.programId), deriveSeasonAddress(season, program.programId)];
+  const seasonAccounts = await Promise.all(seasonAddresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+  const winner = game.gameStatus === 2 ? game.playerA : game.playerB;
+  const scoreAddress = season === null ? null : deriveSeasonScoreAddress(season, winner, program.programId);
+  const scoreAccount = scoreAddress ? await rpc.getCompressedAccount(bn(scoreAddress.toBytes())) : null;
+
+  const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+  const inputs = [
+    ...(scoreAccount ? [scoreAccount] : []),
+    ...(season === null ? [] : [seasonAccounts[0]]),
+    account,
+    ...(season === null ? [] : [seasonAccounts[1]]),
+  ];
+  const newAddresses = [resultAddress, ...(scoreAddress && !scoreAccount ? [scoreAddress] : [])];
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [...inputs.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+    newAddresses.map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const metas = inputs.map((a, i) =>
+    toAccountMeta(a, new web3.PublicKey(a!.address!), proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+  );
+  const readOnly = (meta: any) => {
+    const { outputStateTreeIndex: _, ...rest } = meta;
+    return rest;
+  };
+  const gameIndex = inputs.indexOf(account);
+  const protocol = toProtocolConfigRef(
+    coder, configAccount, proofRpcResult.rootIndices[inputs.length], remainingAccounts, outputStateTree
+  );
+  const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+
+  let seasonUpdate = null;
+  if (season !== null) {
+    seasonUpdate = {
+      season: camelizeKeys(coder.types.decode("Season", seasonAccounts[1]!.data!.data)),
+      seasonMeta: readOnly(metas[gameIndex + 1]),
+      leaderboard: camelizeKeys(coder.types.decode("Leaderboard", seasonAccounts[0]!.data!.data)),
+      leaderboardMeta: metas[gameIndex - 1],
+      score: scoreAccount
+        ? { score: camelizeKeys(coder.types.decode("SeasonScore", scoreAccount.data!.data)), meta: metas[0] }
+        : null,
+    };
+  }
+
+  const tx = await program.methods
+    .recordGameResult(
+      { 0: proofRpcResult.compressedProof },
+      {
+        addressTreeInfo: {
+          rootIndex: proofRpcResult.rootIndices[inputs.length + 1],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+      },
+      { game, meta: readOnly(metas[gameIndex]) },
+      { season: seasonUpdate, profiles: null, rivalry: null, stats: null },
+      protocol
+    )
+    .accounts({ signer: signer.publicKey })
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([signer])
+    .transaction();
+
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return resultAddress;
+}
+
+// Mirrors `derive_play_queue_address` in the program: ["play_queue", mode, issuer?]
+function derivePlayQueueAddress(mode: number, issuer: web3.PublicKey | null, programId: web3.PublicKey): web3.PublicKey {
+  const seeds = [Buffer.from("play_queue"), Buffer.from([mode]), ...(issuer ? [issuer.toBytes()] : [])];
+  return deriveAddressV2(deriveAddressSeedV2(seeds), new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_credential_address` in the program: ["credential", issuer, holder]
+function deriveCredentialAddress(issuer: web3.PublicKey, holder: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("credential"), issuer.toBytes(), holder.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+describe("battleship_play_queues", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const tags = { region: 0, speed: 0, gridSize: 5 };
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let issuer: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let queueAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decodeQueue = async () => {
+    const account = await rpc.getCompressedAccount(bn(queueAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("PlayQueue", account!.data!.data));
+  };
+
+  const decodeGame = async (gameAddress: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(gameAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("GameState", account!.data!.data));
+  };
+
+  const decodeProfile = async (owner: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(
+      bn(deriveProfileAddress(owner, addressTree, program.programId).toBytes())
+    );
+    return camelizeKeys(coder.types.decode("PlayerProfile", account!.data!.data));
+  };
+
+  // Opens the casual queue, verified by `queueIssuer`'s credentials if given
+  const sendOpenPlayQueue = async (queueIssuer: web3.PublicKey | null = null) => {
+    const address = derivePlayQueueAddress(WAGER_MODE_CASUAL, queueIssuer, program.programId);
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.openPlayQueue(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        WAGER_MODE_CASUAL,
+        queueIssuer,
+        protocol
+      ),
+      signerA,
+      remainingAccounts
+    );
+  };
+
+  // Waits in the queue, or starts a game with whoever is waiting there.
+  // Returns the address of the game the deposit went to.
+  const sendJoinQueue = async (
+    signer: web3.Keypair,
+    x: number,
+    y: number,
+    isHorizontal: boolean,
+    address = queueAddress,
+    credentialAddress: web3.PublicKey | null = null
+  ) => {
+    const queueAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
+    const queue = camelizeKeys(coder.types.decode("PlayQueue", queueAccount!.data!.data));
+    const profile = await loadProfile(program, coder, rpc, signer.publicKey);
+    const gameAddress = queue.waiting
+      ? deriveGameAddress(queue.waiting.player, queue.waiting.gameId.toNumber(), addressTree, program.programId)
+      : profile.gameAddress;
+    // Pairing creates the game and its log
+    const newAddresses = queue.waiting
+      ? [gameAddress, deriveMoveLogAddress(gameAddress, addressTree, program.programId)]
+      : [];
+    // The credential is read, so it sits between the profile and the config
+    const credentialAccount = credentialAddress ? await rpc.getCompressedAccount(bn(credentialAddress.toBytes())) : null;
+    const credentialInputs = credentialAccount
+      ? [{ hash: credentialAccount.hash, tree: credentialAccount.treeInfo.tree, queue: credentialAccount.treeInfo.queue }]
+      : [];
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [
+        { hash: queueAccount!.hash, tree: queueAccount!.treeInfo.tree, queue: queueAccount!.treeInfo.queue },
+        profile.slotInputs[0],
+        ...credentialInputs,
+        profile.slotInputs[1],
+      ],
+      newAddresses.map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const queueMeta = toAccountMeta(
+      queueAccount, address, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const slot = {
+      profile: camelizeKeys(coder.types.decode("PlayerProfile", profile.profileAccount!.data!.data)),
+      profileMeta: toAccountMeta(
+        profile.profileAccount,
+        new web3.PublicKey(profile.profileAccount!.address),
+        proofRpcResult.rootIndices[1],
+        remainingAccounts,
+        outputStateTree
+      ),
+      protocol: toProtocolConfigRef(
+        coder, profile.configAccount, proofRpcResult.rootIndices[2 + credentialInputs.length], remainingAccounts, outputStateTree
+      ),
+    };
+    let credential = null;
+    if (credentialAccount) {
+      const { outputStateTreeIndex: _, ...meta } = toAccountMeta(
+        credentialAccount, credentialAddress!, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+      );
+      credential = { credential: camelizeKeys(coder.types.decode("Credential", credentialAccount.data!.data)), meta };
+    }
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.joinQueue(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: queue.waiting ? proofRpcResult.rootIndices[3 + credentialInputs.length] : 0,
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { queue, meta: queueMeta },
+        { ship: { x, y, isHorizontal }, boardHash: Array.from(randomBytes(32)), tags },
+        slot,
+        credential
+      ),
+      signer,
+      remainingAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId) }
+    );
+    return gameAddress;
+  };
+
+  const sendLeaveQueue = async (signer: web3.Keypair, vault: web3.PublicKey) => {
+    const profileAddress = deriveProfileAddress(signer.publicKey, addressTree, program.programId);
+    const accounts = await Promise.all([queueAddress, profileAddress].map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const queueMeta = toAccountMeta(accounts[0], queueAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const profileMeta = toAccountMeta(accounts[1], profileAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.leaveQueue(
+        { 0: proofRpcResult.compressedProof },
+        // Only names the tree the waiting game's address is derived in
+        { rootIndex: 0, addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        { queue: camelizeKeys(coder.types.decode("PlayQueue", accounts[0]!.data!.data)), meta: queueMeta },
+        camelizeKeys(coder.types.decode("PlayerProfile", accounts[1]!.data!.data)),
+        profileMeta,
+        protocol
+      ),
+      signer,
+      remainingAccounts,
+      { vault }
+    );
+  };
+
+  const sendIssueCredential = async (signer: web3.Keypair, holder: web3.PublicKey, expiresSlot: number) => {
+    const address = deriveCredentialAddress(signer.publicKey, holder, program.programId);
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.issueCredential(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        holder,
+        new anchor.BN(expiresSlot),
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendRevokeCredential = async (signer: web3.Keypair, credentialIssuer: web3.PublicKey, holder: web3.PublicKey) => {
+    const address = deriveCredentialAddress(credentialIssuer, holder, program.programId);
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = toAccountMeta(account, address, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods.revokeCredential(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("Credential", account!.data!.data)),
+        accountMeta,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    issuer = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(issuer.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    queueAddress = derivePlayQueueAddress(WAGER_MODE_CASUAL, null, program.programId);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. A Queued Player Waits With Their Deposit", async () => {
+    await sendOpenPlayQueue();
+    // One open queue per mode
+    await assert.rejects(sendOpenPlayQueue());
+
+    const gameId = (await decodeProfile(signerA.publicKey)).nextGameId.toNumber();
+    const gameAddress = await sendJoinQueue(signerA, 0, 0, true);
+    const queue = await decodeQueue();
+    assert.ok(queue.waiting.player.equals(signerA.publicKey));
+    assert.strictEqual(queue.waiting.gameId.toNumber(), gameId);
+    assert.strictEqual(await rpc.getBalance(deriveVaultAddress(gameAddress, program.programId)), GAME_DEPOSIT_LAMPORTS);
+    assert.strictEqual((await decodeProfile(signerA.publicKey)).activeGames, 1);
+
+    await rejectsWith(sendJoinQueue(signerA, 0, 0, true), "TicketNotQueued");
+    console.log("✅ A waiting in the queue.");
+  });
+
+  it("2. Leaving The Queue Refunds The Deposit Once", async () => {
+    const { waiting } = await decodeQueue();
+    const gameAddress = deriveGameAddress(signerA.publicKey, waiting.gameId.toNumber(), addressTree, program.programId);
+    const vault = deriveVaultAddress(gameAddress, program.programId);
+    // Only the waiting player leaves
+    await rejectsWith(sendLeaveQueue(signerB, vault), "TicketNotQueued");
+
+    const [refunded] = await balanceChanges(rpc, [vault], () => sendLeaveQueue(signerA, vault));
+    assert.strictEqual(refunded, -GAME_DEPOSIT_LAMPORTS);
+    assert.strictEqual((await decodeQueue()).waiting, null);
+    assert.strictEqual((await decodeProfile(signerA.publicKey)).activeGames, 0);
+
+    await rejectsWith(sendLeaveQueue(signerA, vault), "TicketNotQueued");
+    console.log("✅ A's deposit refunded.");
+  });
+
+  it("3. The Next Joiner Starts A Game With The Waiting Player", async () => {
+    // The id A left with stays spent, so A waits for a new game
+    const hostGame = await sendJoinQueue(signerA, 0, 0, true);
+    const gameAddress = await sendJoinQueue(signerB, 4, 0, false);
+    assert.ok(gameAddress.equals(hostGame));
+
+    const game = await decodeGame(gameAddress);
+    assert.strictEqual(game.gameStatus, 1);
+    assert.ok(game.playerA.equals(signerA.publicKey));
+    assert.ok(game.playerB.equals(signerB.publicKey));
+    assert.strictEqual(await rpc.getBalance(deriveVaultAddress(gameAddress, program.programId)), 2 * GAME_DEPOSIT_LAMPORTS);
+    assert.strictEqual((await decodeQueue()).waiting, null);
+    assert.strictEqual((await decodeProfile(signerB.publicKey)).activeGames, 1);
+
+    // The game plays like any other
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+    assert.strictEqual((await decodeGame(gameAddress)).gameStatus, 2);
+    console.log("✅ Queue paired A and B.");
+  });
+
+  it("4. A Verified Queue Admits Only Its Issuer's Credential Holders", async () => {
+    const verifiedQueue = derivePlayQueueAddress(WAGER_MODE_CASUAL, issuer.publicKey, program.programId);
+    const credentialA = deriveCredentialAddress(issuer.publicKey, signerA.publicKey, program.programId);
+    await sendOpenPlayQueue(issuer.publicKey);
+    await rejectsWith(sendJoinQueue(signerA, 0, 0, true, verifiedQueue), "CredentialRequired");
+
+    // Already expired
+    await sendIssueCredential(issuer, signerA.publicKey, 1);
+    const credential = await rpc.getCompressedAccount(bn(credentialA.toBytes()));
+    assert.ok(camelizeKeys(coder.types.decode("Credential", credential!.data!.data)).holder.equals(signerA.publicKey));
+    await rejectsWith(sendJoinQueue(signerA, 0, 0, true, verifiedQueue, credentialA), "CredentialRequired");
+    // The open queue takes none
+    await rejectsWith(sendJoinQueue(signerA, 0, 0, true, queueAddress, credentialA), "CredentialRequired");
+    // Another issuer's credential doesn't count
+    await sendIssueCredential(signerB, signerA.publicKey, 0);
+    await rejectsWith(
+      sendJoinQueue(signerA, 0, 0, true, verifiedQueue, deriveCredentialAddress(signerB.publicKey, signerA.publicKey, program.programId)),
+      "CredentialRequired"
+    );
+
+    await sendIssueCredential(issuer, signerB.publicKey, 0);
+    const credentialB = deriveCredentialAddress(issuer.publicKey, signerB.publicKey, program.programId);
+    const hostGame = await sendJoinQueue(signerB, 0, 0, true, verifiedQueue, credentialB);
+
+    await sendRevokeCredential(issuer, issuer.publicKey, signerA.publicKey);
+    await sendIssueCredential(issuer, signerA.publicKey, 0);
+    const gameAddress = await sendJoinQueue(signerA, 4, 0, false, verifiedQueue, credentialA);
+    assert.ok(gameAddress.equals(hostGame));
+    const game = await decodeGame(gameAddress);
+    assert.ok(game.playerA.equals(signerB.publicKey));
+    assert.ok(game.playerB.equals(signerA.publicKey));
+    console.log("✅ Verified queue paired B and A.");
+  });
+
+  it("5. A Revoked Credential No Longer Admits Its Holder", async () => {
+    const verifiedQueue = derivePlayQueueAddress(WAGER_MODE_CASUAL, issuer.publicKey, program.programId);
+    const credentialA = deriveCredentialAddress(issuer.publicKey, signerA.publicKey, program.programId);
+    // Only the issuer revokes
+    await rejectsWith(sendRevokeCredential(signerA, issuer.publicKey, signerA.publicKey), "CredentialRequired");
+
+    await sendRevokeCredential(issuer, issuer.publicKey, signerA.publicKey);
+    await assert.rejects(sendJoinQueue(signerA, 0, 0, true, verifiedQueue, credentialA));
+    await rejectsWith(sendJoinQueue(signerA, 0, 0, true, verifiedQueue), "CredentialRequired");
+    await assert.rejects(sendRevokeCredential(issuer, issuer.publicKey, signerA.publicKey));
+    console.log("✅ A's revoked credential rejected.");
+  });
+});
+
+// Mirrors `derive_tournament_address` in the program: ["tournament", organizer, tournament_id]
+function deriveTournamentAddress(organizer: web3.PublicKey, tournamentId: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([
+    Buffer.from("tournament"),
+    organizer.toBytes(),
+    new anchor.BN(tournamentId).toArrayLike(Buffer, "le", 8),
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+describe("battleship_tournaments", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  const TOURNAMENT_ID = 0;
+  const MAX_PLAYERS = 3;
+
+  let organizer: web3.Keypair;
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let signerC: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let tournamentAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts, accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1_000_000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decodeTournament = async () => {
+    const account = await rpc.getCompressedAccount(bn(tournamentAddress.toBytes()));
+    return camelizeKeys(coder.types.decode("Tournament", account!.data!.data));
+  };
+
+  const sendCreateTournament = async (maxPlayers: number) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(tournamentAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createTournament(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        new anchor.BN(TOURNAMENT_ID),
+        maxPlayers,
+        protocol
+      ),
+      organizer,
+      remainingAccounts
+    );
+  };
+
+  // Registering or starting: the tournament is the only account updated
+  const sendTournamentUpdate = async (method: "registerForTournament" | "startTournament", signer: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(tournamentAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(account, tournamentAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    await send(
+      program.methods[method](
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("Tournament", account!.data!.data)),
+        meta,
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  const sendCreateTournamentGame = async (signer: web3.Keypair, matchIndex: number) => {
+    const account = await rpc.getCompressedAccount(bn(tournamentAddress.toBytes()));
+    const profile = await loadProfile(program, coder, rpc, signer.publicKey);
+    const gameAddress = profile.gameAddress;
+    const logAddress = deriveMoveLogAddress(gameAddress, addressTree, program.programId);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, ...profile.slotInputs],
+      [gameAddress, logAddress].map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(account, tournamentAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const slot = toActiveGameSlot(
+      coder, profile, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createTournamentGame(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[3],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { tournament: camelizeKeys(coder.types.decode("Tournament", account!.data!.data)), meta },
+        matchIndex,
+        { ship: { x: 0, y: 0, isHorizontal: true }, boardHash: Array.from(randomBytes(32)) },
+        slot
+      ),
+      signer,
+      remainingAccounts,
+      { vault: deriveVaultAddress(gameAddress, program.programId) }
+    );
+    return gameAddress;
+  };
+
+  const sendReportTournamentResult = async (matchIndex: number, resultAddress: web3.PublicKey) => {
+    const accounts = await Promise.all([tournamentAddress, resultAddress].map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(accounts[0], tournamentAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const { outputStateTreeIndex: _, ...resultMeta } = toAccountMeta(
+      accounts[1], resultAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree
+    );
+    // Anyone may report a recorded result
+    await send(
+      program.methods.reportTournamentResult(
+        { 0: proofRpcResult.compressedProof },
+        { tournament: camelizeKeys(coder.types.decode("Tournament", accounts[0]!.data!.data)), meta },
+        matchIndex,
+        { result: camelizeKeys(coder.types.decode("GameResult", accounts[1]!.data!.data)), meta: resultMeta },
+        protocol
+      ),
+      organizer,
+      remainingAccounts
+    );
+  };
+
+  // A hosts match 0 against `opponent` and wins it, returning its result
+  const sendPlayMatch = async (opponent: web3.Keypair) => {
+    const gameAddress = await sendCreateTournamentGame(signerA, 0);
+    await sendJoinGame(
+      program, coder, rpc, opponent, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    await sendPerfectWin(program, coder, rpc, signerA, opponent, gameAddress, outputStateTree);
+    return sendRecordGameResult(program, coder, rpc, organizer, gameAddress, outputStateTree);
+  };
+
+  before(async () => {
+    organizer = new web3.Keypair();
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    signerC = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [organizer, signerA, signerB, signerC]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    tournamentAddress = deriveTournamentAddress(organizer.publicKey, TOURNAMENT_ID, program.programId);
+    for (const signer of [signerA, signerB, signerC]) {
+      await sendCreateProfile(program, rpc, signer, outputStateTree);
+    }
+  });
+
+  it("1. Players Register Until The Organizer Starts The Bracket", async () => {
+    await rejectsWith(sendCreateTournament(1), "TournamentMismatch");
+    await sendCreateTournament(MAX_PLAYERS);
+
+    await sendTournamentUpdate("registerForTournament", signerA);
+    await sendTournamentUpdate("registerForTournament", signerB);
+    await rejectsWith(sendTournamentUpdate("registerForTournament", signerB), "TournamentMismatch");
+    // Only the organizer starts the bracket
+    await rejectsWith(sendTournamentUpdate("startTournament", signerA), "NotOrganizer");
+    await sendTournamentUpdate("registerForTournament", signerC);
+    // Full
+    await rejectsWith(sendTournamentUpdate("registerForTournament", organizer), "TournamentMismatch");
+    await sendTournamentUpdate("startTournament", organizer);
+
+    const tournament = await decodeTournament();
+    assert.strictEqual(tournament.status, 1);
+    assert.strictEqual(tournament.round, 1);
+    assert.strictEqual(tournament.matches.length, 2);
+    assert.ok(tournament.matches[0].playerA.equals(signerA.publicKey));
+    assert.ok(tournament.matches[0].playerB.equals(signerB.publicKey));
+    // C, the odd one out, has a bye
+    assert.ok(tournament.matches[1].winner.equals(signerC.publicKey));
+    console.log("✅ Round 1 drawn.");
+  });
+
+  it("2. Match Games Are Hosted By Player A For Their Pairing", async () => {
+    await rejectsWith(sendCreateTournamentGame(signerB, 0), "NotAPlayer");
+    await rejectsWith(sendCreateTournamentGame(signerA, 1), "NotAPlayer");
+    const gameAddress = await sendCreateTournamentGame(signerA, 0);
+    assert.deepStrictEqual((await decodeTournament()).matches[0].gameAddress, Array.from(gameAddress.toBytes()));
+    await rejectsWith(sendCreateTournamentGame(signerA, 0), "TournamentMismatch");
+
+    // Reserved for the paired opponent
+    await rejectsWith(
+      sendJoinGame(program, coder, rpc, signerC, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))),
+      "GameReserved"
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+    const resultAddress = await sendRecordGameResult(program, coder, rpc, organizer, gameAddress, outputStateTree);
+
+    // The result only counts for its own match
+    await rejectsWith(sendReportTournamentResult(1, resultAddress), "TournamentMismatch");
+    await sendReportTournamentResult(0, resultAddress);
+
+    const tournament = await decodeTournament();
+    assert.strictEqual(tournament.round, 2);
+    assert.strictEqual(tournament.matches.length, 1);
+    assert.ok(tournament.matches[0].playerA.equals(signerA.publicKey));
+    assert.ok(tournament.matches[0].playerB.equals(signerC.publicKey));
+
+    // Round 2's match has no game yet, so the old result can't be reported again
+    await rejectsWith(sendReportTournamentResult(0, resultAddress), "TournamentMismatch");
+    console.log("✅ A advances to face C.");
+  });
+
+  it("3. The Final's Winner Is Crowned Champion", async () => {
+    const resultAddress = await sendPlayMatch(signerC);
+    await sendReportTournamentResult(0, resultAddress);
+
+    const tournament = await decodeTournament();
+    assert.strictEqual(tournament.status, 2);
+    assert.ok(tournament.champion.equals(signerA.publicKey));
+    assert.ok(tournament.matches[0].winner.equals(signerA.publicKey));
+
+    await rejectsWith(sendReportTournamentResult(0, resultAddress), "TournamentMismatch");
+    console.log("✅ A wins the tournament.");
+  });
+});
+
+// Mirrors `derive_season_address` in the program: ["season", season]
+function deriveSeasonAddress(season: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("season"), new anchor.BN(season).toArrayLike(Buffer, "le", 4)]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_leaderboard_address` in the program: ["leaderboard", season]
+function deriveLeaderboardAddress(season: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("leaderboard"), new anchor.BN(season).toArrayLike(Buffer, "le", 4)]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_season_score_address` in the program: ["season_score", season, player]
+function deriveSeasonScoreAddress(season: number, player: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([
+    Buffer.from("season_score"),
+    new anchor.BN(season).toArrayLike(Buffer, "le", 4),
+    player.toBytes(),
+  ]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_season_archive_address` in the program: ["season_archive", season]
+function deriveSeasonArchiveAddress(season: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("season_archive"), new anchor.BN(season).toArrayLike(Buffer, "le", 4)]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_season_rewards_address` in the program: ["season_rewards", season]
+function deriveSeasonRewardsAddress(season: number, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("season_rewards"), new anchor.BN(season).toArrayLike(Buffer, "le", 4)]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+describe("battleship_seasons", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+  const admin = loadAdminKeypair();
+
+  // Long enough for a game to finish inside the season
+  const SEASON_SLOTS = 250;
+  const FUNDING = 4_000_000;
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let rewardsAddress: web3.PublicKey;
+  let vault: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: web3.AccountMeta[], accounts: any = {}) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey, ...accounts })
+      .remainingAccounts(remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decode = async (name: string, address: web3.PublicKey) => {
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    return camelizeKeys(coder.types.decode(name, account!.data!.data));
+  };
+
+  // Opens `season`, ending `season - 1` if there is one
+  const sendRolloverSeason = async (signer: web3.Keypair, season: number, seasonSlots: number) => {
+    const current = season > 1 ? [deriveLeaderboardAddress(season - 1, program.programId), deriveSeasonAddress(season - 1, program.programId)] : [];
+    const accounts = await Promise.all(current.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })),
+      [deriveSeasonAddress(season, program.programId), deriveLeaderboardAddress(season, program.programId)].map((a) => ({
+        tree: addressTree,
+        queue: addressTree,
+        address: bn(a.toBytes()),
+      }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    let rollover = null;
+    if (accounts.length) {
+      const { outputStateTreeIndex: _, ...seasonMeta } = toAccountMeta(
+        accounts[1], current[1], proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+      );
+      rollover = {
+        season: camelizeKeys(coder.types.decode("Season", accounts[1]!.data!.data)),
+        seasonMeta,
+        leaderboard: camelizeKeys(coder.types.decode("Leaderboard", accounts[0]!.data!.data)),
+        leaderboardMeta: toAccountMeta(accounts[0], current[0], proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree),
+      };
+    }
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.rolloverSeason(
+        { 0: proofRpcResult.compressedProof },
+        {
+          rootIndex: proofRpcResult.rootIndices[accounts.length],
+          addressMerkleTreePubkeyIndex,
+          addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+        },
+        remainingAccounts.insertOrGet(outputStateTree),
+        rollover,
+        new anchor.BN(seasonSlots)
+      ),
+      signer,
+      remainingAccounts.toAccountMetas().remainingAccounts,
+      { programData: programDataAddress(program.programId) }
+    );
+  };
+
+  const sendCreateSeasonRewards = async (signer: web3.Keypair) => {
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(rewardsAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createSeasonRewards(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[0], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        1,
+        [10_000] // Winner takes all
+      ),
+      signer,
+      remainingAccounts.toAccountMetas().remainingAccounts,
+      { programData: programDataAddress(program.programId) }
+    );
+  };
+
+  const sendFundSeasonRewards = async (funder: web3.Keypair, amount: number) => {
+    const account = await rpc.getCompressedAccount(bn(rewardsAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(account, rewardsAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    await send(
+      program.methods.fundSeasonRewards(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("SeasonRewards", account!.data!.data)),
+        meta,
+        new anchor.BN(amount),
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree)
+      ),
+      funder,
+      remainingAccounts.toAccountMetas().remainingAccounts,
+      { vault }
+    );
+  };
+
+  // Pays season 1's final leaderboard to `payees`, in standings order
+  const sendDistributeSeasonRewards = async (signer: web3.Keypair, payees: web3.PublicKey[]) => {
+    const boardAddress = deriveLeaderboardAddress(1, program.programId);
+    const accounts = await Promise.all([rewardsAddress, boardAddress].map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })),
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(accounts[0], rewardsAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const { outputStateTreeIndex: _, ...boardMeta } = toAccountMeta(
+      accounts[1], boardAddress, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    // The placed players' accounts go first
+    const winners = payees.map((pubkey) => ({ pubkey, isSigner: false, isWritable: true }));
+    await send(
+      program.methods.distributeSeasonRewards(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("SeasonRewards", accounts[0]!.data!.data)),
+        meta,
+        { leaderboard: { 0: { leaderboard: camelizeKeys(coder.types.decode("Leaderboard", accounts[1]!.data!.data)), meta: boardMeta } } }
+      ),
+      signer,
+      [...winners, ...remainingAccounts.toAccountMetas().remainingAccounts],
+      { programData: programDataAddress(program.programId), vault }
+    );
+  };
+
+  // Archives `season`'s standings and closes its leaderboard
+  const sendResetSeason = async (signer: web3.Keypair, season: number) => {
+    const current = [deriveLeaderboardAddress(season, program.programId), deriveSeasonAddress(season, program.programId)];
+    const accounts = await Promise.all(current.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })),
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveSeasonArchiveAddress(season, program.programId).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const leaderboardMeta = toAccountMeta(accounts[0], current[0], proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const { outputStateTreeIndex: _, ...seasonMeta } = toAccountMeta(
+      accounts[1], current[1], proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.resetSeason(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        {
+          season: camelizeKeys(coder.types.decode("Season", accounts[1]!.data!.data)),
+          seasonMeta,
+          leaderboard: camelizeKeys(coder.types.decode("Leaderboard", accounts[0]!.data!.data)),
+          leaderboardMeta,
+        }
+      ),
+      signer,
+      remainingAccounts.toAccountMetas().remainingAccounts,
+      { programData: programDataAddress(program.programId) }
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    await rpc.requestAirdrop(signerA.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await rpc.requestAirdrop(signerB.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    rewardsAddress = deriveSeasonRewardsAddress(1, program.programId);
+    vault = derivePrizeVaultAddress(rewardsAddress, program.programId);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. The Admin Opens The First Season", async () => {
+    await rejectsWith(sendRolloverSeason(signerA, 1, SEASON_SLOTS), "NotAdmin");
+    await rejectsWith(sendRolloverSeason(admin, 1, 0), "SeasonMismatch");
+    await sendRolloverSeason(admin, 1, SEASON_SLOTS);
+
+    const season = await decode("Season", deriveSeasonAddress(1, program.programId));
+    assert.strictEqual(season.endSlot.toNumber() - season.startSlot.toNumber(), SEASON_SLOTS);
+    // Season 1 is still running
+    await rejectsWith(sendRolloverSeason(admin, 2, SEASON_SLOTS), "SeasonMismatch");
+    console.log("✅ Season 1 open.");
+  });
+
+  it("2. A Win During The Season Scores On Its Leaderboard", async () => {
+    const gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+    await sendRecordGameResult(program, coder, rpc, signerB, gameAddress, outputStateTree, 1);
+
+    const score = await decode("SeasonScore", deriveSeasonScoreAddress(1, signerA.publicKey, program.programId));
+    assert.strictEqual(score.wins, 1);
+    const board = await decode("Leaderboard", deriveLeaderboardAddress(1, program.programId));
+    assert.strictEqual(board.entries.length, 1);
+    assert.ok(board.entries[0].player.equals(signerA.publicKey));
+    assert.strictEqual(board.entries[0].wins, 1);
+    console.log("✅ A leads season 1.");
+  });
+
+  it("3. Season Rewards Pay The Final Standings Once", async () => {
+    await rejectsWith(sendCreateSeasonRewards(signerA), "NotAdmin");
+    await sendCreateSeasonRewards(admin);
+    // Anyone funds the pool
+    await sendFundSeasonRewards(signerB, FUNDING);
+    assert.strictEqual((await decode("SeasonRewards", rewardsAddress)).funded.toNumber(), FUNDING);
+    assert.strictEqual(await rpc.getBalance(vault), FUNDING);
+
+    // Standings are final only once the season rolls over
+    await rejectsWith(sendDistributeSeasonRewards(admin, [signerA.publicKey]), "SeasonMismatch");
+    const { endSlot } = await decode("Season", deriveSeasonAddress(1, program.programId));
+    while ((await rpc.getSlot()) < endSlot.toNumber()) {
+      await sleep(1000);
+    }
+    await sendRolloverSeason(admin, 2, SEASON_SLOTS);
+    assert.ok((await decode("Leaderboard", deriveLeaderboardAddress(1, program.programId))).archived);
+
+    await rejectsWith(sendDistributeSeasonRewards(signerA, [signerA.publicKey]), "NotAdmin");
+    await rejectsWith(sendDistributeSeasonRewards(admin, [signerB.publicKey]), "WrongPlayerAccount");
+    const [won] = await balanceChanges(rpc, [signerA.publicKey], () => sendDistributeSeasonRewards(admin, [signerA.publicKey]));
+    assert.strictEqual(won, FUNDING);
+    assert.strictEqual(await rpc.getBalance(vault), 0);
+    assert.ok((await decode("SeasonRewards", rewardsAddress)).distributed);
+
+    await rejectsWith(sendDistributeSeasonRewards(admin, [signerA.publicKey]), "NothingToClaim");
+    await rejectsWith(sendFundSeasonRewards(signerB, FUNDING), "PrizePoolClosed");
+    console.log("✅ Season 1 rewards paid to A.");
+  });
+
+  it("4. Resetting A Rolled-Over Season Archives Its Standings", async () => {
+    // Season 2 is still live
+    await rejectsWith(sendResetSeason(admin, 2), "SeasonMismatch");
+    await rejectsWith(sendResetSeason(signerA, 1), "NotAdmin");
+
+    const season = await decode("Season", deriveSeasonAddress(1, program.programId));
+    const board = await decode("Leaderboard", deriveLeaderboardAddress(1, program.programId));
+    await sendResetSeason(admin, 1);
+
+    const archive = await decode("SeasonArchive", deriveSeasonArchiveAddress(1, program.programId));
+    assert.strictEqual(archive.season, 1);
+    assert.strictEqual(archive.startSlot.toNumber(), season.startSlot.toNumber());
+    assert.strictEqual(archive.endSlot.toNumber(), season.endSlot.toNumber());
+    assert.strictEqual(archive.standings.length, board.entries.length);
+    assert.ok(archive.standings[0].player.equals(signerA.publicKey));
+    assert.strictEqual(archive.standings[0].wins, 1);
+    assert.ok(archive.archivedSlot.toNumber() >= season.endSlot.toNumber());
+
+    // The leaderboard is closed and the archive already exists
+    await assert.rejects(sendResetSeason(admin, 1));
+    console.log("✅ Season 1 archived.");
+  });
+});
+
+// Mirrors `derive_club_address` in the program: ["club", name]
+function deriveClubAddress(name: string, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("club"), Buffer.from(name)]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_club_member_address` in the program: ["club_member", player]
+function deriveClubMemberAddress(player: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("club_member"), player.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+// Mirrors `derive_club_claim_address` in the program: ["club_claim", result_address, member]
+function deriveClubClaimAddress(resultAddress: web3.PublicKey, member: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  const seed = deriveAddressSeedV2([Buffer.from("club_claim"), resultAddress.toBytes(), member.toBytes()]);
+  return deriveAddressV2(seed, new web3.PublicKey(batchAddressTree), programId);
+}
+
+describe("battleship_clubs", () => {
+  const program = anchor.workspace.Battleship as Program<Battleship>;
+  const coder = new anchor.BorshCoder(idl as anchor.Idl);
+
+  const CLUB_NAME = "harbor_fleet";
+  const OTHER_CLUB_NAME = "reef_raiders";
+
+  let signerA: web3.Keypair;
+  let signerB: web3.Keypair;
+  let signerC: web3.Keypair;
+  let rpc: Rpc;
+  let outputStateTree: web3.PublicKey;
+  let addressTree: web3.PublicKey;
+  let clubAddress: web3.PublicKey;
+
+  const send = async (method: any, signer: web3.Keypair, remainingAccounts: PackedAccounts) => {
+    const tx = await method
+      .accounts({ signer: signer.publicKey })
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    await confirmTx(rpc, await rpc.sendTransaction(tx, [signer]));
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  };
+
+  const decodeClub = async (address = clubAddress) => {
+    const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+    return camelizeKeys(coder.types.decode("Club", account!.data!.data));
+  };
+
+  const sendCreateClub = async (founder: web3.Keypair, name: string) => {
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [configInput],
+      [deriveClubAddress(name, program.programId), deriveClubMemberAddress(founder.publicKey, program.programId)].map((a) => ({
+        tree: addressTree,
+        queue: addressTree,
+        address: bn(a.toBytes()),
+      }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.createClub(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[1], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        name,
+        protocol
+      ),
+      founder,
+      remainingAccounts
+    );
+  };
+
+  const sendJoinClub = async (player: web3.Keypair) => {
+    const account = await rpc.getCompressedAccount(bn(clubAddress.toBytes()));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }, configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(deriveClubMemberAddress(player.publicKey, program.programId).toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = toAccountMeta(account, clubAddress, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[1], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.joinClub(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[2], addressMerkleTreePubkeyIndex, addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex },
+        remainingAccounts.insertOrGet(outputStateTree),
+        { club: camelizeKeys(coder.types.decode("Club", account!.data!.data)), meta },
+        protocol
+      ),
+      player,
+      remainingAccounts
+    );
+  };
+
+  // Credits `member`'s recorded result to `club`
+  const sendRecordClubResult = async (signer: web3.Keypair, member: web3.PublicKey, resultAddress: web3.PublicKey, club = clubAddress) => {
+    const memberAddress = deriveClubMemberAddress(member, program.programId);
+    const claimAddress = deriveClubClaimAddress(resultAddress, member, program.programId);
+    const addresses = [club, resultAddress, memberAddress];
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      [{ tree: addressTree, queue: addressTree, address: bn(claimAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const [clubMeta, resultMeta, memberMeta] = accounts.map((a, i) =>
+      toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    const readOnly = (meta: any) => {
+      const { outputStateTreeIndex: _, ...rest } = meta;
+      return rest;
+    };
+    const protocol = toProtocolConfigRef(
+      coder, configAccount, proofRpcResult.rootIndices[3], remainingAccounts, outputStateTree
+    );
+    const addressMerkleTreePubkeyIndex = remainingAccounts.insertOrGet(addressTree);
+    await send(
+      program.methods.recordClubResult(
+        { 0: proofRpcResult.compressedProof },
+        {
+          addressTreeInfo: {
+            rootIndex: proofRpcResult.rootIndices[4],
+            addressMerkleTreePubkeyIndex,
+            addressQueuePubkeyIndex: addressMerkleTreePubkeyIndex,
+          },
+          outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+        },
+        { result: camelizeKeys(coder.types.decode("GameResult", accounts[1]!.data!.data)), meta: readOnly(resultMeta) },
+        { membership: camelizeKeys(coder.types.decode("ClubMember", accounts[2]!.data!.data)), meta: readOnly(memberMeta) },
+        { club: camelizeKeys(coder.types.decode("Club", accounts[0]!.data!.data)), meta: clubMeta },
+        protocol
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  // `member`'s membership, closed by `signer`
+  const sendLeaveClub = async (signer: web3.Keypair, member: web3.PublicKey) => {
+    const memberAddress = deriveClubMemberAddress(member, program.programId);
+    const addresses = [memberAddress, clubAddress];
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const { configAccount, configInput } = await loadProtocolConfig(program, rpc);
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [...accounts.map((a) => ({ hash: a!.hash, tree: a!.treeInfo.tree, queue: a!.treeInfo.queue })), configInput],
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const [memberMeta, clubMeta] = accounts.map((a, i) =>
+      toAccountMeta(a, addresses[i], proofRpcResult.rootIndices[i], remainingAccounts, outputStateTree)
+    );
+    await send(
+      program.methods.leaveClub(
+        { 0: proofRpcResult.compressedProof },
+        camelizeKeys(coder.types.decode("ClubMember", accounts[0]!.data!.data)),
+        memberMeta,
+        { club: camelizeKeys(coder.types.decode("Club", accounts[1]!.data!.data)), meta: clubMeta },
+        toProtocolConfigRef(coder, configAccount, proofRpcResult.rootIndices[2], remainingAccounts, outputStateTree)
+      ),
+      signer,
+      remainingAccounts
+    );
+  };
+
+  before(async () => {
+    signerA = new web3.Keypair();
+    signerB = new web3.Keypair();
+    signerC = new web3.Keypair();
+    rpc = createRpc(
+      "http://127.0.0.1:8899",
+      "http://127.0.0.1:8784",
+      "http://127.0.0.1:3001",
+      { commitment: "confirmed" }
+    );
+    for (const signer of [signerA, signerB, signerC]) {
+      await rpc.requestAirdrop(signer.publicKey, web3.LAMPORTS_PER_SOL * 2);
+    }
+    await sleep(2000);
+
+    outputStateTree = defaultTestStateTreeAccounts().merkleTree;
+    addressTree = new web3.PublicKey(batchAddressTree);
+    clubAddress = deriveClubAddress(CLUB_NAME, program.programId);
+    await sendCreateProfile(program, rpc, signerA, outputStateTree);
+    await sendCreateProfile(program, rpc, signerB, outputStateTree);
+  });
+
+  it("1. Players Found And Join A Club", async () => {
+    await rejectsWith(sendCreateClub(signerA, "Harbor Fleet"), "InvalidName");
+    await sendCreateClub(signerA, CLUB_NAME);
+    await sendJoinClub(signerB);
+    // One membership per player
+    await assert.rejects(sendJoinClub(signerB));
+
+    const club = await decodeClub();
+    assert.ok(club.founder.equals(signerA.publicKey));
+    assert.strictEqual(club.members, 2);
+    console.log("✅ A and B in the club.");
+  });
+
+  it("2. Each Member's Result Counts For Their Club Once", async () => {
+    const gameAddress = await sendCreateGame(
+      program, coder, rpc, signerA, outputStateTree, 0, 0, true, Array.from(randomBytes(32))
+    );
+    await sendJoinGame(
+      program, coder, rpc, signerB, gameAddress, outputStateTree, 4, 0, false, Array.from(randomBytes(32))
+    );
+    await sendPerfectWin(program, coder, rpc, signerA, signerB, gameAddress, outputStateTree);
+    const resultAddress = await sendRecordGameResult(program, coder, rpc, signerC, gameAddress, outputStateTree);
+
+    // Credited to the member's own club only
+    await sendCreateClub(signerC, OTHER_CLUB_NAME);
+    await rejectsWith(
+      sendRecordClubResult(signerC, signerA.publicKey, resultAddress, deriveClubAddress(OTHER_CLUB_NAME, program.programId)),
+      "ClubMismatch"
+    );
+    // C founded a club but didn't play
+    await rejectsWith(
+      sendRecordClubResult(signerC, signerC.publicKey, resultAddress, deriveClubAddress(OTHER_CLUB_NAME, program.programId)),
+      "NotAPlayer"
+    );
+
+    await sendRecordClubResult(signerC, signerA.publicKey, resultAddress);
+    await sendRecordClubResult(signerC, signerB.publicKey, resultAddress);
+    const club = await decodeClub();
+    assert.deepStrictEqual([club.wins, club.losses, club.draws], [1, 1, 0]);
+
+    await assert.rejects(sendRecordClubResult(signerC, signerA.publicKey, resultAddress));
+    assert.strictEqual((await decodeClub()).wins, 1);
+    console.log("✅ Club credited a win and a loss.");
+  });
+
+  it("3. Members Leave Their Club Once", async () => {
+    await rejectsWith(sendLeaveClub(signerA, signerB.publicKey), "NotAPlayer");
+    await sendLeaveClub(signerB, signerB.publicKey);
+    assert.strictEqual((await decodeClub()).members, 1);
+
+    // The closed membership can't be proven again
+    await assert.rejects(sendLeaveClub(signerB, signerB.publicKey));
+    console.log("✅ B left the club.");
+  });
 });
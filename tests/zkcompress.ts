@@ -29,6 +29,203 @@ require("dotenv").config();
 const anchorWalletPath = path.join(os.homedir(), ".config/solana/id.json");
 process.env.ANCHOR_WALLET = anchorWalletPath;
 
+const computeBudgetIx = () => web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+// Mirrors the program's ["message", owner] address, or ["message", owner,
+// suffix] for create_accounts_batch
+function deriveMessageAddress(
+  owner: web3.PublicKey,
+  addressTree: web3.PublicKey,
+  programId: web3.PublicKey,
+  suffix?: Buffer
+): web3.PublicKey {
+  const seeds = [new TextEncoder().encode("message"), owner.toBytes()];
+  if (suffix) seeds.push(suffix);
+  return deriveAddressV2(deriveAddressSeedV2(seeds), addressTree, programId);
+}
+
+// Decodes a program type with camelCase field names, the way instruction
+// arguments are encoded, so decoded accounts can be passed straight back
+function decodeType(coder: anchor.BorshCoder, typeName: string, data: Buffer): any {
+  const camelize = (value: any): any => {
+    if (Array.isArray(value)) return value.map(camelize);
+    if (value === null || typeof value !== "object" || value.constructor !== Object) return value;
+    return Object.fromEntries(
+      Object.entries(value).map(([key, field]) => [key.replace(/_([a-z0-9])/g, (_, c) => c.toUpperCase()), camelize(field)])
+    );
+  };
+  return camelize(coder.types.decode(typeName, data));
+}
+
+// Builds the CompressedAccountMeta for an account fetched from the indexer
+function toAccountMeta(
+  account: any,
+  rootIndex: number,
+  remainingAccounts: PackedAccounts,
+  outputStateTree: web3.PublicKey
+) {
+  return {
+    treeInfo: {
+      rootIndex,
+      proveByIndex: false,
+      merkleTreePubkeyIndex: remainingAccounts.insertOrGet(account.treeInfo.tree),
+      queuePubkeyIndex: remainingAccounts.insertOrGet(account.treeInfo.queue),
+      leafIndex: account.leafIndex,
+    },
+    address: Array.from(new web3.PublicKey(account.address).toBytes()),
+    outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+  };
+}
+
+// Signs and sends `tx`, then waits until the indexer has caught up
+async function sendAndIndex(rpc: Rpc, tx: web3.Transaction, signer: web3.Keypair): Promise<string> {
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  const sig = await rpc.sendTransaction(tx, [signer]);
+  await confirmTx(rpc, sig);
+  await rpc.confirmTransactionIndexed(await rpc.getSlot());
+  return sig;
+}
+
+// Signs and sends `tx`, expecting the program to reject it with `error`
+async function assertRejected(rpc: Rpc, tx: web3.Transaction, signer: web3.Keypair, error: RegExp, message: string) {
+  tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+  tx.sign(signer);
+  await assert.rejects(rpc.sendTransaction(tx, [signer]).then((sig) => confirmTx(rpc, sig)), error, message);
+}
+
+// Fetches a compressed account with a fresh validity proof and its packed
+// meta, ready to pass to a mutating instruction
+async function loadAccount(
+  rpc: Rpc,
+  coder: anchor.BorshCoder,
+  programId: web3.PublicKey,
+  address: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  typeName = "MyCompressedAccount"
+) {
+  const account = await rpc.getCompressedAccount(bn(address.toBytes()));
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
+    []
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(programId));
+  const meta = toAccountMeta(account, proofRpcResult.rootIndices[0], remainingAccounts, outputStateTree);
+  const { outputStateTreeIndex: _, ...burnMeta } = meta;
+  return {
+    account,
+    current: account!.data!.data.length ? decodeType(coder, typeName, account!.data!.data) : null,
+    proof: { 0: proofRpcResult.compressedProof },
+    meta,
+    burnMeta,
+    remainingAccounts: remainingAccounts.toAccountMetas().remainingAccounts,
+  };
+}
+
+// Creates `owner`'s ["message", owner] account with create_account
+async function sendCreateAccount(
+  program: Program<Update>,
+  rpc: Rpc,
+  owner: web3.Keypair,
+  outputStateTree: web3.PublicKey,
+  message: string
+): Promise<web3.PublicKey> {
+  const addressTree = new web3.PublicKey(batchAddressTree);
+  const address = deriveMessageAddress(owner.publicKey, addressTree, program.programId);
+  const proofRpcResult = await rpc.getValidityProofV0(
+    [],
+    [{ tree: addressTree, queue: addressTree, address: bn(address.toBytes()) }]
+  );
+  const remainingAccounts = new PackedAccounts();
+  remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+  const addressIndex = remainingAccounts.insertOrGet(addressTree);
+  const treeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+  const tx = await program.methods
+    .createAccount(
+      { 0: proofRpcResult.compressedProof },
+      { rootIndex: proofRpcResult.rootIndices[0], addressMerkleTreePubkeyIndex: addressIndex, addressQueuePubkeyIndex: addressIndex },
+      treeIndex,
+      message
+    )
+    .accounts({ signer: owner.publicKey })
+    .preInstructions([computeBudgetIx()])
+    .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+    .signers([owner])
+    .transaction();
+  await sendAndIndex(rpc, tx, owner);
+  return address;
+}
+
+const PERMISSION_UPDATE = 1;
+const PERMISSION_CLOSE = 2;
+
+// Grants `delegate` the `permissions` bits on `owner`'s account at `address`
+async function sendSetDelegate(
+  program: Program<Update>,
+  coder: anchor.BorshCoder,
+  rpc: Rpc,
+  owner: web3.Keypair,
+  address: web3.PublicKey,
+  outputStateTree: web3.PublicKey,
+  delegate: web3.PublicKey,
+  permissions: number
+): Promise<string> {
+  const target = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+  const tx = await program.methods
+    .setDelegate(target.proof, target.current, target.meta, delegate, permissions)
+    .accounts({ signer: owner.publicKey })
+    .preInstructions([computeBudgetIx()])
+    .remainingAccounts(target.remainingAccounts)
+    .signers([owner])
+    .transaction();
+  return sendAndIndex(rpc, tx, owner);
+}
+
+// The program's upgrade authority, which anchor deploys with the wallet
+function loadAdminKeypair(): web3.Keypair {
+  const fs = require("fs");
+  return web3.Keypair.fromSecretKey(Uint8Array.from(JSON.parse(fs.readFileSync(anchorWalletPath, "utf8"))));
+}
+
+function programDataAddress(programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync(
+    [programId.toBuffer()],
+    new web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+  )[0];
+}
+
+// Mirrors MESSAGE_CONFIG_SEED in the program
+// Crank reward escrow of the compressed account at `address`
+function rewardVaultAddress(address: web3.PublicKey, programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("crank_reward"), address.toBuffer()], programId)[0];
+}
+
+function messageConfigAddress(programId: web3.PublicKey): web3.PublicKey {
+  return web3.PublicKey.findProgramAddressSync([Buffer.from("message_config")], programId)[0];
+}
+
+const MAX_MESSAGE_LEN = 256;
+
+// Sets the configured message limit as `signer`
+async function sendSetMessageLimit(program: Program<Update>, rpc: Rpc, signer: web3.Keypair, limit: number) {
+  const tx = await program.methods
+    .setMessageLimit(limit)
+    .accounts({ signer: signer.publicKey, programData: programDataAddress(program.programId) })
+    .signers([signer])
+    .transaction();
+  return sendAndIndex(rpc, tx, signer);
+}
+
+// A funded fresh keypair
+async function fundedKeypair(rpc: Rpc): Promise<web3.Keypair> {
+  const keypair = new web3.Keypair();
+  await rpc.requestAirdrop(keypair.publicKey, web3.LAMPORTS_PER_SOL);
+  await sleep(2000);
+  return keypair;
+}
+
 describe("zkcompress", () => {
   const program = anchor.workspace.Update as Program<Update>;
   const coder = new anchor.BorshCoder(idl as anchor.Idl);
@@ -58,6 +255,17 @@ describe("zkcompress", () => {
     const messageSeed = new TextEncoder().encode("message");
     const seed = deriveAddressSeedV2([messageSeed, signer.publicKey.toBytes()]);
     address = deriveAddressV2(seed, addressTree, new web3.PublicKey(program.idl.address));
+
+    // Message paths read the limit from the config, created once per validator
+    if (!(await rpc.getAccountInfo(messageConfigAddress(program.programId)))) {
+      const admin = loadAdminKeypair();
+      const tx = await program.methods
+        .initMessageConfig()
+        .accounts({ signer: admin.publicKey, programData: programDataAddress(program.programId) })
+        .signers([admin])
+        .transaction();
+      await sendAndIndex(rpc, tx, admin);
+    }
   });
 
   // ===============================
@@ -104,7 +312,7 @@ describe("zkcompress", () => {
     await rpc.confirmTransactionIndexed(slot);
 
     const compressedAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
-    const decoded = coder.types.decode("MyCompressedAccount", compressedAccount!.data!.data);
+    const decoded = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
 
     assert.ok(decoded.owner.equals(signer.publicKey), "Owner should match");
     assert.strictEqual(decoded.message, message, "Message should match");
@@ -119,7 +327,7 @@ describe("zkcompress", () => {
 
     // Fetch current account
     const compressedAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
-    const currentAccount = coder.types.decode("MyCompressedAccount", compressedAccount!.data!.data);
+    const currentAccount = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
 
     // Get validity proof using treeInfo
     const proofRpcResult = await rpc.getValidityProofV0(
@@ -173,20 +381,110 @@ describe("zkcompress", () => {
     await rpc.confirmTransactionIndexed(slot);
 
     const updatedAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
-    const decoded = coder.types.decode("MyCompressedAccount", updatedAccount!.data!.data);
+    const decoded = decodeType(coder, "MyCompressedAccount", updatedAccount!.data!.data);
 
     assert.strictEqual(decoded.message, newMessage, "Message should be updated");
     console.log("✅ Updated account message to:", decoded.message);
   });
 
+  // ===============================
+  // TEST 2b: Reject Update From A Non-Owner
+  // ===============================
+  it("2a. message limit is admin-set and enforced", async () => {
+    const admin = loadAdminKeypair();
+    const stranger = await fundedKeypair(rpc);
+    await assert.rejects(sendSetMessageLimit(program, rpc, stranger, 8), /NotAdmin|0x177b/);
+    await assert.rejects(sendSetMessageLimit(program, rpc, admin, MAX_MESSAGE_LEN + 1), /InvalidMessageLimit|0x177a/);
+
+    // Tighten the limit, then try to write past it
+    await sendSetMessageLimit(program, rpc, admin, 8);
+    try {
+      const config = await program.account.messageConfig.fetch(messageConfigAddress(program.programId));
+      assert.strictEqual(config.maxMessageLen, 8, "Limit should be stored");
+
+      const target = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+      const tx = await program.methods
+        .updateAccount(target.proof, target.current, target.meta, "nine byte")
+        .accounts({ signer: signer.publicKey })
+        .preInstructions([computeBudgetIx()])
+        .remainingAccounts(target.remainingAccounts)
+        .signers([signer])
+        .transaction();
+      await assertRejected(rpc, tx, signer, /MessageTooLong|0x1772/, "Messages past the limit are rejected");
+    } finally {
+      await sendSetMessageLimit(program, rpc, admin, MAX_MESSAGE_LEN);
+    }
+
+    // Past the restored limit too
+    await assert.rejects(
+      sendCreateAccount(program, rpc, stranger, outputStateTree, "x".repeat(MAX_MESSAGE_LEN + 1)),
+      /MessageTooLong|0x1772/,
+      "Messages past MAX_MESSAGE_LEN are rejected"
+    );
+    console.log("✅ Message limit enforced");
+  });
+
+  it("2b. reject update from a non-owner", async () => {
+    const attacker = new web3.Keypair();
+    await rpc.requestAirdrop(attacker.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    // The real current state, fetched like any client could
+    const compressedAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
+    const currentAccount = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
+
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{
+        hash: compressedAccount!.hash,
+        tree: compressedAccount!.treeInfo.tree,
+        queue: compressedAccount!.treeInfo.queue
+      }],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = {
+      treeInfo: {
+        rootIndex: proofRpcResult.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.queue),
+        leafIndex: compressedAccount!.leafIndex,
+      },
+      address: Array.from(address.toBytes()),
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+
+    const tx = await program.methods
+      .updateAccount({ 0: proofRpcResult.compressedProof }, currentAccount, accountMeta, "Hijacked!")
+      .accounts({ signer: attacker.publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([attacker])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(attacker);
+    await assert.rejects(
+      rpc.sendTransaction(tx, [attacker]).then((sig) => confirmTx(rpc, sig)),
+      /NotOwner|0x1770/,
+      "Only the owner may update the account"
+    );
+
+    const unchanged = await rpc.getCompressedAccount(bn(address.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", unchanged!.data!.data);
+    assert.strictEqual(decoded.message, currentAccount.message, "Message should be untouched");
+    console.log("✅ Non-owner update rejected");
+  });
+
   // ===============================
   // TEST 3: Close Compressed Account
   // ===============================
   it("3. close compressed account", async () => {
     // Fetch current account
     const compressedAccount = await rpc.getCompressedAccount(bn(address.toBytes()));
-    const decoded = coder.types.decode("MyCompressedAccount", compressedAccount!.data!.data);
-    const currentMessage = decoded.message;
+    const currentAccount = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
 
     const proofRpcResult = await rpc.getValidityProofV0(
       [{
@@ -221,7 +519,7 @@ describe("zkcompress", () => {
     const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
 
     const tx = await program.methods
-      .closeAccount(proof, accountMeta, currentMessage)
+      .closeAccount(proof, accountMeta, currentAccount)
       .accounts({ signer: signer.publicKey })
       .preInstructions([computeBudgetIx])
       .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
@@ -241,6 +539,24 @@ describe("zkcompress", () => {
     console.log("✅ Closed compressed account");
   });
 
+  it("3b. reject reinit of another signer's closed account", async () => {
+    const attacker = await fundedKeypair(rpc);
+    const closed = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+
+    // Neither suffix makes the attacker's derived address match
+    for (const suffix of [Buffer.alloc(0), Buffer.from("x")]) {
+      const tx = await program.methods
+        .reinitAccount(closed.proof, closed.meta, suffix)
+        .accounts({ signer: attacker.publicKey })
+        .preInstructions([computeBudgetIx()])
+        .remainingAccounts(closed.remainingAccounts)
+        .signers([attacker])
+        .transaction();
+      await assertRejected(rpc, tx, attacker, /NotOwner|0x1770/, "Only the address's creator may reopen it");
+    }
+    console.log("✅ Stranger reinit rejected");
+  });
+
   // ===============================
   // TEST 4: Reinitialize Compressed Account
   // ===============================
@@ -281,7 +597,7 @@ describe("zkcompress", () => {
     const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
 
     const tx = await program.methods
-      .reinitAccount(proof, accountMeta)
+      .reinitAccount(proof, accountMeta, Buffer.alloc(0))
       .accounts({ signer: signer.publicKey })
       .preInstructions([computeBudgetIx])
       .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
@@ -355,6 +671,8 @@ describe("zkcompress", () => {
 
     // Now burn it
     const compressedAccount = await rpc.getCompressedAccount(bn(burnAddress.toBytes()));
+    const currentAccount = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
+    assert.strictEqual(currentAccount.message, burnMessage);
 
     const burnProof = await rpc.getValidityProofV0(
       [{
@@ -385,7 +703,7 @@ describe("zkcompress", () => {
     };
 
     const burnTx = await program.methods
-      .burnAccount({ 0: burnProof.compressedProof }, burnAccountMeta, burnMessage)
+      .burnAccount({ 0: burnProof.compressedProof }, burnAccountMeta, currentAccount)
       .accounts({ signer: burnSigner.publicKey })
       .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
       .remainingAccounts(burnRemaining.toAccountMetas().remainingAccounts)
@@ -400,4 +718,1142 @@ describe("zkcompress", () => {
 
     console.log("✅ Burned compressed account permanently");
   });
+
+  it("5b. reject close and burn from a non-owner", async () => {
+    const owner = await fundedKeypair(rpc);
+    const attacker = await fundedKeypair(rpc);
+    const ownerAddress = await sendCreateAccount(program, rpc, owner, outputStateTree, "Keep out");
+    const target = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+
+    const closeTx = await program.methods
+      .closeAccount(target.proof, target.meta, target.current)
+      .accounts({ signer: attacker.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([attacker])
+      .transaction();
+    await assertRejected(rpc, closeTx, attacker, /NotOwner|0x1770/, "Only the owner may close the account");
+
+    const burnTx = await program.methods
+      .burnAccount(target.proof, target.burnMeta, target.current)
+      .accounts({ signer: attacker.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([attacker])
+      .transaction();
+    await assertRejected(rpc, burnTx, attacker, /NotOwner|0x1770/, "Only the owner may burn the account");
+
+    const unchanged = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", unchanged!.data!.data);
+    assert.strictEqual(decoded.message, "Keep out", "Account should survive both attempts");
+    console.log("✅ Non-owner close and burn rejected");
+  });
+
+  // ===============================
+  // TEST 6: Batch Update Under One Proof
+  // ===============================
+  it("6. batch update compressed accounts under one proof", async () => {
+    // Two fresh accounts from two new signers; the first signs for both as
+    // the second's delegate
+    const owners = [new web3.Keypair(), new web3.Keypair()];
+    const addresses: web3.PublicKey[] = [];
+    for (const owner of owners) {
+      await rpc.requestAirdrop(owner.publicKey, web3.LAMPORTS_PER_SOL);
+    }
+    await sleep(2000);
+
+    for (const owner of owners) {
+      const messageSeed = new TextEncoder().encode("message");
+      const seed = deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes()]);
+      const ownerAddress = deriveAddressV2(seed, addressTree, new web3.PublicKey(program.idl.address));
+      addresses.push(ownerAddress);
+
+      const createProof = await rpc.getValidityProofV0(
+        [],
+        [{ tree: addressTree, queue: addressTree, address: bn(ownerAddress.toBytes()) }]
+      );
+      const createRemaining = new PackedAccounts();
+      createRemaining.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+      const createAddrIdx = createRemaining.insertOrGet(addressTree);
+      const createTreeIdx = createRemaining.insertOrGet(outputStateTree);
+
+      const createTx = await program.methods
+        .createAccount(
+          { 0: createProof.compressedProof },
+          { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: createAddrIdx, addressQueuePubkeyIndex: createAddrIdx },
+          createTreeIdx,
+          "Before batch"
+        )
+        .accounts({ signer: owner.publicKey })
+        .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+        .remainingAccounts(createRemaining.toAccountMetas().remainingAccounts)
+        .signers([owner])
+        .transaction();
+
+      createTx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      createTx.sign(owner);
+      await confirmTx(rpc, await rpc.sendTransaction(createTx, [owner]));
+    }
+
+    // Wait for indexer
+    await sleep(2000);
+    let slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    // The second owner lets the first update their account
+    const delegated = await rpc.getCompressedAccount(bn(addresses[1].toBytes()));
+    const delegateProof = await rpc.getValidityProofV0(
+      [{
+        hash: delegated!.hash,
+        tree: delegated!.treeInfo.tree,
+        queue: delegated!.treeInfo.queue
+      }],
+      []
+    );
+    const delegateRemaining = new PackedAccounts();
+    delegateRemaining.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const delegateMeta = {
+      treeInfo: {
+        rootIndex: delegateProof.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: delegateRemaining.insertOrGet(delegated!.treeInfo.tree),
+        queuePubkeyIndex: delegateRemaining.insertOrGet(delegated!.treeInfo.queue),
+        leafIndex: delegated!.leafIndex,
+      },
+      address: Array.from(addresses[1].toBytes()),
+      outputStateTreeIndex: delegateRemaining.insertOrGet(outputStateTree),
+    };
+    const delegateTx = await program.methods
+      .setDelegate(
+        { 0: delegateProof.compressedProof },
+        decodeType(coder, "MyCompressedAccount", delegated!.data!.data),
+        delegateMeta,
+        owners[0].publicKey,
+        PERMISSION_UPDATE
+      )
+      .accounts({ signer: owners[1].publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(delegateRemaining.toAccountMetas().remainingAccounts)
+      .signers([owners[1]])
+      .transaction();
+
+    delegateTx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    delegateTx.sign(owners[1]);
+    await confirmTx(rpc, await rpc.sendTransaction(delegateTx, [owners[1]]));
+
+    slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    // One proof covers both inputs
+    const accounts = await Promise.all(addresses.map((a) => rpc.getCompressedAccount(bn(a.toBytes()))));
+    const batchProof = await rpc.getValidityProofV0(
+      accounts.map((account) => ({
+        hash: account!.hash,
+        tree: account!.treeInfo.tree,
+        queue: account!.treeInfo.queue,
+      })),
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const outputStateTreeIndex = remainingAccounts.insertOrGet(outputStateTree);
+
+    const updates = accounts.map((account, i) => ({
+      currentAccount: decodeType(coder, "MyCompressedAccount", account!.data!.data),
+      accountMeta: {
+        treeInfo: {
+          rootIndex: batchProof.rootIndices[i],
+          proveByIndex: false,
+          merkleTreePubkeyIndex: remainingAccounts.insertOrGet(account!.treeInfo.tree),
+          queuePubkeyIndex: remainingAccounts.insertOrGet(account!.treeInfo.queue),
+          leafIndex: account!.leafIndex,
+        },
+        address: Array.from(addresses[i].toBytes()),
+        outputStateTreeIndex,
+      },
+      newMessage: `Batched ${i}`,
+    }));
+
+    const tx = await program.methods
+      .updateAccountsBatch({ 0: batchProof.compressedProof }, updates)
+      .accounts({ signer: owners[0].publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owners[0]])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(owners[0]);
+    const sig = await rpc.sendTransaction(tx, [owners[0]]);
+    await confirmTx(rpc, sig);
+    console.log("Batch update TX:", sig);
+
+    slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    for (const [i, a] of addresses.entries()) {
+      const updated = await rpc.getCompressedAccount(bn(a.toBytes()));
+      const decoded = decodeType(coder, "MyCompressedAccount", updated!.data!.data);
+      assert.strictEqual(decoded.message, `Batched ${i}`, "Message should be updated");
+    }
+    console.log("✅ Updated both accounts in one CPI");
+  });
+
+  it("6b. reject a batch update that includes a non-owned account", async () => {
+    const owner = await fundedKeypair(rpc);
+    const attacker = await fundedKeypair(rpc);
+    const ownerAddress = await sendCreateAccount(program, rpc, owner, outputStateTree, "Mine");
+    const attackerAddress = await sendCreateAccount(program, rpc, attacker, outputStateTree, "Theirs");
+
+    // The attacker's own account rides along with the victim's
+    const accounts = await Promise.all(
+      [attackerAddress, ownerAddress].map((a) => rpc.getCompressedAccount(bn(a.toBytes())))
+    );
+    const batchProof = await rpc.getValidityProofV0(
+      accounts.map((account) => ({ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue })),
+      []
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const updates = accounts.map((account, i) => ({
+      currentAccount: decodeType(coder, "MyCompressedAccount", account!.data!.data),
+      accountMeta: toAccountMeta(account, batchProof.rootIndices[i], remainingAccounts, outputStateTree),
+      newMessage: "Hijacked!",
+    }));
+
+    const tx = await program.methods
+      .updateAccountsBatch({ 0: batchProof.compressedProof }, updates)
+      .accounts({ signer: attacker.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([attacker])
+      .transaction();
+    await assertRejected(rpc, tx, attacker, /NotOwner|0x1770/, "Every batched account needs update rights");
+
+    const unchanged = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", unchanged!.data!.data);
+    assert.strictEqual(decoded.message, "Mine", "Victim's message should be untouched");
+    console.log("✅ Non-owner batch update rejected");
+  });
+
+  it("6c. an update-only delegate can't close or burn", async () => {
+    const owner = await fundedKeypair(rpc);
+    const delegate = await fundedKeypair(rpc);
+    const ownerAddress = await sendCreateAccount(program, rpc, owner, outputStateTree, "Delegated");
+    await sendSetDelegate(program, coder, rpc, owner, ownerAddress, outputStateTree, delegate.publicKey, PERMISSION_UPDATE);
+
+    // The update bit lets the delegate edit the message
+    let target = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+    const updateTx = await program.methods
+      .updateAccount(target.proof, target.current, target.meta, "Edited by delegate")
+      .accounts({ signer: delegate.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([delegate])
+      .transaction();
+    await sendAndIndex(rpc, updateTx, delegate);
+
+    // ...but without the close bit it can neither close nor burn
+    target = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+    assert.strictEqual(target.current.message, "Edited by delegate", "Delegate update should land");
+    const closeTx = await program.methods
+      .closeAccount(target.proof, target.meta, target.current)
+      .accounts({ signer: delegate.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([delegate])
+      .transaction();
+    await assertRejected(rpc, closeTx, delegate, /NotOwner|0x1770/, "Closing needs PERMISSION_CLOSE");
+    const burnTx = await program.methods
+      .burnAccount(target.proof, target.burnMeta, target.current)
+      .accounts({ signer: delegate.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([delegate])
+      .transaction();
+    await assertRejected(rpc, burnTx, delegate, /NotOwner|0x1770/, "Burning needs PERMISSION_CLOSE");
+    console.log("✅ Update-only delegate kept from closing");
+  });
+
+  it("6d. a revoked delegate is rejected and a close delegate can close", async () => {
+    const owner = await fundedKeypair(rpc);
+    const delegate = await fundedKeypair(rpc);
+    const ownerAddress = await sendCreateAccount(program, rpc, owner, outputStateTree, "Revocable");
+    await sendSetDelegate(program, coder, rpc, owner, ownerAddress, outputStateTree, delegate.publicKey, PERMISSION_UPDATE);
+
+    // Passing no bits revokes the delegate
+    await sendSetDelegate(program, coder, rpc, owner, ownerAddress, outputStateTree, delegate.publicKey, 0);
+    let target = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+    assert.ok(target.current.delegate.equals(web3.PublicKey.default), "Delegate should be cleared");
+    const updateTx = await program.methods
+      .updateAccount(target.proof, target.current, target.meta, "Too late")
+      .accounts({ signer: delegate.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([delegate])
+      .transaction();
+    await assertRejected(rpc, updateTx, delegate, /NotOwner|0x1770/, "A revoked delegate can't update");
+
+    // Bits outside PERMISSION_ALL are refused
+    const badTx = await program.methods
+      .setDelegate(target.proof, target.current, target.meta, delegate.publicKey, 4)
+      .accounts({ signer: owner.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([owner])
+      .transaction();
+    await assertRejected(rpc, badTx, owner, /InvalidPermissions|0x1771/, "Unknown permission bits are rejected");
+
+    // A close-only delegate can close
+    await sendSetDelegate(program, coder, rpc, owner, ownerAddress, outputStateTree, delegate.publicKey, PERMISSION_CLOSE);
+    target = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+    const closeTx = await program.methods
+      .closeAccount(target.proof, target.meta, target.current)
+      .accounts({ signer: delegate.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([delegate])
+      .transaction();
+    await sendAndIndex(rpc, closeTx, delegate);
+    console.log("✅ Revoked delegate rejected, close delegate closed");
+  });
+
+  // ===============================
+  // TEST 7: Transfer Ownership
+  // ===============================
+  it("7. transfer ownership to another wallet", async () => {
+    const owner = new web3.Keypair();
+    const newOwner = new web3.Keypair();
+    await rpc.requestAirdrop(owner.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    const messageSeed = new TextEncoder().encode("message");
+    const seed = deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes()]);
+    const ownerAddress = deriveAddressV2(seed, addressTree, new web3.PublicKey(program.idl.address));
+
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(ownerAddress.toBytes()) }]
+    );
+    const createRemaining = new PackedAccounts();
+    createRemaining.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const createAddrIdx = createRemaining.insertOrGet(addressTree);
+    const createTreeIdx = createRemaining.insertOrGet(outputStateTree);
+
+    const createTx = await program.methods
+      .createAccount(
+        { 0: createProof.compressedProof },
+        { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: createAddrIdx, addressQueuePubkeyIndex: createAddrIdx },
+        createTreeIdx,
+        "Handed over"
+      )
+      .accounts({ signer: owner.publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(createRemaining.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction();
+
+    createTx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    createTx.sign(owner);
+    await confirmTx(rpc, await rpc.sendTransaction(createTx, [owner]));
+
+    // Wait for indexer
+    await sleep(2000);
+    let slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    const compressedAccount = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [{
+        hash: compressedAccount!.hash,
+        tree: compressedAccount!.treeInfo.tree,
+        queue: compressedAccount!.treeInfo.queue
+      }],
+      []
+    );
+
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = {
+      treeInfo: {
+        rootIndex: proofRpcResult.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.queue),
+        leafIndex: compressedAccount!.leafIndex,
+      },
+      address: Array.from(ownerAddress.toBytes()),
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+
+    const tx = await program.methods
+      .transferOwnership(
+        { 0: proofRpcResult.compressedProof },
+        decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data),
+        accountMeta,
+        newOwner.publicKey
+      )
+      .accounts({ signer: owner.publicKey, vault: rewardVaultAddress(ownerAddress, program.programId) })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(owner);
+    const sig = await rpc.sendTransaction(tx, [owner]);
+    await confirmTx(rpc, sig);
+    console.log("Transfer TX:", sig);
+
+    slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    const transferred = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", transferred!.data!.data);
+    assert.ok(decoded.owner.equals(newOwner.publicKey), "Owner should be the new wallet");
+    assert.strictEqual(decoded.message, "Handed over", "Message should be kept");
+    console.log("✅ Transferred account to:", decoded.owner.toBase58());
+  });
+
+  // ===============================
+  // TEST 8: Batch Creation Under One Proof
+  // ===============================
+  it("8. batch create compressed accounts under one proof", async () => {
+    const owner = new web3.Keypair();
+    await rpc.requestAirdrop(owner.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    const messageSeed = new TextEncoder().encode("message");
+    const suffixes = ["record-0", "record-1"].map((s) => Buffer.from(s));
+    const batchAddresses = suffixes.map((suffix) =>
+      deriveAddressV2(
+        deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes(), suffix]),
+        addressTree,
+        new web3.PublicKey(program.idl.address)
+      )
+    );
+
+    const batchProof = await rpc.getValidityProofV0(
+      [],
+      batchAddresses.map((a) => ({ tree: addressTree, queue: addressTree, address: bn(a.toBytes()) }))
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+
+    const tx = await program.methods
+      .createAccountsBatch(
+        { 0: batchProof.compressedProof },
+        { rootIndex: batchProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx,
+        suffixes.map((seedSuffix, i) => ({ seedSuffix, message: `Migrated ${i}` }))
+      )
+      .accounts({ signer: owner.publicKey })
+      .preInstructions([web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 })])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction();
+
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(owner);
+    const sig = await rpc.sendTransaction(tx, [owner]);
+    await confirmTx(rpc, sig);
+    console.log("Batch create TX:", sig);
+
+    const slot = await rpc.getSlot();
+    await rpc.confirmTransactionIndexed(slot);
+
+    for (const [i, a] of batchAddresses.entries()) {
+      const created = await rpc.getCompressedAccount(bn(a.toBytes()));
+      const decoded = decodeType(coder, "MyCompressedAccount", created!.data!.data);
+      assert.ok(decoded.owner.equals(owner.publicKey), "Owner should match");
+      assert.strictEqual(decoded.message, `Migrated ${i}`, "Message should match");
+    }
+    console.log("✅ Created both accounts in one CPI");
+  });
+
+  // ===============================
+  // TEST 9: Raw Data Account Create / Update
+  // ===============================
+  it("9. create and update a raw data account", async () => {
+    const schema = 7;
+    const schemaBytes = Buffer.alloc(2);
+    schemaBytes.writeUInt16LE(schema);
+    const rawAddress = deriveAddressV2(
+      deriveAddressSeedV2([new TextEncoder().encode("raw"), signer.publicKey.toBytes(), schemaBytes]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+    // Create
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(rawAddress.toBytes()) }]
+    );
+    let remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+
+    let tx = await program.methods
+      .createRawAccount(
+        { 0: createProof.compressedProof },
+        { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx,
+        schema,
+        Buffer.from([1, 2, 3, 4])
+      )
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    let sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    // Update
+    const rawAccount = await rpc.getCompressedAccount(bn(rawAddress.toBytes()));
+    const currentRaw = decodeType(coder, "RawDataAccount", rawAccount!.data!.data);
+    assert.strictEqual(currentRaw.schema, schema, "Schema should match");
+
+    const updateProof = await rpc.getValidityProofV0(
+      [{ hash: rawAccount!.hash, tree: rawAccount!.treeInfo.tree, queue: rawAccount!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = {
+      treeInfo: {
+        rootIndex: updateProof.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(rawAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(rawAccount!.treeInfo.queue),
+        leafIndex: rawAccount!.leafIndex,
+      },
+      address: Array.from(rawAddress.toBytes()),
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+
+    tx = await program.methods
+      .updateRawAccount({ 0: updateProof.compressedProof }, currentRaw, accountMeta, Buffer.from([9, 9]))
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    console.log("Raw update TX:", sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const updated = await rpc.getCompressedAccount(bn(rawAddress.toBytes()));
+    const decoded = decodeType(coder, "RawDataAccount", updated!.data!.data);
+    assert.deepStrictEqual(Array.from(decoded.data), [9, 9], "Data should be replaced");
+    console.log("✅ Raw data account updated");
+  });
+
+  // ===============================
+  // TEST 10: KV Entry Create / Overwrite
+  // ===============================
+  it("10. create and overwrite a KV entry", async () => {
+    const key = Buffer.from("settings/theme");
+    const kvAddress = deriveAddressV2(
+      deriveAddressSeedV2([new TextEncoder().encode("kv"), signer.publicKey.toBytes(), key]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+    // Create
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(kvAddress.toBytes()) }]
+    );
+    let remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+
+    let tx = await program.methods
+      .kvCreate(
+        { 0: createProof.compressedProof },
+        { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx,
+        key,
+        Buffer.from("dark"),
+        null
+      )
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    let sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    // Overwrite
+    const kvAccount = await rpc.getCompressedAccount(bn(kvAddress.toBytes()));
+    const currentEntry = decodeType(coder, "KvEntry", kvAccount!.data!.data);
+    assert.strictEqual(Buffer.from(currentEntry.value).toString(), "dark", "Value should match");
+
+    const overwriteProof = await rpc.getValidityProofV0(
+      [{ hash: kvAccount!.hash, tree: kvAccount!.treeInfo.tree, queue: kvAccount!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const accountMeta = {
+      treeInfo: {
+        rootIndex: overwriteProof.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(kvAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(kvAccount!.treeInfo.queue),
+        leafIndex: kvAccount!.leafIndex,
+      },
+      address: Array.from(kvAddress.toBytes()),
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+
+    tx = await program.methods
+      .kvOverwrite({ 0: overwriteProof.compressedProof }, currentEntry, accountMeta, Buffer.from("light"))
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    console.log("KV overwrite TX:", sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const updated = await rpc.getCompressedAccount(bn(kvAddress.toBytes()));
+    const decoded = decodeType(coder, "KvEntry", updated!.data!.data);
+    assert.strictEqual(Buffer.from(decoded.value).toString(), "light", "Value should be overwritten");
+    console.log("✅ KV entry overwritten");
+  });
+
+  // ===============================
+  // TEST 10b: KV Delete / Recreate
+  // ===============================
+  it("10b. delete a KV entry and set the key again", async () => {
+    const key = Buffer.from("settings/theme");
+    const kvAddress = deriveAddressV2(
+      deriveAddressSeedV2([new TextEncoder().encode("kv"), signer.publicKey.toBytes(), key]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+
+    // Delete
+    const live = await loadAccount(rpc, coder, program.programId, kvAddress, outputStateTree, "KvEntry");
+    let tx = await program.methods
+      .kvDelete(live.proof, live.meta, live.current)
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(live.remainingAccounts)
+      .signers([signer])
+      .transaction();
+    await sendAndIndex(rpc, tx, signer);
+
+    const closed = await loadAccount(rpc, coder, program.programId, kvAddress, outputStateTree, "KvEntry");
+    assert.isNull(closed.current, "Deleted entry should be empty");
+
+    // Recreate from the closed account; no new address, so the address
+    // tree info goes unread
+    tx = await program.methods
+      .kvCreate(
+        closed.proof,
+        { rootIndex: 0, addressMerkleTreePubkeyIndex: 0, addressQueuePubkeyIndex: 0 },
+        closed.meta.outputStateTreeIndex,
+        key,
+        Buffer.from("solarized"),
+        closed.meta
+      )
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(closed.remainingAccounts)
+      .signers([signer])
+      .transaction();
+    await sendAndIndex(rpc, tx, signer);
+
+    const revived = await rpc.getCompressedAccount(bn(kvAddress.toBytes()));
+    const decoded = decodeType(coder, "KvEntry", revived!.data!.data);
+    assert.strictEqual(Buffer.from(decoded.value).toString(), "solarized", "Key should hold the new value");
+    assert.ok(new web3.PublicKey(decoded.owner).equals(signer.publicKey), "Owner should be the signer");
+    console.log("✅ KV key set again after delete");
+  });
+
+  // ===============================
+  // TEST 11: Append-Only Log
+  // ===============================
+  it("11. append entries to a log page", async () => {
+    const pageBytes = Buffer.alloc(4);
+    pageBytes.writeUInt32LE(0);
+    const logAddress = deriveAddressV2(
+      deriveAddressSeedV2([new TextEncoder().encode("log"), signer.publicKey.toBytes(), pageBytes]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+    // First entry starts page 0
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(logAddress.toBytes()) }]
+    );
+    let remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+    const addressTreeInfo = { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx };
+
+    let tx = await program.methods
+      .appendEntry({ 0: createProof.compressedProof }, addressTreeInfo, treeIdx, null, Buffer.from("login"))
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    let sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    // Second entry goes into the same page
+    const logAccount = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+    const currentLog = decodeType(coder, "LogAccount", logAccount!.data!.data);
+    const appendProof = await rpc.getValidityProofV0(
+      [{ hash: logAccount!.hash, tree: logAccount!.treeInfo.tree, queue: logAccount!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const meta = {
+      treeInfo: {
+        rootIndex: appendProof.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(logAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(logAccount!.treeInfo.queue),
+        leafIndex: logAccount!.leafIndex,
+      },
+      address: Array.from(logAddress.toBytes()),
+      outputStateTreeIndex: remainingAccounts.insertOrGet(outputStateTree),
+    };
+
+    tx = await program.methods
+      .appendEntry({ 0: appendProof.compressedProof }, addressTreeInfo, meta.outputStateTreeIndex, { log: currentLog, meta }, Buffer.from("logout"))
+      .accounts({ signer: signer.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([signer])
+      .transaction();
+    tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+    tx.sign(signer);
+    sig = await rpc.sendTransaction(tx, [signer]);
+    await confirmTx(rpc, sig);
+    console.log("Append TX:", sig);
+    await rpc.confirmTransactionIndexed(await rpc.getSlot());
+
+    const updated = await rpc.getCompressedAccount(bn(logAddress.toBytes()));
+    const decoded = decodeType(coder, "LogAccount", updated!.data!.data);
+    assert.strictEqual(decoded.entries.length, 2, "Page should hold both entries");
+    assert.strictEqual(Buffer.from(decoded.entries[1].data).toString(), "logout", "Newest entry goes last");
+    console.log("✅ Log page holds", decoded.entries.length, "entries");
+  });
+
+  // ===============================
+  // TEST 12: Decompress To PDA And Back
+  // ===============================
+  it("12. decompress to a PDA and compress it back", async () => {
+    const owner = new web3.Keypair();
+    await rpc.requestAirdrop(owner.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    const messageSeed = new TextEncoder().encode("message");
+    const ownerAddress = deriveAddressV2(
+      deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes()]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const [pda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("message_pda"), owner.publicKey.toBuffer(), ownerAddress.toBuffer()],
+      program.programId
+    );
+    const delegate = web3.Keypair.generate().publicKey;
+    const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+    const send = async (tx: web3.Transaction) => {
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(owner);
+      const sig = await rpc.sendTransaction(tx, [owner]);
+      await confirmTx(rpc, sig);
+      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+      return sig;
+    };
+
+    // Create the compressed account
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(ownerAddress.toBytes()) }]
+    );
+    let remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    let addrIdx = remainingAccounts.insertOrGet(addressTree);
+    let treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+    await send(await program.methods
+      .createAccount(
+        { 0: createProof.compressedProof },
+        { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx,
+        "Round trip"
+      )
+      .accounts({ signer: owner.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction());
+
+    await sendSetDelegate(program, coder, rpc, owner, ownerAddress, outputStateTree, delegate, PERMISSION_UPDATE);
+
+    // Decompress it into the PDA, naming the suffix it migrates back to
+    const suffix = Buffer.from("migrated");
+    const compressedAccount = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    const currentAccount = decodeType(coder, "MyCompressedAccount", compressedAccount!.data!.data);
+    const burnProof = await rpc.getValidityProofV0(
+      [{ hash: compressedAccount!.hash, tree: compressedAccount!.treeInfo.tree, queue: compressedAccount!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const burnMeta = {
+      treeInfo: {
+        rootIndex: burnProof.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.tree),
+        queuePubkeyIndex: remainingAccounts.insertOrGet(compressedAccount!.treeInfo.queue),
+        leafIndex: compressedAccount!.leafIndex,
+      },
+      address: Array.from(ownerAddress.toBytes()),
+    };
+    const decompressSig = await send(await program.methods
+      .decompressToPda({ 0: burnProof.compressedProof }, burnMeta, currentAccount, suffix)
+      .accounts({ signer: owner.publicKey, pda })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction());
+    console.log("Decompress TX:", decompressSig);
+
+    const pdaAccount = await program.account.messagePda.fetch(pda);
+    assert.strictEqual(pdaAccount.message, "Round trip", "PDA should hold the message");
+    assert.ok(pdaAccount.delegate.equals(delegate), "PDA should keep the delegate");
+    assert.strictEqual(pdaAccount.permissions, PERMISSION_UPDATE, "PDA should keep the permissions");
+    assert.ok(Buffer.from(pdaAccount.seedSuffix).equals(suffix), "PDA should store its migration suffix");
+
+    // Compress it back under the stored suffix
+    const migratedAddress = deriveAddressV2(
+      deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes(), suffix]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const compressProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(migratedAddress.toBytes()) }]
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    addrIdx = remainingAccounts.insertOrGet(addressTree);
+    treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+    const compressSig = await send(await program.methods
+      .compressPda(
+        { 0: compressProof.compressedProof },
+        { rootIndex: compressProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx
+      )
+      .accounts({ signer: owner.publicKey, pda })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction());
+    console.log("Compress TX:", compressSig);
+
+    assert.strictEqual(await rpc.getAccountInfo(pda), null, "PDA should be closed");
+    const migrated = await rpc.getCompressedAccount(bn(migratedAddress.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", migrated!.data!.data);
+    assert.strictEqual(decoded.message, "Round trip", "Message should survive the round trip");
+    assert.ok(new web3.PublicKey(decoded.delegate).equals(delegate), "Delegate should survive the round trip");
+    assert.strictEqual(decoded.permissions, PERMISSION_UPDATE, "Permissions should survive the round trip");
+    console.log("✅ Round-tripped through a PDA");
+  });
+
+  // ===============================
+  // TEST 12b: Compress A Created PDA
+  // ===============================
+  it("12b. create a PDA and compress it", async () => {
+    const owner = await fundedKeypair(rpc);
+    const suffix = Buffer.from("migrated");
+    const migratedAddress = deriveMessageAddress(owner.publicKey, addressTree, program.programId, suffix);
+    const pdaFor = (address: web3.PublicKey) =>
+      web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("message_pda"), owner.publicKey.toBuffer(), address.toBuffer()],
+        program.programId
+      )[0];
+    const pda = pdaFor(migratedAddress);
+
+    // An empty suffix would migrate onto create_account's address
+    const unsuffixed = deriveMessageAddress(owner.publicKey, addressTree, program.programId);
+    await assertRejected(
+      rpc,
+      await program.methods
+        .createMessagePda(Buffer.alloc(0), "No suffix")
+        .accounts({ signer: owner.publicKey, pda: pdaFor(unsuffixed) })
+        .signers([owner])
+        .transaction(),
+      owner,
+      /invalid instruction data/i,
+      "Empty seed suffixes are rejected"
+    );
+
+    let tx = await program.methods
+      .createMessagePda(suffix, "Started as a PDA")
+      .accounts({ signer: owner.publicKey, pda })
+      .signers([owner])
+      .transaction();
+    await sendAndIndex(rpc, tx, owner);
+
+    // A second PDA under another suffix lives alongside the first
+    const otherPda = pdaFor(deriveMessageAddress(owner.publicKey, addressTree, program.programId, Buffer.from("other")));
+    tx = await program.methods
+      .createMessagePda(Buffer.from("other"), "Second PDA")
+      .accounts({ signer: owner.publicKey, pda: otherPda })
+      .signers([owner])
+      .transaction();
+    await sendAndIndex(rpc, tx, owner);
+
+    const pdaAccount = await program.account.messagePda.fetch(pda);
+    assert.ok(pdaAccount.owner.equals(owner.publicKey), "PDA should belong to the signer");
+    assert.strictEqual(pdaAccount.message, "Started as a PDA", "PDA should hold the message");
+    assert.strictEqual((await program.account.messagePda.fetch(otherPda)).message, "Second PDA");
+    const proofRpcResult = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(migratedAddress.toBytes()) }]
+    );
+    const remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+    tx = await program.methods
+      .compressPda(
+        { 0: proofRpcResult.compressedProof },
+        { rootIndex: proofRpcResult.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx
+      )
+      .accounts({ signer: owner.publicKey, pda })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction();
+    await sendAndIndex(rpc, tx, owner);
+
+    assert.strictEqual(await rpc.getAccountInfo(pda), null, "PDA should be closed");
+    const migrated = await rpc.getCompressedAccount(bn(migratedAddress.toBytes()));
+    const decoded = decodeType(coder, "MyCompressedAccount", migrated!.data!.data);
+    assert.ok(new web3.PublicKey(decoded.owner).equals(owner.publicKey), "Owner should carry over");
+    assert.strictEqual(decoded.message, "Started as a PDA", "Message should carry over");
+    console.log("✅ Created PDA compressed");
+  });
+
+  // ===============================
+  // TEST 13: Expiry And Cleanup Crank
+  // ===============================
+  it("13. crank closes an expired account and takes the reward", async () => {
+    const owner = new web3.Keypair();
+    const cranker = new web3.Keypair();
+    await rpc.requestAirdrop(owner.publicKey, web3.LAMPORTS_PER_SOL);
+    await rpc.requestAirdrop(cranker.publicKey, web3.LAMPORTS_PER_SOL);
+    await sleep(2000);
+
+    const messageSeed = new TextEncoder().encode("message");
+    const ownerAddress = deriveAddressV2(
+      deriveAddressSeedV2([messageSeed, owner.publicKey.toBytes()]),
+      addressTree,
+      new web3.PublicKey(program.idl.address)
+    );
+    const [vault] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("crank_reward"), ownerAddress.toBuffer()],
+      program.programId
+    );
+    const computeBudgetIx = web3.ComputeBudgetProgram.setComputeUnitLimit({ units: 1000000 });
+
+    const send = async (tx: web3.Transaction, payer: web3.Keypair) => {
+      tx.recentBlockhash = (await rpc.getRecentBlockhash()).blockhash;
+      tx.sign(payer);
+      const sig = await rpc.sendTransaction(tx, [payer]);
+      await confirmTx(rpc, sig);
+      await rpc.confirmTransactionIndexed(await rpc.getSlot());
+      return sig;
+    };
+    const metaFor = async (proofResult: any, account: any, remaining: PackedAccounts) => ({
+      treeInfo: {
+        rootIndex: proofResult.rootIndices[0],
+        proveByIndex: false,
+        merkleTreePubkeyIndex: remaining.insertOrGet(account.treeInfo.tree),
+        queuePubkeyIndex: remaining.insertOrGet(account.treeInfo.queue),
+        leafIndex: account.leafIndex,
+      },
+      address: Array.from(ownerAddress.toBytes()),
+      outputStateTreeIndex: remaining.insertOrGet(outputStateTree),
+    });
+
+    // Create the account
+    const createProof = await rpc.getValidityProofV0(
+      [],
+      [{ tree: addressTree, queue: addressTree, address: bn(ownerAddress.toBytes()) }]
+    );
+    let remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const addrIdx = remainingAccounts.insertOrGet(addressTree);
+    const treeIdx = remainingAccounts.insertOrGet(outputStateTree);
+    await send(await program.methods
+      .createAccount(
+        { 0: createProof.compressedProof },
+        { rootIndex: createProof.rootIndices[0], addressMerkleTreePubkeyIndex: addrIdx, addressQueuePubkeyIndex: addrIdx },
+        treeIdx,
+        "Ephemeral"
+      )
+      .accounts({ signer: owner.publicKey })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction(), owner);
+
+    // Expire shortly, escrowing a reward
+    let account = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    let current = decodeType(coder, "MyCompressedAccount", account!.data!.data);
+    let proofResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const reward = await rpc.getMinimumBalanceForRentExemption(0);
+    const expiresAt = (await rpc.getSlot()) + 10;
+    await send(await program.methods
+      .setExpiry({ 0: proofResult.compressedProof }, current, await metaFor(proofResult, account, remainingAccounts), bn(expiresAt), bn(reward))
+      .accounts({ signer: owner.publicKey, vault })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([owner])
+      .transaction(), owner);
+    assert.strictEqual(await rpc.getBalance(vault), reward, "Reward should be escrowed");
+
+    // An expiring account can't move to a PDA, which has nothing to enforce it
+    const expiring = await loadAccount(rpc, coder, program.programId, ownerAddress, outputStateTree);
+    const [expiringPda] = web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("message_pda"), owner.publicKey.toBuffer(), ownerAddress.toBuffer()],
+      program.programId
+    );
+    await assertRejected(
+      rpc,
+      await program.methods
+        .decompressToPda(expiring.proof, expiring.burnMeta, expiring.current, Buffer.from("migrated"))
+        .accounts({ signer: owner.publicKey, pda: expiringPda })
+        .preInstructions([computeBudgetIx])
+        .remainingAccounts(expiring.remainingAccounts)
+        .signers([owner])
+        .transaction(),
+      owner,
+      /0x1776/,
+      "Decompressing an expiring account should fail with InvalidExpiry"
+    );
+
+    while ((await rpc.getSlot()) < expiresAt) {
+      await sleep(1000);
+    }
+
+    // Anyone can crank it now
+    account = await rpc.getCompressedAccount(bn(ownerAddress.toBytes()));
+    current = decodeType(coder, "MyCompressedAccount", account!.data!.data);
+    proofResult = await rpc.getValidityProofV0(
+      [{ hash: account!.hash, tree: account!.treeInfo.tree, queue: account!.treeInfo.queue }],
+      []
+    );
+    remainingAccounts = new PackedAccounts();
+    remainingAccounts.addSystemAccountsV2(new SystemAccountMetaConfig(program.programId));
+    const sig = await send(await program.methods
+      .closeExpired({ 0: proofResult.compressedProof }, await metaFor(proofResult, account, remainingAccounts), current)
+      .accounts({ signer: cranker.publicKey, vault })
+      .preInstructions([computeBudgetIx])
+      .remainingAccounts(remainingAccounts.toAccountMetas().remainingAccounts)
+      .signers([cranker])
+      .transaction(), cranker);
+    console.log("Close expired TX:", sig);
+
+    assert.strictEqual(await rpc.getBalance(vault), 0, "Reward should go to the cranker");
+    console.log("✅ Cranker closed the expired account");
+  });
+
+  // ===============================
+  // TEST 13b: Transfer Settles The Escrow
+  // ===============================
+  it("13b. transfer refunds the escrowed reward to the outgoing owner", async () => {
+    const owner = await fundedKeypair(rpc);
+    const newOwner = await fundedKeypair(rpc);
+    const address = await sendCreateAccount(program, rpc, owner, outputStateTree, "Escrowed");
+    const vault = rewardVaultAddress(address, program.programId);
+
+    const reward = await rpc.getMinimumBalanceForRentExemption(0);
+    let target = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+    let tx = await program.methods
+      .setExpiry(target.proof, target.current, target.meta, bn((await rpc.getSlot()) + 1000), bn(reward))
+      .accounts({ signer: owner.publicKey, vault })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([owner])
+      .transaction();
+    await sendAndIndex(rpc, tx, owner);
+    assert.strictEqual(await rpc.getBalance(vault), reward, "Reward should be escrowed");
+
+    const balanceBefore = await rpc.getBalance(owner.publicKey);
+    target = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+    tx = await program.methods
+      .transferOwnership(target.proof, target.current, target.meta, newOwner.publicKey)
+      .accounts({ signer: owner.publicKey, vault })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([owner])
+      .transaction();
+    await sendAndIndex(rpc, tx, owner);
+
+    assert.strictEqual(await rpc.getBalance(vault), 0, "Escrow should be emptied");
+    assert.isAbove(await rpc.getBalance(owner.publicKey), balanceBefore, "Outgoing owner should get the reward back");
+
+    // The new owner can clear the expiry, but there's nothing left to take
+    target = await loadAccount(rpc, coder, program.programId, address, outputStateTree);
+    assert.strictEqual(target.current.crankReward.toNumber(), 0, "Transferred account should hold no reward");
+    const newOwnerBefore = await rpc.getBalance(newOwner.publicKey);
+    tx = await program.methods
+      .setExpiry(target.proof, target.current, target.meta, null, bn(0))
+      .accounts({ signer: newOwner.publicKey, vault })
+      .preInstructions([computeBudgetIx()])
+      .remainingAccounts(target.remainingAccounts)
+      .signers([newOwner])
+      .transaction();
+    await sendAndIndex(rpc, tx, newOwner);
+    assert.isBelow(await rpc.getBalance(newOwner.publicKey), newOwnerBefore, "New owner should only pay the fee");
+    console.log("✅ Transfer settled the escrow with its funder");
+  });
 });
\ No newline at end of file